@@ -0,0 +1,61 @@
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+/// Git commit of the source tree (`TREX_GIT_COMMIT` for builds without repository)
+fn git_commit() -> Option<String> {
+    if let Ok(commit) = env::var("TREX_GIT_COMMIT") {
+        return Some(commit).filter(|commit| !commit.is_empty());
+    }
+    let output = Command::new("git")
+        .args(&["rev-parse", "HEAD"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let commit = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    Some(commit).filter(|commit| !commit.is_empty())
+}
+
+/// Build time in RFC 3339 format (`SOURCE_DATE_EPOCH` for reproducible builds)
+fn build_date() -> String {
+    let secs = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<i64>().ok())
+        .unwrap_or_else(|| time::get_time().sec);
+    time::at_utc(time::Timespec::new(secs, 0))
+        .rfc3339()
+        .to_string()
+}
+
+/// Enabled cargo features, e.g. "webviewer,with-gdal"
+fn features() -> String {
+    let prefix = "CARGO_FEATURE_";
+    let mut features = env::vars()
+        .filter(|(key, _)| key.starts_with(prefix))
+        .map(|(key, _)| key[prefix.len()..].to_lowercase().replace('_', "-"))
+        .filter(|feature| feature != "default")
+        .collect::<Vec<_>>();
+    features.sort();
+    features.join(",")
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-env-changed=TREX_GIT_COMMIT");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    // Capture new commits (missing paths would rerun the script on every build)
+    for git_path in &[".git/HEAD", ".git/refs"] {
+        if Path::new(git_path).exists() {
+            println!("cargo:rerun-if-changed={}", git_path);
+        }
+    }
+    println!(
+        "cargo:rustc-env=TREX_GIT_COMMIT={}",
+        git_commit().unwrap_or_default()
+    );
+    println!("cargo:rustc-env=TREX_BUILD_DATE={}", build_date());
+    println!("cargo:rustc-env=TREX_FEATURES={}", features());
+}
@@ -6,11 +6,18 @@ main() {
     cargo build --all --tests --bins
     cargo run -- --version
 
+    # Core without server dependencies (geometry processing and MVT encoder)
+    if [ $TRAVIS_OS_NAME = linux ]; then
+        rustup target add wasm32-unknown-unknown
+        (cd t-rex-core && cargo check --no-default-features --target wasm32-unknown-unknown)
+    fi
+
     if [ ! -z $DISABLE_TESTS ]; then
         return
     fi
 
     cargo test --all
+    (cd t-rex-webserver && cargo test --features grpc grpc)
     if [ $TRAVIS_OS_NAME = linux ]; then
         cargo test --all -- --ignored
     fi
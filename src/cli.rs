@@ -0,0 +1,210 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+//! Command line functions shared by `t_rex` and `t_rex_seed`
+
+use clap::ArgMatches;
+use env_logger::Builder;
+use log::Record;
+use std::collections::BTreeMap;
+use std::env;
+use std::io::Write;
+use t_rex_core::cache::{GpkgCache, Tilecache};
+use t_rex_service::manifest::Manifest;
+use t_rex_service::seed_state::{SeedParams, SeedState};
+use t_rex_webserver as webserver;
+use tile_grid::Extent;
+use time;
+
+#[cfg(feature = "with-gdal")]
+extern crate t_rex_gdal;
+
+pub fn version_info() -> String {
+    #[cfg(feature = "with-gdal")]
+    let version = format!(
+        "{} (GDAL version {})",
+        crate_version!(),
+        t_rex_gdal::gdal_version()
+    );
+    #[cfg(not(feature = "with-gdal"))]
+    let version = crate_version!().to_string();
+    version
+}
+
+/// Version and build details captured by the build script
+pub fn build_info() -> webserver::BuildInfo {
+    let non_empty = |value: &str| Some(value.to_string()).filter(|value| !value.is_empty());
+    #[allow(unused_mut)]
+    let mut dependencies = BTreeMap::new();
+    #[cfg(feature = "with-gdal")]
+    dependencies.insert("GDAL".to_string(), t_rex_gdal::gdal_version());
+    webserver::BuildInfo {
+        version: crate_version!().to_string(),
+        git_commit: non_empty(env!("TREX_GIT_COMMIT")),
+        build_date: non_empty(env!("TREX_BUILD_DATE")),
+        features: env!("TREX_FEATURES")
+            .split(',')
+            .filter(|feature| !feature.is_empty())
+            .map(|feature| feature.to_string())
+            .collect(),
+        dependencies,
+    }
+}
+
+/// Arguments of tile generation
+pub const GENERATE_ARGS: &str = "-c, --config=<FILE> 'Load from custom config file'
+                                     --loglevel=[error|warn|info|debug|trace] 'Log level (Default: info)'
+                                     --tileset=[NAME] 'Tileset name'
+                                     --minzoom=[LEVEL] 'Minimum zoom level'
+                                     --maxzoom=[LEVEL] 'Maximum zoom level'
+                                     --extent=[minx,miny,maxx,maxy] 'Extent of tiles'
+                                     --nodes=[NUM] 'Number of generator nodes'
+                                     --nodeno=[NUM] 'Number of this nodes (0 <= n < nodes)'
+                                     --progress=[true|false] 'Show progress bar'
+                                     --overwrite=[false|true] 'Overwrite previously cached tiles'
+                                     --write-empty=[false|true] 'Write empty tiles into cache (one file per tile without features)'
+                                     --metatile-size=[NUM] 'Generate blocks of NUM x NUM tiles with one query per layer (Default: 1)'
+                                     --manifest=[FILE] 'Write JSON lines manifest of produced tiles'
+                                     --resume=[FILE] 'Record progress in state FILE and skip tiles completed in a previous run'
+                                     --format=[cache|gpkg] 'Output into configured tile cache or GeoPackage (Default: cache)'
+                                     --out=[FILE] 'Output file of GeoPackage format'
+                                     --profile=[NAME] 'Use layer queries of profile NAME (Default: TREX_PROFILE)'
+                                     --dbconn=[NAME=URL]... 'Connection of named datasource (Default: TREX_DATASOURCE_<NAME>)'
+                                     --strict=[false|true] 'Exit on configuration warnings like mismatching layer geometry types'";
+
+pub fn init_logger(args: &ArgMatches<'_>) {
+    let mut builder = Builder::new();
+    builder.format(|buf, record: &Record<'_>| {
+        let t = time::now();
+        writeln!(
+            buf,
+            "{}.{:03} {} {}",
+            time::strftime("%Y-%m-%d %H:%M:%S", &t).unwrap(),
+            t.tm_nsec / 1000_000,
+            record.level(),
+            record.args()
+        )
+    });
+
+    // Precedence: --loglevel, RUST_LOG, --log-level
+    let rust_log_env = env::var("RUST_LOG");
+    let rust_log = match (args.value_of("loglevel"), rust_log_env.as_ref()) {
+        (None, Ok(rust_log)) => rust_log,
+        (loglevel, _) => match loglevel.or(args.value_of("log-level")).unwrap_or("info") {
+            "debug" => "debug,tokio=info",
+            loglevel => loglevel,
+        },
+    };
+    builder.parse_filters(rust_log);
+
+    builder.init();
+}
+
+pub fn generate(args: &ArgMatches<'_>) {
+    log::info!("{}", build_info().summary());
+    let config = webserver::config_from_args(&args);
+    let mut service = webserver::service_from_args(&config, &args);
+    match args.value_of("format").unwrap_or("cache") {
+        "cache" => {
+            config.cache.expect(
+                "Missing configuration entry [cache.file], [cache.sqlite] or [[cache.tier]]",
+            );
+        }
+        "gpkg" => {
+            let out = args
+                .value_of("out")
+                .expect("Missing output file for GeoPackage (--out)");
+            if args.is_present("resume") {
+                panic!("Resuming is not supported for GeoPackage output");
+            }
+            let gpkg = GpkgCache::create(out, &service.grid).unwrap_or_else(|e| panic!("{}", e));
+            service.cache = Tilecache::GpkgCache(gpkg);
+        }
+        format => panic!("Unknown output format '{}'", format),
+    }
+    let tileset = args.value_of("tileset");
+    let minzoom = args.value_of("minzoom").map(|s| {
+        s.parse::<u8>()
+            .expect("Error parsing 'minzoom' as integer value")
+    });
+    let maxzoom = args.value_of("maxzoom").map(|s| {
+        s.parse::<u8>()
+            .expect("Error parsing 'maxzoom' as integer value")
+    });
+    let extent = args.value_of("extent").and_then(|numlist| {
+        let arr: Vec<f64> = numlist
+            .split(",")
+            .map(|v| {
+                v.parse()
+                    .expect("Error parsing 'extent' as list of float values")
+            })
+            .collect();
+        Some(Extent {
+            minx: arr[0],
+            miny: arr[1],
+            maxx: arr[2],
+            maxy: arr[3],
+        })
+    });
+    let nodes = args.value_of("nodes").map(|s| {
+        s.parse::<u8>()
+            .expect("Error parsing 'nodes' as integer value")
+    });
+    let nodeno = args.value_of("nodeno").map(|s| {
+        s.parse::<u8>()
+            .expect("Error parsing 'nodeno' as integer value")
+    });
+    let progress = args.value_of("progress").map_or(true, |s| {
+        s.parse::<bool>()
+            .expect("Error parsing 'progress' as boolean value")
+    });
+    let overwrite = args.value_of("overwrite").map_or(false, |s| {
+        s.parse::<bool>()
+            .expect("Error parsing 'overwrite' as boolean value")
+    });
+    let write_empty = args.value_of("write-empty").map_or(false, |s| {
+        s.parse::<bool>()
+            .expect("Error parsing 'write-empty' as boolean value")
+    });
+    let metatile_size = args.value_of("metatile-size").map_or(1, |s| {
+        s.parse::<u8>()
+            .expect("Error parsing 'metatile-size' as integer value")
+    });
+    let manifest = args.value_of("manifest").map(|fname| {
+        Manifest::create(fname)
+            .unwrap_or_else(|e| panic!("Error creating manifest '{}': {}", fname, e))
+    });
+    let seed_state = args.value_of("resume").map(|fname| {
+        let params = SeedParams {
+            tileset: tileset.map(|name| name.to_string()),
+            minzoom,
+            maxzoom,
+            extent: extent
+                .as_ref()
+                .map(|ext| (ext.minx, ext.miny, ext.maxx, ext.maxy)),
+            nodes,
+            nodeno,
+            metatile_size,
+        };
+        SeedState::open(fname, params).unwrap_or_else(|e| panic!("{}", e))
+    });
+    let warnings = service.prepare_feature_queries();
+    webserver::exit_on_warnings(args, &warnings);
+    let stats = service.generate(
+        tileset,
+        minzoom,
+        maxzoom,
+        extent,
+        nodes,
+        nodeno,
+        progress,
+        overwrite,
+        write_empty,
+        metatile_size,
+        manifest,
+        seed_state,
+    );
+    println!("Statistics:\n{:?}", stats);
+}
@@ -5,97 +5,24 @@
 
 #[macro_use]
 extern crate clap;
+#[macro_use]
+extern crate log;
+
+mod cli;
 
-use clap::{App, AppSettings, ArgMatches, SubCommand};
+use crate::cli::{build_info, generate, init_logger, version_info};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use dotenv::dotenv;
-use env_logger::Builder;
-use log::Record;
 use std::env;
-use std::io::Write;
+use std::fs;
+use std::path::Path;
+use std::process;
+use t_rex_core::core::{config, tilematrixset, upgrade, Config};
+use t_rex_core::mvt::vector_tile;
+use t_rex_service::mvt_service::MvtService;
+use t_rex_service::tile_diff;
 use t_rex_webserver as webserver;
-use tile_grid::Extent;
-use time;
-
-fn init_logger(args: &ArgMatches<'_>) {
-    let mut builder = Builder::new();
-    builder.format(|buf, record: &Record<'_>| {
-        let t = time::now();
-        writeln!(
-            buf,
-            "{}.{:03} {} {}",
-            time::strftime("%Y-%m-%d %H:%M:%S", &t).unwrap(),
-            t.tm_nsec / 1000_000,
-            record.level(),
-            record.args()
-        )
-    });
-
-    let rust_log_env = env::var("RUST_LOG");
-    let rust_log = if args.value_of("loglevel").is_none() && rust_log_env.is_ok() {
-        rust_log_env.as_ref().unwrap()
-    } else {
-        match args.value_of("loglevel").unwrap_or("info") {
-            "debug" => "debug,tokio=info",
-            loglevel => loglevel,
-        }
-    };
-    builder.parse_filters(rust_log);
-
-    builder.init();
-}
-
-fn generate(args: &ArgMatches<'_>) {
-    let config = webserver::config_from_args(&args);
-    let mut service = webserver::service_from_args(&config, &args);
-    config
-        .cache
-        .expect("Missing configuration entry base in [cache.file]");
-    let tileset = args.value_of("tileset");
-    let minzoom = args.value_of("minzoom").map(|s| {
-        s.parse::<u8>()
-            .expect("Error parsing 'minzoom' as integer value")
-    });
-    let maxzoom = args.value_of("maxzoom").map(|s| {
-        s.parse::<u8>()
-            .expect("Error parsing 'maxzoom' as integer value")
-    });
-    let extent = args.value_of("extent").and_then(|numlist| {
-        let arr: Vec<f64> = numlist
-            .split(",")
-            .map(|v| {
-                v.parse()
-                    .expect("Error parsing 'extent' as list of float values")
-            })
-            .collect();
-        Some(Extent {
-            minx: arr[0],
-            miny: arr[1],
-            maxx: arr[2],
-            maxy: arr[3],
-        })
-    });
-    let nodes = args.value_of("nodes").map(|s| {
-        s.parse::<u8>()
-            .expect("Error parsing 'nodes' as integer value")
-    });
-    let nodeno = args.value_of("nodeno").map(|s| {
-        s.parse::<u8>()
-            .expect("Error parsing 'nodeno' as integer value")
-    });
-    let progress = args.value_of("progress").map_or(true, |s| {
-        s.parse::<bool>()
-            .expect("Error parsing 'progress' as boolean value")
-    });
-    let overwrite = args.value_of("overwrite").map_or(false, |s| {
-        s.parse::<bool>()
-            .expect("Error parsing 'overwrite' as boolean value")
-    });
-    service.prepare_feature_queries();
-    let stats = service.generate(
-        tileset, minzoom, maxzoom, extent, nodes, nodeno, progress, overwrite,
-    );
-    println!("Statistics:\n{:?}", stats);
-}
+use tile_grid::Grid;
 
 fn drilldown(args: &ArgMatches<'_>) {
     let config = webserver::config_from_args(&args);
@@ -130,19 +57,216 @@ fn drilldown(args: &ArgMatches<'_>) {
     print!("{}", stats.as_csv());
 }
 
-#[cfg(feature = "with-gdal")]
-extern crate t_rex_gdal;
+fn check(args: &ArgMatches<'_>) {
+    let config = webserver::config_from_args(&args);
+    let mut service = webserver::service_from_args(&config, &args);
+    // Check the queries of all tilesets, also with lazy loading
+    service.active_tilesets = None;
+    let warnings = service.prepare_feature_queries();
+    if !warnings.is_empty() {
+        println!("Error in configuration check:\n{}", warnings.join("\n"));
+        process::exit(1)
+    }
+    println!("Configuration check successful");
+}
 
-fn version_info() -> String {
-    #[cfg(feature = "with-gdal")]
-    let version = format!(
-        "{} (GDAL version {})",
-        crate_version!(),
-        t_rex_gdal::gdal_version()
+fn query(args: &ArgMatches<'_>) {
+    let tile = args.value_of("tile").map(|spec| {
+        let zxy: Vec<u32> = spec
+            .split('/')
+            .map(|v| {
+                v.parse()
+                    .expect("Error parsing 'tile' as tile coordinates Z/X/Y")
+            })
+            .collect();
+        if zxy.len() != 3 {
+            panic!("Error parsing 'tile' as tile coordinates Z/X/Y");
+        }
+        (zxy[0] as u8, zxy[1], zxy[2])
+    });
+    let zoom = match (args.value_of("zoom"), tile) {
+        (Some(z), _) => {
+            let zoom = z
+                .parse::<u8>()
+                .expect("Error parsing 'zoom' as integer value");
+            if tile.map_or(false, |(z, _, _)| z != zoom) {
+                panic!("Zoom level of 'tile' differs from 'zoom'");
+            }
+            zoom
+        }
+        (None, Some((z, _, _))) => z,
+        (None, None) => panic!("Missing zoom level (--zoom or --tile)"),
+    };
+    let explain = if args.is_present("analyze") {
+        Some(true)
+    } else if args.is_present("explain") {
+        Some(false)
+    } else {
+        None
+    };
+    let config = webserver::config_from_args(&args);
+    let mut service = webserver::service_from_args(&config, &args);
+    service.prepare_feature_queries();
+    let report = service.layer_query_report(
+        args.value_of("tileset").unwrap(),
+        args.value_of("layer").unwrap(),
+        zoom,
+        tile.map(|(_, x, y)| (x, y)),
+        explain,
     );
-    #[cfg(not(feature = "with-gdal"))]
-    let version = crate_version!().to_string();
-    version
+    match report {
+        Ok(report) => print!("{}", report),
+        Err(e) => {
+            println!("{}", e);
+            process::exit(1)
+        }
+    }
+}
+
+/// Tile of a `diff` argument: tile file or tile coordinates [TILESET/]Z/X/Y (XYZ adressing scheme)
+fn diff_tile(
+    spec: &str,
+    args: &ArgMatches<'_>,
+    service: &mut Option<MvtService>,
+) -> vector_tile::Tile {
+    if Path::new(spec).is_file() {
+        let data = fs::read(spec).unwrap_or_else(|e| panic!("Error reading '{}': {}", spec, e));
+        return tile_diff::read_tile(&data).unwrap_or_else(|e| panic!("'{}': {}", spec, e));
+    }
+    let parts: Vec<&str> = spec.split('/').collect();
+    let (tileset, zxy) = match parts.len() {
+        3 => (
+            args.value_of("tileset")
+                .expect("Missing tileset of tile coordinates (--tileset)"),
+            &parts[..],
+        ),
+        4 => (parts[0], &parts[1..]),
+        _ => panic!(
+            "'{}' is neither a tile file, a directory nor tile coordinates [TILESET/]Z/X/Y",
+            spec
+        ),
+    };
+    let zoom = zxy[0]
+        .parse::<u8>()
+        .expect("Error parsing zoom level of tile coordinates");
+    let xtile = zxy[1]
+        .parse::<u32>()
+        .expect("Error parsing x of tile coordinates");
+    let ytile = zxy[2]
+        .parse::<u32>()
+        .expect("Error parsing y of tile coordinates");
+    let service = service.get_or_insert_with(|| {
+        if args.value_of("config").is_none() {
+            panic!("Missing configuration file for tile coordinates (--config)");
+        }
+        let config = webserver::config_from_args(&args);
+        let mut service = webserver::service_from_args(&config, &args);
+        service.prepare_feature_queries();
+        service
+    });
+    if service.get_tileset(tileset).is_none() {
+        panic!("Unknown tileset '{}'", tileset);
+    }
+    let tile = service
+        .url_tile(xtile, ytile, zoom)
+        .unwrap_or_else(|| panic!("Tile {}/{}/{} is outside of the grid", zoom, xtile, ytile));
+    let y = service.grid_ytile(&tile);
+    service.tile(tileset, xtile, y, zoom, None)
+}
+
+fn diff(args: &ArgMatches<'_>) {
+    let a = args.value_of("a").unwrap();
+    let b = args.value_of("b").unwrap();
+    let geojson = args.value_of("geojson-out");
+    let mut features = Vec::new();
+    match (Path::new(a).is_dir(), Path::new(b).is_dir()) {
+        (true, true) => {
+            let stats = tile_diff::diff_dirs(
+                Path::new(a),
+                Path::new(b),
+                geojson.is_some(),
+                |path, diff| features.extend(diff.geojson_features(path)),
+            )
+            .unwrap_or_else(|e| panic!("Error comparing directories: {}", e));
+            print!("{}", stats.report());
+        }
+        (false, false) => {
+            let mut service = None;
+            let tile_a = diff_tile(a, args, &mut service);
+            let tile_b = diff_tile(b, args, &mut service);
+            let diff = tile_diff::diff_tiles(&tile_a, &tile_b, geojson.is_some());
+            print!("{}", diff.report());
+            features = diff.geojson_features(b);
+        }
+        _ => panic!("Cache directories can only be compared with cache directories"),
+    }
+    if let Some(fname) = geojson {
+        tile_diff::write_geojson(fname, features)
+            .unwrap_or_else(|e| panic!("Error writing '{}': {}", fname, e));
+    }
+}
+
+fn upgrade_config(args: &ArgMatches<'_>) {
+    let path = args.value_of("config").unwrap();
+    let config_toml = config::read_config_file(path).unwrap_or_else(|e| {
+        println!("{}", e);
+        process::exit(1)
+    });
+    let upgrade = upgrade::upgrade_config(&config_toml, args.value_of("from"));
+    if let Some(version) = upgrade.detected_before {
+        info!("Configuration written for t-rex before {}", version);
+    }
+    for (line, migration) in &upgrade.applied {
+        info!(
+            "{}:{} - {} ({})",
+            path, line, migration.note, migration.version
+        );
+    }
+    let output = args.value_of("output").unwrap_or(path);
+    if args.is_present("dry-run") {
+        print!(
+            "{}",
+            upgrade::diff(&config_toml, &upgrade.config, path, output)
+        );
+        return;
+    }
+    if upgrade.applied.is_empty() {
+        info!("No migrations applied");
+        if output == path {
+            return;
+        }
+    }
+    if output.ends_with(".gz") {
+        println!("Writing compressed configs is not supported");
+        process::exit(1)
+    }
+    if output == path {
+        let backup = format!("{}.bak", path);
+        fs::copy(path, &backup).unwrap_or_else(|e| panic!("Error writing '{}': {}", backup, e));
+    }
+    fs::write(output, &upgrade.config)
+        .unwrap_or_else(|e| panic!("Error writing '{}': {}", output, e));
+}
+
+fn grid_export(args: &ArgMatches<'_>) {
+    let config = webserver::config_from_args(&args);
+    let grid = Grid::from_config(&config.grid).unwrap_or_else(|e| {
+        println!("Error reading grid - {}", e);
+        process::exit(1)
+    });
+    let json = match args.value_of("format").unwrap_or("tms-json") {
+        "tms-json" => tilematrixset::tile_matrix_set(&grid),
+        format => {
+            println!("Unsupported format '{}'", format);
+            process::exit(1)
+        }
+    };
+    let json = serde_json::to_string_pretty(&json).unwrap();
+    match args.value_of("output") {
+        Some(output) => fs::write(output, json + "\n")
+            .unwrap_or_else(|e| panic!("Error writing '{}': {}", output, e)),
+        None => println!("{}", json),
+    }
 }
 
 fn main() {
@@ -153,9 +277,16 @@ fn main() {
         .version(&version_info as &str)
         .author("Pirmin Kalberer <pka@sourcepole.ch>")
         .about("vector tile server specialized on publishing MVT tiles from your own data")
+        // --version with --verbose
+        .setting(AppSettings::DisableVersion)
+        .arg(Arg::from_usage("-V, --version 'Prints version information'"))
+        .arg(Arg::from_usage("--verbose 'Print build details with --version'"))
+        .arg(Arg::from_usage("--log-level=[LEVEL] 'Log level (error|warn|info|debug|trace) or filter like info,t_rex_core=debug (Default: info, overridden by RUST_LOG)'")
+                        .global(true))
         .subcommand(SubCommand::with_name("serve")
-                        .args_from_usage("--dbconn=[SPEC] 'PostGIS connection postgresql://USER@HOST/DBNAME'
+                        .args_from_usage("--dbconn=[SPEC]... 'PostGIS connection postgresql://USER@HOST/DBNAME or NAME=URL of a named datasource'
                                               --datasource=[FILE_OR_GDAL_DS] 'GDAL datasource specification'
+                                              --csv=[FILE] 'CSV file with lon/lat or WKT geometry columns'
                                               --detect-geometry-types=[true|false] 'Detect geometry types when undefined'
                                               --qgs=[FILE] 'QGIS project file'
                                               --loglevel=[error|warn|info|debug|trace] 'Log level (Default: info)'
@@ -165,31 +296,29 @@ fn main() {
                                               --cache=[DIR] 'Use tile cache in DIR'
                                               -c, --config=[FILE] 'Load from custom config file'
                                               --bind=[IPADDRESS] 'Bind web server to this address (0.0.0.0 for all)'
-                                              --port=[PORT] 'Bind web server to this port'
-                                              --openbrowser=[true|false] 'Open backend URL in browser'")
+                                              --port=[PORT] 'Bind web server to this port (0 for any free port)'
+                                              --openbrowser=[true|false] 'Open backend URL in browser'
+                                              --profile=[NAME] 'Use layer queries of profile NAME (Default: TREX_PROFILE)'
+                                              --strict=[false|true] 'Exit on configuration warnings like mismatching layer geometry types'
+                                              --no-viewer-assets 'Serve without built-in viewer, fonts and landing page'
+                                              --watch-config 'Restart server after changes of the config file or included files (development)'
+                                              --dump-config 'Print configuration with merged tileset and layer defaults and exit'")
                         .about("Start web server and serve MVT vector tiles"))
         .subcommand(SubCommand::with_name("genconfig")
-                        .args_from_usage("--dbconn=[SPEC] 'PostGIS connection postgresql://USER@HOST/DBNAME'
+                        .args_from_usage("--dbconn=[SPEC]... 'PostGIS connection postgresql://USER@HOST/DBNAME or NAME=URL of a named datasource'
                                               --datasource=[FILE_OR_GDAL_DS] 'GDAL datasource specification'
+                                              --csv=[FILE] 'CSV file with lon/lat or WKT geometry columns'
                                               --detect-geometry-types=[true|false] 'Detect geometry types when undefined'
                                               --qgs=[FILE] 'QGIS project file'
                                               --loglevel=[error|warn|info|debug|trace] 'Log level (Default: info)'
                                               --simplify=[true|false] 'Simplify geometries'
                                               --clip=[true|false] 'Clip geometries'
-                                              --no-transform=[true|false] 'Do not transform to grid SRS'")
+                                              --no-transform=[true|false] 'Do not transform to grid SRS'
+                                              --defaults=[false|true] 'Emit values repeated in all tilesets and layers into [tileset_defaults] and [layer_defaults]'")
                         .about("Generate configuration template"))
         .subcommand(SubCommand::with_name("generate")
                         .setting(AppSettings::AllowLeadingHyphen)
-                        .args_from_usage("-c, --config=<FILE> 'Load from custom config file'
-                                              --loglevel=[error|warn|info|debug|trace] 'Log level (Default: info)'
-                                              --tileset=[NAME] 'Tileset name'
-                                              --minzoom=[LEVEL] 'Minimum zoom level'
-                                              --maxzoom=[LEVEL] 'Maximum zoom level'
-                                              --extent=[minx,miny,maxx,maxy] 'Extent of tiles'
-                                              --nodes=[NUM] 'Number of generator nodes'
-                                              --nodeno=[NUM] 'Number of this nodes (0 <= n < nodes)'
-                                              --progress=[true|false] 'Show progress bar'
-                                              --overwrite=[false|true] 'Overwrite previously cached tiles'")
+                        .args_from_usage(cli::GENERATE_ARGS)
                         .about("Generate tiles for cache"))
         .subcommand(SubCommand::with_name("drilldown")
                         .setting(AppSettings::AllowLeadingHyphen)
@@ -199,8 +328,50 @@ fn main() {
                                               --minzoom=[LEVEL] 'Minimum zoom level'
                                               --maxzoom=[LEVEL] 'Maximum zoom level'
                                               --points=[x1,y1,x2,y2,..] 'Drilldown points'
-                                              --progress=[true|false] 'Show progress bar'")
-                        .about("Tile layer statistics"));
+                                              --progress=[true|false] 'Show progress bar'
+                                              --profile=[NAME] 'Use layer queries of profile NAME (Default: TREX_PROFILE)'")
+                        .about("Tile layer statistics"))
+        .subcommand(SubCommand::with_name("check")
+                        .args_from_usage("-c, --config=<FILE> 'Load from custom config file'
+                                              --loglevel=[error|warn|info|debug|trace] 'Log level (Default: info)'
+                                              --profile=[NAME] 'Use layer queries of profile NAME (Default: TREX_PROFILE)'")
+                        .about("Check configuration and layer queries"))
+        .subcommand(SubCommand::with_name("query")
+                        .args_from_usage("-c, --config=<FILE> 'Load from custom config file'
+                                              --loglevel=[error|warn|info|debug|trace] 'Log level (Default: info)'
+                                              --tileset=<NAME> 'Tileset name'
+                                              --layer=<NAME> 'Layer name'
+                                              --zoom=[LEVEL] 'Zoom level'
+                                              --tile=[Z/X/Y] 'Tile coordinates for bbox parameters (Default: grid extent)'
+                                              --explain 'Print query plan (EXPLAIN)'
+                                              --analyze 'Print query plan with execution statistics (EXPLAIN ANALYZE)'
+                                              --profile=[NAME] 'Use layer queries of profile NAME (Default: TREX_PROFILE)'")
+                        .about("Print layer query with parameter values"))
+        .subcommand(SubCommand::with_name("diff")
+                        .args_from_usage("-c, --config=[FILE] 'Load from custom config file (for tile coordinates)'
+                                              --loglevel=[error|warn|info|debug|trace] 'Log level (Default: info)'
+                                              --tileset=[NAME] 'Tileset name of tile coordinates Z/X/Y'
+                                              --profile=[NAME] 'Use layer queries of profile NAME (Default: TREX_PROFILE)'
+                                              --geojson-out=[FILE] 'Write added and removed features as GeoJSON (tile coordinates)'
+                                              <a> 'Tile file, cache directory or tile coordinates [TILESET/]Z/X/Y'
+                                              <b> 'Tile file, cache directory or tile coordinates [TILESET/]Z/X/Y'")
+                        .about("Compare two tiles or tile cache directories"))
+        .subcommand(SubCommand::with_name("upgrade-config")
+                        .args_from_usage("-c, --config=<FILE> 'Config file to upgrade'
+                                              --loglevel=[error|warn|info|debug|trace] 'Log level (Default: info)'
+                                              --from=[VERSION] 'Version of t-rex the config was written for (Default: detected from legacy syntax)'
+                                              -o, --output=[FILE] 'Write upgraded config to FILE (Default: replace config, keeping a copy with .bak extension)'
+                                              --dry-run 'Print changes as diff without writing'")
+                        .about("Migrate configuration of older t-rex versions"))
+        .subcommand(SubCommand::with_name("grid")
+                        .setting(AppSettings::SubcommandRequiredElseHelp)
+                        .subcommand(SubCommand::with_name("export")
+                                        .args_from_usage("-c, --config=<FILE> 'Load from custom config file'
+                                                              --loglevel=[error|warn|info|debug|trace] 'Log level (Default: info)'
+                                                              --format=[tms-json] 'Output format (Default: tms-json, OGC TileMatrixSet 2.0 JSON)'
+                                                              -o, --output=[FILE] 'Write grid definition to FILE (Default: stdout)'")
+                                        .about("Export grid definition"))
+                        .about("Grid definition commands"));
 
     match app.get_matches_from_safe_borrow(env::args()) {
         //app.get_matches() prohibits later call of app.print_help()
@@ -210,7 +381,11 @@ fn main() {
         Result::Ok(matches) => match matches.subcommand() {
             ("serve", Some(sub_m)) => {
                 init_logger(sub_m);
-                let _ = webserver::webserver(sub_m.clone());
+                if sub_m.is_present("dump-config") {
+                    print!("{}", webserver::dump_config(sub_m));
+                } else {
+                    let _ = webserver::webserver(sub_m.clone(), build_info());
+                }
             }
             ("genconfig", Some(sub_m)) => {
                 init_logger(sub_m);
@@ -224,6 +399,36 @@ fn main() {
                 init_logger(sub_m);
                 drilldown(sub_m);
             }
+            ("check", Some(sub_m)) => {
+                init_logger(sub_m);
+                check(sub_m);
+            }
+            ("query", Some(sub_m)) => {
+                init_logger(sub_m);
+                query(sub_m);
+            }
+            ("diff", Some(sub_m)) => {
+                init_logger(sub_m);
+                diff(sub_m);
+            }
+            ("upgrade-config", Some(sub_m)) => {
+                init_logger(sub_m);
+                upgrade_config(sub_m);
+            }
+            ("grid", Some(grid_m)) => match grid_m.subcommand() {
+                ("export", Some(sub_m)) => {
+                    init_logger(sub_m);
+                    grid_export(sub_m);
+                }
+                _ => unreachable!(),
+            },
+            _ if matches.is_present("version") => {
+                if matches.is_present("verbose") {
+                    println!("{}", build_info().details());
+                } else {
+                    println!("t_rex {}", version_info);
+                }
+            }
             _ => {
                 let _ = app.print_help();
                 println!("");
@@ -0,0 +1,30 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+//! Headless tile generation without web server assets
+
+#[macro_use]
+extern crate clap;
+
+mod cli;
+
+use crate::cli::{generate, init_logger, version_info};
+use clap::{App, AppSettings, Arg};
+use dotenv::dotenv;
+
+fn main() {
+    dotenv().ok();
+    let version_info = version_info();
+    let matches = App::new("t_rex_seed")
+        .version(&version_info as &str)
+        .author("Pirmin Kalberer <pka@sourcepole.ch>")
+        .about("Generate t-rex vector tiles for cache")
+        .setting(AppSettings::AllowLeadingHyphen)
+        .arg(Arg::from_usage("--log-level=[LEVEL] 'Log level (error|warn|info|debug|trace) or filter like info,t_rex_core=debug (Default: info, overridden by RUST_LOG)'"))
+        .args_from_usage(cli::GENERATE_ARGS)
+        .get_matches();
+    init_logger(&matches);
+    generate(&matches);
+}
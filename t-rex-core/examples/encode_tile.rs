@@ -0,0 +1,99 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+//! Encode GeoJSON points and lines into a vector tile without any datasource.
+//!
+//! This only uses functionality available without default features:
+//!
+//!     cargo run -p t-rex-core --no-default-features --example encode_tile -- 8/133/89 tile.pbf
+
+use serde_json::Value;
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use t_rex_core::core::feature::{FeatureAttr, FeatureAttrValType, FeatureStruct};
+use t_rex_core::core::geom::{self, GeometryType};
+use t_rex_core::mvt::tile::{encode_tile, LayerFeatures};
+use tile_grid::{lonlat_to_merc, Grid};
+
+const GEOJSON: &str = r#"{
+  "type": "FeatureCollection",
+  "features": [
+    {"type": "Feature", "properties": {"name": "Bern"},
+     "geometry": {"type": "Point", "coordinates": [7.44, 46.95]}},
+    {"type": "Feature", "properties": {"name": "Zurich"},
+     "geometry": {"type": "Point", "coordinates": [8.54, 47.37]}},
+    {"type": "Feature", "properties": {"name": "Bern-Zurich"},
+     "geometry": {"type": "LineString", "coordinates": [[7.44, 46.95], [8.54, 47.37]]}}
+  ]
+}"#;
+
+fn point(coords: &Value) -> geom::Point {
+    let (x, y) = lonlat_to_merc(coords[0].as_f64().unwrap(), coords[1].as_f64().unwrap());
+    geom::Point::new(x, y, Some(3857))
+}
+
+fn geometry(geojson: &Value) -> Option<GeometryType> {
+    let coords = &geojson["coordinates"];
+    match geojson["type"].as_str()? {
+        "Point" => Some(GeometryType::Point(point(coords))),
+        "LineString" => Some(GeometryType::LineString(geom::LineString {
+            points: coords.as_array()?.iter().map(point).collect(),
+            srid: Some(3857),
+        })),
+        _ => None,
+    }
+}
+
+fn feature(geojson: &Value) -> Option<FeatureStruct> {
+    let attributes = geojson["properties"]
+        .as_object()?
+        .iter()
+        .filter_map(|(key, value)| {
+            value.as_str().map(|s| FeatureAttr {
+                key: key.clone(),
+                value: FeatureAttrValType::String(s.to_string()),
+            })
+        })
+        .collect();
+    Some(FeatureStruct {
+        fid: None,
+        attributes,
+        geometry: geometry(&geojson["geometry"])?,
+    })
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let zxy: Vec<u32> = args
+        .get(1)
+        .map(|s| s.as_str())
+        .unwrap_or("8/133/89")
+        .split('/')
+        .map(|n| n.parse().expect("Tile z/x/y expected"))
+        .collect();
+    let extent = Grid::web_mercator().tile_extent_xyz(zxy[1], zxy[2], zxy[0] as u8);
+
+    let geojson: Value = serde_json::from_str(GEOJSON).unwrap();
+    let features = geojson["features"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(feature)
+        .collect();
+    let layers = vec![LayerFeatures {
+        name: "places".to_string(),
+        features,
+    }];
+    let tiledata = encode_tile(layers, &extent, 64);
+
+    match args.get(2) {
+        Some(fname) => {
+            let mut f = File::create(fname).unwrap();
+            f.write_all(&tiledata).unwrap();
+        }
+        None => println!("Encoded tile size: {} bytes", tiledata.len()),
+    }
+}
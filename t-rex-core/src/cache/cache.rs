@@ -3,8 +3,37 @@
 // Licensed under the MIT License. See LICENSE file in the project root for full license information.
 //
 
+use std::collections::BTreeMap;
 use std::io;
 use std::io::Read;
+use std::time::SystemTime;
+
+/// Number of cached tiles with their oldest and newest creation time
+#[derive(PartialEq, Clone, Debug)]
+pub struct TileTimes {
+    pub tiles: u64,
+    pub oldest: SystemTime,
+    pub newest: SystemTime,
+}
+
+impl TileTimes {
+    pub fn new(time: SystemTime) -> TileTimes {
+        TileTimes {
+            tiles: 1,
+            oldest: time,
+            newest: time,
+        }
+    }
+    /// Add tiles of other tile times
+    pub fn merge(&mut self, other: &TileTimes) {
+        self.tiles += other.tiles;
+        self.oldest = self.oldest.min(other.oldest);
+        self.newest = self.newest.max(other.newest);
+    }
+}
+
+/// Tile times per zoom level
+pub type ZoomTileTimes = BTreeMap<u8, TileTimes>;
 
 pub trait Cache {
     fn info(&self) -> String;
@@ -15,6 +44,11 @@ pub trait Cache {
         F: FnMut(&mut dyn Read);
     fn write(&self, path: &str, obj: &[u8]) -> Result<(), io::Error>;
     fn exists(&self, path: &str) -> bool;
+    /// Creation time of a cache entry. `None` if missing or not recorded by the cache.
+    fn modified(&self, path: &str) -> Option<SystemTime>;
+    /// Tile times per zoom level of the tiles in `tileset/z/x/y.pbf`.
+    /// `None` if the cache doesn't record creation times.
+    fn tile_times(&self, tileset: &str) -> Option<ZoomTileTimes>;
 }
 
 #[derive(Clone)]
@@ -42,4 +76,10 @@ impl Cache for Nocache {
     fn exists(&self, _path: &str) -> bool {
         false
     }
+    fn modified(&self, _path: &str) -> Option<SystemTime> {
+        None
+    }
+    fn tile_times(&self, _tileset: &str) -> Option<ZoomTileTimes> {
+        None
+    }
 }
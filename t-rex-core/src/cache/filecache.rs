@@ -3,15 +3,48 @@
 // Licensed under the MIT License. See LICENSE file in the project root for full license information.
 //
 
-use crate::cache::cache::Cache;
+use crate::cache::cache::{Cache, TileTimes, ZoomTileTimes};
 use std::fs::{self, File};
 use std::io::{self, Read, Write};
 use std::path::Path;
+use std::time::SystemTime;
 
 #[derive(Clone)]
 pub struct Filecache {
     pub basepath: String,
     pub baseurl: Option<String>,
+    /// File extension of tiles instead of "pbf"
+    pub extension: Option<String>,
+}
+
+impl Filecache {
+    /// File path of a cache path like `tileset/z/x/y.pbf`
+    fn file_path(&self, path: &str) -> String {
+        let path = match self.extension {
+            Some(ref ext) if path.ends_with(".pbf") => format!(
+                "{}.{}",
+                &path[..path.len() - 4],
+                ext.trim_start_matches('.')
+            ),
+            _ => path.to_string(),
+        };
+        format!("{}/{}", self.basepath, path)
+    }
+    /// Subdirectories with numeric names (zoom levels and tile columns)
+    fn numeric_dirs(dir: &Path) -> Vec<(u32, fs::DirEntry)> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().map(|t| t.is_dir()).unwrap_or(false))
+            .filter_map(|entry| {
+                let num = entry.file_name().to_str()?.parse().ok()?;
+                Some((num, entry))
+            })
+            .collect()
+    }
 }
 
 impl Cache for Filecache {
@@ -27,7 +60,7 @@ impl Cache for Filecache {
     where
         F: FnMut(&mut dyn Read),
     {
-        let fullpath = format!("{}/{}", self.basepath, path);
+        let fullpath = self.file_path(path);
         debug!("Filecache.read {}", fullpath);
         match File::open(&fullpath) {
             Ok(mut f) => {
@@ -38,7 +71,7 @@ impl Cache for Filecache {
         }
     }
     fn write(&self, path: &str, obj: &[u8]) -> Result<(), io::Error> {
-        let fullpath = format!("{}/{}", self.basepath, path);
+        let fullpath = self.file_path(path);
         debug!("Filecache.write {}", fullpath);
         let p = Path::new(&fullpath);
         fs::create_dir_all(p.parent().unwrap())?;
@@ -47,7 +80,46 @@ impl Cache for Filecache {
     }
 
     fn exists(&self, path: &str) -> bool {
-        let fullpath = format!("{}/{}", self.basepath, path);
+        let fullpath = self.file_path(path);
         Path::new(&fullpath).exists()
     }
+    /// File modification time
+    fn modified(&self, path: &str) -> Option<SystemTime> {
+        let fullpath = self.file_path(path);
+        fs::metadata(&fullpath).and_then(|m| m.modified()).ok()
+    }
+    /// File modification times collected by scanning the directory of the tileset
+    fn tile_times(&self, tileset: &str) -> Option<ZoomTileTimes> {
+        let ext = self
+            .extension
+            .as_ref()
+            .map(|ext| ext.trim_start_matches('.'))
+            .unwrap_or("pbf");
+        let mut times = ZoomTileTimes::new();
+        let dir = Path::new(&self.basepath).join(tileset);
+        for (zoom, zdir) in Filecache::numeric_dirs(&dir) {
+            if zoom > u8::max_value() as u32 {
+                continue;
+            }
+            for (_, xdir) in Filecache::numeric_dirs(&zdir.path()) {
+                for entry in fs::read_dir(xdir.path()).into_iter().flatten() {
+                    let path = match entry {
+                        Ok(entry) => entry.path(),
+                        Err(_) => continue,
+                    };
+                    if path.extension().and_then(|e| e.to_str()) != Some(ext) {
+                        continue;
+                    }
+                    if let Ok(time) = fs::metadata(&path).and_then(|m| m.modified()) {
+                        let tile_times = TileTimes::new(time);
+                        times
+                            .entry(zoom as u8)
+                            .and_modify(|t| t.merge(&tile_times))
+                            .or_insert(tile_times);
+                    }
+                }
+            }
+        }
+        Some(times)
+    }
 }
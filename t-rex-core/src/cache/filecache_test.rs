@@ -20,6 +20,7 @@ fn test_dircache() {
     let cache = Filecache {
         basepath: basepath,
         baseurl: Some("http://localhost:6767".to_string()),
+        extension: None,
     };
     let path = "tileset/0/1/2.pbf";
     let fullpath = format!("{}/{}", cache.basepath, path);
@@ -42,3 +43,70 @@ fn test_dircache() {
     });
     assert_eq!(&s, "0123456789");
 }
+
+#[test]
+fn test_extension() {
+    use std::env;
+
+    let mut dir = env::temp_dir();
+    dir.push("t_rex_test_extension");
+    let basepath = format!("{}", &dir.display());
+    let _ = fs::remove_dir_all(&basepath);
+
+    let cache = Filecache {
+        basepath: basepath.clone(),
+        baseurl: None,
+        extension: Some("vector.pbf".to_string()),
+    };
+    cache.write("tileset/0/1/2.pbf", b"tile").unwrap();
+    assert!(Path::new(&format!("{}/tileset/0/1/2.vector.pbf", basepath)).exists());
+    assert!(cache.exists("tileset/0/1/2.pbf"));
+
+    // Tiles written by other tools
+    fs::write(format!("{}/tileset/0/1/3.vector.pbf", basepath), b"tile").unwrap();
+    let mut s = String::new();
+    assert!(cache.read("tileset/0/1/3.pbf", |f| {
+        let _ = f.read_to_string(&mut s);
+    }));
+    assert_eq!(&s, "tile");
+
+    let cache = Filecache {
+        basepath: basepath.clone(),
+        baseurl: None,
+        extension: Some(".mvt".to_string()),
+    };
+    cache.write("tileset/0/1/2.pbf", b"tile").unwrap();
+    assert!(Path::new(&format!("{}/tileset/0/1/2.mvt", basepath)).exists());
+}
+
+#[test]
+fn test_tile_times() {
+    use std::env;
+
+    let mut dir = env::temp_dir();
+    dir.push("t_rex_test_tile_times");
+    let basepath = format!("{}", &dir.display());
+    let _ = fs::remove_dir_all(&basepath);
+
+    let cache = Filecache {
+        basepath: basepath.clone(),
+        baseurl: None,
+        extension: None,
+    };
+    assert_eq!(cache.modified("tileset/0/1/2.pbf"), None);
+    assert_eq!(cache.tile_times("tileset").unwrap().len(), 0);
+
+    cache.write("tileset/0/0/0.pbf", b"tile").unwrap();
+    cache.write("tileset/2/1/1.pbf", b"tile").unwrap();
+    cache.write("tileset/2/1/2.pbf", b"tile").unwrap();
+    cache.write("tileset.json", b"{}").unwrap();
+    fs::write(format!("{}/tileset/2/1/readme.txt", basepath), b"").unwrap();
+    let modified = cache.modified("tileset/2/1/2.pbf").unwrap();
+
+    let times = cache.tile_times("tileset").unwrap();
+    assert_eq!(times.keys().collect::<Vec<_>>(), vec![&0, &2]);
+    assert_eq!(times[&0].tiles, 1);
+    assert_eq!(times[&2].tiles, 2);
+    assert!(times[&2].oldest <= times[&2].newest);
+    assert!(times[&2].newest >= modified);
+}
@@ -0,0 +1,436 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use crate::cache::cache::{Cache, ZoomTileTimes};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tile_grid::{extent_to_merc, Extent, Grid, Origin, TmsTile, XyzTile};
+
+/// GeoPackage output of `generate` (http://www.geopackage.org/spec/).
+///
+/// Each tileset is written into a tile pyramid user data table with the tileset name.
+/// Tiles are stored as (gzip compressed) MVT according to the vector tiles extension draft
+/// of the OGC Vector Tiles Pilot. Layers and fields from the TileJSON `vector_layers`
+/// are written into the `gpkgext_vt_layers` and `gpkgext_vt_fields` tables.
+/// Other cache entries like style JSON are not stored.
+#[derive(Clone)]
+pub struct GpkgCache {
+    pub path: String,
+    grid: Grid,
+    conn: Arc<Mutex<Connection>>,
+    /// TileJSON of tilesets without tiles
+    tilejsons: Arc<Mutex<HashMap<String, serde_json::Value>>>,
+    /// Tilesets with initialized tile table
+    tables: Arc<Mutex<HashSet<String>>>,
+}
+
+/// `application_id` of GeoPackage files ("GPKG")
+const APPLICATION_ID: i32 = 0x4750_4B47;
+
+/// GeoPackage version 1.2
+const USER_VERSION: i32 = 10200;
+
+const VT_EXTENSION_DEF: &str = "OGC Vector Tiles Pilot: GeoPackage vector tiles extension (draft)";
+
+const SCHEMA: &str = "
+CREATE TABLE gpkg_spatial_ref_sys (
+    srs_name TEXT NOT NULL,
+    srs_id INTEGER PRIMARY KEY,
+    organization TEXT NOT NULL,
+    organization_coordsys_id INTEGER NOT NULL,
+    definition TEXT NOT NULL,
+    description TEXT
+);
+CREATE TABLE gpkg_contents (
+    table_name TEXT NOT NULL PRIMARY KEY,
+    data_type TEXT NOT NULL,
+    identifier TEXT UNIQUE,
+    description TEXT DEFAULT '',
+    last_change DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+    min_x DOUBLE,
+    min_y DOUBLE,
+    max_x DOUBLE,
+    max_y DOUBLE,
+    srs_id INTEGER,
+    CONSTRAINT fk_gc_r_srs_id FOREIGN KEY (srs_id) REFERENCES gpkg_spatial_ref_sys(srs_id)
+);
+CREATE TABLE gpkg_tile_matrix_set (
+    table_name TEXT NOT NULL PRIMARY KEY,
+    srs_id INTEGER NOT NULL,
+    min_x DOUBLE NOT NULL,
+    min_y DOUBLE NOT NULL,
+    max_x DOUBLE NOT NULL,
+    max_y DOUBLE NOT NULL,
+    CONSTRAINT fk_gtms_table_name FOREIGN KEY (table_name) REFERENCES gpkg_contents(table_name),
+    CONSTRAINT fk_gtms_srs FOREIGN KEY (srs_id) REFERENCES gpkg_spatial_ref_sys (srs_id)
+);
+CREATE TABLE gpkg_tile_matrix (
+    table_name TEXT NOT NULL,
+    zoom_level INTEGER NOT NULL,
+    matrix_width INTEGER NOT NULL,
+    matrix_height INTEGER NOT NULL,
+    tile_width INTEGER NOT NULL,
+    tile_height INTEGER NOT NULL,
+    pixel_x_size DOUBLE NOT NULL,
+    pixel_y_size DOUBLE NOT NULL,
+    CONSTRAINT pk_ttm PRIMARY KEY (table_name, zoom_level),
+    CONSTRAINT fk_tmm_table_name FOREIGN KEY (table_name) REFERENCES gpkg_contents(table_name)
+);
+CREATE TABLE gpkg_extensions (
+    table_name TEXT,
+    column_name TEXT,
+    extension_name TEXT NOT NULL,
+    definition TEXT NOT NULL,
+    scope TEXT NOT NULL,
+    CONSTRAINT ge_tce UNIQUE (table_name, column_name, extension_name)
+);
+CREATE TABLE gpkgext_vt_layers (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    table_name TEXT NOT NULL,
+    name TEXT NOT NULL,
+    description TEXT,
+    minzoom INTEGER,
+    maxzoom INTEGER,
+    attributes_table_name TEXT,
+    CONSTRAINT fk_gpkgext_vt_layers_table_name FOREIGN KEY (table_name) REFERENCES gpkg_contents(table_name),
+    CONSTRAINT uk_gpkgext_vt_layers UNIQUE (table_name, name)
+);
+CREATE TABLE gpkgext_vt_fields (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    layer_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    type TEXT NOT NULL,
+    CONSTRAINT fk_gpkgext_vt_fields_layer_id FOREIGN KEY (layer_id) REFERENCES gpkgext_vt_layers(id),
+    CONSTRAINT uk_gpkgext_vt_fields UNIQUE (layer_id, name)
+);
+INSERT INTO gpkg_spatial_ref_sys VALUES
+    ('Undefined cartesian SRS', -1, 'NONE', -1, 'undefined', 'undefined cartesian coordinate reference system'),
+    ('Undefined geographic SRS', 0, 'NONE', 0, 'undefined', 'undefined geographic coordinate reference system'),
+    ('WGS 84 geodetic', 4326, 'EPSG', 4326, 'GEOGCS[\"WGS 84\",DATUM[\"WGS_1984\",SPHEROID[\"WGS 84\",6378137,298.257223563,AUTHORITY[\"EPSG\",\"7030\"]],AUTHORITY[\"EPSG\",\"6326\"]],PRIMEM[\"Greenwich\",0,AUTHORITY[\"EPSG\",\"8901\"]],UNIT[\"degree\",0.0174532925199433,AUTHORITY[\"EPSG\",\"9122\"]],AUTHORITY[\"EPSG\",\"4326\"]]', 'longitude/latitude coordinates in decimal degrees on the WGS 84 spheroid');
+";
+
+const WEB_MERCATOR_WKT: &str = "PROJCS[\"WGS 84 / Pseudo-Mercator\",GEOGCS[\"WGS 84\",DATUM[\"WGS_1984\",SPHEROID[\"WGS 84\",6378137,298.257223563,AUTHORITY[\"EPSG\",\"7030\"]],AUTHORITY[\"EPSG\",\"6326\"]],PRIMEM[\"Greenwich\",0,AUTHORITY[\"EPSG\",\"8901\"]],UNIT[\"degree\",0.0174532925199433,AUTHORITY[\"EPSG\",\"9122\"]],AUTHORITY[\"EPSG\",\"4326\"]],PROJECTION[\"Mercator_1SP\"],PARAMETER[\"central_meridian\",0],PARAMETER[\"scale_factor\",1],PARAMETER[\"false_easting\",0],PARAMETER[\"false_northing\",0],UNIT[\"metre\",1,AUTHORITY[\"EPSG\",\"9001\"]],AXIS[\"X\",EAST],AXIS[\"Y\",NORTH],EXTENSION[\"PROJ4\",\"+proj=merc +a=6378137 +b=6378137 +lat_ts=0.0 +lon_0=0.0 +x_0=0.0 +y_0=0 +k=1.0 +units=m +nadgrids=@null +wktext +no_defs\"],AUTHORITY[\"EPSG\",\"3857\"]]";
+
+fn io_error<E: ToString>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e.to_string())
+}
+
+/// Split cache path `tileset/z/x/y.pbf` into its components
+fn parse_tile_path(path: &str) -> Option<(&str, u8, u32, u32)> {
+    let mut parts = path.rsplitn(4, '/');
+    let y = parts.next()?.trim_end_matches(".pbf").parse().ok()?;
+    let x = parts.next()?.parse().ok()?;
+    let z = parts.next()?.parse().ok()?;
+    let tileset = parts.next()?;
+    Some((tileset, z, x, y))
+}
+
+/// Quoted SQL identifier
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+impl GpkgCache {
+    /// Create GeoPackage file. An existing file is replaced.
+    pub fn create(path: &str, grid: &Grid) -> Result<Self, String> {
+        if Path::new(path).exists() {
+            fs::remove_file(path)
+                .map_err(|e| format!("Error replacing GeoPackage '{}': {}", path, e))?;
+        }
+        let conn = Connection::open(path)
+            .and_then(|conn| {
+                conn.execute_batch(&format!(
+                    "PRAGMA application_id = {}; PRAGMA user_version = {};",
+                    APPLICATION_ID, USER_VERSION
+                ))?;
+                conn.execute_batch(SCHEMA)?;
+                Ok(conn)
+            })
+            .map_err(|e| format!("Error creating GeoPackage '{}': {}", path, e))?;
+        match grid.srid {
+            -1 | 0 | 4326 => {}
+            3857 => {
+                conn.execute(
+                    "INSERT INTO gpkg_spatial_ref_sys VALUES ('WGS 84 / Pseudo-Mercator', 3857, 'EPSG', 3857, ?1, NULL)",
+                    params![WEB_MERCATOR_WKT],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+            srid => {
+                warn!(
+                    "GeoPackage '{}': no WKT definition for EPSG:{} available",
+                    path, srid
+                );
+                conn.execute(
+                    "INSERT INTO gpkg_spatial_ref_sys VALUES (?1, ?2, 'EPSG', ?2, 'undefined', NULL)",
+                    params![format!("EPSG:{}", srid), srid],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(GpkgCache {
+            path: path.to_string(),
+            grid: grid.clone(),
+            conn: Arc::new(Mutex::new(conn)),
+            tilejsons: Arc::new(Mutex::new(HashMap::new())),
+            tables: Arc::new(Mutex::new(HashSet::new())),
+        })
+    }
+    /// Tileset and tile of a cache path. The GeoPackage `tile_row` is the XYZ row.
+    /// Cache paths of Web Mercator and top-left grids are in XYZ scheme, others in TMS scheme.
+    fn path_tile<'a>(&self, path: &'a str) -> Option<(&'a str, XyzTile)> {
+        let (tileset, z, x, y) = parse_tile_path(path)?;
+        let tile = if self.grid.srid == 3857 || self.grid.origin == Origin::TopLeft {
+            XyzTile { x, y, z }
+        } else {
+            self.grid.tms_to_xyz(&TmsTile { x, y, z })
+        };
+        Some((tileset, tile))
+    }
+    /// Bounds of tileset in grid SRS
+    fn contents_bounds(&self, bounds: Option<Extent>) -> Extent {
+        match (self.grid.srid, bounds) {
+            (4326, Some(bounds)) => bounds,
+            (3857, Some(bounds)) => extent_to_merc(&bounds),
+            _ => self.grid.extent.clone(),
+        }
+    }
+    /// Create tables of tileset on first tile
+    fn init_tileset(&self, tileset: &str) -> Result<(), io::Error> {
+        let mut tables = self.tables.lock().unwrap();
+        if tables.contains(tileset) {
+            return Ok(());
+        }
+        let tilejson = self
+            .tilejsons
+            .lock()
+            .unwrap()
+            .remove(tileset)
+            .ok_or_else(|| {
+                io_error(format!(
+                    "GeoPackage: missing TileJSON of tileset '{}'",
+                    tileset
+                ))
+            })?;
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(io_error)?;
+        self.create_tileset(&tx, tileset, &tilejson)
+            .and_then(|_| tx.commit())
+            .map_err(io_error)?;
+        tables.insert(tileset.to_string());
+        Ok(())
+    }
+    /// Create tile table and metadata entries of tileset from its TileJSON
+    fn create_tileset(
+        &self,
+        conn: &Connection,
+        tileset: &str,
+        tilejson: &serde_json::Value,
+    ) -> rusqlite::Result<()> {
+        let bounds = tilejson["bounds"].as_array().and_then(|b| {
+            let b = b.iter().map(|v| v.as_f64()).collect::<Option<Vec<_>>>()?;
+            if b.len() == 4 {
+                Some(Extent {
+                    minx: b[0],
+                    miny: b[1],
+                    maxx: b[2],
+                    maxy: b[3],
+                })
+            } else {
+                None
+            }
+        });
+        let bounds = self.contents_bounds(bounds);
+        let grid = &self.grid;
+        conn.execute_batch(&format!(
+            "CREATE TABLE {} (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                zoom_level INTEGER NOT NULL,
+                tile_column INTEGER NOT NULL,
+                tile_row INTEGER NOT NULL,
+                tile_data BLOB NOT NULL,
+                UNIQUE (zoom_level, tile_column, tile_row)
+            )",
+            quote_ident(tileset)
+        ))?;
+        conn.execute(
+            "INSERT INTO gpkg_contents (table_name, data_type, identifier, description, min_x, min_y, max_x, max_y, srs_id)
+             VALUES (?1, 'vector-tiles', ?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                tileset,
+                tilejson["description"].as_str().unwrap_or(""),
+                bounds.minx,
+                bounds.miny,
+                bounds.maxx,
+                bounds.maxy,
+                grid.srid
+            ],
+        )?;
+        conn.execute(
+            "INSERT INTO gpkg_tile_matrix_set VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                tileset,
+                grid.srid,
+                grid.extent.minx,
+                grid.extent.miny,
+                grid.extent.maxx,
+                grid.extent.maxy
+            ],
+        )?;
+        let (tile_width, tile_height) = grid.tile_size();
+        let minzoom = tilejson["minzoom"].as_u64().unwrap_or(0) as u8;
+        let maxzoom = tilejson["maxzoom"]
+            .as_u64()
+            .map(|z| z as u8)
+            .unwrap_or(grid.maxzoom())
+            .min(grid.maxzoom());
+        for zoom in minzoom..=maxzoom {
+            let (matrix_width, matrix_height) = grid.level_limit(zoom);
+            conn.execute(
+                "INSERT INTO gpkg_tile_matrix VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
+                params![
+                    tileset,
+                    zoom,
+                    matrix_width,
+                    matrix_height,
+                    tile_width,
+                    tile_height,
+                    grid.resolution(zoom)
+                ],
+            )?;
+        }
+        for extension in &["im_vector_tiles", "im_vector_tiles_mapbox"] {
+            conn.execute(
+                "INSERT INTO gpkg_extensions VALUES (?1, 'tile_data', ?2, ?3, 'read-write')",
+                params![tileset, extension, VT_EXTENSION_DEF],
+            )?;
+        }
+        for table in &["gpkgext_vt_layers", "gpkgext_vt_fields"] {
+            conn.execute(
+                "INSERT OR IGNORE INTO gpkg_extensions VALUES (?1, NULL, 'im_vector_tiles', ?2, 'read-write')",
+                params![table, VT_EXTENSION_DEF],
+            )?;
+        }
+        for layer in tilejson["vector_layers"].as_array().unwrap_or(&vec![]) {
+            conn.execute(
+                "INSERT INTO gpkgext_vt_layers (table_name, name, description, minzoom, maxzoom) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    tileset,
+                    layer["id"].as_str().unwrap_or(""),
+                    layer["description"].as_str(),
+                    layer["minzoom"].as_i64(),
+                    layer["maxzoom"].as_i64()
+                ],
+            )?;
+            let layer_id = conn.last_insert_rowid();
+            if let Some(fields) = layer["fields"].as_object() {
+                for (name, fieldtype) in fields {
+                    // Field types are `String`, `Number` or `Boolean`
+                    let fieldtype = match fieldtype.as_str() {
+                        Some(t @ "Number") | Some(t @ "Boolean") => t,
+                        _ => "String",
+                    };
+                    conn.execute(
+                        "INSERT INTO gpkgext_vt_fields (layer_id, name, type) VALUES (?1, ?2, ?3)",
+                        params![layer_id, name, fieldtype],
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Tile data of tile in cache path adressing scheme
+    fn lookup(&self, path: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        let (tileset, tile) = match self.path_tile(path) {
+            Some(tile) => tile,
+            None => return Ok(None),
+        };
+        if !self.tables.lock().unwrap().contains(tileset) {
+            return Ok(None);
+        }
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            &format!(
+                "SELECT tile_data FROM {} WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+                quote_ident(tileset)
+            ),
+            params![tile.z, tile.x, tile.y],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(io_error)
+    }
+}
+
+impl Cache for GpkgCache {
+    fn info(&self) -> String {
+        format!("GeoPackage output: {}", self.path)
+    }
+    fn baseurl(&self) -> String {
+        "http://localhost:6767".to_string()
+    }
+    fn read<F>(&self, path: &str, mut read: F) -> bool
+    where
+        F: FnMut(&mut dyn Read),
+    {
+        match self.lookup(path) {
+            Ok(Some(data)) => {
+                read(&mut &data[..]);
+                true
+            }
+            Ok(None) => false,
+            Err(e) => {
+                error!("GpkgCache: error reading {}: {}", path, e);
+                false
+            }
+        }
+    }
+    /// Write tile. The TileJSON (`tileset.json`) written by `init_cache` is kept
+    /// for creating the tables of the tileset.
+    fn write(&self, path: &str, obj: &[u8]) -> Result<(), io::Error> {
+        debug!("GpkgCache.write {}", path);
+        if let Some((tileset, tile)) = self.path_tile(path) {
+            self.init_tileset(tileset)?;
+            let conn = self.conn.lock().unwrap();
+            return conn
+                .execute(
+                    &format!(
+                        "INSERT OR REPLACE INTO {} (zoom_level, tile_column, tile_row, tile_data) VALUES (?1, ?2, ?3, ?4)",
+                        quote_ident(tileset)
+                    ),
+                    params![tile.z, tile.x, tile.y, obj],
+                )
+                .map(|_| ())
+                .map_err(io_error);
+        }
+        if path.ends_with(".json") && !path.contains('/') && !path.ends_with(".style.json") {
+            let tilejson = serde_json::from_slice(obj).map_err(io_error)?;
+            self.tilejsons
+                .lock()
+                .unwrap()
+                .insert(path.trim_end_matches(".json").to_string(), tilejson);
+        }
+        Ok(())
+    }
+    fn exists(&self, path: &str) -> bool {
+        match self.lookup(path) {
+            Ok(entry) => entry.is_some(),
+            Err(_) => false,
+        }
+    }
+    /// GeoPackage tiles have no creation time
+    fn modified(&self, _path: &str) -> Option<SystemTime> {
+        None
+    }
+    fn tile_times(&self, _tileset: &str) -> Option<ZoomTileTimes> {
+        None
+    }
+}
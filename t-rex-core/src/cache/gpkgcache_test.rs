@@ -0,0 +1,64 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use crate::cache::cache::Cache;
+use crate::cache::gpkgcache::GpkgCache;
+use rusqlite::{Connection, NO_PARAMS};
+use std::env;
+use tile_grid::Grid;
+
+#[test]
+fn test_gpkgcache() {
+    let mut path = env::temp_dir();
+    path.push("t_rex_test.gpkg");
+    let path = format!("{}", &path.display());
+    let cache = GpkgCache::create(&path, &Grid::web_mercator()).unwrap();
+
+    // Tile without TileJSON of tileset
+    assert!(cache.write("osm/0/0/0.pbf", b"tile").is_err());
+
+    let tilejson = r#"{"minzoom": 0, "maxzoom": 2, "bounds": [-180.0, -90.0, 180.0, 90.0],
+        "vector_layers": [{"id": "points", "description": "", "minzoom": 0, "maxzoom": 2,
+        "fields": {"name": "", "population": "Number"}}]}"#;
+    cache.write("osm.json", tilejson.as_bytes()).unwrap();
+    cache.write("osm.style.json", b"{}").unwrap();
+    assert!(!cache.exists("osm/1/0/0.pbf"));
+    cache.write("osm/1/0/0.pbf", b"tile").unwrap();
+    assert!(cache.exists("osm/1/0/0.pbf"));
+    let mut s = String::new();
+    assert!(cache.read("osm/1/0/0.pbf", |f| {
+        let _ = f.read_to_string(&mut s);
+    }));
+    assert_eq!(s, "tile");
+
+    let conn = Connection::open(&path).unwrap();
+    let query = |sql: &str| -> String { conn.query_row(sql, NO_PARAMS, |row| row.get(0)).unwrap() };
+    assert_eq!(
+        query("SELECT CAST(application_id AS TEXT) FROM pragma_application_id"),
+        "1196444487"
+    );
+    assert_eq!(
+        query("SELECT group_concat(srs_id) FROM gpkg_spatial_ref_sys"),
+        "-1,0,3857,4326"
+    );
+    assert_eq!(
+        query("SELECT table_name || ',' || data_type || ',' || srs_id FROM gpkg_contents"),
+        "osm,vector-tiles,3857"
+    );
+    assert_eq!(
+        query("SELECT group_concat(matrix_width || 'x' || matrix_height) FROM gpkg_tile_matrix"),
+        "1x1,2x2,4x4"
+    );
+    // XYZ tile 1/0/0 is top left
+    assert_eq!(
+        query("SELECT zoom_level || '/' || tile_column || '/' || tile_row FROM osm"),
+        "1/0/0"
+    );
+    assert_eq!(
+        query("SELECT group_concat(name || ':' || type) FROM gpkgext_vt_fields"),
+        "name:String,population:Number"
+    );
+    assert_eq!(query("SELECT name FROM gpkgext_vt_layers"), "points");
+}
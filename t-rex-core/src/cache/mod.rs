@@ -5,22 +5,40 @@
 
 pub mod cache;
 pub mod filecache;
+pub mod gpkgcache;
+pub mod sqlitecache;
+pub mod tieredcache;
 
 #[cfg(test)]
 mod filecache_test;
+#[cfg(test)]
+mod gpkgcache_test;
+#[cfg(test)]
+mod sqlitecache_test;
+#[cfg(test)]
+mod tieredcache_test;
 
 pub use self::cache::Cache;
 pub use self::cache::Nocache;
+pub use self::cache::{TileTimes, ZoomTileTimes};
 pub use self::filecache::Filecache;
+pub use self::gpkgcache::GpkgCache;
+pub use self::sqlitecache::SqliteCache;
+pub use self::tieredcache::{CacheTier, TieredCache};
+use crate::core::config::{CacheFileCfg, CacheSqliteCfg};
 use crate::core::ApplicationCfg;
 use crate::core::Config;
 use std::io;
 use std::io::Read;
+use std::time::SystemTime;
 
 #[derive(Clone)]
 pub enum Tilecache {
     Nocache(Nocache),
     Filecache(Filecache),
+    SqliteCache(SqliteCache),
+    GpkgCache(GpkgCache),
+    TieredCache(TieredCache),
 }
 
 impl Cache for Tilecache {
@@ -28,12 +46,18 @@ impl Cache for Tilecache {
         match self {
             &Tilecache::Nocache(ref cache) => cache.info(),
             &Tilecache::Filecache(ref cache) => cache.info(),
+            &Tilecache::SqliteCache(ref cache) => cache.info(),
+            &Tilecache::GpkgCache(ref cache) => cache.info(),
+            &Tilecache::TieredCache(ref cache) => cache.info(),
         }
     }
     fn baseurl(&self) -> String {
         match self {
             &Tilecache::Nocache(ref cache) => cache.baseurl(),
             &Tilecache::Filecache(ref cache) => cache.baseurl(),
+            &Tilecache::SqliteCache(ref cache) => cache.baseurl(),
+            &Tilecache::GpkgCache(ref cache) => cache.baseurl(),
+            &Tilecache::TieredCache(ref cache) => cache.baseurl(),
         }
     }
     fn read<F>(&self, path: &str, read: F) -> bool
@@ -43,42 +67,135 @@ impl Cache for Tilecache {
         match self {
             &Tilecache::Nocache(ref cache) => cache.read(path, read),
             &Tilecache::Filecache(ref cache) => cache.read(path, read),
+            &Tilecache::SqliteCache(ref cache) => cache.read(path, read),
+            &Tilecache::GpkgCache(ref cache) => cache.read(path, read),
+            &Tilecache::TieredCache(ref cache) => cache.read(path, read),
         }
     }
     fn write(&self, path: &str, obj: &[u8]) -> Result<(), io::Error> {
         match self {
             &Tilecache::Nocache(ref cache) => cache.write(path, obj),
             &Tilecache::Filecache(ref cache) => cache.write(path, obj),
+            &Tilecache::SqliteCache(ref cache) => cache.write(path, obj),
+            &Tilecache::GpkgCache(ref cache) => cache.write(path, obj),
+            &Tilecache::TieredCache(ref cache) => cache.write(path, obj),
         }
     }
     fn exists(&self, path: &str) -> bool {
         match self {
             &Tilecache::Nocache(ref cache) => cache.exists(path),
             &Tilecache::Filecache(ref cache) => cache.exists(path),
+            &Tilecache::SqliteCache(ref cache) => cache.exists(path),
+            &Tilecache::GpkgCache(ref cache) => cache.exists(path),
+            &Tilecache::TieredCache(ref cache) => cache.exists(path),
+        }
+    }
+    fn modified(&self, path: &str) -> Option<SystemTime> {
+        match self {
+            &Tilecache::Nocache(ref cache) => cache.modified(path),
+            &Tilecache::Filecache(ref cache) => cache.modified(path),
+            &Tilecache::SqliteCache(ref cache) => cache.modified(path),
+            &Tilecache::GpkgCache(ref cache) => cache.modified(path),
+            &Tilecache::TieredCache(ref cache) => cache.modified(path),
+        }
+    }
+    fn tile_times(&self, tileset: &str) -> Option<ZoomTileTimes> {
+        match self {
+            &Tilecache::Nocache(ref cache) => cache.tile_times(tileset),
+            &Tilecache::Filecache(ref cache) => cache.tile_times(tileset),
+            &Tilecache::SqliteCache(ref cache) => cache.tile_times(tileset),
+            &Tilecache::GpkgCache(ref cache) => cache.tile_times(tileset),
+            &Tilecache::TieredCache(ref cache) => cache.tile_times(tileset),
         }
     }
 }
 
+impl Tilecache {
+    /// SQLite caches (of all tiers)
+    pub fn sqlite_caches(&self) -> Vec<&SqliteCache> {
+        match self {
+            &Tilecache::SqliteCache(ref cache) => vec![cache],
+            &Tilecache::TieredCache(ref cache) => cache
+                .tiers
+                .iter()
+                .flat_map(|tier| tier.cache.sqlite_caches())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn cache_from_config(
+    file: &Option<CacheFileCfg>,
+    sqlite: &Option<CacheSqliteCfg>,
+) -> Result<Tilecache, String> {
+    match (file, sqlite) {
+        (Some(file), None) => Ok(Tilecache::Filecache(Filecache {
+            basepath: file.base.clone(),
+            baseurl: file.baseurl.clone(),
+            extension: file.extension.clone(),
+        })),
+        (None, Some(sqlite)) => {
+            let mut cache = SqliteCache::new(
+                &sqlite.dir,
+                sqlite.baseurl.clone(),
+                sqlite.ttl,
+                sqlite.max_size_mb.map(|mb| mb * 1024 * 1024),
+            );
+            cache.vacuum_interval = sqlite.vacuum_interval;
+            Ok(Tilecache::SqliteCache(cache))
+        }
+        _ => Err("Either [cache.file] or [cache.sqlite] expected".to_string()),
+    }
+}
+
 impl<'a> Config<'a, ApplicationCfg> for Tilecache {
     fn from_config(config: &ApplicationCfg) -> Result<Self, String> {
-        config
-            .cache
-            .as_ref()
-            .map(|cache| {
-                let fc = Filecache {
-                    basepath: cache.file.base.clone(),
-                    baseurl: cache.file.baseurl.clone(),
-                };
-                Tilecache::Filecache(fc)
-            })
-            .or(Some(Tilecache::Nocache(Nocache)))
-            .ok_or("".to_string())
+        match config.cache {
+            Some(ref cache) if !cache.tier.is_empty() => {
+                if cache.file.is_some() || cache.sqlite.is_some() {
+                    return Err(
+                        "[[cache.tier]] can't be combined with [cache.file] or [cache.sqlite]"
+                            .to_string(),
+                    );
+                }
+                let tiers = cache
+                    .tier
+                    .iter()
+                    .map(|tier| {
+                        Ok(CacheTier {
+                            cache: cache_from_config(&tier.file, &tier.sqlite)?,
+                            writable: tier.writable,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                Ok(Tilecache::TieredCache(TieredCache { tiers }))
+            }
+            Some(ref cache) => cache_from_config(&cache.file, &cache.sqlite),
+            None => Ok(Tilecache::Nocache(Nocache)),
+        }
     }
     fn gen_config() -> String {
         let toml = r#"
 #[cache.file]
 #base = "/tmp/mvtcache"
 #baseurl = "http://example.com/tiles"
+#extension = "mvt" # File extension of tiles, e.g. of caches created by other tools (Default: "pbf")
+
+#[cache.sqlite]
+#dir = "/tmp/mvtcache" # One SQLite file per tileset
+#ttl = 86400 # Expiry of cached tiles in seconds
+#max_size_mb = 1000 # Maximal size of a tileset file. Oldest tiles are evicted.
+#vacuum_interval = 600 # Interval of background eviction and vacuum in seconds (Default: 300)
+
+# Cache tiers (instead of [cache.file] or [cache.sqlite]), looked up in this order
+#[[cache.tier]]
+#[cache.tier.sqlite]
+#dir = "/tmp/mvtcache"
+#[[cache.tier]]
+#writable = false # Generated tiles are not written into this tier (Default: true)
+#[cache.tier.file]
+#base = "/var/tiles"
 "#;
         toml.to_string()
     }
@@ -0,0 +1,356 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use crate::cache::cache::{Cache, TileTimes, ZoomTileTimes};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, OptionalExtension, NO_PARAMS};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Tile cache with one SQLite file per tileset.
+///
+/// Tiles are stored in a table `tiles (z, x, y, data, created_at)` in WAL mode,
+/// which allows concurrent reads from the webserver while a generate process is writing.
+/// Other cache entries (metadata JSON) are stored in a separate `metadata.db` file.
+#[derive(Clone)]
+pub struct SqliteCache {
+    pub dir: String,
+    pub baseurl: Option<String>,
+    /// Expiry of cache entries in seconds
+    pub ttl: Option<u64>,
+    /// Maximal size of a tileset file in bytes
+    pub max_size: Option<u64>,
+    /// Interval of background eviction and vacuum in seconds
+    pub vacuum_interval: Option<u64>,
+    pools: Arc<RwLock<HashMap<String, Pool<SqliteConnectionManager>>>>,
+}
+
+/// File name of the database containing non-tile cache entries
+const METADATA_DB: &str = "metadata.db";
+
+/// Waiting time for locks held by concurrent writers
+const BUSY_TIMEOUT_MS: u32 = 5000;
+
+const SCHEMA: &str = "
+PRAGMA auto_vacuum = INCREMENTAL;
+CREATE TABLE IF NOT EXISTS tiles (
+    z INTEGER NOT NULL,
+    x INTEGER NOT NULL,
+    y INTEGER NOT NULL,
+    data BLOB NOT NULL,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (z, x, y)
+);
+CREATE INDEX IF NOT EXISTS tiles_created_at ON tiles (created_at);
+CREATE TABLE IF NOT EXISTS files (
+    path TEXT PRIMARY KEY,
+    data BLOB NOT NULL,
+    created_at INTEGER NOT NULL
+);
+";
+
+fn io_error<E: ToString>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e.to_string())
+}
+
+fn system_time(unix_time: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(unix_time.max(0) as u64)
+}
+
+fn unix_time() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Split cache path `tileset/z/x/y.pbf` into its components
+fn parse_tile_path(path: &str) -> Option<(&str, u8, u32, u32)> {
+    let mut parts = path.rsplitn(4, '/');
+    let y = parts.next()?.trim_end_matches(".pbf").parse().ok()?;
+    let x = parts.next()?.parse().ok()?;
+    let z = parts.next()?.parse().ok()?;
+    let tileset = parts.next()?;
+    Some((tileset, z, x, y))
+}
+
+impl SqliteCache {
+    pub fn new(dir: &str, baseurl: Option<String>, ttl: Option<u64>, max_size: Option<u64>) -> Self {
+        SqliteCache {
+            dir: dir.to_string(),
+            baseurl,
+            ttl,
+            max_size,
+            vacuum_interval: None,
+            pools: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+    fn db_path(&self, fname: &str) -> String {
+        format!("{}/{}", self.dir, fname)
+    }
+    /// Connection pool of database file, created on first use
+    fn pool(&self, fname: &str) -> Result<Pool<SqliteConnectionManager>, io::Error> {
+        if let Some(pool) = self.pools.read().unwrap().get(fname) {
+            return Ok(pool.clone());
+        }
+        let mut pools = self.pools.write().unwrap();
+        if let Some(pool) = pools.get(fname) {
+            return Ok(pool.clone());
+        }
+        fs::create_dir_all(&self.dir)?;
+        let path = self.db_path(fname);
+        debug!("SqliteCache: opening {}", path);
+        let manager = SqliteConnectionManager::file(&path).with_init(|c| {
+            c.execute_batch(&format!(
+                "PRAGMA busy_timeout = {}; PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;",
+                BUSY_TIMEOUT_MS
+            ))
+        });
+        let pool = Pool::builder()
+            .max_size(4)
+            .build(manager)
+            .map_err(io_error)?;
+        pool.get()
+            .map_err(io_error)?
+            .execute_batch(SCHEMA)
+            .map_err(io_error)?;
+        pools.insert(fname.to_string(), pool.clone());
+        Ok(pool)
+    }
+    /// Oldest creation time of valid entries
+    fn min_created_at(&self) -> i64 {
+        self.ttl.map(|ttl| unix_time() - ttl as i64).unwrap_or(0)
+    }
+    /// Lookup entry. Expired entries are deleted.
+    fn lookup(&self, path: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        Ok(self.lookup_entry(path)?.map(|(data, _)| data))
+    }
+    /// Lookup entry with creation time. Expired entries are deleted.
+    fn lookup_entry(&self, path: &str) -> Result<Option<(Vec<u8>, i64)>, io::Error> {
+        let min_created_at = self.min_created_at();
+        let entry: Option<(Vec<u8>, i64)> = match parse_tile_path(path) {
+            Some((tileset, z, x, y)) => {
+                let conn = self.pool(&format!("{}.sqlite", tileset))?.get().map_err(io_error)?;
+                let entry = conn
+                    .query_row(
+                        "SELECT data, created_at FROM tiles WHERE z = ?1 AND x = ?2 AND y = ?3",
+                        params![z, x, y],
+                        |row| Ok((row.get(0)?, row.get(1)?)),
+                    )
+                    .optional()
+                    .map_err(io_error)?;
+                if let Some((_, created_at)) = entry {
+                    if created_at < min_created_at {
+                        conn.execute(
+                            "DELETE FROM tiles WHERE z = ?1 AND x = ?2 AND y = ?3",
+                            params![z, x, y],
+                        )
+                        .map_err(io_error)?;
+                        return Ok(None);
+                    }
+                }
+                entry
+            }
+            None => {
+                let conn = self.pool(METADATA_DB)?.get().map_err(io_error)?;
+                conn.query_row(
+                    "SELECT data, created_at FROM files WHERE path = ?1",
+                    params![path],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()
+                .map_err(io_error)?
+            }
+        };
+        Ok(entry)
+    }
+    /// Creation times of valid tiles grouped by zoom level
+    fn tile_times_of_file(&self, fname: &str) -> Result<ZoomTileTimes, io::Error> {
+        let conn = self.pool(fname)?.get().map_err(io_error)?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT z, count(*), min(created_at), max(created_at) FROM tiles WHERE created_at >= ?1 GROUP BY z",
+            )
+            .map_err(io_error)?;
+        let rows = stmt
+            .query_map(params![self.min_created_at()], |row| {
+                Ok((
+                    row.get::<_, u8>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            })
+            .map_err(io_error)?;
+        let mut times = ZoomTileTimes::new();
+        for row in rows {
+            let (zoom, tiles, oldest, newest) = row.map_err(io_error)?;
+            times.insert(
+                zoom,
+                TileTimes {
+                    tiles: tiles as u64,
+                    oldest: system_time(oldest),
+                    newest: system_time(newest),
+                },
+            );
+        }
+        Ok(times)
+    }
+    /// Delete expired tiles and evict oldest tiles of tileset files exceeding `max_size`.
+    /// Free pages are released to the file system afterwards.
+    pub fn trim(&self) -> Result<(), io::Error> {
+        if !Path::new(&self.dir).is_dir() {
+            return Ok(());
+        }
+        for entry in fs::read_dir(&self.dir)? {
+            let fname = entry?.file_name().to_string_lossy().to_string();
+            if fname.ends_with(".sqlite") {
+                self.trim_file(&fname)?;
+            }
+        }
+        Ok(())
+    }
+    fn trim_file(&self, fname: &str) -> Result<(), io::Error> {
+        let conn = self.pool(fname)?.get().map_err(io_error)?;
+        if self.ttl.is_some() {
+            let expired = conn
+                .execute(
+                    "DELETE FROM tiles WHERE created_at < ?1",
+                    params![self.min_created_at()],
+                )
+                .map_err(io_error)?;
+            if expired > 0 {
+                info!("SqliteCache: {} expired tiles deleted from {}", expired, fname);
+            }
+        }
+        if let Some(max_size) = self.max_size {
+            let page_size: i64 = conn
+                .query_row("PRAGMA page_size", NO_PARAMS, |row| row.get(0))
+                .map_err(io_error)?;
+            let used_pages: i64 = conn
+                .query_row(
+                    "SELECT (SELECT page_count FROM pragma_page_count) - (SELECT freelist_count FROM pragma_freelist_count)",
+                    NO_PARAMS,
+                    |row| row.get(0),
+                )
+                .map_err(io_error)?;
+            let size = (used_pages * page_size) as u64;
+            if size > max_size {
+                let count: i64 = conn
+                    .query_row("SELECT count(*) FROM tiles", NO_PARAMS, |row| row.get(0))
+                    .map_err(io_error)?;
+                // Evict proportionally to the excess size plus 10% headroom
+                let excess = 1.0 - max_size as f64 / size as f64 + 0.1;
+                let evict = ((count as f64 * excess).ceil() as i64).min(count);
+                conn.execute(
+                    "DELETE FROM tiles WHERE rowid IN (SELECT rowid FROM tiles ORDER BY created_at LIMIT ?1)",
+                    params![evict],
+                )
+                .map_err(io_error)?;
+                info!(
+                    "SqliteCache: {} of {} tiles evicted from {} ({} bytes)",
+                    evict, count, fname, size
+                );
+            }
+        }
+        conn.execute_batch("PRAGMA incremental_vacuum; PRAGMA wal_checkpoint(TRUNCATE);")
+            .map_err(io_error)
+    }
+    /// Run `trim` periodically in a background thread until `stopped` is set
+    pub fn spawn_vacuum_task(&self, interval: Duration, stopped: Arc<AtomicBool>) {
+        let cache = self.clone();
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            if stopped.load(Ordering::Relaxed) {
+                break;
+            }
+            if let Err(e) = cache.trim() {
+                error!("SqliteCache: vacuum of {} failed: {}", cache.dir, e);
+            }
+        });
+    }
+}
+
+impl Cache for SqliteCache {
+    fn info(&self) -> String {
+        format!("Tile cache SQLite directory: {}", self.dir)
+    }
+    fn baseurl(&self) -> String {
+        self.baseurl
+            .clone()
+            .unwrap_or("http://localhost:6767".to_string())
+    }
+    fn read<F>(&self, path: &str, mut read: F) -> bool
+    where
+        F: FnMut(&mut dyn Read),
+    {
+        debug!("SqliteCache.read {}", path);
+        match self.lookup(path) {
+            Ok(Some(data)) => {
+                read(&mut &data[..]);
+                true
+            }
+            Ok(None) => false,
+            Err(e) => {
+                error!("SqliteCache: error reading {}: {}", path, e);
+                false
+            }
+        }
+    }
+    fn write(&self, path: &str, obj: &[u8]) -> Result<(), io::Error> {
+        debug!("SqliteCache.write {}", path);
+        match parse_tile_path(path) {
+            Some((tileset, z, x, y)) => {
+                let conn = self.pool(&format!("{}.sqlite", tileset))?.get().map_err(io_error)?;
+                conn.execute(
+                    "INSERT OR REPLACE INTO tiles (z, x, y, data, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![z, x, y, obj, unix_time()],
+                )
+            }
+            None => {
+                let conn = self.pool(METADATA_DB)?.get().map_err(io_error)?;
+                conn.execute(
+                    "INSERT OR REPLACE INTO files (path, data, created_at) VALUES (?1, ?2, ?3)",
+                    params![path, obj, unix_time()],
+                )
+            }
+        }
+        .map(|_| ())
+        .map_err(io_error)
+    }
+    fn exists(&self, path: &str) -> bool {
+        match self.lookup(path) {
+            Ok(entry) => entry.is_some(),
+            Err(_) => false,
+        }
+    }
+    fn modified(&self, path: &str) -> Option<SystemTime> {
+        match self.lookup_entry(path) {
+            Ok(entry) => entry.map(|(_, created_at)| system_time(created_at)),
+            Err(_) => None,
+        }
+    }
+    fn tile_times(&self, tileset: &str) -> Option<ZoomTileTimes> {
+        let fname = format!("{}.sqlite", tileset);
+        // Don't create files of tilesets without cached tiles
+        if !Path::new(&self.db_path(&fname)).exists() {
+            return Some(ZoomTileTimes::new());
+        }
+        match self.tile_times_of_file(&fname) {
+            Ok(times) => Some(times),
+            Err(e) => {
+                error!("SqliteCache: error reading tile times of {}: {}", fname, e);
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use crate::cache::cache::Cache;
+use crate::cache::filecache::Filecache;
+use crate::cache::sqlitecache::SqliteCache;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
+
+fn test_dir(name: &str) -> String {
+    let mut dir = env::temp_dir();
+    dir.push(name);
+    let path = format!("{}", &dir.display());
+    let _ = fs::remove_dir_all(&path);
+    path
+}
+
+#[test]
+fn test_sqlitecache() {
+    let dir = test_dir("t_rex_test_sqlite");
+    let cache = SqliteCache::new(&dir, None, None, None);
+    let path = "tileset/0/1/2.pbf";
+    let obj = "0123456789";
+
+    // Cache miss
+    assert_eq!(cache.read(path, |_| {}), false);
+    assert!(!cache.exists(path));
+
+    // Write into cache
+    cache.write(path, obj.as_bytes()).unwrap();
+    assert!(Path::new(&format!("{}/tileset.sqlite", dir)).exists());
+    assert!(cache.exists(path));
+
+    // Read from cache
+    let mut s = String::new();
+    assert!(cache.read(path, |f| {
+        let _ = f.read_to_string(&mut s);
+    }));
+    assert_eq!(&s, "0123456789");
+
+    // Tileset names with dots and metadata entries
+    cache.write("osm.roads/3/4/5.pbf", b"roads").unwrap();
+    assert!(Path::new(&format!("{}/osm.roads.sqlite", dir)).exists());
+    cache.write("osm.roads.json", b"{}").unwrap();
+    assert!(cache.exists("osm.roads.json"));
+    assert!(!cache.exists("osm.roads/3/4/6.pbf"));
+}
+
+#[test]
+fn test_sqlitecache_tile_times() {
+    let dir = test_dir("t_rex_test_sqlite_tile_times");
+    let cache = SqliteCache::new(&dir, None, None, None);
+    // No database file created for tilesets without tiles
+    assert_eq!(cache.tile_times("tileset").unwrap().len(), 0);
+    assert!(!Path::new(&format!("{}/tileset.sqlite", dir)).exists());
+
+    let before = SystemTime::now() - Duration::from_secs(1);
+    cache.write("tileset/0/0/0.pbf", b"tile").unwrap();
+    cache.write("tileset/3/1/2.pbf", b"tile").unwrap();
+    cache.write("tileset/3/2/2.pbf", b"tile").unwrap();
+    cache.write("tileset.json", b"{}").unwrap();
+    let modified = cache.modified("tileset/3/1/2.pbf").unwrap();
+    assert!(modified >= before && modified <= SystemTime::now());
+    assert!(cache.modified("tileset.json").is_some());
+    assert_eq!(cache.modified("tileset/3/0/0.pbf"), None);
+
+    let times = cache.tile_times("tileset").unwrap();
+    assert_eq!(times.keys().collect::<Vec<_>>(), vec![&0, &3]);
+    assert_eq!(times[&3].tiles, 2);
+    assert_eq!(times[&3].oldest, modified);
+    assert!(times[&3].newest >= modified);
+}
+
+#[test]
+fn test_sqlitecache_ttl() {
+    let dir = test_dir("t_rex_test_sqlite_ttl");
+    let cache = SqliteCache::new(&dir, None, Some(0), None);
+    let path = "tileset/0/0/0.pbf";
+    cache.write(path, b"tile").unwrap();
+    // Entries expire one second after creation with ttl = 0
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    assert!(!cache.read(path, |_| {}));
+    let cache = SqliteCache::new(&dir, None, None, None);
+    assert!(!cache.exists(path));
+}
+
+#[test]
+fn test_sqlitecache_trim() {
+    let dir = test_dir("t_rex_test_sqlite_trim");
+    let cache = SqliteCache::new(&dir, None, None, Some(200_000));
+    let obj = vec![7u8; 10_000];
+    for x in 0..100 {
+        cache.write(&format!("tileset/10/{}/0.pbf", x), &obj).unwrap();
+    }
+    cache.trim().unwrap();
+    let remaining = (0..100)
+        .filter(|x| cache.exists(&format!("tileset/10/{}/0.pbf", x)))
+        .count();
+    assert!(remaining > 0 && remaining < 20, "{} remaining", remaining);
+    // Newest tiles are kept
+    assert!(cache.exists("tileset/10/99/0.pbf"));
+}
+
+/// Compare write and read throughput of SQLite and file cache.
+/// Set `T_REX_BENCH_DIR` for benchmarking on a specific disk:
+/// `T_REX_BENCH_DIR=/mnt/hdd cargo test --release -p t-rex-core -- --ignored bench_cache --nocapture`
+#[test]
+#[ignore]
+fn bench_cache() {
+    let basedir = env::var("T_REX_BENCH_DIR")
+        .unwrap_or_else(|_| format!("{}", env::temp_dir().display()));
+    let numtiles = 20_000;
+    let obj = vec![42u8; 20_000];
+    let filecache = Filecache {
+        basepath: format!("{}/t_rex_bench_file", basedir),
+        baseurl: None,
+        extension: None,
+    };
+    let sqlitecache = SqliteCache::new(&format!("{}/t_rex_bench_sqlite", basedir), None, None, None);
+    let _ = fs::remove_dir_all(&filecache.basepath);
+    let _ = fs::remove_dir_all(&sqlitecache.dir);
+
+    fn bench<C: Cache>(name: &str, cache: &C, numtiles: u32, obj: &[u8]) {
+        let path = |i: u32| format!("bench/14/{}/{}.pbf", i / 100, i % 100);
+        let now = Instant::now();
+        for i in 0..numtiles {
+            cache.write(&path(i), obj).unwrap();
+        }
+        let write_ms = now.elapsed().as_millis();
+        let now = Instant::now();
+        for i in 0..numtiles {
+            assert!(cache.read(&path((i * 7919) % numtiles), |_| {}));
+        }
+        let read_ms = now.elapsed().as_millis();
+        println!(
+            "{}: {} tiles written in {} ms, read in {} ms",
+            name, numtiles, write_ms, read_ms
+        );
+    }
+    bench("Filecache", &filecache, numtiles, &obj);
+    bench("SqliteCache", &sqlitecache, numtiles, &obj);
+}
@@ -0,0 +1,98 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use crate::cache::cache::{Cache, ZoomTileTimes};
+use crate::cache::Tilecache;
+use std::io::{self, Read};
+use std::time::SystemTime;
+
+/// Cache tier with write flag
+#[derive(Clone)]
+pub struct CacheTier {
+    pub cache: Tilecache,
+    /// Generated tiles are written into this tier
+    pub writable: bool,
+}
+
+/// Stack of caches.
+///
+/// Reads are served from the first tier containing the entry,
+/// writes go to all writable tiers.
+#[derive(Clone)]
+pub struct TieredCache {
+    pub tiers: Vec<CacheTier>,
+}
+
+impl Cache for TieredCache {
+    fn info(&self) -> String {
+        let tiers = self
+            .tiers
+            .iter()
+            .map(|tier| {
+                let mode = if tier.writable { "rw" } else { "ro" };
+                format!("{} ({})", tier.cache.info(), mode)
+            })
+            .collect::<Vec<_>>();
+        format!("Tiered cache: {}", tiers.join(" > "))
+    }
+    fn baseurl(&self) -> String {
+        match self.tiers.first() {
+            Some(tier) => tier.cache.baseurl(),
+            None => "http://localhost:6767".to_string(),
+        }
+    }
+    fn read<F>(&self, path: &str, mut read: F) -> bool
+    where
+        F: FnMut(&mut dyn Read),
+    {
+        // Trait object avoids infinite instantiation of nested tiered caches
+        let read: &mut dyn FnMut(&mut dyn Read) = &mut read;
+        self.tiers
+            .iter()
+            .any(|tier| tier.cache.read(path, &mut *read))
+    }
+    /// Write into all writable tiers. Returns the first error after trying all tiers.
+    fn write(&self, path: &str, obj: &[u8]) -> Result<(), io::Error> {
+        let mut result = Ok(());
+        for tier in self.tiers.iter().filter(|tier| tier.writable) {
+            if let Err(e) = tier.cache.write(path, obj) {
+                debug!("TieredCache.write {}: {}", path, e);
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+    fn exists(&self, path: &str) -> bool {
+        self.tiers.iter().any(|tier| tier.cache.exists(path))
+    }
+    /// Creation time in the first tier containing the entry, like `read`
+    fn modified(&self, path: &str) -> Option<SystemTime> {
+        self.tiers
+            .iter()
+            .find(|tier| tier.cache.exists(path))
+            .and_then(|tier| tier.cache.modified(path))
+    }
+    /// Tile times of all tiers recording creation times. Tiles contained in several tiers are counted
+    /// in each tier.
+    fn tile_times(&self, tileset: &str) -> Option<ZoomTileTimes> {
+        let mut result: Option<ZoomTileTimes> = None;
+        for times in self
+            .tiers
+            .iter()
+            .filter_map(|tier| tier.cache.tile_times(tileset))
+        {
+            let merged = result.get_or_insert_with(ZoomTileTimes::new);
+            for (zoom, tile_times) in times {
+                merged
+                    .entry(zoom)
+                    .and_modify(|t| t.merge(&tile_times))
+                    .or_insert(tile_times);
+            }
+        }
+        result
+    }
+}
@@ -0,0 +1,135 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use crate::cache::cache::Cache;
+use crate::cache::filecache::Filecache;
+use crate::cache::tieredcache::{CacheTier, TieredCache};
+use crate::cache::Tilecache;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn filecache(name: &str) -> Filecache {
+    let mut dir = env::temp_dir();
+    dir.push(name);
+    let basepath = format!("{}", &dir.display());
+    let _ = fs::remove_dir_all(&basepath);
+    Filecache {
+        basepath,
+        baseurl: None,
+        extension: None,
+    }
+}
+
+#[test]
+fn test_tiered_cache() {
+    let first = filecache("t_rex_test_tier1");
+    let second = filecache("t_rex_test_tier2");
+    let readonly = filecache("t_rex_test_tier3");
+    readonly.write("tileset/0/0/0.pbf", b"base").unwrap();
+    let cache = TieredCache {
+        tiers: vec![
+            CacheTier {
+                cache: Tilecache::Filecache(first.clone()),
+                writable: true,
+            },
+            CacheTier {
+                cache: Tilecache::Filecache(second.clone()),
+                writable: true,
+            },
+            CacheTier {
+                cache: Tilecache::Filecache(readonly.clone()),
+                writable: false,
+            },
+        ],
+    };
+
+    // Lookup falls through to read-only tier
+    let mut s = String::new();
+    assert!(cache.read("tileset/0/0/0.pbf", |f| {
+        let _ = f.read_to_string(&mut s);
+    }));
+    assert_eq!(s, "base");
+    assert!(!cache.exists("tileset/1/0/0.pbf"));
+
+    // Write into writable tiers only
+    cache.write("tileset/1/0/0.pbf", b"tile").unwrap();
+    assert!(first.exists("tileset/1/0/0.pbf"));
+    assert!(second.exists("tileset/1/0/0.pbf"));
+    assert!(!readonly.exists("tileset/1/0/0.pbf"));
+
+    // First tier wins
+    first.write("tileset/0/0/0.pbf", b"new").unwrap();
+    let mut s = String::new();
+    cache.read("tileset/0/0/0.pbf", |f| {
+        let _ = f.read_to_string(&mut s);
+    });
+    assert_eq!(s, "new");
+    assert_eq!(
+        cache.modified("tileset/0/0/0.pbf"),
+        first.modified("tileset/0/0/0.pbf")
+    );
+
+    // Tile times of all tiers
+    let times = cache.tile_times("tileset").unwrap();
+    assert_eq!(times[&0].tiles, 2);
+    assert_eq!(times[&1].tiles, 2);
+
+    assert!(cache
+        .info()
+        .starts_with("Tiered cache: Tile cache directory: "));
+    assert!(cache.info().ends_with("t_rex_test_tier3 (ro)"));
+    assert!(Path::new(&format!("{}/tileset/1/0/0.pbf", second.basepath)).exists());
+}
+
+#[test]
+fn test_tier_config() {
+    use crate::core::config::{ApplicationCfg, DEFAULT_CONFIG};
+    use crate::core::{parse_config, Config};
+
+    let toml = format!(
+        "{}{}",
+        DEFAULT_CONFIG,
+        r#"
+[[cache.tier]]
+[cache.tier.sqlite]
+dir = "/tmp/mvtcache"
+vacuum_interval = 60
+[[cache.tier]]
+writable = false
+[cache.tier.file]
+base = "/var/tiles"
+"#
+    );
+    let config: ApplicationCfg = parse_config(toml, "").unwrap();
+    let cache = Tilecache::from_config(&config).unwrap();
+    match cache {
+        Tilecache::TieredCache(ref tiered) => {
+            assert_eq!(tiered.tiers.len(), 2);
+            assert!(tiered.tiers[0].writable);
+            assert!(!tiered.tiers[1].writable);
+        }
+        _ => panic!("TieredCache expected"),
+    }
+    assert_eq!(cache.sqlite_caches().len(), 1);
+    assert_eq!(cache.sqlite_caches()[0].vacuum_interval, Some(60));
+
+    let toml = format!(
+        "{}{}",
+        DEFAULT_CONFIG,
+        r#"
+[cache.file]
+base = "/tmp/mvtcache"
+[[cache.tier]]
+[cache.tier.file]
+base = "/var/tiles"
+"#
+    );
+    let config: ApplicationCfg = parse_config(toml, "").unwrap();
+    assert_eq!(
+        Tilecache::from_config(&config).err(),
+        Some("[[cache.tier]] can't be combined with [cache.file] or [cache.sqlite]".to_string())
+    );
+}
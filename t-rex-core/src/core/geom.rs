@@ -3,17 +3,85 @@
 // Licensed under the MIT License. See LICENSE file in the project root for full license information.
 //
 
+#[cfg(feature = "server")]
 use postgis::ewkb;
+use tile_grid::Extent;
 
 // Aliases for rust-postgis geometry types
+#[cfg(feature = "server")]
 pub type Point = ewkb::Point;
+#[cfg(feature = "server")]
 pub type LineString = ewkb::LineString;
+#[cfg(feature = "server")]
 pub type Polygon = ewkb::Polygon;
+#[cfg(feature = "server")]
 pub type MultiPoint = ewkb::MultiPoint;
+#[cfg(feature = "server")]
 pub type MultiLineString = ewkb::MultiLineString;
+#[cfg(feature = "server")]
 pub type MultiPolygon = ewkb::MultiPolygon;
+#[cfg(feature = "server")]
 pub type GeometryCollection = ewkb::GeometryCollection;
 
+#[cfg(not(feature = "server"))]
+pub use self::standalone::*;
+
+/// Geometry types with the same layout as the rust-postgis types,
+/// used when building without PostgreSQL support.
+#[cfg(not(feature = "server"))]
+mod standalone {
+    use super::GeometryType;
+
+    #[derive(PartialEq, Clone, Debug)]
+    pub struct Point {
+        pub x: f64,
+        pub y: f64,
+        pub srid: Option<i32>,
+    }
+
+    impl Point {
+        pub fn new(x: f64, y: f64, srid: Option<i32>) -> Point {
+            Point { x, y, srid }
+        }
+    }
+
+    #[derive(PartialEq, Clone, Debug)]
+    pub struct LineString {
+        pub points: Vec<Point>,
+        pub srid: Option<i32>,
+    }
+
+    #[derive(PartialEq, Clone, Debug)]
+    pub struct Polygon {
+        pub rings: Vec<LineString>,
+        pub srid: Option<i32>,
+    }
+
+    #[derive(PartialEq, Clone, Debug)]
+    pub struct MultiPoint {
+        pub points: Vec<Point>,
+        pub srid: Option<i32>,
+    }
+
+    #[derive(PartialEq, Clone, Debug)]
+    pub struct MultiLineString {
+        pub lines: Vec<LineString>,
+        pub srid: Option<i32>,
+    }
+
+    #[derive(PartialEq, Clone, Debug)]
+    pub struct MultiPolygon {
+        pub polygons: Vec<Polygon>,
+        pub srid: Option<i32>,
+    }
+
+    #[derive(Debug)]
+    pub struct GeometryCollection {
+        pub geometries: Vec<GeometryType>,
+        pub srid: Option<i32>,
+    }
+}
+
 /// Generic Geometry Data Type
 #[derive(Debug)]
 pub enum GeometryType {
@@ -30,11 +98,45 @@ impl Clone for GeometryType {
     fn clone(&self) -> Self {
         match self {
             &GeometryType::Point(ref p) => GeometryType::Point(Point::new(p.x, p.y, None)),
-            _ => panic!("Not implemented yet"), // TODO: either implement other types or don't clone (FeatureStruct)...
+            &GeometryType::LineString(ref g) => GeometryType::LineString(clone_line(g)),
+            &GeometryType::Polygon(ref g) => GeometryType::Polygon(clone_polygon(g)),
+            &GeometryType::MultiPoint(ref g) => GeometryType::MultiPoint(MultiPoint {
+                points: g.points.iter().map(clone_point).collect(),
+                srid: g.srid,
+            }),
+            &GeometryType::MultiLineString(ref g) => {
+                GeometryType::MultiLineString(MultiLineString {
+                    lines: g.lines.iter().map(clone_line).collect(),
+                    srid: g.srid,
+                })
+            }
+            &GeometryType::MultiPolygon(ref g) => GeometryType::MultiPolygon(MultiPolygon {
+                polygons: g.polygons.iter().map(clone_polygon).collect(),
+                srid: g.srid,
+            }),
+            _ => panic!("Not implemented yet"), // TODO: GeometryCollection
         }
     }
 }
 
+fn clone_point(p: &Point) -> Point {
+    Point::new(p.x, p.y, p.srid)
+}
+
+fn clone_line(g: &LineString) -> LineString {
+    LineString {
+        points: g.points.iter().map(clone_point).collect(),
+        srid: g.srid,
+    }
+}
+
+fn clone_polygon(g: &Polygon) -> Polygon {
+    Polygon {
+        rings: g.rings.iter().map(clone_line).collect(),
+        srid: g.srid,
+    }
+}
+
 impl GeometryType {
     pub fn is_empty(&self) -> bool {
         match self {
@@ -46,4 +148,411 @@ impl GeometryType {
             _ => false,
         }
     }
+    /// Simplify lines and polygons with Douglas-Peucker algorithm.
+    /// Polygon rings collapsing to less than 4 points are removed.
+    pub fn simplified(&self, tolerance: f64) -> GeometryType {
+        match self {
+            &GeometryType::LineString(ref g) => {
+                GeometryType::LineString(simplify_line(g, tolerance))
+            }
+            &GeometryType::Polygon(ref g) => GeometryType::Polygon(simplify_polygon(g, tolerance)),
+            &GeometryType::MultiLineString(ref g) => {
+                GeometryType::MultiLineString(MultiLineString {
+                    lines: g.lines.iter().map(|l| simplify_line(l, tolerance)).collect(),
+                    srid: g.srid,
+                })
+            }
+            &GeometryType::MultiPolygon(ref g) => GeometryType::MultiPolygon(MultiPolygon {
+                polygons: g
+                    .polygons
+                    .iter()
+                    .map(|p| simplify_polygon(p, tolerance))
+                    .filter(|p| !p.rings.is_empty())
+                    .collect(),
+                srid: g.srid,
+            }),
+            _ => self.clone(),
+        }
+    }
+    /// Round coordinates to a grid with cell size `size`.
+    /// Consecutive duplicate points are kept.
+    pub fn snapped(&self, size: f64) -> GeometryType {
+        match self {
+            &GeometryType::Point(ref p) => GeometryType::Point(snap_point(p, size)),
+            &GeometryType::LineString(ref g) => GeometryType::LineString(snap_line(g, size)),
+            &GeometryType::Polygon(ref g) => GeometryType::Polygon(snap_polygon(g, size)),
+            &GeometryType::MultiPoint(ref g) => GeometryType::MultiPoint(MultiPoint {
+                points: g.points.iter().map(|p| snap_point(p, size)).collect(),
+                srid: g.srid,
+            }),
+            &GeometryType::MultiLineString(ref g) => {
+                GeometryType::MultiLineString(MultiLineString {
+                    lines: g.lines.iter().map(|l| snap_line(l, size)).collect(),
+                    srid: g.srid,
+                })
+            }
+            &GeometryType::MultiPolygon(ref g) => GeometryType::MultiPolygon(MultiPolygon {
+                polygons: g.polygons.iter().map(|p| snap_polygon(p, size)).collect(),
+                srid: g.srid,
+            }),
+            _ => self.clone(),
+        }
+    }
+    /// Clip geometry to `extent`. Lines are split at the extent boundary, polygon rings are
+    /// clipped with the Sutherland-Hodgman algorithm. None, if nothing remains.
+    /// Geometry collections are not supported.
+    pub fn clipped(&self, extent: &Extent) -> Option<GeometryType> {
+        let bbox = self.bbox()?;
+        if !intersects(&bbox, extent) {
+            return None;
+        }
+        if within(&bbox, extent) {
+            return Some(self.clone());
+        }
+        match self {
+            &GeometryType::Point(_) => Some(self.clone()),
+            &GeometryType::MultiPoint(ref g) => {
+                let points: Vec<Point> = g
+                    .points
+                    .iter()
+                    .filter(|p| point_within(p, extent))
+                    .map(clone_point)
+                    .collect();
+                non_empty(points).map(|points| {
+                    GeometryType::MultiPoint(MultiPoint {
+                        points,
+                        srid: g.srid,
+                    })
+                })
+            }
+            &GeometryType::LineString(ref g) => {
+                let mut lines = clip_line(g, extent);
+                if lines.len() == 1 {
+                    lines.pop().map(GeometryType::LineString)
+                } else {
+                    non_empty(lines).map(|lines| {
+                        GeometryType::MultiLineString(MultiLineString {
+                            lines,
+                            srid: g.srid,
+                        })
+                    })
+                }
+            }
+            &GeometryType::MultiLineString(ref g) => {
+                let lines = g.lines.iter().flat_map(|l| clip_line(l, extent)).collect();
+                non_empty(lines).map(|lines| {
+                    GeometryType::MultiLineString(MultiLineString {
+                        lines,
+                        srid: g.srid,
+                    })
+                })
+            }
+            &GeometryType::Polygon(ref g) => clip_polygon(g, extent).map(GeometryType::Polygon),
+            &GeometryType::MultiPolygon(ref g) => {
+                let polygons = g
+                    .polygons
+                    .iter()
+                    .filter_map(|p| clip_polygon(p, extent))
+                    .collect();
+                non_empty(polygons).map(|polygons| {
+                    GeometryType::MultiPolygon(MultiPolygon {
+                        polygons,
+                        srid: g.srid,
+                    })
+                })
+            }
+            &GeometryType::GeometryCollection(_) => None,
+        }
+    }
+    /// Bounding box of all coordinates. None for empty geometries.
+    pub fn bbox(&self) -> Option<Extent> {
+        let points: Vec<&Point> = match self {
+            &GeometryType::Point(ref p) => vec![p],
+            &GeometryType::LineString(ref g) => g.points.iter().collect(),
+            &GeometryType::Polygon(ref g) => g.rings.iter().flat_map(|r| r.points.iter()).collect(),
+            &GeometryType::MultiPoint(ref g) => g.points.iter().collect(),
+            &GeometryType::MultiLineString(ref g) => {
+                g.lines.iter().flat_map(|l| l.points.iter()).collect()
+            }
+            &GeometryType::MultiPolygon(ref g) => g
+                .polygons
+                .iter()
+                .flat_map(|p| p.rings.iter())
+                .flat_map(|r| r.points.iter())
+                .collect(),
+            &GeometryType::GeometryCollection(_) => return None,
+        };
+        points.iter().fold(None, |bbox, p| {
+            Some(match bbox {
+                None => Extent {
+                    minx: p.x,
+                    miny: p.y,
+                    maxx: p.x,
+                    maxy: p.y,
+                },
+                Some(e) => Extent {
+                    minx: e.minx.min(p.x),
+                    miny: e.miny.min(p.y),
+                    maxx: e.maxx.max(p.x),
+                    maxy: e.maxy.max(p.y),
+                },
+            })
+        })
+    }
+}
+
+fn intersects(a: &Extent, b: &Extent) -> bool {
+    a.minx <= b.maxx && a.maxx >= b.minx && a.miny <= b.maxy && a.maxy >= b.miny
+}
+
+fn within(a: &Extent, b: &Extent) -> bool {
+    a.minx >= b.minx && a.maxx <= b.maxx && a.miny >= b.miny && a.maxy <= b.maxy
+}
+
+fn point_within(p: &Point, extent: &Extent) -> bool {
+    p.x >= extent.minx && p.x <= extent.maxx && p.y >= extent.miny && p.y <= extent.maxy
+}
+
+fn non_empty<T>(v: Vec<T>) -> Option<Vec<T>> {
+    if v.is_empty() {
+        None
+    } else {
+        Some(v)
+    }
+}
+
+/// Parts of line within `extent`
+fn clip_line(g: &LineString, extent: &Extent) -> Vec<LineString> {
+    let mut lines = Vec::new();
+    let mut points: Vec<Point> = Vec::new();
+    for segment in g.points.windows(2) {
+        let (a, b) = (&segment[0], &segment[1]);
+        if let Some((start, end, end_inside)) = clip_segment(a, b, extent) {
+            if points.is_empty() {
+                if start == end {
+                    // Touching the extent boundary only
+                    continue;
+                }
+                points.push(start);
+            }
+            points.push(end);
+            if !end_inside {
+                lines.push(LineString {
+                    points: points.split_off(0),
+                    srid: g.srid,
+                });
+            }
+        }
+    }
+    if points.len() >= 2 {
+        lines.push(LineString {
+            points,
+            srid: g.srid,
+        });
+    }
+    lines
+}
+
+/// Part of the segment from `a` to `b` within `extent` (Liang-Barsky algorithm).
+/// Returns start and end point and whether `b` is within `extent`.
+fn clip_segment(a: &Point, b: &Point, extent: &Extent) -> Option<(Point, Point, bool)> {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let (mut t0, mut t1) = (0.0, 1.0);
+    for &(p, q) in &[
+        (-dx, a.x - extent.minx),
+        (dx, extent.maxx - a.x),
+        (-dy, a.y - extent.miny),
+        (dy, extent.maxy - a.y),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let r = q / p;
+            if p < 0.0 {
+                if r > t1 {
+                    return None;
+                }
+                t0 = f64::max(t0, r);
+            } else {
+                if r < t0 {
+                    return None;
+                }
+                t1 = f64::min(t1, r);
+            }
+        }
+    }
+    let point_at = |t: f64| Point::new(a.x + t * dx, a.y + t * dy, a.srid);
+    let start = if t0 > 0.0 {
+        point_at(t0)
+    } else {
+        clone_point(a)
+    };
+    let end = if t1 < 1.0 {
+        point_at(t1)
+    } else {
+        clone_point(b)
+    };
+    Some((start, end, t1 >= 1.0))
+}
+
+/// Clipped polygon. None, if the exterior ring is outside of `extent`.
+fn clip_polygon(g: &Polygon, extent: &Extent) -> Option<Polygon> {
+    let mut rings = g.rings.iter().map(|r| clip_ring(r, extent));
+    let exterior = rings.next()??;
+    let mut clipped = vec![exterior];
+    clipped.extend(rings.filter_map(|r| r));
+    Some(Polygon {
+        rings: clipped,
+        srid: g.srid,
+    })
+}
+
+/// Clip closed ring to `extent` (Sutherland-Hodgman algorithm)
+fn clip_ring(ring: &LineString, extent: &Extent) -> Option<LineString> {
+    let mut points: Vec<(f64, f64)> = ring.points.iter().map(|p| (p.x, p.y)).collect();
+    if points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    // Clip against left, right, bottom and top edge
+    for edge in 0..4 {
+        let inside = |&(x, y): &(f64, f64)| match edge {
+            0 => x >= extent.minx,
+            1 => x <= extent.maxx,
+            2 => y >= extent.miny,
+            _ => y <= extent.maxy,
+        };
+        let intersection = |a: (f64, f64), b: (f64, f64)| match edge {
+            0 | 1 => {
+                let x = if edge == 0 { extent.minx } else { extent.maxx };
+                (x, a.1 + (x - a.0) / (b.0 - a.0) * (b.1 - a.1))
+            }
+            _ => {
+                let y = if edge == 2 { extent.miny } else { extent.maxy };
+                (a.0 + (y - a.1) / (b.1 - a.1) * (b.0 - a.0), y)
+            }
+        };
+        let input = points.split_off(0);
+        for (i, &cur) in input.iter().enumerate() {
+            let prev = input[(i + input.len() - 1) % input.len()];
+            if inside(&cur) {
+                if !inside(&prev) {
+                    points.push(intersection(prev, cur));
+                }
+                points.push(cur);
+            } else if inside(&prev) {
+                points.push(intersection(prev, cur));
+            }
+        }
+    }
+    if points.len() < 3 {
+        return None;
+    }
+    points.push(points[0]);
+    Some(LineString {
+        points: points
+            .iter()
+            .map(|&(x, y)| Point::new(x, y, ring.srid))
+            .collect(),
+        srid: ring.srid,
+    })
+}
+
+fn snap_point(p: &Point, size: f64) -> Point {
+    Point::new(
+        (p.x / size).round() * size,
+        (p.y / size).round() * size,
+        p.srid,
+    )
+}
+
+fn snap_line(g: &LineString, size: f64) -> LineString {
+    LineString {
+        points: g.points.iter().map(|p| snap_point(p, size)).collect(),
+        srid: g.srid,
+    }
+}
+
+fn snap_polygon(g: &Polygon, size: f64) -> Polygon {
+    Polygon {
+        rings: g.rings.iter().map(|r| snap_line(r, size)).collect(),
+        srid: g.srid,
+    }
+}
+
+fn simplify_line(g: &LineString, tolerance: f64) -> LineString {
+    let mut keep = vec![false; g.points.len()];
+    if !g.points.is_empty() {
+        keep[0] = true;
+        keep[g.points.len() - 1] = true;
+        douglas_peucker(&g.points, 0, g.points.len() - 1, tolerance, &mut keep);
+    }
+    LineString {
+        points: g
+            .points
+            .iter()
+            .zip(keep.iter())
+            .filter(|&(_, &k)| k)
+            .map(|(p, _)| clone_point(p))
+            .collect(),
+        srid: g.srid,
+    }
+}
+
+/// Simplified polygon. Without rings if the exterior ring collapses.
+fn simplify_polygon(g: &Polygon, tolerance: f64) -> Polygon {
+    let mut rings = Vec::new();
+    for (i, ring) in g.rings.iter().enumerate() {
+        let ring = simplify_line(ring, tolerance);
+        if ring.points.len() >= 4 {
+            rings.push(ring);
+        } else if i == 0 {
+            break;
+        }
+    }
+    Polygon {
+        rings,
+        srid: g.srid,
+    }
+}
+
+fn douglas_peucker(
+    points: &[Point],
+    first: usize,
+    last: usize,
+    tolerance: f64,
+    keep: &mut [bool],
+) {
+    if last <= first + 1 {
+        return;
+    }
+    let (a, b) = (&points[first], &points[last]);
+    let mut max_dist = 0.0;
+    let mut index = first;
+    for (i, p) in points.iter().enumerate().take(last).skip(first + 1) {
+        let dist = segment_distance(p, a, b);
+        if dist > max_dist {
+            max_dist = dist;
+            index = i;
+        }
+    }
+    if max_dist > tolerance {
+        keep[index] = true;
+        douglas_peucker(points, first, index, tolerance, keep);
+        douglas_peucker(points, index, last, tolerance, keep);
+    }
+}
+
+/// Distance of point `p` to the segment from `a` to `b`
+fn segment_distance(p: &Point, a: &Point, b: &Point) -> f64 {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 > 0.0 {
+        (((p.x - a.x) * dx + (p.y - a.y) * dy) / len2).max(0.0).min(1.0)
+    } else {
+        0.0
+    };
+    let (x, y) = (a.x + t * dx, a.y + t * dy);
+    ((p.x - x).powi(2) + (p.y - y).powi(2)).sqrt()
 }
@@ -4,6 +4,7 @@
 //
 
 use crate::core::geom::{GeometryType, Point};
+#[cfg(feature = "server")]
 use postgis::ewkb;
 
 #[cfg(test)]
@@ -15,6 +16,7 @@ impl GeometryType {
 
 #[test]
 fn test_geom_creation() {
+    #[cfg(feature = "server")]
     let _: GeometryType = GeometryType::Point(ewkb::Point::new(960000.0, 6002729.0, Some(3857)));
     let _: GeometryType = GeometryType::Point(Point::new(960000.0, 6002729.0, Some(3857)));
     let g3 = GeometryType::new_point(960000.0, 6002729.0);
@@ -24,3 +26,199 @@ fn test_geom_creation() {
     };
     assert_eq!(p.x, 960000.0);
 }
+
+#[test]
+fn test_geom_bbox() {
+    use crate::core::geom::{LineString, MultiPolygon, Polygon};
+    use tile_grid::Extent;
+
+    let line = LineString {
+        points: vec![
+            Point::new(3.0, 1.0, Some(3857)),
+            Point::new(-1.0, 4.0, Some(3857)),
+        ],
+        srid: Some(3857),
+    };
+    let g = GeometryType::MultiPolygon(MultiPolygon {
+        polygons: vec![Polygon {
+            rings: vec![line],
+            srid: Some(3857),
+        }],
+        srid: Some(3857),
+    });
+    let bbox = Some(Extent {
+        minx: -1.0,
+        miny: 1.0,
+        maxx: 3.0,
+        maxy: 4.0,
+    });
+    assert_eq!(g.bbox(), bbox);
+    assert_eq!(g.clone().bbox(), bbox);
+    assert_eq!(GeometryType::new_point(2.0, 5.0).bbox().unwrap().maxy, 5.0);
+
+    let empty = GeometryType::LineString(LineString {
+        points: vec![],
+        srid: None,
+    });
+    assert_eq!(empty.bbox(), None);
+}
+
+#[test]
+fn test_geom_simplified() {
+    use crate::core::geom::{LineString, MultiPolygon, Polygon};
+
+    let line = |coords: &[(f64, f64)]| LineString {
+        points: coords.iter().map(|&(x, y)| Point::new(x, y, None)).collect(),
+        srid: None,
+    };
+    let g = GeometryType::LineString(line(&[
+        (0.0, 0.0),
+        (1.0, 0.1),
+        (2.0, -0.1),
+        (3.0, 4.5),
+        (4.0, 6.0),
+    ]));
+    match g.simplified(0.5) {
+        GeometryType::LineString(l) => {
+            let coords: Vec<(f64, f64)> = l.points.iter().map(|p| (p.x, p.y)).collect();
+            assert_eq!(coords, vec![(0.0, 0.0), (2.0, -0.1), (4.0, 6.0)]);
+        }
+        _ => panic!(),
+    }
+
+    let square = line(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0), (0.0, 0.0)]);
+    let hole = line(&[(4.0, 4.0), (4.2, 4.0), (4.2, 4.2), (4.0, 4.0)]);
+    let tiny = line(&[(20.0, 20.0), (20.1, 20.0), (20.1, 20.1), (20.0, 20.0)]);
+    let g = GeometryType::MultiPolygon(MultiPolygon {
+        polygons: vec![
+            Polygon {
+                rings: vec![square, hole],
+                srid: None,
+            },
+            Polygon {
+                rings: vec![tiny],
+                srid: None,
+            },
+        ],
+        srid: None,
+    });
+    match g.simplified(1.0) {
+        GeometryType::MultiPolygon(mp) => {
+            // Hole and tiny polygon collapsed
+            assert_eq!(mp.polygons.len(), 1);
+            assert_eq!(mp.polygons[0].rings.len(), 1);
+            assert_eq!(mp.polygons[0].rings[0].points.len(), 5);
+        }
+        _ => panic!(),
+    }
+    match g.simplified(0.01) {
+        GeometryType::MultiPolygon(mp) => assert_eq!(mp.polygons.len(), 2),
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn test_geom_snapped() {
+    use crate::core::geom::{LineString, MultiPoint};
+
+    let g = GeometryType::LineString(LineString {
+        points: vec![Point::new(0.24, 10.26, None), Point::new(0.26, -3.76, None)],
+        srid: Some(2056),
+    });
+    match g.snapped(0.5) {
+        GeometryType::LineString(l) => {
+            let coords: Vec<(f64, f64)> = l.points.iter().map(|p| (p.x, p.y)).collect();
+            assert_eq!(coords, vec![(0.0, 10.5), (0.5, -4.0)]);
+            assert_eq!(l.srid, Some(2056));
+        }
+        _ => panic!(),
+    }
+    let g = GeometryType::MultiPoint(MultiPoint {
+        points: vec![Point::new(1234.5, 99.0, None)],
+        srid: None,
+    });
+    match g.snapped(100.0) {
+        GeometryType::MultiPoint(mp) => {
+            assert_eq!((mp.points[0].x, mp.points[0].y), (1200.0, 100.0))
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn test_geom_clipped() {
+    use crate::core::geom::{LineString, MultiPoint, Polygon};
+    use tile_grid::Extent;
+
+    let extent = Extent {
+        minx: 0.0,
+        miny: 0.0,
+        maxx: 10.0,
+        maxy: 10.0,
+    };
+    let line = |coords: &[(f64, f64)]| LineString {
+        points: coords
+            .iter()
+            .map(|&(x, y)| Point::new(x, y, None))
+            .collect(),
+        srid: None,
+    };
+    let coords = |l: &LineString| l.points.iter().map(|p| (p.x, p.y)).collect::<Vec<_>>();
+
+    assert!(GeometryType::new_point(5.0, 5.0).clipped(&extent).is_some());
+    assert!(GeometryType::new_point(5.0, 11.0)
+        .clipped(&extent)
+        .is_none());
+    let g = GeometryType::MultiPoint(MultiPoint {
+        points: vec![Point::new(5.0, 5.0, None), Point::new(-1.0, 5.0, None)],
+        srid: None,
+    });
+    match g.clipped(&extent) {
+        Some(GeometryType::MultiPoint(mp)) => assert_eq!(mp.points.len(), 1),
+        _ => panic!(),
+    }
+
+    // Line leaving and reentering the extent
+    let g = GeometryType::LineString(line(&[(5.0, 5.0), (15.0, 5.0), (15.0, 8.0), (5.0, 8.0)]));
+    match g.clipped(&extent) {
+        Some(GeometryType::MultiLineString(ml)) => {
+            assert_eq!(coords(&ml.lines[0]), vec![(5.0, 5.0), (10.0, 5.0)]);
+            assert_eq!(coords(&ml.lines[1]), vec![(10.0, 8.0), (5.0, 8.0)]);
+        }
+        _ => panic!(),
+    }
+    let g = GeometryType::LineString(line(&[(-5.0, 5.0), (15.0, 5.0)]));
+    match g.clipped(&extent) {
+        Some(GeometryType::LineString(l)) => {
+            assert_eq!(coords(&l), vec![(0.0, 5.0), (10.0, 5.0)])
+        }
+        _ => panic!(),
+    }
+    let g = GeometryType::LineString(line(&[(-5.0, 5.0), (5.0, 15.0)]));
+    assert!(g.clipped(&extent).is_none());
+
+    // Polygon with hole outside of the extent
+    let g = GeometryType::Polygon(Polygon {
+        rings: vec![
+            line(&[(5.0, 5.0), (20.0, 5.0), (20.0, 8.0), (5.0, 8.0), (5.0, 5.0)]),
+            line(&[(15.0, 6.0), (16.0, 6.0), (16.0, 7.0), (15.0, 6.0)]),
+        ],
+        srid: None,
+    });
+    match g.clipped(&extent) {
+        Some(GeometryType::Polygon(p)) => {
+            assert_eq!(p.rings.len(), 1);
+            assert_eq!(
+                coords(&p.rings[0]),
+                vec![(5.0, 5.0), (10.0, 5.0), (10.0, 8.0), (5.0, 8.0), (5.0, 5.0)]
+            );
+        }
+        _ => panic!(),
+    }
+    // Unchanged within extent
+    let g = GeometryType::LineString(line(&[(1.0, 1.0), (2.0, 2.0)]));
+    match g.clipped(&extent) {
+        Some(GeometryType::LineString(l)) => assert_eq!(coords(&l), vec![(1.0, 1.0), (2.0, 2.0)]),
+        _ => panic!(),
+    }
+}
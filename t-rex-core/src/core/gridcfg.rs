@@ -4,10 +4,12 @@
 //
 
 use crate::core::config::GridCfg;
+use crate::core::tilematrixset::grid_from_tile_matrix_set;
 use crate::core::Config;
+use std::fs;
 use tile_grid::{Extent, Grid, Origin, Unit};
 
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Deserialize, Clone, PartialEq, Debug)]
 pub struct ExtentCfg {
     pub minx: f64,
     pub miny: f64,
@@ -30,8 +32,8 @@ impl<'a> Config<'a, GridCfg> for Grid {
     fn from_config(grid_cfg: &GridCfg) -> Result<Self, String> {
         if let Some(ref gridname) = grid_cfg.predefined {
             match gridname.as_str() {
-                "wgs84" => Ok(Grid::wgs84()),
-                "web_mercator" => Ok(Grid::web_mercator()),
+                "wgs84" | "WorldCRS84Quad" => Ok(Grid::wgs84()),
+                "web_mercator" | "WebMercatorQuad" => Ok(Grid::web_mercator()),
                 _ => Err(format!("Unkown grid '{}'", gridname)),
             }
         } else if let Some(ref usergrid) = grid_cfg.user {
@@ -46,7 +48,7 @@ impl<'a> Config<'a, GridCfg> for Grid {
                 "BottomLeft" => Ok(Origin::BottomLeft),
                 _ => Err(format!("Unexpected enum value '{}'", usergrid.origin)),
             };
-            let grid = Grid::new(
+            let mut grid = Grid::new(
                 usergrid.width,
                 usergrid.height,
                 Extent::from(&usergrid.extent),
@@ -55,7 +57,15 @@ impl<'a> Config<'a, GridCfg> for Grid {
                 usergrid.resolutions.clone(),
                 origin?,
             );
+            grid.tile_matrix_set = usergrid.tile_matrix_set.clone();
             Ok(grid)
+        } else if let Some(ref path) = grid_cfg.tms_json {
+            let json = fs::read_to_string(path)
+                .map_err(|e| format!("Could not read tile matrix set '{}': {}", path, e))?;
+            serde_json::from_str(&json)
+                .map_err(|e| e.to_string())
+                .and_then(|tms| grid_from_tile_matrix_set(&tms))
+                .map_err(|e| format!("Invalid tile matrix set '{}': {}", path, e))
         } else {
             Err("Invalid grid definition".to_string())
         }
@@ -6,7 +6,7 @@
 use crate::core::config::GridCfg;
 
 use crate::core::Config;
-use tile_grid::{Extent, Grid, Origin};
+use tile_grid::{Extent, Grid, Origin, TmsTile};
 
 #[test]
 fn test_grid_from_config() {
@@ -27,6 +27,16 @@ fn test_grid_from_config() {
             maxy: 20037508.3427892480,
         }
     );
+    assert_eq!(grid.tile_matrix_set, Some("WebMercatorQuad".to_string()));
+
+    for name in &["wgs84", "WorldCRS84Quad"] {
+        let toml = format!("predefined = \"{}\"", name);
+        let config: GridCfg = parse_config(toml, "").unwrap();
+        let grid = Grid::from_config(&config).unwrap();
+        assert_eq!(grid.srid, 4326);
+        assert_eq!(grid.tile_matrix_set, Some("WorldCRS84Quad".to_string()));
+        assert_eq!(grid.level_limit(0), (2, 1));
+    }
 
     let toml = r#"
         #[grid.user]
@@ -38,6 +48,7 @@ fn test_grid_from_config() {
         units = "m"
         resolutions = [4000.0,3750.0,3500.0,3250.0,3000.0,2750.0,2500.0,2250.0,2000.0,1750.0,1500.0,1250.0,1000.0,750.0,650.0,500.0,250.0,100.0,50.0,20.0,10.0,5.0,2.5,2.0,1.5,1.0,0.5,0.25,0.1]
         origin = "TopLeft"
+        tile_matrix_set = "SwissLV95"
         "#;
     let config: GridCfg = parse_config(toml.to_string(), "").unwrap();
     let grid = Grid::from_config(&config).unwrap();
@@ -51,6 +62,7 @@ fn test_grid_from_config() {
         }
     );
     assert_eq!(grid.origin, Origin::TopLeft);
+    assert_eq!(grid.tile_matrix_set, Some("SwissLV95".to_string()));
 
     let extent = grid.tile_extent(10, 4, 17); // lake of Zurich
     assert_eq!(
@@ -63,7 +75,9 @@ fn test_grid_from_config() {
         }
     );
     //BBOX ZH: (2669255.48 1223902.28, 2716899.60125 1283304.23625)
-    let extent = grid.tile_extent_xyz(10, 4, 17);
+    // Rows of XYZ and grid adressing scheme are both counted from the top
+    assert_eq!(grid.tile_extent_xyz(10, 4, 17), extent);
+    let extent = grid.tile_extent_tms(&TmsTile { x: 10, y: 4, z: 17 });
     assert_eq!(
         extent,
         Extent {
@@ -3,10 +3,15 @@
 // Licensed under the MIT License. See LICENSE file in the project root for full license information.
 //
 
-use crate::core::config::{self, LayerCfg};
+use crate::core::config::{self, CoerceCfg, LayerCfg, LayerParamCfg};
+use crate::core::feature::{Feature, FeatureAttr, FeatureAttrValType};
+use crate::core::geom::GeometryType;
 use crate::core::Config;
 use crate::service::glstyle_converter::toml_style_to_gljson;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use tile_grid::{Extent, Grid};
+use toml::Value;
 
 #[derive(Clone, Debug)]
 pub struct LayerQuery {
@@ -15,6 +20,262 @@ pub struct LayerQuery {
     pub sql: Option<String>,
 }
 
+/// Attribute with zoom range
+#[derive(Clone, Debug)]
+pub struct LayerAttribute {
+    pub name: String,
+    pub minzoom: Option<u8>,
+    pub maxzoom: Option<u8>,
+}
+
+/// Prefix of query string keys with layer query parameter values
+pub const PARAM_PREFIX: &str = "param_";
+
+/// Requested values of layer query parameters by parameter name
+pub type ParamValues = BTreeMap<String, String>;
+
+/// Type of a layer query parameter
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ParamType {
+    Int,
+    Float,
+    /// Date in ISO 8601 format (YYYY-MM-DD)
+    Date,
+    String,
+}
+
+/// Value of a layer query parameter. Dates are stored as text.
+#[derive(Clone, PartialEq, PartialOrd, Debug)]
+pub enum ParamValue {
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl fmt::Display for ParamValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParamValue::Int(value) => write!(f, "{}", value),
+            ParamValue::Float(value) => write!(f, "{}", value),
+            ParamValue::Text(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+fn valid_date(value: &str) -> bool {
+    let parts: Vec<&str> = value.split('-').collect();
+    if parts.len() != 3
+        || parts[0].len() != 4
+        || parts[1].len() != 2
+        || parts[2].len() != 2
+        || !parts.iter().all(|p| p.chars().all(|c| c.is_ascii_digit()))
+    {
+        return false;
+    }
+    let year: u32 = parts[0].parse().unwrap_or(0);
+    let month: u32 = parts[1].parse().unwrap_or(0);
+    let day: u32 = parts[2].parse().unwrap_or(0);
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+    day >= 1 && day <= days
+}
+
+impl ParamType {
+    fn from_config(value: &str) -> Option<ParamType> {
+        match value {
+            "int" => Some(ParamType::Int),
+            "float" => Some(ParamType::Float),
+            "date" => Some(ParamType::Date),
+            "string" => Some(ParamType::String),
+            _ => None,
+        }
+    }
+    fn name(&self) -> &str {
+        match self {
+            ParamType::Int => "int",
+            ParamType::Float => "float",
+            ParamType::Date => "date",
+            ParamType::String => "string",
+        }
+    }
+    /// PostgreSQL type cast of the query variable
+    pub fn sql_cast(&self) -> &str {
+        match self {
+            ParamType::Int => "INT8",
+            ParamType::Float => "FLOAT8",
+            // Bound as text
+            ParamType::Date => "TEXT::DATE",
+            ParamType::String => "TEXT",
+        }
+    }
+    /// Parse a requested value
+    pub fn parse(&self, value: &str) -> Option<ParamValue> {
+        match self {
+            ParamType::Int => value.parse().ok().map(ParamValue::Int),
+            ParamType::Float => value
+                .parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .map(ParamValue::Float),
+            ParamType::Date if valid_date(value) => Some(ParamValue::Text(value.to_string())),
+            ParamType::Date => None,
+            ParamType::String => Some(ParamValue::Text(value.to_string())),
+        }
+    }
+    /// Parse a configured value
+    fn parse_config(&self, value: &Value) -> Option<ParamValue> {
+        match (self, value) {
+            (ParamType::Int, Value::Integer(v)) => Some(ParamValue::Int(*v)),
+            (ParamType::Float, Value::Integer(v)) => Some(ParamValue::Float(*v as f64)),
+            (ParamType::Float, Value::Float(v)) => self.parse(&v.to_string()),
+            (ParamType::Date, Value::String(v)) | (ParamType::String, Value::String(v)) => {
+                self.parse(v)
+            }
+            (ParamType::Date, Value::Datetime(v)) => self.parse(&v.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Layer query parameter with value from the request (PostGIS).
+/// Query variables `!<name>!` are replaced with the bound value.
+#[derive(Clone, Debug)]
+pub struct LayerParam {
+    pub name: String,
+    pub param_type: ParamType,
+    /// Value without request parameter
+    pub default: ParamValue,
+    pub min: Option<ParamValue>,
+    pub max: Option<ParamValue>,
+    /// Allowed values (all values within min/max if empty)
+    pub values: Vec<ParamValue>,
+    /// Value bound to the layer query
+    pub value: ParamValue,
+}
+
+/// Variables replaced by the datasource, which can't be used as parameter names
+const RESERVED_PARAM_NAMES: [&str; 5] = [
+    "bbox",
+    "zoom",
+    "pixel_width",
+    "scale_denominator",
+    "buffer_width",
+];
+
+impl LayerParam {
+    fn from_config(layer_name: &str, cfg: &LayerParamCfg) -> Result<LayerParam, String> {
+        let name = &cfg.name;
+        if name.is_empty()
+            || !name.starts_with(|c: char| c.is_ascii_alphabetic())
+            || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            || RESERVED_PARAM_NAMES.contains(&name.as_str())
+        {
+            return Err(format!(
+                "Layer '{}': invalid parameter name '{}'",
+                layer_name, name
+            ));
+        }
+        let param_type = ParamType::from_config(&cfg.param_type).ok_or_else(|| {
+            format!(
+                "Layer '{}': invalid type '{}' of parameter '{}' (expected \"int\", \"float\", \"date\" or \"string\")",
+                layer_name, cfg.param_type, name
+            )
+        })?;
+        let value = |key: &str, value: &Value| {
+            param_type.parse_config(value).ok_or_else(|| {
+                format!(
+                    "Layer '{}': invalid {} {} of parameter '{}' (expected {})",
+                    layer_name,
+                    key,
+                    value,
+                    name,
+                    param_type.name()
+                )
+            })
+        };
+        let default = value("default", &cfg.default)?;
+        let mut param = LayerParam {
+            name: name.clone(),
+            param_type,
+            default: default.clone(),
+            min: cfg.min.as_ref().map(|v| value("min", v)).transpose()?,
+            max: cfg.max.as_ref().map(|v| value("max", v)).transpose()?,
+            values: cfg
+                .values
+                .iter()
+                .map(|v| value("value", v))
+                .collect::<Result<_, _>>()?,
+            value: default.clone(),
+        };
+        param.value = param
+            .check(&default.to_string())
+            .map_err(|e| format!("Layer '{}': default {}", layer_name, e))?;
+        Ok(param)
+    }
+    /// Parse and check a requested value
+    pub fn check(&self, value: &str) -> Result<ParamValue, String> {
+        let parsed = self.param_type.parse(value).ok_or_else(|| {
+            format!(
+                "value '{}' of parameter '{}' is not a valid {}",
+                value,
+                self.name,
+                self.param_type.name()
+            )
+        })?;
+        let too_small = self.min.as_ref().map_or(false, |min| parsed < *min);
+        let too_large = self.max.as_ref().map_or(false, |max| parsed > *max);
+        if too_small || too_large {
+            let bound = |bound: &Option<ParamValue>| {
+                bound.as_ref().map(|v| v.to_string()).unwrap_or_default()
+            };
+            return Err(format!(
+                "value '{}' of parameter '{}' is out of range ({}..{})",
+                value,
+                self.name,
+                bound(&self.min),
+                bound(&self.max)
+            ));
+        }
+        if !self.values.is_empty() && !self.values.contains(&parsed) {
+            return Err(format!(
+                "value '{}' of parameter '{}' is not allowed",
+                value, self.name
+            ));
+        }
+        Ok(parsed)
+    }
+    fn runtime_config(&self) -> String {
+        let literal = |value: &ParamValue| match value {
+            ParamValue::Text(text) => format!("{:?}", text),
+            ParamValue::Float(v) if v.fract() == 0.0 => format!("{:.1}", v),
+            value => value.to_string(),
+        };
+        let mut lines = vec![
+            "[[tileset.layer.param]]".to_string(),
+            format!("name = \"{}\"", self.name),
+            format!("type = \"{}\"", self.param_type.name()),
+            format!("default = {}", literal(&self.default)),
+        ];
+        if let Some(ref min) = self.min {
+            lines.push(format!("min = {}", literal(min)));
+        }
+        if let Some(ref max) = self.max {
+            lines.push(format!("max = {}", literal(max)));
+        }
+        if !self.values.is_empty() {
+            let values: Vec<String> = self.values.iter().map(literal).collect();
+            lines.push(format!("values = [{}]", values.join(", ")));
+        }
+        lines.join("\n")
+    }
+}
+
 #[derive(Default, Clone, Debug)]
 pub struct Layer {
     pub name: String,
@@ -31,29 +292,233 @@ pub struct Layer {
     pub query_limit: Option<u32>,
     // Explicit queries
     pub query: Vec<LayerQuery>,
+    /// Query parameters with values from the request
+    pub params: Vec<LayerParam>,
     pub minzoom: Option<u8>,
     pub maxzoom: Option<u8>,
+    /// Attributes with zoom range
+    pub attributes: Vec<LayerAttribute>,
+    /// Zoom range of all attributes (except `fid_field`)
+    pub attributes_minzoom: Option<u8>,
+    pub attributes_maxzoom: Option<u8>,
     /// Width and height of the tiles
     pub tile_size: u32,
+    /// Cell size of the grid coordinates are rounded to before tiling (grid SRS units)
+    pub snap_to_grid: Option<f64>,
+    /// Encode features ordered by id, geometry and attributes
+    pub deterministic: bool,
     /// Simplify geometry (lines and polygons)
     pub simplify: bool,
     /// Simplification tolerance (default to !pixel_width!/2)
     pub tolerance: String,
-    /// Tile buffer size in pixels (None: no clipping)
+    /// Tile buffer size in `buffer_unit` (None: no clipping)
     pub buffer_size: Option<u32>,
+    pub buffer_unit: BufferUnit,
+    /// Clip geometries to tile buffer (lines and polygons)
+    pub clip_geom: bool,
+    /// Handling of features intersecting multiple tiles
+    pub duplicate_features: DuplicateFeatures,
     /// Fix invalid geometries before clipping (lines and polygons)
     pub make_valid: bool,
     /// Apply ST_Shift_Longitude to (transformed) bbox
     pub shift_longitude: bool,
+    /// Encode layer with ST_AsMVT in PostGIS (PostGIS 3.0+)
+    pub use_st_asmvt: bool,
+    /// Maximal number of vertices of geometry parts split with ST_Subdivide before clipping
+    pub subdivide: Option<u32>,
+    /// Attribute resolved from `<localized_name>:<lang>` attributes for the requested language
+    pub localized_name: Option<String>,
+    /// Language used for `localized_name` without requested language
+    pub default_lang: Option<String>,
+    /// Disable the layer while `table_name` is missing instead of failing
+    pub skip_missing_table: bool,
+    /// Query layer for each request instead of storing it in the tile cache (`cache = false`)
+    pub no_cache: bool,
+    /// Cache-Control max-age in seconds of tiles with this uncached layer
+    pub cache_control_max_age: Option<u32>,
+    /// Coercion of attribute types without MVT value type (PostGIS)
+    pub coerce: AttributeCoercion,
     // Inline style
     pub style: Option<String>,
 }
 
+/// Unit of the layer `buffer_size`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BufferUnit {
+    /// Pixels of a 256 pixel tile, independent of the grid tile size and the layer `tile_size`
+    Pixels,
+    /// Units of the grid SRS (e.g. meters), same buffer width at all zoom levels
+    Grid,
+}
+
+impl Default for BufferUnit {
+    fn default() -> BufferUnit {
+        BufferUnit::Pixels
+    }
+}
+
+/// Handling of features intersecting multiple tiles (PostGIS)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DuplicateFeatures {
+    /// Include features in all intersecting tiles
+    All,
+    /// Include features in all intersecting tiles with attribute `label_tile`,
+    /// which is true in the tile containing the label point of the feature
+    Flag,
+    /// Include features only in the tile containing their label point
+    LabelPoint,
+}
+
+impl Default for DuplicateFeatures {
+    fn default() -> DuplicateFeatures {
+        DuplicateFeatures::All
+    }
+}
+
+/// Conversion of attribute values without MVT value type
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Coercion {
+    /// Double precision float
+    Double,
+    /// 64 bit integer (rounded)
+    Int,
+    /// String
+    String,
+    /// Omit the attribute
+    Skip,
+}
+
+impl Coercion {
+    fn from_config(value: &str, allowed: &[Coercion]) -> Option<Coercion> {
+        let coercion = match value {
+            "double" => Coercion::Double,
+            "int" => Coercion::Int,
+            "string" => Coercion::String,
+            "skip" => Coercion::Skip,
+            _ => return None,
+        };
+        if allowed.contains(&coercion) {
+            Some(coercion)
+        } else {
+            None
+        }
+    }
+    fn name(&self) -> &str {
+        match self {
+            Coercion::Double => "double",
+            Coercion::Int => "int",
+            Coercion::String => "string",
+            Coercion::Skip => "skip",
+        }
+    }
+}
+
+/// Coercion of PostgreSQL column types without MVT value type.
+/// `text`, `varchar`, integer, `float4`, `float8` and `bool` columns are encoded unchanged.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AttributeCoercion {
+    /// `numeric`: double (default), int, string or skip
+    pub numeric: Coercion,
+    /// `json` and `jsonb`: JSON text string (default) or skip
+    pub json: Coercion,
+    /// Arrays: string with comma-joined elements (default) or skip
+    pub array: Coercion,
+    /// Other types like `date`, `timestamp` or `uuid`: text representation (default) or skip
+    pub other: Coercion,
+}
+
+impl Default for AttributeCoercion {
+    fn default() -> AttributeCoercion {
+        AttributeCoercion {
+            numeric: Coercion::Double,
+            json: Coercion::String,
+            array: Coercion::String,
+            other: Coercion::String,
+        }
+    }
+}
+
+impl AttributeCoercion {
+    fn from_config(layer_name: &str, cfg: &CoerceCfg) -> Result<AttributeCoercion, String> {
+        let defaults = AttributeCoercion::default();
+        let all = [
+            Coercion::Double,
+            Coercion::Int,
+            Coercion::String,
+            Coercion::Skip,
+        ];
+        let string_or_skip = [Coercion::String, Coercion::Skip];
+        let coercion =
+            |key: &str, value: &Option<String>, default, allowed: &[Coercion]| match value {
+                None => Ok(default),
+                Some(value) => Coercion::from_config(value, allowed).ok_or_else(|| {
+                    let names: Vec<String> = allowed
+                        .iter()
+                        .map(|c| format!("\"{}\"", c.name()))
+                        .collect();
+                    format!(
+                        "Layer '{}': invalid coerce {} '{}' (expected {})",
+                        layer_name,
+                        key,
+                        value,
+                        names.join(", ")
+                    )
+                }),
+            };
+        Ok(AttributeCoercion {
+            numeric: coercion("numeric", &cfg.numeric, defaults.numeric, &all)?,
+            json: coercion("json", &cfg.json, defaults.json, &string_or_skip)?,
+            array: coercion("array", &cfg.array, defaults.array, &string_or_skip)?,
+            other: coercion("other", &cfg.other, defaults.other, &string_or_skip)?,
+        })
+    }
+    /// Config value with non-default coercions, e.g. `{ numeric = "int" }`
+    fn runtime_config(&self) -> Option<String> {
+        let defaults = AttributeCoercion::default();
+        let entries: Vec<String> = [
+            ("numeric", self.numeric, defaults.numeric),
+            ("json", self.json, defaults.json),
+            ("array", self.array, defaults.array),
+            ("other", self.other, defaults.other),
+        ]
+        .iter()
+        .filter(|(_, coercion, default)| coercion != default)
+        .map(|(key, coercion, _)| format!("{} = \"{}\"", key, coercion.name()))
+        .collect();
+        if entries.is_empty() {
+            None
+        } else {
+            Some(format!("{{ {} }}", entries.join(", ")))
+        }
+    }
+}
+
+/// Attribute of layers with `duplicate_features = "flag"`
+pub const LABEL_TILE_ATTRIBUTE: &str = "label_tile";
+
+/// Feature with localized name attribute
+pub struct LocalizedFeature<'a> {
+    pub layer: &'a Layer,
+    pub feature: &'a dyn Feature,
+    pub lang: Option<&'a str>,
+}
+
+/// Check language code of a request (e.g. `de` or `zh-Hant`)
+pub fn valid_lang(lang: &str) -> bool {
+    lang.len() > 0
+        && lang.len() <= 35
+        && lang.starts_with(|c: char| c.is_ascii_alphabetic())
+        && lang
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
 impl Layer {
     pub fn new(name: &str) -> Layer {
         Layer {
             name: String::from(name),
             tile_size: 4096,
+            clip_geom: true,
             ..Default::default()
         }
     }
@@ -70,6 +535,70 @@ impl Layer {
                 .unwrap_or(default),
         )
     }
+    /// Layer with the requested parameter values bound to the query.
+    /// Parameters without requested value are bound to their default.
+    pub fn with_param_values(&self, values: &ParamValues) -> Result<Layer, String> {
+        let mut layer = self.clone();
+        for param in &mut layer.params {
+            param.value = match values.get(&param.name) {
+                Some(value) => param.check(value)?,
+                None => param.default.clone(),
+            };
+        }
+        Ok(layer)
+    }
+    /// Tile buffer in grid units.
+    /// A `buffer_size` in pixels of a 256 pixel tile is scaled by the tile width in grid units,
+    /// i.e. `buffer_size * resolution * grid tile size / 256`.
+    pub fn buffer_width(&self, grid: &Grid, zoom: u8) -> f64 {
+        let size = f64::from(self.buffer_size.unwrap_or(0));
+        match self.buffer_unit {
+            BufferUnit::Pixels => {
+                size * grid.resolution(zoom) * f64::from(grid.tile_size().0) / 256.0
+            }
+            BufferUnit::Grid => size,
+        }
+    }
+    /// Extent buffered by the tile buffer
+    pub fn buffered_extent(&self, extent: &Extent, grid: &Grid, zoom: u8) -> Extent {
+        let buf = self.buffer_width(grid, zoom);
+        Extent {
+            minx: extent.minx - buf,
+            miny: extent.miny - buf,
+            maxx: extent.maxx + buf,
+            maxy: extent.maxy + buf,
+        }
+    }
+    /// Zoom range of attribute. None, if included at all zoom levels.
+    pub fn attribute_zoom_range(&self, name: &str) -> Option<(u8, u8)> {
+        if self.fid_field.as_ref().map(|f| f == name).unwrap_or(false) {
+            return None;
+        }
+        let attr = self.attributes.iter().find(|a| a.name == name);
+        let minzoom = attr.and_then(|a| a.minzoom).or(self.attributes_minzoom);
+        let maxzoom = attr.and_then(|a| a.maxzoom).or(self.attributes_maxzoom);
+        if minzoom.is_none() && maxzoom.is_none() {
+            None
+        } else {
+            Some((minzoom.unwrap_or(0), maxzoom.unwrap_or(22)))
+        }
+    }
+    /// Check whether attribute is included at zoom level
+    pub fn attribute_in_zoom(&self, name: &str, zoom: u8) -> bool {
+        match self.attribute_zoom_range(name) {
+            Some((minzoom, maxzoom)) => zoom >= minzoom && zoom <= maxzoom,
+            None => true,
+        }
+    }
+    /// Attributes depend on zoom level
+    pub fn has_zoom_dependent_attributes(&self) -> bool {
+        self.attributes_minzoom.is_some()
+            || self.attributes_maxzoom.is_some()
+            || self
+                .attributes
+                .iter()
+                .any(|a| a.minzoom.is_some() || a.maxzoom.is_some())
+    }
     // SQL query for zoom level
     pub fn query(&self, level: u8) -> Option<&String> {
         let mut queries = self
@@ -90,6 +619,44 @@ impl Layer {
             .find(|ref q| level >= q.0 && level <= q.1);
         query.and_then(|ref q| q.2)
     }
+    /// Replace `<localized_name>:<lang>` attributes with a single `localized_name` attribute.
+    /// The value is taken from the requested language, `default_lang` or the unlocalized
+    /// attribute, whichever is found first and not empty.
+    pub fn localize_attributes(
+        &self,
+        attributes: Vec<FeatureAttr>,
+        lang: Option<&str>,
+    ) -> Vec<FeatureAttr> {
+        let name = match self.localized_name {
+            Some(ref name) => name,
+            None => return attributes,
+        };
+        let prefix = format!("{}:", name);
+        let mut keys: Vec<String> = lang
+            .iter()
+            .chain(self.default_lang.as_ref().map(|l| l.as_str()).iter())
+            .map(|lang| format!("{}{}", prefix, lang))
+            .collect();
+        keys.push(name.clone());
+        let value = keys.iter().find_map(|key| {
+            attributes
+                .iter()
+                .find(|attr| &attr.key == key)
+                .filter(|attr| attr.value != FeatureAttrValType::String(String::new()))
+                .map(|attr| attr.value.clone())
+        });
+        let mut localized: Vec<FeatureAttr> = attributes
+            .into_iter()
+            .filter(|attr| &attr.key != name && !attr.key.starts_with(&prefix))
+            .collect();
+        if let Some(value) = value {
+            localized.push(FeatureAttr {
+                key: name.clone(),
+                value,
+            });
+        }
+        localized
+    }
     /// Layer properties needed e.g. for metadata.json
     pub fn metadata(&self) -> HashMap<&str, String> {
         //TODO: return Zoom-Level Array
@@ -107,6 +674,92 @@ impl Layer {
 
 impl<'a> Config<'a, LayerCfg> for Layer {
     fn from_config(layer_cfg: &LayerCfg) -> Result<Self, String> {
+        if let Some(ref lang) = layer_cfg.default_lang {
+            if !valid_lang(lang) {
+                return Err(format!(
+                    "Layer '{}': invalid default_lang '{}'",
+                    layer_cfg.name, lang
+                ));
+            }
+        }
+        let buffer_unit = match layer_cfg.buffer_unit.as_ref().map(|unit| unit.as_str()) {
+            None | Some("pixels") => BufferUnit::Pixels,
+            Some("grid") => BufferUnit::Grid,
+            Some(unit) => {
+                return Err(format!(
+                    "Layer '{}': invalid buffer_unit '{}' (expected \"pixels\" or \"grid\")",
+                    layer_cfg.name, unit
+                ))
+            }
+        };
+        let duplicate_features = match layer_cfg
+            .duplicate_features
+            .as_ref()
+            .map(|mode| mode.as_str())
+        {
+            None | Some("all") => DuplicateFeatures::All,
+            Some("flag") => DuplicateFeatures::Flag,
+            Some("label_point") => DuplicateFeatures::LabelPoint,
+            Some(mode) => {
+                return Err(format!(
+                    "Layer '{}': invalid duplicate_features '{}' (expected \"all\", \"flag\" or \"label_point\")",
+                    layer_cfg.name, mode
+                ))
+            }
+        };
+        let skip_missing_table = match layer_cfg.missing_table.as_ref().map(|s| s.as_str()) {
+            None | Some("error") => false,
+            Some("skip") => true,
+            Some(policy) => {
+                return Err(format!(
+                    "Layer '{}': invalid missing_table '{}' (expected \"error\" or \"skip\")",
+                    layer_cfg.name, policy
+                ))
+            }
+        };
+        let no_cache = !layer_cfg.cache.unwrap_or(true);
+        if layer_cfg.cache_control_max_age.is_some() && !no_cache {
+            return Err(format!(
+                "Layer '{}': cache_control_max_age requires cache = false",
+                layer_cfg.name
+            ));
+        }
+        let coerce = match layer_cfg.coerce {
+            Some(ref cfg) => AttributeCoercion::from_config(&layer_cfg.name, cfg)?,
+            None => AttributeCoercion::default(),
+        };
+        if let Some(max_vertices) = layer_cfg.subdivide {
+            if max_vertices < 5 {
+                return Err(format!(
+                    "Layer '{}': subdivide must be at least 5 vertices",
+                    layer_cfg.name
+                ));
+            }
+        }
+        let tile_size = match layer_cfg.geometry_precision_bits {
+            Some(_) if layer_cfg.tile_size != config::default_tile_size() => {
+                return Err(format!(
+                    "Layer '{}': tile_size and geometry_precision_bits are mutually exclusive",
+                    layer_cfg.name
+                ));
+            }
+            Some(bits) if bits < 8 || bits > 24 => {
+                return Err(format!(
+                    "Layer '{}': geometry_precision_bits must be between 8 and 24",
+                    layer_cfg.name
+                ));
+            }
+            Some(bits) => 1 << bits,
+            None => layer_cfg.tile_size,
+        };
+        if let Some(size) = layer_cfg.snap_to_grid {
+            if !(size > 0.0) {
+                return Err(format!(
+                    "Layer '{}': snap_to_grid must be positive",
+                    layer_cfg.name
+                ));
+            }
+        }
         let queries = layer_cfg
             .query
             .iter()
@@ -116,6 +769,19 @@ impl<'a> Config<'a, LayerCfg> for Layer {
                 sql: lq.sql.clone(),
             })
             .collect();
+        let params: Vec<LayerParam> = layer_cfg
+            .param
+            .iter()
+            .map(|cfg| LayerParam::from_config(&layer_cfg.name, cfg))
+            .collect::<Result<_, _>>()?;
+        for (i, param) in params.iter().enumerate() {
+            if params[..i].iter().any(|p| p.name == param.name) {
+                return Err(format!(
+                    "Layer '{}': duplicate parameter '{}'",
+                    layer_cfg.name, param.name
+                ));
+            }
+        }
         let style = match layer_cfg.style {
             Some(ref style) => {
                 let gljson = toml_style_to_gljson(&style);
@@ -134,26 +800,68 @@ impl<'a> Config<'a, LayerCfg> for Layer {
             table_name: layer_cfg.table_name.clone(),
             query_limit: layer_cfg.query_limit,
             query: queries,
+            params,
             minzoom: layer_cfg.minzoom,
             maxzoom: layer_cfg.maxzoom,
-            tile_size: layer_cfg.tile_size,
+            attributes: layer_cfg
+                .attribute
+                .iter()
+                .map(|attr| LayerAttribute {
+                    name: attr.name.clone(),
+                    minzoom: attr.minzoom,
+                    maxzoom: attr.maxzoom,
+                })
+                .collect(),
+            attributes_minzoom: layer_cfg.attributes_minzoom,
+            attributes_maxzoom: layer_cfg.attributes_maxzoom,
+            tile_size,
+            snap_to_grid: layer_cfg.snap_to_grid,
+            deterministic: layer_cfg.deterministic,
             simplify: layer_cfg.simplify,
             tolerance: layer_cfg.tolerance.clone(),
             buffer_size: layer_cfg.buffer_size,
+            buffer_unit,
+            duplicate_features,
+            clip_geom: layer_cfg.clip_geom,
             make_valid: layer_cfg.make_valid,
             shift_longitude: layer_cfg.shift_longitude,
+            use_st_asmvt: layer_cfg.use_st_asmvt,
+            subdivide: layer_cfg.subdivide,
+            localized_name: layer_cfg.localized_name.clone(),
+            default_lang: layer_cfg.default_lang.clone(),
+            skip_missing_table,
+            no_cache,
+            cache_control_max_age: layer_cfg.cache_control_max_age,
+            coerce,
             style: style,
         })
     }
 
     fn gen_config() -> String {
         let toml = r#"
+# Defaults merged into all tilesets and layers. Explicit values override defaults,
+# tables are merged and arrays are replaced as a whole.
+#[tileset_defaults]
+#cache_limits = {minzoom = 0, maxzoom = 14}
+#[layer_defaults]
+#buffer_size = 10
+
 [[tileset]]
 name = "points"
 #minzoom = 0
 #maxzoom = 22
 #attribution = "© Contributeurs de OpenStreetMap" # Acknowledgment of ownership, authorship or copyright.
 #cache_limits = {minzoom = 0, maxzoom = 22, no_cache = false}
+#layer_order = ["points"] # Layer order in tiles (Default: order of layer entries)
+#max_tile_bytes = 500000 # Simplify geometries until tiles fit into this size
+#max_tile_memory = 50000000 # Abort creating tiles exceeding this size while reading features
+#mbtiles = "contours.mbtiles" # Serve tiles from MBTiles archive instead of layers
+#overzoom = true # Create tiles above the archive maxzoom from parent tiles
+#default_style_file = "points-style.json" # Style JSON of the viewer (Default: generated from layer styles)
+#on_layer_error = "skip" # Omit layers with query errors instead of failing the tile (Default: "fail")
+#always_gzip = true # Serve gzip compressed tiles also without Accept-Encoding: gzip
+#static_dir = "static/points" # Files served under /points/static/, a viewer.html replaces the preview page /points/
+#aliases = ["pois"] # Former names resolving to this tileset
 
 [[tileset.layer]]
 name = "points"
@@ -163,11 +871,43 @@ geometry_type = "POINT"
 #simplify = true
 #tolerance = "!pixel_width!/2"
 #buffer_size = 10
+#buffer_unit = "grid" # Unit of buffer_size: "pixels" of a 256 pixel tile or "grid" SRS units (Default: "pixels")
+#clip_geom = false # Skip clipping to tile buffer (e.g. for pre-tiled data)
+#duplicate_features = "label_point" # Features in multiple tiles: "all", "flag" (attribute label_tile) or only in tile of "label_point" (Default: "all")
 #make_valid = true
+#use_st_asmvt = true # Encode layer in PostGIS with ST_AsMVT
+#subdivide = 1024 # Split large geometries into parts with max. 1024 vertices before clipping
+#geometry_precision_bits = 14 # Tile coordinate precision, alternative to tile_size = 16384 (Default: 12)
+#snap_to_grid = 0.01 # Round coordinates to a grid in grid SRS units before tiling
+#deterministic = true # Byte-identical tiles independent of the feature order of the query
+#localized_name = "name" # Attribute name from name:<lang> for requests with ?lang=<lang>
+#default_lang = "en" # Language of localized_name without ?lang parameter
+#missing_table = "skip" # Disable layer while table_name is missing instead of failing (Default: "error")
+#cache = false # Query frequently changing layer for each request and merge it into the cached tile
+#cache_control_max_age = 10 # Cache-Control max-age of tiles with this uncached layer (Default: 0)
+# Coercion of column types without MVT value type (Defaults: numeric = "double", json/jsonb = "string",
+# arrays = "string" with comma-joined elements, other types like date = "string"). "skip" omits the attribute.
+#coerce = { numeric = "int", json = "skip" }
+#attributes_minzoom = 10 # Include attributes (except fid_field) from zoom level 10
+#[tileset.layer.style] # Style JSON layer properties
+#circle-color = "red" # Shorthand for paint or layout property
+#circle-radius = 4
 #[[tileset.layer.query]]
 #minzoom = 0
 #maxzoom = 22
 #sql = "SELECT name,wkb_geometry FROM mytable"
+#query_file = "sql/points.sql" # SQL query from file (instead of sql)
+#profile = "prod" # Only use query with selected profile (--profile or TREX_PROFILE)
+#[[tileset.layer.param]] # Query variable !date! with value from request ?param_date=2023-01-01 (PostGIS)
+#name = "date"
+#type = "date" # "int", "float", "date" (YYYY-MM-DD) or "string"
+#default = "2023-01-01"
+#min = "2020-01-01"
+#max = "2023-12-31"
+#values = ["2022-01-01", "2023-01-01"] # Allowed values
+#[[tileset.layer.attribute]]
+#name = "population"
+#minzoom = 12
 "#;
         toml.to_string()
     }
@@ -206,12 +946,31 @@ geometry_type = "POINT"
             lines.push(format!("fid_field = \"{}\"", fid_field));
         }
         if self.tile_size != 4096 {
-            lines.push(format!(r#"tile_size = "{}""#, self.tile_size));
+            lines.push(format!("tile_size = {}", self.tile_size));
+        }
+        if let Some(size) = self.snap_to_grid {
+            lines.push(format!("snap_to_grid = {}", size));
+        }
+        if self.deterministic {
+            lines.push(format!("deterministic = true"));
         }
         match self.buffer_size {
             Some(ref buffer_size) => lines.push(format!("buffer_size = {}", buffer_size)),
             _ => lines.push(format!("#buffer_size = 10")),
         }
+        if self.buffer_unit == BufferUnit::Grid {
+            lines.push(format!(r#"buffer_unit = "grid""#));
+        }
+        if !self.clip_geom {
+            lines.push(format!("clip_geom = false"));
+        }
+        match self.duplicate_features {
+            DuplicateFeatures::All => {}
+            DuplicateFeatures::Flag => lines.push(format!(r#"duplicate_features = "flag""#)),
+            DuplicateFeatures::LabelPoint => {
+                lines.push(format!(r#"duplicate_features = "label_point""#))
+            }
+        }
         match self.make_valid {
             true => lines.push(format!("make_valid = true")),
             _ => lines.push(format!("#make_valid = true")),
@@ -219,6 +978,30 @@ geometry_type = "POINT"
         if self.shift_longitude {
             lines.push(format!("shift_longitude = true"));
         }
+        if self.use_st_asmvt {
+            lines.push(format!("use_st_asmvt = true"));
+        }
+        if let Some(max_vertices) = self.subdivide {
+            lines.push(format!("subdivide = {}", max_vertices));
+        }
+        if let Some(ref localized_name) = self.localized_name {
+            lines.push(format!("localized_name = \"{}\"", localized_name));
+        }
+        if let Some(ref default_lang) = self.default_lang {
+            lines.push(format!("default_lang = \"{}\"", default_lang));
+        }
+        if self.skip_missing_table {
+            lines.push(format!(r#"missing_table = "skip""#));
+        }
+        if self.no_cache {
+            lines.push(format!("cache = false"));
+        }
+        if let Some(max_age) = self.cache_control_max_age {
+            lines.push(format!("cache_control_max_age = {}", max_age));
+        }
+        if let Some(coerce) = self.coerce.runtime_config() {
+            lines.push(format!("coerce = {}", coerce));
+        }
         if self.geometry_type != Some("POINT".to_string()) {
             // simplify is ignored for points
             lines.push(format!("simplify = {}", self.simplify));
@@ -230,6 +1013,9 @@ geometry_type = "POINT"
             Some(ref query_limit) => lines.push(format!("query_limit = {}", query_limit)),
             _ => lines.push("#query_limit = 1000".to_string()),
         }
+        for param in &self.params {
+            lines.push(param.runtime_config());
+        }
         match self.query(0) {
             Some(ref query) => {
                 lines.push("[[tileset.layer.query]]".to_string());
@@ -242,3 +1028,16 @@ geometry_type = "POINT"
         lines.join("\n") + "\n"
     }
 }
+
+impl<'a> Feature for LocalizedFeature<'a> {
+    fn fid(&self) -> Option<u64> {
+        self.feature.fid()
+    }
+    fn attributes(&self) -> Vec<FeatureAttr> {
+        self.layer
+            .localize_attributes(self.feature.attributes(), self.lang)
+    }
+    fn geometry(&self) -> Result<GeometryType, String> {
+        self.feature.geometry()
+    }
+}
@@ -4,7 +4,8 @@
 //
 
 use crate::core::config::Config;
-use crate::core::layer::Layer;
+use crate::core::layer::{AttributeCoercion, Coercion, Layer, ParamValue, ParamValues};
+#[cfg(feature = "server")]
 use crate::service::tileset::Tileset;
 
 fn layer_from_config(toml: &str) -> Result<Layer, String> {
@@ -85,6 +86,7 @@ fn test_toml_decode() {
     assert_eq!(cfg.name, "points2");
     assert_eq!(cfg.table_name, None);
     assert_eq!(cfg.query.len(), 0);
+    assert!(cfg.clip_geom);
     assert_eq!(cfg.minzoom(), 0);
     assert_eq!(cfg.maxzoom(30), 30);
 
@@ -231,6 +233,7 @@ fn test_toml_decode() {
 }
 
 #[test]
+#[cfg(feature = "server")]
 fn test_layers_from_config() {
     use crate::core::config::TilesetCfg;
     use crate::core::parse_config;
@@ -278,3 +281,510 @@ fn test_layers_from_config() {
         Some(" - missing field `name`".to_string())
     );
 }
+
+#[test]
+fn test_attribute_zooms() {
+    let toml = r#"
+        name = "points"
+        table_name = "ne_10m_populated_places"
+        geometry_field = "wkb_geometry"
+        fid_field = "id"
+        attributes_maxzoom = 14
+        [[attribute]]
+        name = "population"
+        minzoom = 10
+        "#;
+    let layer = layer_from_config(toml).unwrap();
+    assert!(layer.has_zoom_dependent_attributes());
+    assert_eq!(layer.attribute_zoom_range("id"), None);
+    assert_eq!(layer.attribute_zoom_range("population"), Some((10, 14)));
+    assert_eq!(layer.attribute_zoom_range("name"), Some((0, 14)));
+    assert!(layer.attribute_in_zoom("id", 20));
+    assert!(!layer.attribute_in_zoom("population", 9));
+    assert!(layer.attribute_in_zoom("population", 12));
+    assert!(!layer.attribute_in_zoom("name", 15));
+
+    let layer = Layer::new("points");
+    assert!(!layer.has_zoom_dependent_attributes());
+    assert!(layer.attribute_in_zoom("name", 0));
+}
+
+#[test]
+fn test_id_column() {
+    let toml = r#"
+        name = "points"
+        table_name = "ne_10m_populated_places"
+        geometry_field = "wkb_geometry"
+        id_column = "fid"
+        "#;
+    let layer = layer_from_config(toml).unwrap();
+    assert_eq!(layer.fid_field, Some("fid".to_string()));
+}
+
+#[test]
+fn test_subdivide() {
+    let toml = r#"
+        name = "coastlines"
+        table_name = "land_polygons"
+        geometry_field = "geom"
+        geometry_type = "POLYGON"
+        subdivide = 512
+        "#;
+    let layer = layer_from_config(toml).unwrap();
+    assert_eq!(layer.subdivide, Some(512));
+    assert!(layer.gen_runtime_config().contains("\nsubdivide = 512\n"));
+
+    let toml = r#"
+        name = "coastlines"
+        subdivide = 4
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some("Layer 'coastlines': subdivide must be at least 5 vertices".to_string())
+    );
+}
+
+#[test]
+fn test_geometry_precision() {
+    let toml = r#"
+        name = "parcels"
+        geometry_precision_bits = 14
+        snap_to_grid = 0.01
+        deterministic = true
+        "#;
+    let layer = layer_from_config(toml).unwrap();
+    assert_eq!(layer.tile_size, 16384);
+    assert_eq!(layer.snap_to_grid, Some(0.01));
+    assert!(layer.deterministic);
+    let runtime_config = layer.gen_runtime_config();
+    assert!(
+        runtime_config.contains("\ntile_size = 16384\nsnap_to_grid = 0.01\ndeterministic = true\n")
+    );
+
+    let toml = r#"
+        name = "parcels"
+        tile_size = 8192
+        geometry_precision_bits = 14
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some(
+            "Layer 'parcels': tile_size and geometry_precision_bits are mutually exclusive"
+                .to_string()
+        )
+    );
+    let toml = r#"
+        name = "parcels"
+        geometry_precision_bits = 32
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some("Layer 'parcels': geometry_precision_bits must be between 8 and 24".to_string())
+    );
+    let toml = r#"
+        name = "parcels"
+        snap_to_grid = 0.0
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some("Layer 'parcels': snap_to_grid must be positive".to_string())
+    );
+}
+
+#[test]
+fn test_clip() {
+    use tile_grid::Grid;
+
+    let toml = r#"
+        name = "roads"
+        table_name = "osm_roads"
+        geometry_field = "geometry"
+        geometry_type = "LINESTRING"
+        buffer_size = 8
+        clip = false
+        "#;
+    let layer = layer_from_config(toml).unwrap();
+    assert!(!layer.clip_geom);
+    assert!(layer.gen_runtime_config().contains("\nclip_geom = false\n"));
+
+    // Buffer in pixels of a 256 pixel tile
+    let grid = Grid::web_mercator();
+    let extent = grid.tile_extent(0, 0, 1);
+    let buffered = layer.buffered_extent(&extent, &grid, 1);
+    let buf = 8.0 * (extent.maxx - extent.minx) / 256.0;
+    assert!((buffered.minx - (extent.minx - buf)).abs() < 1e-6);
+    assert!((buffered.maxy - (extent.maxy + buf)).abs() < 1e-6);
+
+    let grid = Grid::wgs84();
+    let extent = grid.tile_extent(0, 0, 1);
+    let buffered = layer.buffered_extent(&extent, &grid, 1);
+    assert!((buffered.maxx - extent.maxx - 8.0 * grid.resolution(1)).abs() < 1e-9);
+}
+
+#[test]
+fn test_buffer_unit() {
+    use crate::core::layer::BufferUnit;
+    use tile_grid::Grid;
+
+    let toml = r#"
+        name = "roads"
+        table_name = "osm_roads"
+        geometry_type = "LINESTRING"
+        buffer_size = 8
+        "#;
+    let layer = layer_from_config(toml).unwrap();
+    assert_eq!(layer.buffer_unit, BufferUnit::Pixels);
+    assert!(!layer.gen_runtime_config().contains("buffer_unit"));
+    // Pixels of a 256 pixel tile, scaled by the tile width in grid units
+    let grid = Grid::web_mercator();
+    let tile_width = grid.resolution(3) * 256.0;
+    assert!((layer.buffer_width(&grid, 3) - 8.0 * tile_width / 256.0).abs() < 1e-6);
+    assert!((layer.buffer_width(&grid, 4) - layer.buffer_width(&grid, 3) / 2.0).abs() < 1e-6);
+
+    let toml = r#"
+        name = "roads"
+        table_name = "osm_roads"
+        geometry_type = "LINESTRING"
+        buffer_size = 500
+        buffer_unit = "grid"
+        "#;
+    let layer = layer_from_config(toml).unwrap();
+    assert_eq!(layer.buffer_unit, BufferUnit::Grid);
+    assert!(layer
+        .gen_runtime_config()
+        .contains("\nbuffer_size = 500\nbuffer_unit = \"grid\"\n"));
+    // Grid units at all zoom levels and grids
+    for grid in &[Grid::web_mercator(), Grid::wgs84()] {
+        assert_eq!(layer.buffer_width(grid, 3), 500.0);
+        assert_eq!(layer.buffer_width(grid, 10), 500.0);
+    }
+    let extent = Grid::web_mercator().tile_extent(0, 0, 1);
+    let buffered = layer.buffered_extent(&extent, &Grid::web_mercator(), 1);
+    assert_eq!(buffered.minx, extent.minx - 500.0);
+
+    let toml = r#"
+        name = "roads"
+        buffer_unit = "meters"
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some(
+            "Layer 'roads': invalid buffer_unit 'meters' (expected \"pixels\" or \"grid\")"
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn test_duplicate_features() {
+    use crate::core::layer::DuplicateFeatures;
+
+    let toml = r#"
+        name = "labels"
+        table_name = "osm_labels"
+        "#;
+    let layer = layer_from_config(toml).unwrap();
+    assert_eq!(layer.duplicate_features, DuplicateFeatures::All);
+    assert!(!layer.gen_runtime_config().contains("duplicate_features"));
+
+    for (mode, expected) in &[
+        ("all", DuplicateFeatures::All),
+        ("flag", DuplicateFeatures::Flag),
+        ("label_point", DuplicateFeatures::LabelPoint),
+    ] {
+        let toml = format!(
+            r#"
+            name = "labels"
+            table_name = "osm_labels"
+            duplicate_features = "{}"
+            "#,
+            mode
+        );
+        let layer = layer_from_config(&toml).unwrap();
+        assert_eq!(layer.duplicate_features, *expected);
+    }
+    assert!(layer_from_config(
+        "name = \"labels\"\nduplicate_features = \"label_point\""
+    )
+    .unwrap()
+    .gen_runtime_config()
+    .contains("\nduplicate_features = \"label_point\"\n"));
+
+    let toml = r#"
+        name = "labels"
+        duplicate_features = "centroid"
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some(
+            "Layer 'labels': invalid duplicate_features 'centroid' (expected \"all\", \"flag\" or \"label_point\")"
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn test_localized_name() {
+    use crate::core::feature::{FeatureAttr, FeatureAttrValType};
+
+    let toml = r#"
+        name = "places"
+        localized_name = "name"
+        default_lang = "en"
+        "#;
+    let layer = layer_from_config(toml).unwrap();
+    assert!(layer
+        .gen_runtime_config()
+        .contains("\nlocalized_name = \"name\"\ndefault_lang = \"en\"\n"));
+
+    let attr = |key: &str, value: &str| FeatureAttr {
+        key: key.to_string(),
+        value: FeatureAttrValType::String(value.to_string()),
+    };
+    let attributes = vec![
+        attr("name", "Zürich"),
+        attr("name:en", "Zurich"),
+        attr("name:fr", ""),
+        attr("population", "400000"),
+    ];
+    let name = |attrs: Vec<FeatureAttr>| {
+        assert_eq!(attrs.len(), 2);
+        assert_eq!(attrs[0].key, "population");
+        assert_eq!(attrs[1].key, "name");
+        attrs[1].value.clone()
+    };
+    let localized = |lang| name(layer.localize_attributes(attributes.clone(), lang));
+    assert_eq!(localized(Some("en")), FeatureAttrValType::String("Zurich".to_string()));
+    // Fallback to default_lang
+    assert_eq!(localized(None), FeatureAttrValType::String("Zurich".to_string()));
+    assert_eq!(localized(Some("fr")), FeatureAttrValType::String("Zurich".to_string()));
+    // Fallback to unlocalized attribute
+    let mut layer = layer.clone();
+    layer.default_lang = None;
+    assert_eq!(
+        name(layer.localize_attributes(attributes.clone(), Some("de"))),
+        FeatureAttrValType::String("Zürich".to_string())
+    );
+
+    let toml = r#"
+        name = "places"
+        localized_name = "name"
+        default_lang = "../en"
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some("Layer 'places': invalid default_lang '../en'".to_string())
+    );
+}
+
+#[test]
+fn test_missing_table() {
+    let toml = r#"
+        name = "roads"
+        table_name = "osm_roads"
+        "#;
+    let layer = layer_from_config(toml).unwrap();
+    assert!(!layer.skip_missing_table);
+    assert!(!layer.gen_runtime_config().contains("missing_table"));
+
+    let toml = r#"
+        name = "roads"
+        table_name = "osm_roads"
+        missing_table = "skip"
+        "#;
+    let layer = layer_from_config(toml).unwrap();
+    assert!(layer.skip_missing_table);
+    assert!(layer
+        .gen_runtime_config()
+        .contains("\nmissing_table = \"skip\"\n"));
+
+    let toml = r#"
+        name = "roads"
+        missing_table = "ignore"
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some(
+            "Layer 'roads': invalid missing_table 'ignore' (expected \"error\" or \"skip\")"
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn test_layer_cache() {
+    let toml = r#"
+        name = "vehicles"
+        table_name = "vehicle_positions"
+        "#;
+    let layer = layer_from_config(toml).unwrap();
+    assert!(!layer.no_cache);
+    assert!(!layer.gen_runtime_config().contains("cache"));
+
+    let toml = r#"
+        name = "vehicles"
+        table_name = "vehicle_positions"
+        cache = false
+        cache_control_max_age = 10
+        "#;
+    let layer = layer_from_config(toml).unwrap();
+    assert!(layer.no_cache);
+    assert_eq!(layer.cache_control_max_age, Some(10));
+    assert!(layer
+        .gen_runtime_config()
+        .contains("\ncache = false\ncache_control_max_age = 10\n"));
+
+    let toml = r#"
+        name = "vehicles"
+        cache_control_max_age = 10
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some("Layer 'vehicles': cache_control_max_age requires cache = false".to_string())
+    );
+}
+
+#[test]
+fn test_coerce() {
+    let toml = r#"
+        name = "roads"
+        "#;
+    let layer = layer_from_config(toml).unwrap();
+    assert_eq!(layer.coerce, AttributeCoercion::default());
+    assert_eq!(layer.coerce.numeric, Coercion::Double);
+    assert_eq!(layer.coerce.json, Coercion::String);
+    assert_eq!(layer.coerce.array, Coercion::String);
+    assert_eq!(layer.coerce.other, Coercion::String);
+    assert!(!layer.gen_runtime_config().contains("coerce"));
+
+    let toml = r#"
+        name = "roads"
+        coerce = { numeric = "int", array = "skip" }
+        "#;
+    let layer = layer_from_config(toml).unwrap();
+    assert_eq!(layer.coerce.numeric, Coercion::Int);
+    assert_eq!(layer.coerce.json, Coercion::String);
+    assert_eq!(layer.coerce.array, Coercion::Skip);
+    assert!(layer
+        .gen_runtime_config()
+        .contains("\ncoerce = { numeric = \"int\", array = \"skip\" }\n"));
+
+    let toml = r#"
+        name = "roads"
+        coerce = { json = "int" }
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some(
+            "Layer 'roads': invalid coerce json 'int' (expected \"string\", \"skip\")".to_string()
+        )
+    );
+}
+
+#[test]
+fn test_params() {
+    let toml = r#"
+        name = "events"
+        [[param]]
+        name = "date"
+        type = "date"
+        default = "2023-01-01"
+        min = "2020-01-01"
+        max = "2023-12-31"
+        [[param]]
+        name = "category"
+        type = "string"
+        default = "all"
+        values = ["all", "music", "sports"]
+        [[param]]
+        name = "min_size"
+        type = "float"
+        default = 0
+        "#;
+    let layer = layer_from_config(toml).unwrap();
+    assert_eq!(layer.params.len(), 3);
+    assert_eq!(
+        layer.params[0].value,
+        ParamValue::Text("2023-01-01".to_string())
+    );
+    assert_eq!(layer.params[2].default, ParamValue::Float(0.0));
+    assert!(layer.gen_runtime_config().contains(
+        "\n[[tileset.layer.param]]\nname = \"date\"\ntype = \"date\"\ndefault = \"2023-01-01\"\nmin = \"2020-01-01\"\nmax = \"2023-12-31\"\n"
+    ));
+    assert!(layer
+        .gen_runtime_config()
+        .contains("\nvalues = [\"all\", \"music\", \"sports\"]\n"));
+
+    let mut values = ParamValues::new();
+    values.insert("date".to_string(), "2022-02-28".to_string());
+    values.insert("min_size".to_string(), "1e3".to_string());
+    let bound = layer.with_param_values(&values).unwrap();
+    assert_eq!(
+        bound.params[0].value,
+        ParamValue::Text("2022-02-28".to_string())
+    );
+    assert_eq!(bound.params[1].value, ParamValue::Text("all".to_string()));
+    assert_eq!(bound.params[2].value, ParamValue::Float(1000.0));
+
+    let error = |name: &str, value: &str| {
+        let mut values = ParamValues::new();
+        values.insert(name.to_string(), value.to_string());
+        layer.with_param_values(&values).err().unwrap()
+    };
+    assert_eq!(
+        error("date", "2022-02-29"),
+        "value '2022-02-29' of parameter 'date' is not a valid date"
+    );
+    assert_eq!(
+        error("date", "2019-12-31"),
+        "value '2019-12-31' of parameter 'date' is out of range (2020-01-01..2023-12-31)"
+    );
+    assert_eq!(
+        error("category", "politics"),
+        "value 'politics' of parameter 'category' is not allowed"
+    );
+    assert_eq!(
+        error("min_size", "NaN"),
+        "value 'NaN' of parameter 'min_size' is not a valid float"
+    );
+
+    let toml = r#"
+        name = "events"
+        [[param]]
+        name = "zoom"
+        type = "int"
+        default = 0
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some("Layer 'events': invalid parameter name 'zoom'".to_string())
+    );
+    let toml = r#"
+        name = "events"
+        [[param]]
+        name = "limit"
+        type = "int"
+        default = 5
+        max = 4
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some(
+            "Layer 'events': default value '5' of parameter 'limit' is out of range (..4)"
+                .to_string()
+        )
+    );
+    let toml = r#"
+        name = "events"
+        [[param]]
+        name = "limit"
+        type = "int"
+        default = "5"
+        "#;
+    assert_eq!(
+        layer_from_config(toml).err(),
+        Some(
+            "Layer 'events': invalid default \"5\" of parameter 'limit' (expected int)".to_string()
+        )
+    );
+}
@@ -11,6 +11,8 @@ mod gridcfg;
 pub mod layer;
 pub mod screen;
 pub mod stats;
+pub mod tilematrixset;
+pub mod upgrade;
 
 pub use self::config::{parse_config, read_config, ApplicationCfg, Config};
 
@@ -22,3 +24,7 @@ mod geom_test;
 mod gridcfg_test;
 #[cfg(test)]
 mod layer_test;
+#[cfg(test)]
+mod tilematrixset_test;
+#[cfg(test)]
+mod upgrade_test;
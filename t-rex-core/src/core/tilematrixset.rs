@@ -0,0 +1,312 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+//! Grids as OGC Two Dimensional Tile Matrix Set 2.0 JSON (http://docs.opengeospatial.org/is/17-083r4/17-083r4.html)
+
+use serde_json::Value;
+use std::f64::consts;
+use tile_grid::{Extent, Grid, Origin, Unit};
+
+const CRS84: &str = "http://www.opengis.net/def/crs/OGC/1.3/CRS84";
+
+/// Standardized rendering pixel size (0.28 mm)
+const PIXEL_SIZE: f64 = 0.00028;
+
+const METERS_PER_DEGREE: f64 = 6378137.0 * 2.0 * consts::PI / 360.0;
+
+fn crs_uri(srid: i32) -> String {
+    match srid {
+        4326 => CRS84.to_string(),
+        _ => format!("http://www.opengis.net/def/crs/EPSG/0/{}", srid),
+    }
+}
+
+/// SRID of CRS URI, URN or `EPSG:<code>`
+fn srid_from_crs(crs: &Value) -> Result<i32, String> {
+    let uri = match crs {
+        Value::String(uri) => uri.as_str(),
+        Value::Object(obj) => obj.get("uri").and_then(|uri| uri.as_str()).unwrap_or(""),
+        _ => "",
+    };
+    if uri.ends_with("/CRS84") || uri.ends_with(":CRS84") {
+        return Ok(4326);
+    }
+    if uri.contains("EPSG") {
+        if let Some(Ok(srid)) = uri.rsplit(|c| c == '/' || c == ':').next().map(str::parse) {
+            return Ok(srid);
+        }
+    }
+    Err(format!("Unsupported CRS {}", crs))
+}
+
+/// Tile matrix set of `grid` with one tile matrix per zoom level
+pub fn tile_matrix_set(grid: &Grid) -> Value {
+    let extent = &grid.extent;
+    let (corner, origin) = match grid.origin {
+        Origin::TopLeft => ("topLeft", [extent.minx, extent.maxy]),
+        Origin::BottomLeft => ("bottomLeft", [extent.minx, extent.miny]),
+    };
+    let axes = match grid.units {
+        Unit::Degrees => ["Lon", "Lat"],
+        _ => ["E", "N"],
+    };
+    let (width, height) = grid.tile_size();
+    let tile_matrices: Vec<Value> = (0..grid.nlevels())
+        .map(|zoom| {
+            let (matrix_width, matrix_height) = grid.level_limit(zoom);
+            json!({
+                "id": zoom.to_string(),
+                "scaleDenominator": grid.scale_denominator(zoom),
+                "cellSize": grid.resolution(zoom),
+                "cornerOfOrigin": corner,
+                "pointOfOrigin": origin,
+                "tileWidth": width,
+                "tileHeight": height,
+                "matrixWidth": matrix_width,
+                "matrixHeight": matrix_height
+            })
+        })
+        .collect();
+    let mut tms = json!({
+        "crs": crs_uri(grid.srid),
+        "orderedAxes": axes,
+        "boundingBox": {
+            "lowerLeft": [extent.minx, extent.miny],
+            "upperRight": [extent.maxx, extent.maxy]
+        },
+        "tileMatrices": tile_matrices
+    });
+    if let Some(ref id) = grid.tile_matrix_set {
+        tms["id"] = json!(id);
+    }
+    tms
+}
+
+/// Tile matrix with the properties used by t-rex
+struct TileMatrix {
+    id: String,
+    cell_size: f64,
+    scale_denominator: Option<f64>,
+    corner: String,
+    origin: (f64, f64),
+    tile_size: (u16, u16),
+    matrix_size: (u32, u32),
+}
+
+fn number(matrix: &Value, id: &str, key: &str) -> Result<f64, String> {
+    matrix[key]
+        .as_f64()
+        .ok_or_else(|| format!("Tile matrix '{}': missing or invalid `{}`", id, key))
+}
+
+/// Coordinate pair in (x, y) order
+fn point(value: &Value, lat_first: bool) -> Option<(f64, f64)> {
+    let coords = value.as_array()?;
+    if coords.len() != 2 {
+        return None;
+    }
+    let (a, b) = (coords[0].as_f64()?, coords[1].as_f64()?);
+    Some(if lat_first { (b, a) } else { (a, b) })
+}
+
+impl TileMatrix {
+    fn from_json(matrix: &Value, lat_first: bool) -> Result<TileMatrix, String> {
+        let id = match matrix["id"] {
+            Value::String(ref id) => id.clone(),
+            ref id => id.to_string(),
+        };
+        if matrix.get("variableMatrixWidths").is_some() {
+            return Err(format!(
+                "Tile matrix '{}': variable matrix widths are not supported",
+                id
+            ));
+        }
+        let size = |key| -> Result<u32, String> {
+            matrix[key]
+                .as_u64()
+                .filter(|size| *size > 0 && *size <= u32::max_value() as u64)
+                .map(|size| size as u32)
+                .ok_or_else(|| format!("Tile matrix '{}': missing or invalid `{}`", id, key))
+        };
+        let (tile_width, tile_height) = (size("tileWidth")?, size("tileHeight")?);
+        if tile_width > u16::max_value() as u32 || tile_height > u16::max_value() as u32 {
+            return Err(format!("Tile matrix '{}': tile size too large", id));
+        }
+        Ok(TileMatrix {
+            cell_size: number(matrix, &id, "cellSize")?,
+            scale_denominator: matrix["scaleDenominator"].as_f64(),
+            corner: matrix["cornerOfOrigin"]
+                .as_str()
+                .unwrap_or("topLeft")
+                .to_string(),
+            origin: point(&matrix["pointOfOrigin"], lat_first).ok_or_else(|| {
+                format!("Tile matrix '{}': missing or invalid `pointOfOrigin`", id)
+            })?,
+            tile_size: (tile_width as u16, tile_height as u16),
+            matrix_size: (size("matrixWidth")?, size("matrixHeight")?),
+            id,
+        })
+    }
+    /// Width and height of the matrix in CRS units
+    fn span(&self) -> (f64, f64) {
+        (
+            self.matrix_size.0 as f64 * self.tile_size.0 as f64 * self.cell_size,
+            self.matrix_size.1 as f64 * self.tile_size.1 as f64 * self.cell_size,
+        )
+    }
+}
+
+fn approx_eq(a: f64, b: f64, tolerance: f64) -> bool {
+    (a - b).abs() <= tolerance
+}
+
+/// Grid units derived from the ratio of scale denominator and cell size
+fn units(matrix: &TileMatrix, srid: i32) -> Result<Unit, String> {
+    let scale_denominator = match matrix.scale_denominator {
+        Some(scale_denominator) => scale_denominator,
+        None if srid == 4326 => return Ok(Unit::Degrees),
+        None => return Ok(Unit::Meters),
+    };
+    let meters_per_unit = scale_denominator * PIXEL_SIZE / matrix.cell_size;
+    [
+        (Unit::Meters, 1.0),
+        (Unit::Feet, 0.3048),
+        (Unit::Degrees, METERS_PER_DEGREE),
+    ]
+    .iter()
+    .find(|(_, factor)| approx_eq(meters_per_unit / factor, 1.0, 0.01))
+    .map(|(unit, _)| unit.clone())
+    .ok_or_else(|| {
+        format!(
+            "Tile matrix '{}': unsupported units ({} meters per unit)",
+            matrix.id, meters_per_unit
+        )
+    })
+}
+
+/// Grid from a tile matrix set. Tile matrices are mapped to zoom levels in the order of
+/// decreasing cell sizes and have to share tile size, corner of origin and point of origin.
+pub fn grid_from_tile_matrix_set(tms: &Value) -> Result<Grid, String> {
+    let srid = srid_from_crs(&tms["crs"])?;
+    let lat_first = tms["orderedAxes"][0]
+        .as_str()
+        .map(|axis| {
+            let axis = axis.to_lowercase();
+            axis.starts_with("lat") || axis == "n" || axis == "y"
+        })
+        .unwrap_or(false);
+    let matrices = tms["tileMatrices"]
+        .as_array()
+        .filter(|matrices| !matrices.is_empty())
+        .ok_or("Missing `tileMatrices`")?;
+    if matrices.len() > u8::max_value() as usize {
+        return Err("Too many tile matrices".to_string());
+    }
+    let matrices = matrices
+        .iter()
+        .map(|matrix| TileMatrix::from_json(matrix, lat_first))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let first = &matrices[0];
+    let origin = match first.corner.as_str() {
+        "topLeft" => Origin::TopLeft,
+        "bottomLeft" => Origin::BottomLeft,
+        corner => return Err(format!("Unsupported corner of origin '{}'", corner)),
+    };
+    let (width, height) = first.tile_size;
+    if width != height {
+        return Err(format!(
+            "Tile matrix '{}': tiles are not square ({}x{})",
+            first.id, width, height
+        ));
+    }
+    for (prev, matrix) in matrices.iter().zip(matrices.iter().skip(1)) {
+        if matrix.tile_size != first.tile_size {
+            return Err(format!(
+                "Tile matrix '{}': tile size differs from tile matrix '{}'",
+                matrix.id, first.id
+            ));
+        }
+        if matrix.corner != first.corner {
+            return Err(format!(
+                "Tile matrix '{}': corner of origin differs from tile matrix '{}'",
+                matrix.id, first.id
+            ));
+        }
+        let tolerance = matrix.cell_size * 0.01;
+        if !approx_eq(matrix.origin.0, first.origin.0, tolerance)
+            || !approx_eq(matrix.origin.1, first.origin.1, tolerance)
+        {
+            return Err(format!(
+                "Tile matrix '{}': point of origin differs from tile matrix '{}'",
+                matrix.id, first.id
+            ));
+        }
+        if matrix.cell_size >= prev.cell_size {
+            return Err(format!(
+                "Tile matrix '{}': cell size not smaller than in tile matrix '{}'",
+                matrix.id, prev.id
+            ));
+        }
+    }
+
+    // Extent covered by all tile matrices, unless the bounding box has the same origin
+    let (ox, oy) = first.origin;
+    let span_x = matrices.iter().map(|m| m.span().0).fold(f64::MAX, f64::min);
+    let span_y = matrices.iter().map(|m| m.span().1).fold(f64::MAX, f64::min);
+    let mut extent = match origin {
+        Origin::TopLeft => Extent {
+            minx: ox,
+            miny: oy - span_y,
+            maxx: ox + span_x,
+            maxy: oy,
+        },
+        Origin::BottomLeft => Extent {
+            minx: ox,
+            miny: oy,
+            maxx: ox + span_x,
+            maxy: oy + span_y,
+        },
+    };
+    let bbox = &tms["boundingBox"];
+    if let (Some(lower_left), Some(upper_right)) = (
+        point(&bbox["lowerLeft"], lat_first),
+        point(&bbox["upperRight"], lat_first),
+    ) {
+        let bbox_origin = match origin {
+            Origin::TopLeft => (lower_left.0, upper_right.1),
+            Origin::BottomLeft => lower_left,
+        };
+        if bbox_origin == first.origin {
+            extent = Extent {
+                minx: lower_left.0,
+                miny: lower_left.1,
+                maxx: upper_right.0,
+                maxy: upper_right.1,
+            };
+        }
+    }
+
+    let mut grid = Grid::new(
+        width,
+        height,
+        extent,
+        srid,
+        units(first, srid)?,
+        matrices.iter().map(|m| m.cell_size).collect(),
+        origin,
+    );
+    for (zoom, matrix) in matrices.iter().enumerate() {
+        let level_limit = grid.level_limit(zoom as u8);
+        if level_limit != matrix.matrix_size {
+            return Err(format!(
+                "Tile matrix '{}': matrix size {}x{} does not match extent of the tile matrix set ({}x{})",
+                matrix.id, matrix.matrix_size.0, matrix.matrix_size.1, level_limit.0, level_limit.1
+            ));
+        }
+    }
+    grid.tile_matrix_set = tms["id"].as_str().map(|id| id.to_string());
+    Ok(grid)
+}
@@ -0,0 +1,220 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use crate::core::config::GridCfg;
+use crate::core::tilematrixset::{grid_from_tile_matrix_set, tile_matrix_set};
+use crate::core::{parse_config, Config};
+use serde_json::Value;
+use std::fs;
+use tile_grid::{Extent, Grid, Origin, Unit};
+
+fn lv95_grid() -> Grid {
+    let mut grid = Grid::new(
+        256,
+        256,
+        Extent {
+            minx: 2420000.0,
+            miny: 1030000.0,
+            maxx: 2900000.0,
+            maxy: 1350000.0,
+        },
+        2056,
+        Unit::Meters,
+        vec![
+            4000.0, 3750.0, 3500.0, 3250.0, 3000.0, 2750.0, 2500.0, 2250.0, 2000.0, 1750.0, 1500.0,
+            1250.0, 1000.0, 750.0, 650.0, 500.0, 250.0, 100.0, 50.0, 20.0, 10.0, 5.0, 2.5, 2.0,
+            1.5, 1.0, 0.5,
+        ],
+        Origin::TopLeft,
+    );
+    grid.tile_matrix_set = Some("SwissLV95".to_string());
+    grid
+}
+
+fn assert_same_tiles(grid: &Grid, imported: &Grid) {
+    assert_eq!(imported.extent, grid.extent);
+    assert_eq!(imported.srid, grid.srid);
+    assert_eq!(imported.units, grid.units);
+    assert_eq!(imported.origin, grid.origin);
+    assert_eq!(imported.tile_size(), grid.tile_size());
+    assert_eq!(imported.nlevels(), grid.nlevels());
+    assert_eq!(imported.tile_matrix_set, grid.tile_matrix_set);
+    for zoom in 0..grid.nlevels() {
+        assert_eq!(imported.resolution(zoom), grid.resolution(zoom));
+        let (maxx, maxy) = grid.level_limit(zoom);
+        assert_eq!(imported.level_limit(zoom), (maxx, maxy));
+        for (x, y) in &[(0, 0), (maxx / 2, maxy / 2), (maxx - 1, maxy - 1)] {
+            assert_eq!(
+                imported.tile_extent_xyz(*x, *y, zoom),
+                grid.tile_extent_xyz(*x, *y, zoom)
+            );
+        }
+    }
+}
+
+#[test]
+fn test_roundtrip() {
+    let mut bottom_left = lv95_grid();
+    bottom_left.origin = Origin::BottomLeft;
+    bottom_left.tile_matrix_set = None;
+    for grid in &[
+        Grid::web_mercator(),
+        Grid::wgs84(),
+        lv95_grid(),
+        bottom_left,
+    ] {
+        let json = serde_json::to_string_pretty(&tile_matrix_set(grid)).unwrap();
+        let tms: Value = serde_json::from_str(&json).unwrap();
+        let imported = grid_from_tile_matrix_set(&tms).unwrap();
+        assert_same_tiles(grid, &imported);
+    }
+}
+
+#[test]
+fn test_export() {
+    let tms = tile_matrix_set(&lv95_grid());
+    assert_eq!(tms["id"], "SwissLV95");
+    assert_eq!(tms["crs"], "http://www.opengis.net/def/crs/EPSG/0/2056");
+    assert_eq!(tms["tileMatrices"].as_array().unwrap().len(), 27);
+    assert_eq!(
+        tms["tileMatrices"][17],
+        json!({
+            "id": "17",
+            "scaleDenominator": 357142.85714285716,
+            "cellSize": 100.0,
+            "cornerOfOrigin": "topLeft",
+            "pointOfOrigin": [2420000.0, 1350000.0],
+            "tileWidth": 256,
+            "tileHeight": 256,
+            "matrixWidth": 19,
+            "matrixHeight": 13
+        })
+    );
+
+    let tms = tile_matrix_set(&Grid::wgs84());
+    assert_eq!(tms["crs"], "http://www.opengis.net/def/crs/OGC/1.3/CRS84");
+    assert_eq!(tms["orderedAxes"], json!(["Lon", "Lat"]));
+    assert_eq!(
+        tms["tileMatrices"][0]["pointOfOrigin"],
+        json!([-180.0, -90.0])
+    );
+}
+
+#[test]
+fn test_import() {
+    // Extent derived from matrix sizes without bounding box
+    let mut tms = tile_matrix_set(&lv95_grid());
+    tms.as_object_mut().unwrap().remove("boundingBox");
+    let grid = grid_from_tile_matrix_set(&tms).unwrap();
+    assert_eq!(grid.extent.minx, 2420000.0);
+    assert_eq!(grid.extent.maxy, 1350000.0);
+    for zoom in 0..grid.nlevels() {
+        assert_eq!(grid.level_limit(zoom), lv95_grid().level_limit(zoom));
+        assert_eq!(
+            grid.tile_extent_xyz(1, 1, zoom),
+            lv95_grid().tile_extent_xyz(1, 1, zoom)
+        );
+    }
+
+    // EPSG:4326 with latitude first
+    let tms = json!({
+        "crs": "http://www.opengis.net/def/crs/EPSG/0/4326",
+        "orderedAxes": ["Lat", "Lon"],
+        "tileMatrices": [
+            {"id": "0", "scaleDenominator": 279541132.0143589, "cellSize": 0.703125,
+             "cornerOfOrigin": "topLeft", "pointOfOrigin": [90.0, -180.0],
+             "tileWidth": 256, "tileHeight": 256, "matrixWidth": 2, "matrixHeight": 1},
+            {"id": "1", "scaleDenominator": 139770566.00717944, "cellSize": 0.3515625,
+             "pointOfOrigin": [90.0, -180.0],
+             "tileWidth": 256, "tileHeight": 256, "matrixWidth": 4, "matrixHeight": 2}
+        ]
+    });
+    let grid = grid_from_tile_matrix_set(&tms).unwrap();
+    assert_eq!(grid.srid, 4326);
+    assert_eq!(grid.units, Unit::Degrees);
+    assert_eq!(grid.tile_matrix_set, None);
+    assert_eq!(
+        grid.tile_extent_xyz(0, 0, 0),
+        Extent {
+            minx: -180.0,
+            miny: -90.0,
+            maxx: 0.0,
+            maxy: 90.0,
+        }
+    );
+}
+
+#[test]
+fn test_invalid_tile_matrix_sets() {
+    let error = |modify: &dyn Fn(&mut Value)| {
+        let mut tms = tile_matrix_set(&lv95_grid());
+        modify(&mut tms);
+        grid_from_tile_matrix_set(&tms).err().unwrap()
+    };
+    assert_eq!(
+        error(&|tms| tms["tileMatrices"][3]["tileHeight"] = json!(512)),
+        "Tile matrix '3': tile size differs from tile matrix '0'"
+    );
+    assert_eq!(
+        error(&|tms| tms["tileMatrices"][0]["tileHeight"] = json!(512)),
+        "Tile matrix '0': tiles are not square (256x512)"
+    );
+    assert_eq!(
+        error(&|tms| tms["tileMatrices"][5]["pointOfOrigin"] = json!([2600000.0, 1200000.0])),
+        "Tile matrix '5': point of origin differs from tile matrix '0'"
+    );
+    assert_eq!(
+        error(&|tms| tms["tileMatrices"][5]["cornerOfOrigin"] = json!("bottomLeft")),
+        "Tile matrix '5': corner of origin differs from tile matrix '0'"
+    );
+    assert_eq!(
+        error(&|tms| tms["tileMatrices"][5]["cellSize"] = json!(3000.0)),
+        "Tile matrix '5': cell size not smaller than in tile matrix '4'"
+    );
+    assert_eq!(
+        error(&|tms| tms["tileMatrices"][17]["matrixWidth"] = json!(20)),
+        "Tile matrix '17': matrix size 20x13 does not match extent of the tile matrix set (19x13)"
+    );
+    assert_eq!(
+        error(&|tms| tms["tileMatrices"][2]["variableMatrixWidths"] = json!([])),
+        "Tile matrix '2': variable matrix widths are not supported"
+    );
+    assert_eq!(
+        error(&|tms| tms["crs"] = json!("urn:ogc:def:crs:OGC::AutoUTM")),
+        "Unsupported CRS \"urn:ogc:def:crs:OGC::AutoUTM\""
+    );
+    assert_eq!(
+        error(&|tms| tms["tileMatrices"] = json!([])),
+        "Missing `tileMatrices`"
+    );
+}
+
+#[test]
+fn test_grid_from_tms_json() {
+    let path = std::env::temp_dir().join("t_rex_test_lv95.json");
+    let json = serde_json::to_string(&tile_matrix_set(&lv95_grid())).unwrap();
+    fs::write(&path, json).unwrap();
+    let toml = format!(
+        r#"
+        #[grid]
+        tms_json = "{}"
+        "#,
+        path.to_string_lossy()
+    );
+    let config: GridCfg = parse_config(toml, "").unwrap();
+    let grid = Grid::from_config(&config).unwrap();
+    assert_same_tiles(&lv95_grid(), &grid);
+    fs::remove_file(&path).unwrap();
+
+    let toml = r#"
+        #[grid]
+        tms_json = "missing.json"
+        "#;
+    let config: GridCfg = parse_config(toml.to_string(), "").unwrap();
+    assert!(Grid::from_config(&config)
+        .err()
+        .unwrap()
+        .starts_with("Could not read tile matrix set 'missing.json'"));
+}
@@ -0,0 +1,496 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+//! Migration of configurations written for older t-rex versions
+
+use regex::Regex;
+use std::cmp::Ordering;
+
+/// Prefix of comments added by `t_rex upgrade-config`
+const NOTE_PREFIX: &str = "# upgrade-config";
+
+pub enum Action {
+    /// Rename `key` of `table` to `to`
+    RenameKey {
+        table: &'static str,
+        key: &'static str,
+        to: &'static str,
+    },
+    /// Rename `table` to `to`, if it contains `if_key`
+    RenameTable {
+        table: &'static str,
+        if_key: Option<&'static str>,
+        to: &'static str,
+        to_array: bool,
+    },
+    /// Comment out unsupported `key` of `table`
+    RemoveKey {
+        table: &'static str,
+        key: &'static str,
+    },
+    /// Replace `pattern` with `replacement` in all lines
+    ReplaceValue {
+        pattern: &'static str,
+        replacement: &'static str,
+    },
+    /// Add `key = value` to each `table` without `key`
+    AddKey {
+        table: &'static str,
+        key: &'static str,
+        value: &'static str,
+    },
+    /// Annotate each `table` without any of `keys`
+    NoteMissingKeys {
+        table: &'static str,
+        keys: &'static [&'static str],
+    },
+}
+
+pub struct Migration {
+    /// Version introducing the change
+    pub version: &'static str,
+    /// Changed default, which can't be detected in the config.
+    /// Applied to configs older than `version` only.
+    pub changed_default: bool,
+    pub action: Action,
+    /// Description, added as comment for removed keys and annotations
+    pub note: &'static str,
+}
+
+/// Known migrations ordered by version. Extend for configuration changes of new releases.
+pub static MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: "0.7.3",
+        changed_default: false,
+        action: Action::RenameKey {
+            table: "tileset.layer",
+            key: "buffer-size",
+            to: "buffer_size",
+        },
+        note: "Use `buffer_size` instead of `buffer-size`",
+    },
+    Migration {
+        version: "0.8.0",
+        changed_default: false,
+        action: Action::RenameTable {
+            table: "datasource",
+            if_key: None,
+            to: "datasource",
+            to_array: true,
+        },
+        note: "Datasources are defined in [[datasource]] arrays",
+    },
+    Migration {
+        version: "0.8.0",
+        changed_default: false,
+        action: Action::RenameKey {
+            table: "datasource",
+            key: "url",
+            to: "dbconn",
+        },
+        note: "Use `dbconn` instead of `url`",
+    },
+    Migration {
+        version: "0.8.0",
+        changed_default: false,
+        action: Action::RemoveKey {
+            table: "datasource",
+            key: "type",
+        },
+        note: "Datasource type is derived from `dbconn` (PostGIS) or `path` (GDAL)",
+    },
+    Migration {
+        version: "0.8.0",
+        changed_default: false,
+        action: Action::RenameTable {
+            table: "grid",
+            if_key: Some("width"),
+            to: "grid.user",
+            to_array: false,
+        },
+        note: "User defined grids are defined in [grid.user]",
+    },
+    Migration {
+        version: "0.9.0",
+        changed_default: true,
+        action: Action::AddKey {
+            table: "tileset.layer",
+            key: "make_valid",
+            value: "true",
+        },
+        note: "ST_MakeValid is not applied by default anymore",
+    },
+    Migration {
+        version: "0.9.3",
+        changed_default: true,
+        action: Action::NoteMissingKeys {
+            table: "tileset.layer",
+            keys: &["shift_longitude"],
+        },
+        note: "ST_Shift_Longitude is not applied to WGS 84 geometries by default anymore. Set `shift_longitude = true` if needed.",
+    },
+    Migration {
+        version: "0.10.0",
+        changed_default: false,
+        action: Action::ReplaceValue {
+            pattern: r"\$\{([[:alnum:]_]+)\}",
+            replacement: "{{ env.${1} }}",
+        },
+        note: "Replace old environment variable syntax ${VARNAME} with `{{ env.VARNAME }}`",
+    },
+    Migration {
+        version: "0.10.0",
+        changed_default: true,
+        action: Action::NoteMissingKeys {
+            table: "datasource",
+            keys: &["dbconn", "url", "path", "csv"],
+        },
+        note: "TREX_DATASOURCE_URL is not applied anymore. Set `dbconn = \"{{ env.TREX_DATASOURCE_URL }}\"` if needed.",
+    },
+];
+
+/// Upgraded configuration
+pub struct Upgrade {
+    pub config: String,
+    /// Applied migrations with line number in the original config
+    pub applied: Vec<(usize, &'static Migration)>,
+    /// The config is written for a version older than this
+    pub detected_before: Option<&'static str>,
+}
+
+#[derive(PartialEq, Debug)]
+enum Line {
+    Header(String),
+    Key(String),
+    Other,
+}
+
+struct Section {
+    name: String,
+    header: Option<usize>,
+    keys: Vec<(usize, String)>,
+}
+
+impl Section {
+    fn key_line(&self, key: &str) -> Option<usize> {
+        self.keys.iter().find(|(_, k)| k == key).map(|(no, _)| *no)
+    }
+    fn last_line(&self) -> Option<usize> {
+        self.keys.last().map(|(no, _)| *no).or(self.header)
+    }
+}
+
+fn version_cmp(a: &str, b: &str) -> Ordering {
+    let parse = |v: &str| {
+        v.split('.')
+            .map(|n| n.parse::<u32>().unwrap_or(0))
+            .collect::<Vec<_>>()
+    };
+    parse(a).cmp(&parse(b))
+}
+
+/// Bracket nesting after `text`, ignoring strings and comments
+fn bracket_depth(text: &str, mut depth: i32) -> i32 {
+    let mut quote = None;
+    for c in text.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '#') => break,
+            (None, '[') => depth += 1,
+            (None, ']') => depth -= 1,
+            _ => {}
+        }
+    }
+    depth
+}
+
+fn classify(lines: &[&str]) -> Vec<Line> {
+    let mut result = Vec::with_capacity(lines.len());
+    let mut multiline_string: Option<&str> = None;
+    let mut depth = 0;
+    for line in lines {
+        let trimmed = line.trim();
+        if let Some(delim) = multiline_string {
+            if trimmed.contains(delim) {
+                multiline_string = None;
+            }
+            result.push(Line::Other);
+        } else if depth > 0 {
+            depth = bracket_depth(trimmed, depth);
+            result.push(Line::Other);
+        } else if trimmed.starts_with('[') {
+            let name = trimmed
+                .trim_start_matches('[')
+                .split(']')
+                .next()
+                .unwrap_or("")
+                .split('.')
+                .map(|part| part.trim())
+                .collect::<Vec<_>>()
+                .join(".");
+            result.push(Line::Header(name));
+        } else if let (false, Some(pos)) = (trimmed.starts_with('#'), trimmed.find('=')) {
+            let key = trimmed[..pos].trim().trim_matches('"').to_string();
+            let value = trimmed[pos + 1..].trim();
+            for delim in &["\"\"\"", "'''"] {
+                if value.starts_with(delim) && !value[3..].contains(delim) {
+                    multiline_string = Some(delim);
+                }
+            }
+            if value.starts_with('[') {
+                depth = bracket_depth(value, 0);
+            }
+            result.push(Line::Key(key));
+        } else {
+            result.push(Line::Other);
+        }
+    }
+    result
+}
+
+fn sections(lines: &[Line]) -> Vec<Section> {
+    let mut sections = vec![Section {
+        name: String::new(),
+        header: None,
+        keys: Vec::new(),
+    }];
+    for (no, line) in lines.iter().enumerate() {
+        match line {
+            Line::Header(name) => sections.push(Section {
+                name: name.clone(),
+                header: Some(no),
+                keys: Vec::new(),
+            }),
+            Line::Key(key) => sections.last_mut().unwrap().keys.push((no, key.clone())),
+            Line::Other => {}
+        }
+    }
+    sections
+}
+
+fn indent(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+fn note(migration: &Migration, indent: &str) -> String {
+    format!(
+        "{}{} {}: {}",
+        indent, NOTE_PREFIX, migration.version, migration.note
+    )
+}
+
+/// Lines of `migration` matching the config
+fn matches(migration: &Migration, lines: &[&str], sections: &[Section]) -> Vec<usize> {
+    let tables = |table: &'static str| sections.iter().filter(move |s| s.name == table);
+    match migration.action {
+        Action::RenameKey { table, key, .. } | Action::RemoveKey { table, key } => {
+            tables(table).filter_map(|s| s.key_line(key)).collect()
+        }
+        Action::RenameTable { table, if_key, .. } => tables(table)
+            .filter(|s| if_key.map(|key| s.key_line(key).is_some()).unwrap_or(true))
+            .filter_map(|s| s.header)
+            .filter(|no| !lines[*no].trim_start().starts_with("[["))
+            .collect(),
+        Action::ReplaceValue { pattern, .. } => {
+            let re = Regex::new(pattern).unwrap();
+            (0..lines.len())
+                .filter(|no| re.is_match(lines[*no]) && !lines[*no].trim_start().starts_with('#'))
+                .collect()
+        }
+        Action::AddKey { table, key, .. } => tables(table)
+            .filter(|s| s.key_line(key).is_none())
+            .filter_map(|s| s.last_line())
+            .collect(),
+        Action::NoteMissingKeys { table, keys } => tables(table)
+            .filter(|s| keys.iter().all(|key| s.key_line(key).is_none()))
+            .filter_map(|s| s.header)
+            .collect(),
+    }
+}
+
+/// Apply migrations to a config written for version `from` (Default: detected from legacy syntax)
+pub fn upgrade_config(config_toml: &str, from: Option<&str>) -> Upgrade {
+    let lines: Vec<&str> = config_toml.lines().collect();
+    let classified = classify(&lines);
+    let sections = sections(&classified);
+    let detected_before = MIGRATIONS
+        .iter()
+        .filter(|m| !m.changed_default && !matches(m, &lines, &sections).is_empty())
+        .map(|m| m.version)
+        .min_by(|a, b| version_cmp(a, b));
+    let older_than = |version| match from {
+        Some(from) => version_cmp(from, version) == Ordering::Less,
+        None => detected_before
+            .map(|before| version_cmp(before, version) != Ordering::Greater)
+            .unwrap_or(false),
+    };
+
+    let mut replaced: Vec<Option<String>> = vec![None; lines.len()];
+    let mut before: Vec<Vec<String>> = vec![Vec::new(); lines.len()];
+    let mut after: Vec<Vec<String>> = vec![Vec::new(); lines.len()];
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS {
+        if migration.changed_default && !older_than(migration.version) {
+            continue;
+        }
+        for no in matches(migration, &lines, &sections) {
+            let line = replaced[no].clone().unwrap_or(lines[no].to_string());
+            let ind = indent(lines[no]);
+            match migration.action {
+                Action::RenameKey { key, to, .. } => {
+                    replaced[no] = Some(line.replacen(key, to, 1));
+                }
+                Action::RenameTable { to, to_array, .. } => {
+                    let rest = line.splitn(2, ']').nth(1).unwrap_or("");
+                    let rest = rest.trim_start_matches(']');
+                    replaced[no] = Some(if to_array {
+                        format!("{}[[{}]]{}", ind, to, rest)
+                    } else {
+                        format!("{}[{}]{}", ind, to, rest)
+                    });
+                }
+                Action::RemoveKey { .. } => {
+                    before[no].push(note(migration, ind));
+                    replaced[no] = Some(format!("{}#{}", ind, line.trim_start()));
+                }
+                Action::ReplaceValue {
+                    pattern,
+                    replacement,
+                } => {
+                    let re = Regex::new(pattern).unwrap();
+                    replaced[no] = Some(re.replace_all(&line, replacement).to_string());
+                }
+                Action::AddKey { key, value, .. } => {
+                    after[no].push(note(migration, ind));
+                    after[no].push(format!("{}{} = {}", ind, key, value));
+                }
+                Action::NoteMissingKeys { .. } => {
+                    after[no].push(note(migration, ind));
+                }
+            }
+            applied.push((no + 1, migration));
+        }
+    }
+
+    let mut config = String::with_capacity(config_toml.len());
+    for (no, line) in lines.iter().enumerate() {
+        for note in &before[no] {
+            config.push_str(note);
+            config.push('\n');
+        }
+        config.push_str(replaced[no].as_ref().map(|s| s as &str).unwrap_or(line));
+        config.push('\n');
+        for added in &after[no] {
+            config.push_str(added);
+            config.push('\n');
+        }
+    }
+    if !config_toml.ends_with('\n') {
+        config.pop();
+    }
+    applied.sort_by_key(|(no, _)| *no);
+    Upgrade {
+        config,
+        applied,
+        detected_before,
+    }
+}
+
+/// Migrations of legacy syntax found in the config
+pub fn legacy_syntax(config_toml: &str) -> Vec<&'static Migration> {
+    let mut legacy: Vec<&'static Migration> = Vec::new();
+    for (_, migration) in upgrade_config(config_toml, None).applied {
+        if !migration.changed_default && !legacy.iter().any(|m| std::ptr::eq(*m, migration)) {
+            legacy.push(migration);
+        }
+    }
+    legacy
+}
+
+/// Hint pointing to `t_rex upgrade-config` for configs with legacy syntax
+pub fn legacy_hint(path: &str, legacy: &[&Migration]) -> String {
+    let notes: Vec<String> = legacy
+        .iter()
+        .map(|m| format!("{} ({})", m.note, m.version))
+        .collect();
+    format!(
+        "{} - Configuration contains legacy syntax: {}. Run `t_rex upgrade-config --config {}` to migrate.",
+        path,
+        notes.join(", "),
+        path
+    )
+}
+
+/// Line based diff of `old` and `new` in unified format
+pub fn diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    const CONTEXT: usize = 3;
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    // Longest common subsequence lengths of suffixes
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    // Edit script: (op, line index in a, line index in b)
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            ops.push((' ', i, j));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(('-', i, j));
+            i += 1;
+        } else {
+            ops.push(('+', i, j));
+            j += 1;
+        }
+    }
+    let mut out = String::new();
+    let mut k = 0;
+    while k < ops.len() {
+        if ops[k].0 == ' ' {
+            k += 1;
+            continue;
+        }
+        // Hunk from `start` to `end`, merging changes separated by less than 2*CONTEXT lines
+        let start = k.saturating_sub(CONTEXT);
+        let mut end = k;
+        let mut unchanged = 0;
+        while end < ops.len() && unchanged <= 2 * CONTEXT {
+            unchanged = if ops[end].0 == ' ' { unchanged + 1 } else { 0 };
+            end += 1;
+        }
+        let end = end - unchanged.saturating_sub(CONTEXT);
+        let hunk = &ops[start..end];
+        if out.is_empty() {
+            out.push_str(&format!("--- {}\n+++ {}\n", old_name, new_name));
+        }
+        let old_len = hunk.iter().filter(|op| op.0 != '+').count();
+        let new_len = hunk.iter().filter(|op| op.0 != '-').count();
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            hunk[0].1 + 1,
+            old_len,
+            hunk[0].2 + 1,
+            new_len
+        ));
+        for (op, i, j) in hunk {
+            let line = if *op == '+' { b[*j] } else { a[*i] };
+            out.push_str(&format!("{}{}\n", op, line));
+        }
+        k = end;
+    }
+    out
+}
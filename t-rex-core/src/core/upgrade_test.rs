@@ -0,0 +1,213 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use crate::core::config::ApplicationCfg;
+use crate::core::parse_config;
+use crate::core::upgrade::{diff, legacy_syntax, upgrade_config, MIGRATIONS};
+
+// Config written for t-rex 0.7
+const CONFIG_07: &str = r#"[service.mvt]
+viewer = true
+
+[datasource]
+type = "postgis"
+url = "${DBCONN}"
+
+[grid]
+width = 256
+height = 256
+extent = { minx = 2420000.0, miny = 1030000.0, maxx = 2900000.0, maxy = 1350000.0 }
+srid = 2056
+units = "m"
+resolutions = [4000.0,3750.0,3500.0,
+               3250.0,3000.0]
+origin = "TopLeft"
+
+[[tileset]]
+name = "places"
+
+[[tileset.layer]]
+name = "places"
+table_name = "ne_10m_populated_places"
+geometry_field = "wkb_geometry"
+geometry_type = "POINT"
+buffer-size = 10
+[[tileset.layer.query]]
+sql = """
+  SELECT name, wkb_geometry
+  FROM ne_10m_populated_places
+  WHERE url = 'x'"""
+
+[webserver]
+bind = "127.0.0.1"
+port = 6767
+"#;
+
+#[test]
+fn test_upgrade_07() {
+    let upgrade = upgrade_config(CONFIG_07, None);
+    assert_eq!(upgrade.detected_before, Some("0.7.3"));
+    assert_eq!(
+        upgrade.config,
+        r#"[service.mvt]
+viewer = true
+
+[[datasource]]
+# upgrade-config 0.8.0: Datasource type is derived from `dbconn` (PostGIS) or `path` (GDAL)
+#type = "postgis"
+dbconn = "{{ env.DBCONN }}"
+
+[grid.user]
+width = 256
+height = 256
+extent = { minx = 2420000.0, miny = 1030000.0, maxx = 2900000.0, maxy = 1350000.0 }
+srid = 2056
+units = "m"
+resolutions = [4000.0,3750.0,3500.0,
+               3250.0,3000.0]
+origin = "TopLeft"
+
+[[tileset]]
+name = "places"
+
+[[tileset.layer]]
+# upgrade-config 0.9.3: ST_Shift_Longitude is not applied to WGS 84 geometries by default anymore. Set `shift_longitude = true` if needed.
+name = "places"
+table_name = "ne_10m_populated_places"
+geometry_field = "wkb_geometry"
+geometry_type = "POINT"
+buffer_size = 10
+# upgrade-config 0.9.0: ST_MakeValid is not applied by default anymore
+make_valid = true
+[[tileset.layer.query]]
+sql = """
+  SELECT name, wkb_geometry
+  FROM ne_10m_populated_places
+  WHERE url = 'x'"""
+
+[webserver]
+bind = "127.0.0.1"
+port = 6767
+"#
+    );
+    let versions: Vec<&str> = upgrade.applied.iter().map(|(_, m)| m.version).collect();
+    assert_eq!(
+        versions,
+        vec!["0.8.0", "0.8.0", "0.8.0", "0.10.0", "0.8.0", "0.9.3", "0.7.3", "0.9.0"]
+    );
+    assert_eq!(upgrade.applied[0].0, 4);
+
+    // Upgraded config is current
+    assert_eq!(legacy_syntax(&upgrade.config).len(), 0);
+    assert_eq!(upgrade_config(&upgrade.config, None).applied.len(), 0);
+
+    std::env::set_var("DBCONN", "postgresql://pi@localhost/natural_earth_vectors");
+    let config: ApplicationCfg = parse_config(upgrade.config, "").unwrap();
+    assert_eq!(config.datasource.len(), 1);
+    assert_eq!(config.grid.user.unwrap().srid, 2056);
+    assert_eq!(config.tilesets[0].layers[0].buffer_size, Some(10));
+    assert!(config.tilesets[0].layers[0].make_valid);
+}
+
+#[test]
+fn test_changed_defaults() {
+    let config = r#"
+        [[datasource]]
+        name = "db"
+
+        [[tileset]]
+        name = "places"
+
+        [[tileset.layer]]
+        name = "places"
+        shift_longitude = true
+        "#;
+    // Current syntax
+    let upgrade = upgrade_config(config, None);
+    assert_eq!(upgrade.detected_before, None);
+    assert_eq!(upgrade.config, config);
+
+    let upgrade = upgrade_config(config, Some("0.9.3"));
+    assert_eq!(upgrade.applied.len(), 1);
+    assert_eq!(
+        upgrade.config,
+        r#"
+        [[datasource]]
+        # upgrade-config 0.10.0: TREX_DATASOURCE_URL is not applied anymore. Set `dbconn = "{{ env.TREX_DATASOURCE_URL }}"` if needed.
+        name = "db"
+
+        [[tileset]]
+        name = "places"
+
+        [[tileset.layer]]
+        name = "places"
+        shift_longitude = true
+        "#
+    );
+
+    let upgrade = upgrade_config(config, Some("0.8.2"));
+    assert_eq!(upgrade.applied.len(), 2);
+    assert!(upgrade
+        .config
+        .contains("        shift_longitude = true\n        # upgrade-config 0.9.0: ST_MakeValid is not applied by default anymore\n        make_valid = true\n"));
+
+    let upgrade = upgrade_config(config, Some("0.10.0"));
+    assert_eq!(upgrade.applied.len(), 0);
+}
+
+#[test]
+fn test_migrations_ordered() {
+    let versions: Vec<Vec<u32>> = MIGRATIONS
+        .iter()
+        .map(|m| m.version.split('.').map(|n| n.parse().unwrap()).collect())
+        .collect();
+    let mut sorted = versions.clone();
+    sorted.sort();
+    assert_eq!(versions, sorted);
+}
+
+#[test]
+fn test_legacy_hint() {
+    let config = r#"
+        [[tileset]]
+        name = "places"
+
+        [[tileset.layer]]
+        name = "places"
+        buffer-size = 10
+        "#;
+    let legacy = legacy_syntax(config);
+    assert_eq!(legacy.len(), 1);
+    let cfg: Result<ApplicationCfg, _> = parse_config(config.to_string(), "old.toml");
+    assert_eq!(
+        cfg.err(),
+        Some("old.toml - missing field `service`\nold.toml - Configuration contains legacy syntax: Use `buffer_size` instead of `buffer-size` (0.7.3). Run `t_rex upgrade-config --config old.toml` to migrate.".to_string())
+    );
+}
+
+#[test]
+fn test_diff() {
+    let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\n";
+    let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\nn\n";
+    assert_eq!(
+        diff(old, new, "old.toml", "new.toml"),
+        "--- old.toml
++++ new.toml
+@@ -1,5 +1,5 @@
+ a
+-b
++B
+ c
+ d
+ e
+@@ -11,3 +11,4 @@
+ k
+ l
+ m
++n
+"
+    );
+    assert_eq!(diff(old, old, "old.toml", "new.toml"), "");
+}
@@ -0,0 +1,1051 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use crate::core::config::DatasourceCfg;
+use crate::core::feature::{Feature, FeatureAttr, FeatureAttrValType};
+use crate::core::geom::{self, GeometryType};
+use crate::core::layer::Layer;
+use crate::core::Config;
+use crate::datasource::DatasourceType;
+use csv;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+use tile_grid::{extent_to_merc, lonlat_to_merc, Extent, Grid};
+
+/// Number of rows read for detecting geometry and attribute types
+const SAMPLE_ROWS: usize = 1000;
+
+/// Geometry columns of a CSV file
+#[derive(Clone, PartialEq, Debug)]
+pub enum CsvGeometry {
+    /// Point coordinates in two numeric columns
+    LonLat {
+        lon_field: String,
+        lat_field: String,
+    },
+    /// Geometries in a WKT column
+    Wkt { wkt_field: String },
+}
+
+impl Default for CsvGeometry {
+    fn default() -> Self {
+        CsvGeometry::LonLat {
+            lon_field: "lon".to_string(),
+            lat_field: "lat".to_string(),
+        }
+    }
+}
+
+/// Declared type of an attribute column
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CsvAttrType {
+    String,
+    Int,
+    Double,
+    Bool,
+}
+
+impl CsvAttrType {
+    pub fn from_name(name: &str) -> Result<CsvAttrType, String> {
+        match name.to_lowercase().as_str() {
+            "string" | "text" => Ok(CsvAttrType::String),
+            "int" | "integer" => Ok(CsvAttrType::Int),
+            "double" | "float" | "real" => Ok(CsvAttrType::Double),
+            "bool" | "boolean" => Ok(CsvAttrType::Bool),
+            _ => Err(format!(
+                "Unsupported CSV attribute type '{}' (expected string, int, double or bool)",
+                name
+            )),
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            CsvAttrType::String => "string",
+            CsvAttrType::Int => "int",
+            CsvAttrType::Double => "double",
+            CsvAttrType::Bool => "bool",
+        }
+    }
+    /// TileJSON field type
+    pub fn field_type(&self) -> &'static str {
+        match self {
+            CsvAttrType::String => "String",
+            CsvAttrType::Int | CsvAttrType::Double => "Number",
+            CsvAttrType::Bool => "Boolean",
+        }
+    }
+    /// Convert column value. Empty or unparsable values are returned as None.
+    pub(crate) fn parse(&self, value: &str) -> Option<FeatureAttrValType> {
+        let trimmed = value.trim();
+        if trimmed.is_empty() {
+            return None;
+        }
+        match self {
+            CsvAttrType::String => Some(FeatureAttrValType::String(value.to_string())),
+            CsvAttrType::Int => match trimmed.parse::<i64>() {
+                Ok(v) => Some(FeatureAttrValType::Int(v)),
+                Err(_) => trimmed.parse::<u64>().ok().map(FeatureAttrValType::UInt),
+            },
+            CsvAttrType::Double => trimmed.parse::<f64>().ok().map(FeatureAttrValType::Double),
+            CsvAttrType::Bool => match trimmed.to_lowercase().as_str() {
+                "true" | "t" | "yes" | "1" => Some(FeatureAttrValType::Bool(true)),
+                "false" | "f" | "no" | "0" => Some(FeatureAttrValType::Bool(false)),
+                _ => None,
+            },
+        }
+    }
+    /// Narrowest type which can represent `value`
+    pub(crate) fn guess(value: &str) -> CsvAttrType {
+        let value = value.trim();
+        if value.parse::<i64>().is_ok() || value.parse::<u64>().is_ok() {
+            CsvAttrType::Int
+        } else if value.parse::<f64>().is_ok() {
+            CsvAttrType::Double
+        } else {
+            match value.to_lowercase().as_str() {
+                "true" | "false" => CsvAttrType::Bool,
+                _ => CsvAttrType::String,
+            }
+        }
+    }
+    /// Common type of two guesses
+    fn merge(self, other: CsvAttrType) -> CsvAttrType {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (CsvAttrType::Int, CsvAttrType::Double) | (CsvAttrType::Double, CsvAttrType::Int) => {
+                CsvAttrType::Double
+            }
+            _ => CsvAttrType::String,
+        }
+    }
+}
+
+type Coord = (f64, f64);
+
+/// In-memory geometry (XY only)
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) enum CsvGeom {
+    Point(Coord),
+    LineString(Vec<Coord>),
+    Polygon(Vec<Vec<Coord>>),
+    MultiPoint(Vec<Coord>),
+    MultiLineString(Vec<Vec<Coord>>),
+    MultiPolygon(Vec<Vec<Vec<Coord>>>),
+}
+
+impl CsvGeom {
+    fn for_each_coord<F: FnMut(&Coord)>(&self, mut f: F) {
+        match self {
+            CsvGeom::Point(c) => f(c),
+            CsvGeom::LineString(cs) | CsvGeom::MultiPoint(cs) => cs.iter().for_each(f),
+            CsvGeom::Polygon(lines) | CsvGeom::MultiLineString(lines) => {
+                lines.iter().flatten().for_each(f)
+            }
+            CsvGeom::MultiPolygon(polys) => polys.iter().flatten().flatten().for_each(f),
+        }
+    }
+    fn map_coords<F: Fn(f64, f64) -> Coord>(&mut self, f: F) {
+        let apply = |c: &mut Coord| *c = f(c.0, c.1);
+        match self {
+            CsvGeom::Point(c) => apply(c),
+            CsvGeom::LineString(cs) | CsvGeom::MultiPoint(cs) => cs.iter_mut().for_each(apply),
+            CsvGeom::Polygon(lines) | CsvGeom::MultiLineString(lines) => {
+                lines.iter_mut().flatten().for_each(apply)
+            }
+            CsvGeom::MultiPolygon(polys) => polys.iter_mut().flatten().flatten().for_each(apply),
+        }
+    }
+    fn is_finite(&self) -> bool {
+        let mut finite = true;
+        self.for_each_coord(|&(x, y)| finite = finite && x.is_finite() && y.is_finite());
+        finite
+    }
+    fn bbox(&self) -> Extent {
+        let mut bbox = Extent {
+            minx: std::f64::MAX,
+            miny: std::f64::MAX,
+            maxx: std::f64::MIN,
+            maxy: std::f64::MIN,
+        };
+        self.for_each_coord(|&(x, y)| {
+            bbox.minx = bbox.minx.min(x);
+            bbox.miny = bbox.miny.min(y);
+            bbox.maxx = bbox.maxx.max(x);
+            bbox.maxy = bbox.maxy.max(y);
+        });
+        bbox
+    }
+    /// Layer geometry type name
+    pub(crate) fn type_name(&self) -> &'static str {
+        match self {
+            CsvGeom::Point(_) | CsvGeom::MultiPoint(_) => "POINT",
+            CsvGeom::LineString(_) | CsvGeom::MultiLineString(_) => "LINE",
+            CsvGeom::Polygon(_) | CsvGeom::MultiPolygon(_) => "POLYGON",
+        }
+    }
+    pub(crate) fn to_geometry(&self, srid: Option<i32>) -> GeometryType {
+        match self {
+            CsvGeom::Point(c) => GeometryType::Point(to_point(c, srid)),
+            CsvGeom::LineString(cs) => GeometryType::LineString(to_line(cs, srid)),
+            CsvGeom::Polygon(rings) => GeometryType::Polygon(to_polygon(rings, srid)),
+            CsvGeom::MultiPoint(cs) => GeometryType::MultiPoint(geom::MultiPoint {
+                points: cs.iter().map(|c| to_point(c, srid)).collect(),
+                srid: srid,
+            }),
+            CsvGeom::MultiLineString(lines) => {
+                GeometryType::MultiLineString(geom::MultiLineString {
+                    lines: lines.iter().map(|cs| to_line(cs, srid)).collect(),
+                    srid: srid,
+                })
+            }
+            CsvGeom::MultiPolygon(polys) => GeometryType::MultiPolygon(geom::MultiPolygon {
+                polygons: polys.iter().map(|rings| to_polygon(rings, srid)).collect(),
+                srid: srid,
+            }),
+        }
+    }
+}
+
+fn to_point(c: &Coord, srid: Option<i32>) -> geom::Point {
+    geom::Point {
+        x: c.0,
+        y: c.1,
+        srid: srid,
+    }
+}
+
+fn to_line(coords: &[Coord], srid: Option<i32>) -> geom::LineString {
+    geom::LineString {
+        points: coords.iter().map(|c| to_point(c, srid)).collect(),
+        srid: srid,
+    }
+}
+
+fn to_polygon(rings: &[Vec<Coord>], srid: Option<i32>) -> geom::Polygon {
+    geom::Polygon {
+        rings: rings.iter().map(|cs| to_line(cs, srid)).collect(),
+        srid: srid,
+    }
+}
+
+enum WktNode {
+    Coord(Coord),
+    List(Vec<WktNode>),
+}
+
+/// Parse WKT geometry. Z and M ordinates are ignored.
+pub(crate) fn parse_wkt(wkt: &str) -> Option<CsvGeom> {
+    let wkt = wkt.trim();
+    let open = wkt.find('(')?;
+    let geom_type = wkt[..open].trim().to_uppercase();
+    let geom_type = geom_type
+        .split_whitespace()
+        .next()?
+        .trim_end_matches(|c: char| c == 'Z' || c == 'M');
+    let mut rest = &wkt[open..];
+    let node = parse_wkt_node(&mut rest)?;
+    if !rest.trim().is_empty() {
+        return None;
+    }
+    match geom_type {
+        "POINT" => match wkt_coords(&node)?.as_slice() {
+            [c] => Some(CsvGeom::Point(*c)),
+            _ => None,
+        },
+        "LINESTRING" => wkt_coords(&node).map(CsvGeom::LineString),
+        "POLYGON" => wkt_list(&node, wkt_coords).map(CsvGeom::Polygon),
+        "MULTIPOINT" => wkt_coords(&node).map(CsvGeom::MultiPoint),
+        "MULTILINESTRING" => wkt_list(&node, wkt_coords).map(CsvGeom::MultiLineString),
+        "MULTIPOLYGON" => {
+            wkt_list(&node, |n| wkt_list(n, wkt_coords)).map(CsvGeom::MultiPolygon)
+        }
+        _ => None,
+    }
+}
+
+fn parse_wkt_node<'a>(input: &mut &'a str) -> Option<WktNode> {
+    let s: &'a str = *input;
+    let s = s.trim_start();
+    if s.starts_with('(') {
+        let mut rest = &s[1..];
+        let mut items = Vec::new();
+        loop {
+            items.push(parse_wkt_node(&mut rest)?);
+            rest = rest.trim_start();
+            if rest.starts_with(',') {
+                rest = &rest[1..];
+            } else if rest.starts_with(')') {
+                rest = &rest[1..];
+                break;
+            } else {
+                return None;
+            }
+        }
+        *input = rest;
+        Some(WktNode::List(items))
+    } else {
+        let end = s.find(|c: char| c == ',' || c == ')').unwrap_or(s.len());
+        let mut ordinates = s[..end].split_whitespace().map(|v| v.parse::<f64>());
+        let x = ordinates.next()?.ok()?;
+        let y = ordinates.next()?.ok()?;
+        *input = &s[end..];
+        Some(WktNode::Coord((x, y)))
+    }
+}
+
+/// Coordinate list. Accepts `(1 2, 3 4)` as well as `((1 2), (3 4))`.
+fn wkt_coords(node: &WktNode) -> Option<Vec<Coord>> {
+    wkt_list(node, |item| match item {
+        WktNode::Coord(c) => Some(*c),
+        WktNode::List(inner) => match inner.as_slice() {
+            [WktNode::Coord(c)] => Some(*c),
+            _ => None,
+        },
+    })
+}
+
+fn wkt_list<T, F>(node: &WktNode, f: F) -> Option<Vec<T>>
+where
+    F: Fn(&WktNode) -> Option<T>,
+{
+    match node {
+        WktNode::List(items) => items.iter().map(f).collect(),
+        WktNode::Coord(_) => None,
+    }
+}
+
+/// Column indices of geometry
+enum GeomColumns {
+    LonLat(usize, usize),
+    Wkt(usize),
+}
+
+impl GeomColumns {
+    fn contains(&self, idx: usize) -> bool {
+        match *self {
+            GeomColumns::LonLat(lon, lat) => idx == lon || idx == lat,
+            GeomColumns::Wkt(wkt) => idx == wkt,
+        }
+    }
+    fn read(&self, record: &csv::StringRecord) -> Option<CsvGeom> {
+        match *self {
+            GeomColumns::LonLat(lon, lat) => {
+                let x = record.get(lon)?.trim().parse::<f64>().ok()?;
+                let y = record.get(lat)?.trim().parse::<f64>().ok()?;
+                Some(CsvGeom::Point((x, y)))
+            }
+            GeomColumns::Wkt(wkt) => parse_wkt(record.get(wkt)?),
+        }
+    }
+}
+
+struct CsvFeature {
+    fid: Option<u64>,
+    geom: CsvGeom,
+    bbox: Extent,
+    values: Vec<Option<FeatureAttrValType>>,
+}
+
+/// Uniform grid over the data extent, mapping cells to feature indices
+struct CellIndex {
+    extent: Extent,
+    size: usize,
+    cells: Vec<Vec<u32>>,
+}
+
+impl CellIndex {
+    fn new(features: &[CsvFeature]) -> CellIndex {
+        let mut extent = match features.first() {
+            Some(feature) => feature.bbox.clone(),
+            None => Extent {
+                minx: 0.0,
+                miny: 0.0,
+                maxx: 0.0,
+                maxy: 0.0,
+            },
+        };
+        for feature in features {
+            extend(&mut extent, &feature.bbox);
+        }
+        // About 16 features per cell for evenly distributed data
+        let size = ((features.len() as f64 / 16.0).sqrt().ceil() as usize)
+            .max(1)
+            .min(1024);
+        let mut index = CellIndex {
+            extent,
+            size,
+            cells: vec![Vec::new(); size * size],
+        };
+        for (idx, feature) in features.iter().enumerate() {
+            let (col0, row0, col1, row1) = index.cell_range(&feature.bbox);
+            for row in row0..=row1 {
+                for col in col0..=col1 {
+                    index.cells[row * size + col].push(idx as u32);
+                }
+            }
+        }
+        for cell in index.cells.iter_mut() {
+            cell.shrink_to_fit();
+        }
+        index
+    }
+    fn cell_range(&self, bbox: &Extent) -> (usize, usize, usize, usize) {
+        let e = &self.extent;
+        (
+            cell_pos(bbox.minx, e.minx, e.maxx, self.size),
+            cell_pos(bbox.miny, e.miny, e.maxy, self.size),
+            cell_pos(bbox.maxx, e.minx, e.maxx, self.size),
+            cell_pos(bbox.maxy, e.miny, e.maxy, self.size),
+        )
+    }
+    /// Indices of features intersecting `bbox`, in file order
+    fn query(&self, features: &[CsvFeature], bbox: &Extent) -> Vec<usize> {
+        if features.is_empty() || !intersects(&self.extent, bbox) {
+            return Vec::new();
+        }
+        let (col0, row0, col1, row1) = self.cell_range(bbox);
+        let mut ids = Vec::new();
+        for row in row0..=row1 {
+            for col in col0..=col1 {
+                ids.extend(
+                    self.cells[row * self.size + col]
+                        .iter()
+                        .map(|&idx| idx as usize)
+                        .filter(|&idx| intersects(&features[idx].bbox, bbox)),
+                );
+            }
+        }
+        // Features spanning multiple cells are found more than once
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+}
+
+fn cell_pos(v: f64, min: f64, max: f64, size: usize) -> usize {
+    if max <= min {
+        return 0;
+    }
+    let pos = ((v - min) / (max - min) * size as f64).floor();
+    if pos < 0.0 {
+        0
+    } else if pos >= size as f64 {
+        size - 1
+    } else {
+        pos as usize
+    }
+}
+
+fn intersects(a: &Extent, b: &Extent) -> bool {
+    a.minx <= b.maxx && a.maxx >= b.minx && a.miny <= b.maxy && a.maxy >= b.miny
+}
+
+fn extend(extent: &mut Extent, other: &Extent) {
+    extent.minx = extent.minx.min(other.minx);
+    extent.miny = extent.miny.min(other.miny);
+    extent.maxx = extent.maxx.max(other.maxx);
+    extent.maxy = extent.maxy.max(other.maxy);
+}
+
+/// Features of a layer, reprojected to the grid SRS
+pub(crate) struct CsvData {
+    attr_names: Vec<String>,
+    features: Vec<CsvFeature>,
+    index: CellIndex,
+    /// Number of skipped rows
+    pub(crate) invalid_rows: u64,
+}
+
+impl CsvData {
+    pub(crate) fn len(&self) -> usize {
+        self.features.len()
+    }
+}
+
+struct CsvFeatureRef<'a> {
+    attr_names: &'a [String],
+    feature: &'a CsvFeature,
+    srid: Option<i32>,
+    layer: &'a Layer,
+    zoom: u8,
+}
+
+impl<'a> Feature for CsvFeatureRef<'a> {
+    fn fid(&self) -> Option<u64> {
+        self.feature.fid
+    }
+    fn attributes(&self) -> Vec<FeatureAttr> {
+        self.attr_names
+            .iter()
+            .zip(self.feature.values.iter())
+            .filter(|(name, _)| self.layer.attribute_in_zoom(name, self.zoom))
+            .filter_map(|(name, value)| {
+                value.as_ref().map(|value| FeatureAttr {
+                    key: name.clone(),
+                    value: value.clone(),
+                })
+            })
+            .collect()
+    }
+    fn geometry(&self) -> Result<GeometryType, String> {
+        Ok(self.feature.geom.to_geometry(self.srid))
+    }
+}
+
+#[derive(Clone)]
+pub struct CsvDatasource {
+    pub path: String,
+    pub delimiter: u8,
+    pub geometry: CsvGeometry,
+    pub srid: i32,
+    /// Attribute columns with types. All non-geometry columns as strings, if None.
+    pub attributes: Option<BTreeMap<String, CsvAttrType>>,
+    /// Loaded features per layer
+    data: BTreeMap<String, Arc<CsvData>>,
+}
+
+impl CsvDatasource {
+    pub fn new(path: &str) -> CsvDatasource {
+        let mut ds = CsvDatasource {
+            path: path.to_string(),
+            delimiter: b',',
+            geometry: CsvGeometry::default(),
+            srid: 4326,
+            attributes: None,
+            data: BTreeMap::new(),
+        };
+        if let Some(geometry) = ds.detect_geometry() {
+            ds.geometry = geometry;
+        }
+        ds
+    }
+    fn reader(&self) -> Result<csv::Reader<File>, String> {
+        csv::ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .flexible(true)
+            .from_path(&self.path)
+            .map_err(|e| format!("Can't open CSV file '{}': {}", self.path, e))
+    }
+    fn read_error(&self, e: &csv::Error) -> String {
+        format!("Error reading CSV file '{}': {}", self.path, e)
+    }
+    /// Header and first rows of the file
+    fn sample(&self, max_rows: usize) -> Result<(csv::StringRecord, Vec<csv::StringRecord>), String> {
+        let mut reader = self.reader()?;
+        let headers = reader
+            .headers()
+            .map_err(|e| self.read_error(&e))?
+            .clone();
+        let rows = reader
+            .records()
+            .take(max_rows)
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok((headers, rows))
+    }
+    /// Guess geometry columns from header names
+    pub fn detect_geometry(&self) -> Option<CsvGeometry> {
+        let (headers, _) = self.sample(0).ok()?;
+        let find = |names: &[&str]| {
+            headers
+                .iter()
+                .find(|h| names.contains(&h.trim().to_lowercase().as_str()))
+                .map(|h| h.to_string())
+        };
+        if let Some(wkt_field) = find(&["wkt", "geom", "geometry", "the_geom"]) {
+            return Some(CsvGeometry::Wkt { wkt_field });
+        }
+        match (
+            find(&["lon", "lng", "long", "longitude", "x"]),
+            find(&["lat", "latitude", "y"]),
+        ) {
+            (Some(lon_field), Some(lat_field)) => Some(CsvGeometry::LonLat {
+                lon_field,
+                lat_field,
+            }),
+            _ => None,
+        }
+    }
+    fn geom_columns(&self, headers: &csv::StringRecord) -> Result<GeomColumns, String> {
+        let position = |field: &str| {
+            headers.iter().position(|h| h == field).ok_or(format!(
+                "CSV file '{}': column '{}' not found",
+                self.path, field
+            ))
+        };
+        match self.geometry {
+            CsvGeometry::LonLat {
+                ref lon_field,
+                ref lat_field,
+            } => Ok(GeomColumns::LonLat(
+                position(lon_field)?,
+                position(lat_field)?,
+            )),
+            CsvGeometry::Wkt { ref wkt_field } => Ok(GeomColumns::Wkt(position(wkt_field)?)),
+        }
+    }
+    fn attribute_columns(
+        &self,
+        headers: &csv::StringRecord,
+        geom_columns: &GeomColumns,
+    ) -> Vec<(usize, String, CsvAttrType)> {
+        match self.attributes {
+            Some(ref attributes) => {
+                for name in attributes.keys() {
+                    if !headers.iter().any(|h| h == name.as_str()) {
+                        warn!(
+                            "CSV file '{}': attribute column '{}' not found",
+                            self.path, name
+                        );
+                    }
+                }
+                headers
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(idx, name)| {
+                        attributes
+                            .get(name)
+                            .map(|&attr_type| (idx, name.to_string(), attr_type))
+                    })
+                    .collect()
+            }
+            None => headers
+                .iter()
+                .enumerate()
+                .filter(|&(idx, _)| !geom_columns.contains(idx))
+                .map(|(idx, name)| (idx, name.to_string(), CsvAttrType::String))
+                .collect(),
+        }
+    }
+    /// Attribute columns with types guessed from the first rows
+    pub fn guess_attributes(&self) -> Vec<(String, CsvAttrType)> {
+        let (headers, rows) = match self.sample(SAMPLE_ROWS) {
+            Ok(sample) => sample,
+            Err(e) => {
+                warn!("{}", e);
+                return Vec::new();
+            }
+        };
+        let geom_columns = self.geom_columns(&headers).ok();
+        headers
+            .iter()
+            .enumerate()
+            .filter(|&(idx, _)| !geom_columns.as_ref().map_or(false, |g| g.contains(idx)))
+            .map(|(idx, name)| {
+                let attr_type = rows
+                    .iter()
+                    .filter_map(|row| row.get(idx))
+                    .filter(|v| !v.trim().is_empty())
+                    .map(CsvAttrType::guess)
+                    .fold(None, |acc: Option<CsvAttrType>, t| {
+                        Some(acc.map_or(t, |a| a.merge(t)))
+                    })
+                    .unwrap_or(CsvAttrType::String);
+                (name.to_string(), attr_type)
+            })
+            .collect()
+    }
+    /// Stream all rows with a valid geometry. Returns the number of skipped rows.
+    fn read_rows<F>(&self, mut read: F) -> Result<u64, String>
+    where
+        F: FnMut(CsvGeom, &csv::StringRecord),
+    {
+        let mut reader = self.reader()?;
+        let headers = reader
+            .headers()
+            .map_err(|e| self.read_error(&e))?
+            .clone();
+        let geom_columns = self.geom_columns(&headers)?;
+        let mut record = csv::StringRecord::new();
+        let mut invalid_rows = 0;
+        loop {
+            match reader.read_record(&mut record) {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(ref e) if e.is_io_error() => return Err(self.read_error(e)),
+                Err(_) => {
+                    // e.g. invalid UTF-8
+                    invalid_rows += 1;
+                    continue;
+                }
+            }
+            match geom_columns.read(&record) {
+                Some(geom) => read(geom, &record),
+                None => invalid_rows += 1,
+            }
+        }
+        Ok(invalid_rows)
+    }
+    /// Read all features into memory
+    pub(crate) fn load(
+        &self,
+        fid_field: Option<&String>,
+        transform: Option<fn(f64, f64) -> Coord>,
+    ) -> Result<CsvData, String> {
+        let (headers, _) = self.sample(0)?;
+        let geom_columns = self.geom_columns(&headers)?;
+        let attr_columns = self.attribute_columns(&headers, &geom_columns);
+        let fid_column = match fid_field {
+            Some(field) => Some(headers.iter().position(|h| h == field.as_str()).ok_or(
+                format!("CSV file '{}': column '{}' not found", self.path, field),
+            )?),
+            None => None,
+        };
+        let mut features = Vec::new();
+        let mut invalid_coords = 0;
+        let invalid_rows = self.read_rows(|mut geom, record| {
+            if let Some(transform) = transform {
+                geom.map_coords(transform);
+            }
+            if !geom.is_finite() {
+                invalid_coords += 1;
+                return;
+            }
+            let values = attr_columns
+                .iter()
+                .map(|&(idx, _, attr_type)| record.get(idx).and_then(|v| attr_type.parse(v)))
+                .collect();
+            features.push(CsvFeature {
+                fid: fid_column
+                    .and_then(|idx| record.get(idx))
+                    .and_then(|v| v.trim().parse::<u64>().ok()),
+                bbox: geom.bbox(),
+                geom,
+                values,
+            });
+        })?;
+        features.shrink_to_fit();
+        let index = CellIndex::new(&features);
+        Ok(CsvData {
+            attr_names: attr_columns.into_iter().map(|(_, name, _)| name).collect(),
+            features,
+            index,
+            invalid_rows: invalid_rows + invalid_coords,
+        })
+    }
+}
+
+impl DatasourceType for CsvDatasource {
+    /// New instance with connected pool
+    fn connected(&self) -> CsvDatasource {
+        self.clone()
+    }
+    fn detect_layers(&self, detect_geometry_types: bool) -> Vec<Layer> {
+        let name = Path::new(&self.path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or("csv".to_string());
+        let mut layer = Layer::new(&name);
+        layer.table_name = Some(name.clone());
+        layer.srid = Some(self.srid);
+        match self.geometry {
+            CsvGeometry::LonLat { ref lon_field, .. } => {
+                layer.geometry_field = Some(lon_field.clone());
+                layer.geometry_type = Some("POINT".to_string());
+            }
+            CsvGeometry::Wkt { ref wkt_field } => {
+                layer.geometry_field = Some(wkt_field.clone());
+                if detect_geometry_types {
+                    layer.geometry_type = self.sample(SAMPLE_ROWS).ok().and_then(
+                        |(headers, rows)| {
+                            let geom_columns = self.geom_columns(&headers).ok()?;
+                            rows.iter()
+                                .filter_map(|row| geom_columns.read(row))
+                                .map(|geom| geom.type_name().to_string())
+                                .next()
+                        },
+                    );
+                }
+            }
+        }
+        vec![layer]
+    }
+    /// Return column field names and Rust compatible type conversion - without geometry column
+    fn detect_data_columns(&self, _layer: &Layer, _sql: Option<&String>) -> Vec<(String, String)> {
+        match self.attributes {
+            Some(ref attributes) => attributes
+                .keys()
+                .map(|name| (name.clone(), "".to_string()))
+                .collect(),
+            None => self
+                .guess_attributes()
+                .into_iter()
+                .map(|(name, _)| (name, "".to_string()))
+                .collect(),
+        }
+    }
+    fn detect_field_types(&self, _layer: &Layer, _sql: Option<&String>) -> Vec<(String, String)> {
+        match self.attributes {
+            Some(ref attributes) => attributes
+                .iter()
+                .map(|(name, attr_type)| (name.clone(), attr_type.field_type().to_string()))
+                .collect(),
+            // Attributes without declared type are read as strings
+            None => self
+                .guess_attributes()
+                .into_iter()
+                .map(|(name, _)| (name, CsvAttrType::String.field_type().to_string()))
+                .collect(),
+        }
+    }
+    /// Projected extent
+    fn extent_from_wgs84(&self, extent: &Extent, dest_srid: i32) -> Option<Extent> {
+        match dest_srid {
+            4326 => Some(extent.clone()),
+            3857 => Some(extent_to_merc(extent)),
+            _ => None,
+        }
+    }
+    fn layer_extent(&self, layer: &Layer, _grid_srid: i32) -> Option<Extent> {
+        if self.srid != 4326 {
+            // No reprojection to WGS84 available
+            return None;
+        }
+        let mut extent: Option<Extent> = None;
+        let result = self.read_rows(|geom, _| {
+            if !geom.is_finite() {
+                return;
+            }
+            let bbox = geom.bbox();
+            match extent {
+                Some(ref mut extent) => extend(extent, &bbox),
+                None => extent = Some(bbox),
+            }
+        });
+        if let Err(e) = result {
+            warn!("Layer '{}': Unable to get extent: {}", layer.name, e);
+            return None;
+        }
+        extent
+    }
+    fn prepare_queries(&mut self, _tileset: &str, layer: &Layer, grid_srid: i32) {
+        let transform: Option<fn(f64, f64) -> Coord> =
+            if layer.no_transform || self.srid == grid_srid {
+                None
+            } else if self.srid == 4326 && grid_srid == 3857 {
+                info!(
+                    "Layer '{}': Reprojecting geometry to SRID {}",
+                    layer.name, grid_srid
+                );
+                Some(lonlat_to_merc)
+            } else {
+                error!(
+                    "Layer '{}': Reprojecting CSV geometries from SRID {} to SRID {} not supported",
+                    layer.name, self.srid, grid_srid
+                );
+                return;
+            };
+        match self.load(layer.fid_field.as_ref(), transform) {
+            Ok(data) => {
+                info!(
+                    "Layer '{}': {} features loaded from '{}'",
+                    layer.name,
+                    data.len(),
+                    self.path
+                );
+                if data.invalid_rows > 0 {
+                    warn!(
+                        "Layer '{}': {} rows with unparsable coordinates skipped",
+                        layer.name, data.invalid_rows
+                    );
+                }
+                self.data.insert(layer.name.clone(), Arc::new(data));
+            }
+            Err(e) => error!("Layer '{}': {}", layer.name, e),
+        }
+
+        if layer.simplify {
+            if layer.geometry_type != Some("POINT".to_string()) {
+                warn!(
+                    "Layer '{}': Simplification not supported for CSV layers",
+                    layer.name
+                );
+            }
+        }
+        if layer.buffer_size.is_some() && layer.clip_geom {
+            if layer.geometry_type != Some("POINT".to_string()) {
+                warn!(
+                    "Layer '{}': Clipping with buffer_size not supported for CSV layers",
+                    layer.name
+                );
+            }
+        }
+    }
+    fn retrieve_features<F>(
+        &self,
+        _tileset: &str,
+        layer: &Layer,
+        extent: &Extent,
+        zoom: u8,
+        grid: &Grid,
+        mut read: F,
+    ) -> u64
+    where
+        F: FnMut(&dyn Feature),
+    {
+        let data = match self.data.get(&layer.name) {
+            Some(data) => data,
+            None => {
+                debug!("retrieve_features: layer '{}' not loaded", layer.name);
+                return 0;
+            }
+        };
+        let bbox_extent = layer.buffered_extent(extent, grid, zoom);
+
+        let mut cnt = 0;
+        let query_limit = layer.query_limit.unwrap_or(0);
+        for idx in data.index.query(&data.features, &bbox_extent) {
+            let feat = CsvFeatureRef {
+                attr_names: &data.attr_names,
+                feature: &data.features[idx],
+                srid: Some(grid.srid),
+                layer: layer,
+                zoom: zoom,
+            };
+            read(&feat);
+            cnt += 1;
+            if cnt == query_limit as u64 {
+                info!(
+                    "Features of layer {} limited to {} (tile query_limit reached, zoom level {})",
+                    layer.name, cnt, zoom
+                );
+                break;
+            }
+        }
+        cnt
+    }
+    /// Layers which failed to load return an error
+    fn try_retrieve_features_while<F>(
+        &self,
+        tileset: &str,
+        layer: &Layer,
+        extent: &Extent,
+        zoom: u8,
+        grid: &Grid,
+        read: F,
+    ) -> Result<u64, String>
+    where
+        F: FnMut(&dyn Feature) -> bool,
+    {
+        if !self.data.contains_key(&layer.name) {
+            return Err(format!("Layer '{}': data not loaded", layer.name));
+        }
+        Ok(self.retrieve_features_while(tileset, layer, extent, zoom, grid, read))
+    }
+}
+
+impl<'a> Config<'a, DatasourceCfg> for CsvDatasource {
+    fn from_config(ds_cfg: &DatasourceCfg) -> Result<Self, String> {
+        let mut ds = CsvDatasource {
+            path: ds_cfg.csv.clone().unwrap(),
+            delimiter: b',',
+            geometry: CsvGeometry::default(),
+            srid: ds_cfg.srid.unwrap_or(4326),
+            attributes: None,
+            data: BTreeMap::new(),
+        };
+        if let Some(ref delimiter) = ds_cfg.delimiter {
+            match delimiter.as_bytes() {
+                [byte] => ds.delimiter = *byte,
+                _ => {
+                    return Err(format!(
+                        "CSV delimiter '{}' must be a single ASCII character",
+                        delimiter
+                    ))
+                }
+            }
+        }
+        let lonlat = || CsvGeometry::LonLat {
+            lon_field: ds_cfg.lon_field.clone().unwrap_or("lon".to_string()),
+            lat_field: ds_cfg.lat_field.clone().unwrap_or("lat".to_string()),
+        };
+        let wkt = || CsvGeometry::Wkt {
+            wkt_field: ds_cfg.wkt_field.clone().unwrap_or("wkt".to_string()),
+        };
+        ds.geometry = match ds_cfg.geometry.as_ref().map(|g| g.as_str()) {
+            Some("lonlat") => lonlat(),
+            Some("wkt") => wkt(),
+            Some(other) => {
+                return Err(format!(
+                    "Unsupported CSV geometry '{}' (expected 'lonlat' or 'wkt')",
+                    other
+                ))
+            }
+            None if ds_cfg.lon_field.is_some() || ds_cfg.lat_field.is_some() => lonlat(),
+            None if ds_cfg.wkt_field.is_some() => wkt(),
+            None => ds.detect_geometry().unwrap_or_default(),
+        };
+        if let Some(ref attributes) = ds_cfg.attributes {
+            let mut attr_types = BTreeMap::new();
+            for (name, type_name) in attributes {
+                attr_types.insert(name.clone(), CsvAttrType::from_name(type_name)?);
+            }
+            ds.attributes = Some(attr_types);
+        }
+        Ok(ds)
+    }
+
+    fn gen_config() -> String {
+        let toml = r#"
+[[datasource]]
+name = "places"
+# CSV file with point coordinates or WKT geometries
+csv = "<filename>"
+#delimiter = ","
+# Point coordinates in two columns
+#geometry = "lonlat"
+#lon_field = "lon"
+#lat_field = "lat"
+# Geometries in a WKT column
+#geometry = "wkt"
+#wkt_field = "wkt"
+#srid = 4326
+# Attribute columns with types (string, int, double, bool). Default: all columns as string
+#attributes = { name = "string", population = "int" }
+"#;
+        toml.to_string()
+    }
+    fn gen_runtime_config(&self) -> String {
+        let mut config = format!("\n[[datasource]]\ncsv = \"{}\"\n", self.path);
+        if self.delimiter != b',' {
+            config.push_str(&format!(
+                "delimiter = \"{}\"\n",
+                (self.delimiter as char).escape_default()
+            ));
+        }
+        match self.geometry {
+            CsvGeometry::LonLat {
+                ref lon_field,
+                ref lat_field,
+            } => config.push_str(&format!(
+                "geometry = \"lonlat\"\nlon_field = \"{}\"\nlat_field = \"{}\"\n",
+                lon_field, lat_field
+            )),
+            CsvGeometry::Wkt { ref wkt_field } => config.push_str(&format!(
+                "geometry = \"wkt\"\nwkt_field = \"{}\"\n",
+                wkt_field
+            )),
+        }
+        config.push_str(&format!("srid = {}\n", self.srid));
+        let attributes: Vec<(String, CsvAttrType)> = match self.attributes {
+            Some(ref attributes) => attributes
+                .iter()
+                .map(|(name, attr_type)| (name.clone(), *attr_type))
+                .collect(),
+            None => self.guess_attributes(),
+        };
+        if !attributes.is_empty() {
+            let attributes = attributes
+                .iter()
+                .map(|(name, attr_type)| format!("\"{}\" = \"{}\"", name, attr_type.name()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            config.push_str(&format!("attributes = {{ {} }}\n", attributes));
+        }
+        config
+    }
+}
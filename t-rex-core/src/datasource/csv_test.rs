@@ -0,0 +1,382 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use crate::core::config::DatasourceCfg;
+use crate::core::feature::FeatureAttrValType;
+use crate::core::layer::Layer;
+use crate::core::{parse_config, Config};
+use crate::datasource::csv_ds::{parse_wkt, CsvGeom};
+use crate::datasource::{CsvAttrType, CsvDatasource, CsvGeometry, DatasourceType};
+use std::env;
+use std::fs;
+use tile_grid::{Extent, Grid};
+
+const PLACES: &str = "../data/places.csv";
+
+#[test]
+fn test_parse_wkt() {
+    assert_eq!(parse_wkt("POINT (7.44 46.95)"), Some(CsvGeom::Point((7.44, 46.95))));
+    assert_eq!(parse_wkt("point z(1 2 3)"), Some(CsvGeom::Point((1.0, 2.0))));
+    assert_eq!(
+        parse_wkt("LINESTRING(0 0, 1 1,2 0)"),
+        Some(CsvGeom::LineString(vec![(0.0, 0.0), (1.0, 1.0), (2.0, 0.0)]))
+    );
+    assert_eq!(
+        parse_wkt("POLYGON ((0 0, 4 0, 4 4, 0 0), (1 1, 2 1, 2 2, 1 1))"),
+        Some(CsvGeom::Polygon(vec![
+            vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 0.0)],
+            vec![(1.0, 1.0), (2.0, 1.0), (2.0, 2.0), (1.0, 1.0)],
+        ]))
+    );
+    assert_eq!(
+        parse_wkt("MULTIPOINT ((1 2), (3 4))"),
+        parse_wkt("MULTIPOINT (1 2, 3 4)")
+    );
+    assert_eq!(
+        parse_wkt("MULTIPOLYGON (((0 0, 1 0, 1 1, 0 0)), ((5 5, 6 5, 6 6, 5 5)))")
+            .map(|g| g.type_name()),
+        Some("POLYGON")
+    );
+
+    assert_eq!(parse_wkt("POINT EMPTY"), None);
+    assert_eq!(parse_wkt("POINT (1)"), None);
+    assert_eq!(parse_wkt("POINT (1 2, 3 4)"), None);
+    assert_eq!(parse_wkt("LINESTRING (0 0, 1 1"), None);
+    assert_eq!(parse_wkt("CIRCULARSTRING (0 0, 1 1, 2 0)"), None);
+    assert_eq!(parse_wkt("7.44,46.95"), None);
+}
+
+#[test]
+fn test_attr_types() {
+    assert_eq!(
+        CsvAttrType::Int.parse(" 42"),
+        Some(FeatureAttrValType::Int(42))
+    );
+    assert_eq!(CsvAttrType::Int.parse("4.2"), None);
+    assert_eq!(CsvAttrType::Double.parse(""), None);
+    assert_eq!(
+        CsvAttrType::Bool.parse("Yes"),
+        Some(FeatureAttrValType::Bool(true))
+    );
+
+    assert_eq!(CsvAttrType::guess("42"), CsvAttrType::Int);
+    assert_eq!(CsvAttrType::guess("-4.2e3"), CsvAttrType::Double);
+    assert_eq!(CsvAttrType::guess("FALSE"), CsvAttrType::Bool);
+    assert_eq!(CsvAttrType::guess("Bern"), CsvAttrType::String);
+
+    // 64 bit values are kept without loss of precision
+    assert_eq!(
+        CsvAttrType::Int.parse("9223372036854775807"),
+        Some(FeatureAttrValType::Int(i64::max_value()))
+    );
+    assert_eq!(
+        CsvAttrType::Int.parse("-9223372036854775808"),
+        Some(FeatureAttrValType::Int(i64::min_value()))
+    );
+    assert_eq!(
+        CsvAttrType::Int.parse("18446744073709551615"),
+        Some(FeatureAttrValType::UInt(u64::max_value()))
+    );
+    assert_eq!(CsvAttrType::Int.parse("18446744073709551616"), None);
+    assert_eq!(CsvAttrType::guess("18446744073709551615"), CsvAttrType::Int);
+    assert_eq!(
+        CsvAttrType::guess("18446744073709551616"),
+        CsvAttrType::Double
+    );
+
+    assert_eq!(CsvAttrType::from_name("Integer"), Ok(CsvAttrType::Int));
+    assert!(CsvAttrType::from_name("date").is_err());
+}
+
+#[test]
+fn test_detect_layers() {
+    let ds = CsvDatasource::new(PLACES);
+    assert_eq!(
+        ds.geometry,
+        CsvGeometry::LonLat {
+            lon_field: "lon".to_string(),
+            lat_field: "lat".to_string()
+        }
+    );
+    let layers = ds.detect_layers(true);
+    assert_eq!(layers.len(), 1);
+    assert_eq!(layers[0].name, "places");
+    assert_eq!(layers[0].geometry_type, Some("POINT".to_string()));
+    assert_eq!(layers[0].srid, Some(4326));
+
+    assert_eq!(
+        ds.guess_attributes(),
+        vec![
+            ("name".to_string(), CsvAttrType::String),
+            ("population".to_string(), CsvAttrType::Int),
+            ("capital".to_string(), CsvAttrType::Bool),
+        ]
+    );
+
+    let extent = ds.layer_extent(&layers[0], 3857).unwrap();
+    assert_eq!(
+        extent,
+        Extent {
+            minx: 6.1432,
+            miny: 46.2044,
+            maxx: 8.5417,
+            maxy: 47.5596
+        }
+    );
+}
+
+#[test]
+fn test_load() {
+    let ds = CsvDatasource::new(PLACES);
+    let data = ds.load(None, None).unwrap();
+    assert_eq!(data.len(), 4);
+    // Row 'Nowhere' without coordinates
+    assert_eq!(data.invalid_rows, 1);
+}
+
+#[test]
+fn test_retrieve_features() {
+    let mut layer = Layer::new("places");
+    layer.geometry_type = Some("POINT".to_string());
+    let grid = Grid::web_mercator();
+
+    let mut ds = CsvDatasource::new(PLACES);
+    ds.prepare_queries("ts", &layer, grid.srid);
+
+    let switzerland = Extent {
+        minx: 600000.0,
+        miny: 5700000.0,
+        maxx: 1100000.0,
+        maxy: 6100000.0,
+    };
+    let mut names = Vec::new();
+    let cnt = ds.retrieve_features("ts", &layer, &switzerland, 6, &grid, |feat| {
+        for attr in feat.attributes() {
+            if attr.key == "name" {
+                names.push(attr.value);
+            }
+        }
+    });
+    assert_eq!(cnt, 4);
+    assert_eq!(
+        names,
+        vec![
+            FeatureAttrValType::String("Bern".to_string()),
+            FeatureAttrValType::String("Zürich".to_string()),
+            FeatureAttrValType::String("Geneva".to_string()),
+            FeatureAttrValType::String("Basel".to_string()),
+        ]
+    );
+
+    let extent = Extent {
+        minx: 821850.9,
+        miny: 5909499.5,
+        maxx: 860986.7,
+        maxy: 5948635.3,
+    };
+    let mut reccnt = 0;
+    ds.retrieve_features("ts", &layer, &extent, 10, &grid, |feat| {
+        assert_eq!(
+            format!("{:?}", feat.geometry()),
+            "Ok(Point(Point { x: 829040.7757338256, y: 5933590.475412019, srid: Some(3857) }))"
+        );
+        // All columns are strings without declared attributes
+        assert_eq!(
+            format!("{:?}", feat.attributes()[1]),
+            r#"FeatureAttr { key: "population", value: String("133115") }"#
+        );
+        reccnt += 1;
+    });
+    assert_eq!(reccnt, 1);
+
+    layer.query_limit = Some(2);
+    let cnt = ds.retrieve_features("ts", &layer, &switzerland, 6, &grid, |_| {});
+    assert_eq!(cnt, 2);
+}
+
+#[test]
+fn test_attribute_zoom_range() {
+    use crate::core::layer::LayerAttribute;
+
+    let mut layer = Layer::new("places");
+    layer.geometry_type = Some("POINT".to_string());
+    layer.fid_field = Some("name".to_string());
+    layer.attributes_minzoom = Some(8);
+    layer.attributes = vec![LayerAttribute {
+        name: "population".to_string(),
+        minzoom: Some(10),
+        maxzoom: None,
+    }];
+    let grid = Grid::web_mercator();
+
+    let mut ds = CsvDatasource::new(PLACES);
+    ds.prepare_queries("ts", &layer, grid.srid);
+    let world = Extent {
+        minx: -20037508.34,
+        miny: -20037508.34,
+        maxx: 20037508.34,
+        maxy: 20037508.34,
+    };
+    let keys = |zoom| {
+        let mut keys = Vec::new();
+        ds.retrieve_features("ts", &layer, &world, zoom, &grid, |feat| {
+            if keys.is_empty() {
+                keys = feat.attributes().into_iter().map(|attr| attr.key).collect();
+            }
+        });
+        keys
+    };
+    // fid_field is never excluded
+    assert_eq!(keys(6), vec!["name"]);
+    assert!(!keys(8).contains(&"population".to_string()));
+    assert!(keys(8).len() > 1);
+    assert!(keys(10).contains(&"population".to_string()));
+
+    assert_eq!(layer.attribute_zoom_range("name"), None);
+    assert_eq!(layer.attribute_zoom_range("population"), Some((10, 22)));
+    assert_eq!(layer.attribute_zoom_range("other"), Some((8, 22)));
+}
+
+#[test]
+fn test_wkt_from_config() {
+    let mut path = env::temp_dir();
+    path.push("t_rex_test_wkt.csv");
+    fs::write(
+        &path,
+        "id;kind;wkt\n1;road;LINESTRING (0 0, 10 10)\n2;lake;POLYGON ((20 20, 30 20, 30 30, 20 20))\n3;none;\nx;road;LINESTRING (5 0, 5 10)\n",
+    )
+    .unwrap();
+
+    let toml = format!(
+        r#"
+        csv = "{}"
+        delimiter = ";"
+        geometry = "wkt"
+        srid = 3857
+        attributes = {{ id = "int", kind = "string" }}
+        "#,
+        path.display()
+    );
+    let ds_cfg: DatasourceCfg = parse_config(toml, "").unwrap();
+    let mut ds = CsvDatasource::from_config(&ds_cfg).unwrap();
+    assert_eq!(ds.delimiter, b';');
+    assert_eq!(
+        ds.geometry,
+        CsvGeometry::Wkt {
+            wkt_field: "wkt".to_string()
+        }
+    );
+    assert_eq!(ds.detect_layers(true)[0].geometry_type, Some("LINE".to_string()));
+
+    let layer = Layer::new("wkt");
+    let grid = Grid::web_mercator();
+    ds.prepare_queries("ts", &layer, grid.srid);
+    let extent = Extent {
+        minx: 0.0,
+        miny: 0.0,
+        maxx: 8.0,
+        maxy: 8.0,
+    };
+    let mut ids = Vec::new();
+    ds.retrieve_features("ts", &layer, &extent, 10, &grid, |feat| {
+        ids.push(
+            feat.attributes()
+                .iter()
+                .find(|attr| attr.key == "id")
+                .map(|attr| attr.value.clone()),
+        );
+    });
+    // Unparsable id is skipped, feature is kept
+    assert_eq!(ids, vec![Some(FeatureAttrValType::Int(1)), None]);
+
+    let toml = format!(
+        r#"
+        csv = "{}"
+        geometry = "geojson"
+        "#,
+        path.display()
+    );
+    let ds_cfg: DatasourceCfg = parse_config(toml, "").unwrap();
+    assert_eq!(
+        CsvDatasource::from_config(&ds_cfg).err(),
+        Some("Unsupported CSV geometry 'geojson' (expected 'lonlat' or 'wkt')".to_string())
+    );
+}
+
+#[test]
+fn test_gen_runtime_config() {
+    let ds = CsvDatasource::new(PLACES);
+    assert_eq!(
+        ds.gen_runtime_config(),
+        r#"
+[[datasource]]
+csv = "../data/places.csv"
+geometry = "lonlat"
+lon_field = "lon"
+lat_field = "lat"
+srid = 4326
+attributes = { "name" = "string", "population" = "int", "capital" = "bool" }
+"#
+    );
+}
+
+#[test]
+fn test_bigint_attributes() {
+    let mut path = env::temp_dir();
+    path.push("t_rex_test_bigint.csv");
+    fs::write(
+        &path,
+        "id,count,lon,lat\n18446744073709551615,9223372036854775807,7.44,46.95\n9007199254740993,-9223372036854775808,7.45,46.96\n",
+    )
+    .unwrap();
+
+    let toml = format!(
+        r#"
+        csv = "{}"
+        attributes = {{ id = "int", count = "int" }}
+        "#,
+        path.display()
+    );
+    let ds_cfg: DatasourceCfg = parse_config(toml, "").unwrap();
+    let mut ds = CsvDatasource::from_config(&ds_cfg).unwrap();
+    let mut layer = Layer::new("bigint");
+    layer.fid_field = Some("id".to_string());
+    assert_eq!(
+        ds.detect_field_types(&layer, None),
+        vec![
+            ("count".to_string(), "Number".to_string()),
+            ("id".to_string(), "Number".to_string()),
+        ]
+    );
+
+    let grid = Grid::web_mercator();
+    ds.prepare_queries("ts", &layer, grid.srid);
+    let world = Extent {
+        minx: -20037508.34,
+        miny: -20037508.34,
+        maxx: 20037508.34,
+        maxy: 20037508.34,
+    };
+    let mut features = Vec::new();
+    ds.retrieve_features("ts", &layer, &world, 10, &grid, |feat| {
+        features.push((feat.fid(), feat.attributes()));
+    });
+    assert_eq!(features.len(), 2);
+    assert_eq!(features[0].0, Some(u64::max_value()));
+    assert_eq!(
+        features[0].1[1].value,
+        FeatureAttrValType::Int(i64::max_value())
+    );
+    // Above 2^53, not representable as double
+    assert_eq!(features[1].0, Some(9007199254740993));
+    assert_eq!(
+        features[1].1[0].value,
+        FeatureAttrValType::Int(9007199254740993)
+    );
+    assert_eq!(
+        features[1].1[1].value,
+        FeatureAttrValType::Int(i64::min_value())
+    );
+}
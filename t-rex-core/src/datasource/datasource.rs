@@ -10,12 +10,28 @@ use crate::core::Config;
 use tile_grid::Extent;
 use tile_grid::Grid;
 
+/// Error message of tile queries without a free connection of the connection pool
+pub const POOL_TIMEOUT_ERROR: &str = "no connection available";
+
+/// Check whether a datasource error is caused by an exhausted connection pool
+pub fn is_pool_timeout(err: &str) -> bool {
+    err.contains(POOL_TIMEOUT_ERROR)
+}
+
 pub trait DatasourceType {
     /// New instance with connected pool
     fn connected(&self) -> Self;
     fn detect_layers(&self, detect_geometry_types: bool) -> Vec<Layer>;
     /// Return column field names and Rust compatible type conversion - without geometry column
     fn detect_data_columns(&self, layer: &Layer, sql: Option<&String>) -> Vec<(String, String)>;
+    /// Return column field names with TileJSON field type ("Number", "String" or "Boolean",
+    /// empty if unknown) - without geometry column
+    fn detect_field_types(&self, layer: &Layer, sql: Option<&String>) -> Vec<(String, String)> {
+        self.detect_data_columns(layer, sql)
+            .into_iter()
+            .map(|(name, _)| (name, String::new()))
+            .collect()
+    }
     fn layer_extent(&self, layer: &Layer, grid_srid: i32) -> Option<Extent>;
     fn prepare_queries(&mut self, tileset: &str, layer: &Layer, grid_srid: i32);
     /// Projected extent
@@ -32,8 +48,46 @@ pub trait DatasourceType {
     ) -> u64
     where
         F: FnMut(&dyn Feature);
+    /// Retrieve features of one layer until `read` returns false. Return feature count.
+    /// Datasources streaming features should stop reading instead of skipping the remaining features.
+    fn retrieve_features_while<F>(
+        &self,
+        tileset: &str,
+        layer: &Layer,
+        extent: &Extent,
+        zoom: u8,
+        grid: &Grid,
+        mut read: F,
+    ) -> u64
+    where
+        F: FnMut(&dyn Feature) -> bool,
+    {
+        let mut reading = true;
+        self.retrieve_features(tileset, layer, extent, zoom, grid, |feat| {
+            if reading {
+                reading = read(feat);
+            }
+        })
+    }
+    /// Retrieve features like `retrieve_features_while`, returning query errors
+    /// instead of logging them.
+    fn try_retrieve_features_while<F>(
+        &self,
+        tileset: &str,
+        layer: &Layer,
+        extent: &Extent,
+        zoom: u8,
+        grid: &Grid,
+        read: F,
+    ) -> Result<u64, String>
+    where
+        F: FnMut(&dyn Feature) -> bool,
+    {
+        Ok(self.retrieve_features_while(tileset, layer, extent, zoom, grid, read))
+    }
 }
 
+#[derive(Clone)]
 pub struct DummyDatasource;
 
 impl DatasourceType for DummyDatasource {
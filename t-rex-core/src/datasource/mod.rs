@@ -3,11 +3,17 @@
 // Licensed under the MIT License. See LICENSE file in the project root for full license information.
 //
 
+mod csv_ds;
+#[cfg(test)]
+mod csv_test;
 mod datasource;
 mod postgis_ds;
 mod postgis_fields;
+mod postgis_replicas;
 #[cfg(test)]
 mod postgis_test;
 
-pub use self::datasource::{DatasourceType, DummyDatasource};
-pub use self::postgis_ds::PostgisDatasource;
+pub use self::csv_ds::{CsvAttrType, CsvDatasource, CsvGeometry};
+pub use self::datasource::{is_pool_timeout, DatasourceType, DummyDatasource, POOL_TIMEOUT_ERROR};
+pub use self::postgis_ds::{BoundQuery, PostgisDatasource};
+pub use self::postgis_replicas::ReplicaStats;
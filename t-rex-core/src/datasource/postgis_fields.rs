@@ -47,7 +47,14 @@ impl GeometryType {
         };
         // Option<Result<GeometryType, _>> --> Result<GeometryType, String>
         field.map_or_else(
-            || Err("Column not found".to_string()),
+            || {
+                let columns: Vec<&str> = row.columns().iter().map(|col| col.name()).collect();
+                Err(format!(
+                    "geometry_field '{}' not found in query result (columns: {})",
+                    idx,
+                    columns.join(", ")
+                ))
+            },
             |res| res.map_err(|err| format!("{}", err)),
         )
     }
@@ -96,6 +103,18 @@ impl FromSql for FeatureAttrValType {
     }
 }
 
+/// JSON value as text, e.g. the result of `EXPLAIN (FORMAT JSON)`
+pub(crate) struct JsonText(pub String);
+
+impl FromSql for JsonText {
+    fn accepts(ty: &Type) -> bool {
+        ty == &types::JSON
+    }
+    fn from_sql(_ty: &Type, raw: &[u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(JsonText(String::from_utf8(raw.to_vec())?))
+    }
+}
+
 pub(crate) struct FeatureRow<'a> {
     pub layer: &'a Layer,
     pub row: &'a Row<'a>,
@@ -106,7 +125,8 @@ impl<'a> Feature for FeatureRow<'a> {
         self.layer.fid_field.as_ref().and_then(|fid| {
             let val = self.row.get_opt::<_, FeatureAttrValType>(fid as &str);
             match val {
-                Some(Ok(FeatureAttrValType::Int(fid))) => Some(fid as u64),
+                // MVT feature ids are unsigned
+                Some(Ok(FeatureAttrValType::Int(fid))) if fid >= 0 => Some(fid as u64),
                 _ => None,
             }
         })
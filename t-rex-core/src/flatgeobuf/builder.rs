@@ -0,0 +1,166 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+//! Minimal FlatBuffers writer (https://google.github.io/flatbuffers/)
+//!
+//! Tables are written front to back: vtable, table and then the referenced objects,
+//! which keeps all `uoffset` values positive. Alignment is relative to the start
+//! of the size prefixed buffer.
+
+/// Table field value
+pub enum Value {
+    UByte(u8),
+    UShort(u16),
+    Int(i32),
+    ULong(u64),
+    String(String),
+    UBytes(Vec<u8>),
+    UInts(Vec<u32>),
+    Doubles(Vec<f64>),
+    Table(Table),
+    Tables(Vec<Table>),
+}
+
+impl Value {
+    /// Size of the value (or offset) stored in the table
+    fn inline_size(&self) -> usize {
+        match self {
+            Value::UByte(_) => 1,
+            Value::UShort(_) => 2,
+            Value::ULong(_) => 8,
+            _ => 4,
+        }
+    }
+}
+
+/// Table with fields identified by their slot number in the schema
+#[derive(Default)]
+pub struct Table {
+    fields: Vec<(u16, Value)>,
+}
+
+impl Table {
+    pub fn new() -> Table {
+        Table::default()
+    }
+    pub fn add(&mut self, slot: u16, value: Value) {
+        self.fields.push((slot, value));
+    }
+    /// Size prefixed buffer with this table as root
+    pub fn finish_size_prefixed(&self) -> Vec<u8> {
+        let mut buf = vec![0; 8];
+        let pos = write_table(&mut buf, self);
+        patch_offset(&mut buf, 4, pos);
+        let size = (buf.len() - 4) as u32;
+        buf[..4].copy_from_slice(&size.to_le_bytes());
+        buf
+    }
+}
+
+fn pad(buf: &mut Vec<u8>, align: usize, shift: usize) {
+    while (buf.len() + shift) % align != 0 {
+        buf.push(0);
+    }
+}
+
+/// Store the offset from `slot` to `target`
+fn patch_offset(buf: &mut [u8], slot: usize, target: usize) {
+    let offset = (target - slot) as u32;
+    buf[slot..slot + 4].copy_from_slice(&offset.to_le_bytes());
+}
+
+/// Write vtable, table and referenced objects. Returns the table position.
+fn write_table(buf: &mut Vec<u8>, table: &Table) -> usize {
+    // Largest fields first, starting 8 byte aligned after the vtable offset
+    let mut fields = table.fields.iter().collect::<Vec<_>>();
+    fields.sort_by_key(|(_, value)| -(value.inline_size() as i32));
+    let slots = fields.iter().map(|(slot, _)| *slot + 1).max().unwrap_or(0) as usize;
+    let vtable_len = 4 + 2 * slots;
+    let mut field_offsets = vec![0u16; slots];
+    let mut table_len = 4;
+    for (slot, value) in &fields {
+        field_offsets[*slot as usize] = table_len as u16;
+        table_len += value.inline_size();
+    }
+
+    pad(buf, 8, vtable_len + 4);
+    buf.extend_from_slice(&(vtable_len as u16).to_le_bytes());
+    buf.extend_from_slice(&(table_len as u16).to_le_bytes());
+    for offset in &field_offsets {
+        buf.extend_from_slice(&offset.to_le_bytes());
+    }
+    let table_pos = buf.len();
+    buf.extend_from_slice(&(vtable_len as i32).to_le_bytes());
+    let mut children = Vec::new();
+    for (slot, value) in &fields {
+        match value {
+            Value::UByte(v) => buf.push(*v),
+            Value::UShort(v) => buf.extend_from_slice(&v.to_le_bytes()),
+            Value::Int(v) => buf.extend_from_slice(&v.to_le_bytes()),
+            Value::ULong(v) => buf.extend_from_slice(&v.to_le_bytes()),
+            _ => {
+                children.push((table_pos + field_offsets[*slot as usize] as usize, value));
+                buf.extend_from_slice(&[0; 4]);
+            }
+        }
+    }
+    for (slot, value) in children {
+        let pos = write_object(buf, value);
+        patch_offset(buf, slot, pos);
+    }
+    table_pos
+}
+
+/// Write string, vector or table. Returns its position.
+fn write_object(buf: &mut Vec<u8>, value: &Value) -> usize {
+    let write_len = |buf: &mut Vec<u8>, len: usize| {
+        let pos = buf.len();
+        buf.extend_from_slice(&(len as u32).to_le_bytes());
+        pos
+    };
+    match value {
+        Value::String(s) => {
+            pad(buf, 4, 0);
+            let pos = write_len(buf, s.len());
+            buf.extend_from_slice(s.as_bytes());
+            buf.push(0);
+            pos
+        }
+        Value::UBytes(v) => {
+            pad(buf, 4, 0);
+            let pos = write_len(buf, v.len());
+            buf.extend_from_slice(v);
+            pos
+        }
+        Value::UInts(v) => {
+            pad(buf, 4, 0);
+            let pos = write_len(buf, v.len());
+            for n in v {
+                buf.extend_from_slice(&n.to_le_bytes());
+            }
+            pos
+        }
+        Value::Doubles(v) => {
+            pad(buf, 8, 4);
+            let pos = write_len(buf, v.len());
+            for n in v {
+                buf.extend_from_slice(&n.to_le_bytes());
+            }
+            pos
+        }
+        Value::Table(table) => write_table(buf, table),
+        Value::Tables(tables) => {
+            pad(buf, 4, 0);
+            let pos = write_len(buf, tables.len());
+            buf.resize(buf.len() + 4 * tables.len(), 0);
+            for (i, table) in tables.iter().enumerate() {
+                let table_pos = write_table(buf, table);
+                patch_offset(buf, pos + 4 + 4 * i, table_pos);
+            }
+            pos
+        }
+        _ => unreachable!("scalar values are stored inline"),
+    }
+}
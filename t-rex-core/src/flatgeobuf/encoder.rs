@@ -0,0 +1,352 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+//! FlatGeobuf encoder (https://flatgeobuf.org/)
+//!
+//! Writes the magic bytes, a `Header` with the schema of all features and the
+//! `Feature` buffers as specified in `header.fbs` and `feature.fbs`.
+//! Tiles are small, so no spatial index is written.
+
+use crate::core::feature::{Feature, FeatureAttrValType};
+use crate::core::geom::{self, GeometryType};
+use crate::flatgeobuf::builder::{Table, Value};
+use std::collections::HashMap;
+use tile_grid::merc_to_lonlat;
+
+/// Magic bytes of FlatGeobuf version 3
+pub const MAGIC_BYTES: [u8; 8] = [0x66, 0x67, 0x62, 0x03, 0x66, 0x67, 0x62, 0x00];
+
+/// Column with the layer name
+pub const LAYER_COLUMN: &str = "layer";
+
+/// `GeometryType` values
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum FgbGeomType {
+    Unknown = 0,
+    Point = 1,
+    LineString = 2,
+    Polygon = 3,
+    MultiPoint = 4,
+    MultiLineString = 5,
+    MultiPolygon = 6,
+}
+
+/// `ColumnType` values
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum FgbColumnType {
+    Bool = 2,
+    Long = 7,
+    ULong = 8,
+    Float = 9,
+    Double = 10,
+    String = 11,
+}
+
+impl FgbColumnType {
+    fn from_attr(value: &FeatureAttrValType) -> FgbColumnType {
+        match value {
+            FeatureAttrValType::String(_) => FgbColumnType::String,
+            FeatureAttrValType::Float(_) => FgbColumnType::Float,
+            FeatureAttrValType::Double(_) => FgbColumnType::Double,
+            FeatureAttrValType::Int(_) | FeatureAttrValType::SInt(_) => FgbColumnType::Long,
+            FeatureAttrValType::UInt(v) if *v <= i64::max_value() as u64 => FgbColumnType::Long,
+            FeatureAttrValType::UInt(_) => FgbColumnType::ULong,
+            FeatureAttrValType::Bool(_) => FgbColumnType::Bool,
+        }
+    }
+    /// Column type holding values of both types
+    fn merge(self, other: FgbColumnType) -> FgbColumnType {
+        use self::FgbColumnType::*;
+        match (self, other) {
+            (a, b) if a == b => a,
+            // Values above i64::MAX and negative values
+            (Long, ULong) | (ULong, Long) => String,
+            (Bool, _) | (_, Bool) | (String, _) | (_, String) => String,
+            _ => Double,
+        }
+    }
+    /// Append value converted to the column type
+    fn write(self, value: &FeatureAttrValType, buf: &mut Vec<u8>) {
+        let number = match value {
+            FeatureAttrValType::Float(v) => *v as f64,
+            FeatureAttrValType::Double(v) => *v,
+            FeatureAttrValType::Int(v) | FeatureAttrValType::SInt(v) => *v as f64,
+            FeatureAttrValType::UInt(v) => *v as f64,
+            _ => 0.0,
+        };
+        match (self, value) {
+            (FgbColumnType::Bool, FeatureAttrValType::Bool(v)) => buf.push(*v as u8),
+            (FgbColumnType::Long, FeatureAttrValType::Int(v))
+            | (FgbColumnType::Long, FeatureAttrValType::SInt(v)) => {
+                buf.extend_from_slice(&v.to_le_bytes())
+            }
+            (FgbColumnType::Long, FeatureAttrValType::UInt(v)) => {
+                buf.extend_from_slice(&(*v as i64).to_le_bytes())
+            }
+            (FgbColumnType::ULong, FeatureAttrValType::UInt(v)) => {
+                buf.extend_from_slice(&v.to_le_bytes())
+            }
+            (FgbColumnType::Float, FeatureAttrValType::Float(v)) => {
+                buf.extend_from_slice(&v.to_le_bytes())
+            }
+            (FgbColumnType::Double, _) => buf.extend_from_slice(&number.to_le_bytes()),
+            (_, value) => {
+                let s = match value {
+                    FeatureAttrValType::String(v) => v.clone(),
+                    FeatureAttrValType::Float(v) => v.to_string(),
+                    FeatureAttrValType::Double(v) => v.to_string(),
+                    FeatureAttrValType::Int(v) | FeatureAttrValType::SInt(v) => v.to_string(),
+                    FeatureAttrValType::UInt(v) => v.to_string(),
+                    FeatureAttrValType::Bool(v) => v.to_string(),
+                };
+                buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+                buf.extend_from_slice(s.as_bytes());
+            }
+        }
+    }
+}
+
+/// `Geometry` table content
+struct FgbGeometry {
+    geom_type: FgbGeomType,
+    ends: Vec<u32>,
+    xy: Vec<f64>,
+    parts: Vec<FgbGeometry>,
+}
+
+impl FgbGeometry {
+    fn table(&self, with_type: bool) -> Table {
+        let mut table = Table::new();
+        if !self.ends.is_empty() {
+            table.add(0, Value::UInts(self.ends.clone()));
+        }
+        if !self.xy.is_empty() {
+            table.add(1, Value::Doubles(self.xy.clone()));
+        }
+        if with_type {
+            table.add(6, Value::UByte(self.geom_type as u8));
+        }
+        if !self.parts.is_empty() {
+            let parts = self.parts.iter().map(|part| part.table(true)).collect();
+            table.add(7, Value::Tables(parts));
+        }
+        table
+    }
+}
+
+struct FgbFeature {
+    geometry: FgbGeometry,
+    /// Column index and value
+    properties: Vec<(u16, FeatureAttrValType)>,
+}
+
+/// FlatGeobuf feature collection
+pub struct FeatureCollection {
+    columns: Vec<(String, FgbColumnType)>,
+    column_index: HashMap<String, u16>,
+    features: Vec<FgbFeature>,
+    /// Envelope of all features (minx, miny, maxx, maxy)
+    envelope: [f64; 4],
+    /// Convert coordinates from Web Mercator to WGS84
+    to_wgs84: bool,
+    srid: i32,
+}
+
+impl FeatureCollection {
+    /// Collection of features in the grid SRS. Web Mercator coordinates
+    /// (`srid` 3857) are converted to WGS84.
+    pub fn new(srid: i32) -> FeatureCollection {
+        let to_wgs84 = srid == 3857;
+        FeatureCollection {
+            columns: vec![(LAYER_COLUMN.to_string(), FgbColumnType::String)],
+            column_index: HashMap::new(),
+            features: Vec::new(),
+            envelope: [
+                f64::INFINITY,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+            ],
+            to_wgs84,
+            srid: if to_wgs84 { 4326 } else { srid },
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+    pub fn len(&self) -> usize {
+        self.features.len()
+    }
+    fn column_idx(&mut self, key: &str, value: &FeatureAttrValType) -> u16 {
+        let col_type = FgbColumnType::from_attr(value);
+        if let Some(idx) = self.column_index.get(key) {
+            let column = &mut self.columns[*idx as usize];
+            column.1 = column.1.merge(col_type);
+            return *idx;
+        }
+        let idx = self.columns.len() as u16;
+        self.columns.push((key.to_string(), col_type));
+        self.column_index.insert(key.to_string(), idx);
+        idx
+    }
+    /// Add feature with the layer name in column `layer`. Features without
+    /// supported geometry are skipped.
+    pub fn add_feature(&mut self, layer: &str, feature: &dyn Feature) {
+        let geometry = match feature.geometry() {
+            Ok(GeometryType::GeometryCollection(_)) => {
+                warn!("Layer '{}': GeometryCollection not supported", layer);
+                return;
+            }
+            Ok(geom) => self.convert_geom(&geom),
+            Err(e) => {
+                warn!("Layer '{}': {}", layer, e);
+                return;
+            }
+        };
+        let mut properties = vec![(0, FeatureAttrValType::String(layer.to_string()))];
+        for attr in feature.attributes() {
+            if attr.key == LAYER_COLUMN {
+                continue;
+            }
+            let idx = self.column_idx(&attr.key, &attr.value);
+            properties.push((idx, attr.value));
+        }
+        self.features.push(FgbFeature {
+            geometry,
+            properties,
+        });
+    }
+    fn add_points(&mut self, xy: &mut Vec<f64>, points: &[geom::Point]) {
+        for p in points {
+            let (x, y) = if self.to_wgs84 {
+                merc_to_lonlat(p.x, p.y)
+            } else {
+                (p.x, p.y)
+            };
+            self.envelope[0] = self.envelope[0].min(x);
+            self.envelope[1] = self.envelope[1].min(y);
+            self.envelope[2] = self.envelope[2].max(x);
+            self.envelope[3] = self.envelope[3].max(y);
+            xy.push(x);
+            xy.push(y);
+        }
+    }
+    /// Coordinates of multiple lines with the end index of each line.
+    /// The ends are omitted for a single line.
+    fn add_lines(&mut self, lines: &[geom::LineString]) -> (Vec<f64>, Vec<u32>) {
+        let mut xy = Vec::new();
+        let mut ends = Vec::new();
+        for line in lines {
+            self.add_points(&mut xy, &line.points);
+            ends.push((xy.len() / 2) as u32);
+        }
+        if ends.len() == 1 {
+            ends.clear();
+        }
+        (xy, ends)
+    }
+    fn convert_geom(&mut self, geom: &GeometryType) -> FgbGeometry {
+        let mut xy = Vec::new();
+        let mut ends = Vec::new();
+        let mut parts = Vec::new();
+        let geom_type = match geom {
+            GeometryType::Point(ref p) => {
+                self.add_points(&mut xy, std::slice::from_ref(p));
+                FgbGeomType::Point
+            }
+            GeometryType::MultiPoint(ref g) => {
+                self.add_points(&mut xy, &g.points);
+                FgbGeomType::MultiPoint
+            }
+            GeometryType::LineString(ref g) => {
+                self.add_points(&mut xy, &g.points);
+                FgbGeomType::LineString
+            }
+            GeometryType::MultiLineString(ref g) => {
+                let (coords, line_ends) = self.add_lines(&g.lines);
+                xy = coords;
+                ends = line_ends;
+                FgbGeomType::MultiLineString
+            }
+            GeometryType::Polygon(ref g) => {
+                let (coords, ring_ends) = self.add_lines(&g.rings);
+                xy = coords;
+                ends = ring_ends;
+                FgbGeomType::Polygon
+            }
+            GeometryType::MultiPolygon(ref g) => {
+                for polygon in &g.polygons {
+                    let (xy, ends) = self.add_lines(&polygon.rings);
+                    parts.push(FgbGeometry {
+                        geom_type: FgbGeomType::Polygon,
+                        ends,
+                        xy,
+                        parts: Vec::new(),
+                    });
+                }
+                FgbGeomType::MultiPolygon
+            }
+            GeometryType::GeometryCollection(_) => unreachable!(),
+        };
+        FgbGeometry {
+            geom_type,
+            ends,
+            xy,
+            parts,
+        }
+    }
+    /// Common geometry type of all features or `Unknown`
+    fn geometry_type(&self) -> FgbGeomType {
+        let mut types = self.features.iter().map(|f| f.geometry.geom_type);
+        match types.next() {
+            Some(first) if types.all(|t| t == first) => first,
+            _ => FgbGeomType::Unknown,
+        }
+    }
+    fn header(&self, geom_type: FgbGeomType) -> Table {
+        let mut header = Table::new();
+        if !self.is_empty() {
+            header.add(1, Value::Doubles(self.envelope.to_vec()));
+        }
+        header.add(2, Value::UByte(geom_type as u8));
+        let columns = self
+            .columns
+            .iter()
+            .map(|(name, col_type)| {
+                let mut column = Table::new();
+                column.add(0, Value::String(name.clone()));
+                column.add(1, Value::UByte(*col_type as u8));
+                column
+            })
+            .collect();
+        header.add(7, Value::Tables(columns));
+        header.add(8, Value::ULong(self.features.len() as u64));
+        // No spatial index
+        header.add(9, Value::UShort(0));
+        let mut crs = Table::new();
+        crs.add(1, Value::Int(self.srid));
+        header.add(10, Value::Table(crs));
+        header
+    }
+    /// Encode FlatGeobuf file content
+    pub fn encode(&self) -> Vec<u8> {
+        let geom_type = self.geometry_type();
+        let mut data = MAGIC_BYTES.to_vec();
+        data.extend(self.header(geom_type).finish_size_prefixed());
+        for feature in &self.features {
+            let mut properties = Vec::new();
+            for (idx, value) in &feature.properties {
+                properties.extend_from_slice(&idx.to_le_bytes());
+                self.columns[*idx as usize].1.write(value, &mut properties);
+            }
+            let mut table = Table::new();
+            let geometry = feature.geometry.table(geom_type == FgbGeomType::Unknown);
+            table.add(0, Value::Table(geometry));
+            table.add(1, Value::UBytes(properties));
+            data.extend(table.finish_size_prefixed());
+        }
+        data
+    }
+}
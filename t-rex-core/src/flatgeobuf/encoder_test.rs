@@ -0,0 +1,282 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use crate::core::feature::{FeatureAttr, FeatureAttrValType, FeatureStruct};
+use crate::core::geom::{GeometryType, LineString, MultiPolygon, Point, Polygon};
+use crate::flatgeobuf::encoder::{FeatureCollection, MAGIC_BYTES};
+use std::convert::TryInto;
+use tile_grid::lonlat_to_merc;
+
+// Minimal FlatBuffers reader for checking the encoded buffers
+
+fn u32_at(buf: &[u8], pos: usize) -> usize {
+    u32::from_le_bytes(buf[pos..pos + 4].try_into().unwrap()) as usize
+}
+
+/// Size prefixed buffer starting at `pos` and its root table position
+fn root(buf: &[u8], pos: usize) -> (&[u8], usize) {
+    let size = u32_at(buf, pos);
+    let buf = &buf[pos..pos + 4 + size];
+    (buf, 4 + u32_at(buf, 4))
+}
+
+/// Position of a field in a table
+fn field(buf: &[u8], table: usize, slot: usize) -> Option<usize> {
+    let vtable = table - i32::from_le_bytes(buf[table..table + 4].try_into().unwrap()) as usize;
+    let vtable_len = u16::from_le_bytes(buf[vtable..vtable + 2].try_into().unwrap()) as usize;
+    if 4 + 2 * slot >= vtable_len {
+        return None;
+    }
+    let pos = vtable + 4 + 2 * slot;
+    match u16::from_le_bytes(buf[pos..pos + 2].try_into().unwrap()) as usize {
+        0 => None,
+        offset => Some(table + offset),
+    }
+}
+
+/// Position of a string, vector or table referenced by a field
+fn deref(buf: &[u8], table: usize, slot: usize) -> Option<usize> {
+    field(buf, table, slot).map(|pos| pos + u32_at(buf, pos))
+}
+
+fn string(buf: &[u8], pos: usize) -> &str {
+    std::str::from_utf8(&buf[pos + 4..pos + 4 + u32_at(buf, pos)]).unwrap()
+}
+
+fn doubles(buf: &[u8], pos: usize) -> Vec<f64> {
+    assert_eq!((pos + 4) % 8, 0, "vector of doubles is not aligned");
+    (0..u32_at(buf, pos))
+        .map(|i| {
+            let p = pos + 4 + 8 * i;
+            f64::from_le_bytes(buf[p..p + 8].try_into().unwrap())
+        })
+        .collect()
+}
+
+fn tables(buf: &[u8], pos: usize) -> Vec<usize> {
+    (0..u32_at(buf, pos))
+        .map(|i| {
+            let p = pos + 4 + 4 * i;
+            p + u32_at(buf, p)
+        })
+        .collect()
+}
+
+fn point_feature(x: f64, y: f64, attributes: Vec<FeatureAttr>) -> FeatureStruct {
+    FeatureStruct {
+        fid: None,
+        attributes,
+        geometry: GeometryType::Point(Point::new(x, y, None)),
+    }
+}
+
+fn attr(key: &str, value: FeatureAttrValType) -> FeatureAttr {
+    FeatureAttr {
+        key: key.to_string(),
+        value,
+    }
+}
+
+#[test]
+fn test_feature_collection() {
+    let mut collection = FeatureCollection::new(2056);
+    assert!(collection.is_empty());
+    let feature = point_feature(
+        2600000.0,
+        1200000.0,
+        vec![
+            attr("name", FeatureAttrValType::String(String::from("Bern"))),
+            attr("pop", FeatureAttrValType::Int(-5)),
+        ],
+    );
+    collection.add_feature("places", &feature);
+    let feature = point_feature(
+        2600001.0,
+        1199999.0,
+        vec![attr("pop", FeatureAttrValType::UInt(7))],
+    );
+    collection.add_feature("places", &feature);
+    assert_eq!(collection.len(), 2);
+
+    let data = collection.encode();
+    assert_eq!(&data[..8], &MAGIC_BYTES);
+    let (header, table) = root(&data, 8);
+    assert_eq!(
+        doubles(header, deref(header, table, 1).unwrap()),
+        vec![2600000.0, 1199999.0, 2600001.0, 1200000.0]
+    );
+    // Point geometry type
+    assert_eq!(header[field(header, table, 2).unwrap()], 1);
+    let columns = tables(header, deref(header, table, 7).unwrap())
+        .iter()
+        .map(|&col| {
+            let name = string(header, deref(header, col, 0).unwrap());
+            (name, header[field(header, col, 1).unwrap()])
+        })
+        .collect::<Vec<_>>();
+    // String, String, Long
+    assert_eq!(columns, vec![("layer", 11), ("name", 11), ("pop", 7)]);
+    let count = field(header, table, 8).unwrap();
+    assert_eq!(&header[count..count + 8], &[2, 0, 0, 0, 0, 0, 0, 0]);
+    let index_node_size = field(header, table, 9).unwrap();
+    assert_eq!(&header[index_node_size..index_node_size + 2], &[0, 0]);
+    let crs = deref(header, table, 10).unwrap();
+    let code = field(header, crs, 1).unwrap();
+    assert_eq!(u32_at(header, code), 2056);
+
+    let (feature, table) = root(&data, 8 + header.len());
+    let geometry = deref(feature, table, 0).unwrap();
+    assert_eq!(
+        doubles(feature, deref(feature, geometry, 1).unwrap()),
+        vec![2600000.0, 1200000.0]
+    );
+    // No geometry type in features with a common type
+    assert_eq!(field(feature, geometry, 6), None);
+    let properties = deref(feature, table, 1).unwrap();
+    let expected: Vec<u8> = vec![
+        0, 0, 6, 0, 0, 0, 112, 108, 97, 99, 101, 115, // layer "places"
+        1, 0, 4, 0, 0, 0, 66, 101, 114, 110, // name "Bern"
+        2, 0, 251, 255, 255, 255, 255, 255, 255, 255, // pop -5
+    ];
+    assert_eq!(u32_at(feature, properties), expected.len());
+    assert_eq!(
+        &feature[properties + 4..properties + 4 + expected.len()],
+        &expected[..]
+    );
+
+    let offset = 8 + header.len() + feature.len();
+    let (feature, table) = root(&data, offset);
+    assert_eq!(offset + feature.len(), data.len());
+    let properties = deref(feature, table, 1).unwrap();
+    assert_eq!(
+        &feature[properties + 4 + 12..properties + 4 + 22],
+        &[2, 0, 7, 0, 0, 0, 0, 0, 0, 0]
+    );
+}
+
+#[test]
+fn test_64bit_values() {
+    let mut collection = FeatureCollection::new(3857);
+    let values = vec![
+        ("long", FeatureAttrValType::Int(i64::max_value())),
+        ("ulong", FeatureAttrValType::UInt(u64::max_value())),
+        ("mixed", FeatureAttrValType::UInt(u64::max_value())),
+    ];
+    let feature = point_feature(
+        0.0,
+        0.0,
+        values.into_iter().map(|(k, v)| attr(k, v)).collect(),
+    );
+    collection.add_feature("big", &feature);
+    let feature = point_feature(0.0, 0.0, vec![attr("mixed", FeatureAttrValType::Int(-1))]);
+    collection.add_feature("big", &feature);
+
+    let data = collection.encode();
+    let (header, table) = root(&data, 8);
+    let columns = tables(header, deref(header, table, 7).unwrap())
+        .iter()
+        .map(|&col| header[field(header, col, 1).unwrap()])
+        .collect::<Vec<_>>();
+    // String, Long, ULong, String
+    assert_eq!(columns, vec![11, 7, 8, 11]);
+
+    let (feature, table) = root(&data, 8 + header.len());
+    let properties = deref(feature, table, 1).unwrap();
+    let props = &feature[properties + 4..properties + 4 + u32_at(feature, properties)];
+    // layer "big"
+    assert_eq!(&props[..9], &[0, 0, 3, 0, 0, 0, 98, 105, 103]);
+    assert_eq!(&props[9..11], &[1, 0]);
+    assert_eq!(
+        i64::from_le_bytes(props[11..19].try_into().unwrap()),
+        i64::max_value()
+    );
+    assert_eq!(&props[19..21], &[2, 0]);
+    assert_eq!(
+        u64::from_le_bytes(props[21..29].try_into().unwrap()),
+        u64::max_value()
+    );
+    assert_eq!(&props[29..31], &[3, 0]);
+    assert_eq!(string(props, 31), "18446744073709551615");
+}
+
+#[test]
+fn test_mixed_geometries() {
+    let mut collection = FeatureCollection::new(3857);
+    let (x, y) = lonlat_to_merc(7.44, 46.95);
+    collection.add_feature("places", &point_feature(x, y, Vec::new()));
+    let ring = |d: f64| LineString {
+        points: [(0.0, 0.0), (d, 0.0), (d, d), (0.0, 0.0)]
+            .iter()
+            .map(|&(x, y)| Point::new(x, y, None))
+            .collect(),
+        srid: None,
+    };
+    let feature = FeatureStruct {
+        fid: None,
+        attributes: vec![attr("pop", FeatureAttrValType::Double(1.5))],
+        geometry: GeometryType::MultiPolygon(MultiPolygon {
+            polygons: vec![
+                Polygon {
+                    rings: vec![ring(2.0), ring(1.0)],
+                    srid: None,
+                },
+                Polygon {
+                    rings: vec![ring(3.0)],
+                    srid: None,
+                },
+            ],
+            srid: None,
+        }),
+    };
+    collection.add_feature("areas", &feature);
+    let feature = point_feature(0.0, 0.0, vec![attr("pop", FeatureAttrValType::Bool(true))]);
+    collection.add_feature("areas", &feature);
+
+    let data = collection.encode();
+    let (header, table) = root(&data, 8);
+    // Unknown geometry type
+    assert_eq!(header[field(header, table, 2).unwrap()], 0);
+    let crs = deref(header, table, 10).unwrap();
+    assert_eq!(u32_at(header, field(header, crs, 1).unwrap()), 4326);
+    let columns = tables(header, deref(header, table, 7).unwrap());
+    // pop: Double and Bool values as String
+    assert_eq!(header[field(header, columns[1], 1).unwrap()], 11);
+
+    let (point, table) = root(&data, 8 + header.len());
+    let geometry = deref(point, table, 0).unwrap();
+    assert_eq!(point[field(point, geometry, 6).unwrap()], 1);
+    let xy = doubles(point, deref(point, geometry, 1).unwrap());
+    assert!((xy[0] - 7.44).abs() < 1e-9 && (xy[1] - 46.95).abs() < 1e-9);
+
+    let (multipolygon, table) = root(&data, 8 + header.len() + point.len());
+    let geometry = deref(multipolygon, table, 0).unwrap();
+    assert_eq!(multipolygon[field(multipolygon, geometry, 6).unwrap()], 6);
+    assert_eq!(deref(multipolygon, geometry, 1), None);
+    let parts = tables(multipolygon, deref(multipolygon, geometry, 7).unwrap());
+    assert_eq!(parts.len(), 2);
+    // Polygon parts with ring ends
+    assert_eq!(multipolygon[field(multipolygon, parts[0], 6).unwrap()], 3);
+    let ends = deref(multipolygon, parts[0], 0).unwrap();
+    assert_eq!(u32_at(multipolygon, ends), 2);
+    assert_eq!(
+        (
+            u32_at(multipolygon, ends + 4),
+            u32_at(multipolygon, ends + 8)
+        ),
+        (4, 8)
+    );
+    assert_eq!(deref(multipolygon, parts[1], 0), None);
+    assert_eq!(
+        doubles(multipolygon, deref(multipolygon, parts[1], 1).unwrap()).len(),
+        8
+    );
+    let properties = deref(multipolygon, table, 1).unwrap();
+    // layer "areas", pop "1.5"
+    assert_eq!(u32_at(multipolygon, properties), 11 + 9);
+    assert_eq!(
+        &multipolygon[properties + 4 + 11..properties + 4 + 20],
+        &[1, 0, 3, 0, 0, 0, 49, 46, 53]
+    );
+}
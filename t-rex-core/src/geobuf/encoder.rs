@@ -0,0 +1,301 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+//! Geobuf encoder (https://github.com/mapbox/geobuf)
+//!
+//! Writes a `Data` message with a `FeatureCollection` as specified in `geobuf.proto`.
+
+use crate::core::feature::{Feature, FeatureAttrValType};
+use crate::core::geom::{self, GeometryType};
+use protobuf::stream::CodedOutputStream;
+use protobuf::ProtobufResult;
+use std::collections::HashMap;
+use tile_grid::merc_to_lonlat;
+
+/// Feature member with the layer name
+pub const LAYER_KEY: &str = "layer";
+
+/// `Data.Geometry.Type` values
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum GeobufGeomType {
+    Point = 0,
+    MultiPoint = 1,
+    LineString = 2,
+    MultiLineString = 3,
+    Polygon = 4,
+    MultiPolygon = 5,
+}
+
+/// `Data.Value` of a property
+enum GeobufValue<'a> {
+    String(&'a str),
+    Double(f64),
+    PosInt(u64),
+    NegInt(u64),
+    Bool(bool),
+}
+
+impl<'a> GeobufValue<'a> {
+    fn from_attr(value: &'a FeatureAttrValType) -> GeobufValue<'a> {
+        match value {
+            FeatureAttrValType::String(ref v) => GeobufValue::String(v),
+            FeatureAttrValType::Float(v) => GeobufValue::Double(*v as f64),
+            FeatureAttrValType::Double(v) => GeobufValue::Double(*v),
+            FeatureAttrValType::UInt(v) => GeobufValue::PosInt(*v),
+            FeatureAttrValType::Int(v) | FeatureAttrValType::SInt(v) => {
+                if *v >= 0 {
+                    GeobufValue::PosInt(*v as u64)
+                } else {
+                    GeobufValue::NegInt(v.wrapping_neg() as u64)
+                }
+            }
+            FeatureAttrValType::Bool(v) => GeobufValue::Bool(*v),
+        }
+    }
+    fn write(&self, os: &mut CodedOutputStream) -> ProtobufResult<()> {
+        match *self {
+            GeobufValue::String(v) => os.write_string(1, v),
+            GeobufValue::Double(v) => os.write_double(2, v),
+            GeobufValue::PosInt(v) => os.write_uint64(3, v),
+            GeobufValue::NegInt(v) => os.write_uint64(4, v),
+            GeobufValue::Bool(v) => os.write_bool(5, v),
+        }
+    }
+}
+
+/// Encode message content into a buffer
+fn message<F>(write: F) -> Vec<u8>
+where
+    F: FnOnce(&mut CodedOutputStream) -> ProtobufResult<()>,
+{
+    let mut buf = Vec::new();
+    {
+        let mut os = CodedOutputStream::vec(&mut buf);
+        write(&mut os)
+            .and_then(|_| os.flush())
+            .expect("Writing to Vec failed");
+    }
+    buf
+}
+
+/// Geobuf FeatureCollection
+pub struct FeatureCollection {
+    keys: Vec<String>,
+    key_index: HashMap<String, u32>,
+    /// Encoded `Data.Feature` messages
+    features: Vec<Vec<u8>>,
+    /// Number of decimal places of coordinates
+    precision: u32,
+    /// Convert coordinates from Web Mercator to WGS84
+    to_wgs84: bool,
+}
+
+impl FeatureCollection {
+    /// Collection of features in WGS84 (`to_wgs84`: input in Web Mercator) or
+    /// in projected coordinates with cm precision.
+    pub fn new(to_wgs84: bool) -> FeatureCollection {
+        FeatureCollection {
+            keys: Vec::new(),
+            key_index: HashMap::new(),
+            features: Vec::new(),
+            precision: if to_wgs84 { 6 } else { 2 },
+            to_wgs84,
+        }
+    }
+    /// Collection of features in geographic coordinates (e.g. EPSG:4326 grid)
+    pub fn geographic() -> FeatureCollection {
+        FeatureCollection {
+            precision: 6,
+            ..FeatureCollection::new(false)
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+    pub fn len(&self) -> usize {
+        self.features.len()
+    }
+    fn key_idx(&mut self, key: &str) -> u32 {
+        if let Some(idx) = self.key_index.get(key) {
+            return *idx;
+        }
+        let idx = self.keys.len() as u32;
+        self.keys.push(key.to_string());
+        self.key_index.insert(key.to_string(), idx);
+        idx
+    }
+    /// Add feature with the layer name as foreign member. Features without
+    /// supported geometry are skipped.
+    pub fn add_feature(&mut self, layer: &str, feature: &dyn Feature) {
+        let geometry = match feature.geometry() {
+            Ok(GeometryType::GeometryCollection(_)) => {
+                warn!("Layer '{}': GeometryCollection not supported", layer);
+                return;
+            }
+            Ok(geom) => self.encode_geom(&geom),
+            Err(e) => {
+                warn!("Layer '{}': {}", layer, e);
+                return;
+            }
+        };
+        let attrs = feature.attributes();
+        let properties = attrs
+            .iter()
+            .map(|attr| (self.key_idx(&attr.key), GeobufValue::from_attr(&attr.value)))
+            .collect::<Vec<_>>();
+        let layer_key = self.key_idx(LAYER_KEY);
+        let fid = feature.fid();
+        let data = message(|os| {
+            os.write_bytes(1, &geometry)?;
+            match fid {
+                Some(fid) if fid <= i64::max_value() as u64 => os.write_sint64(12, fid as i64)?,
+                // int_id is signed
+                Some(fid) => os.write_string(11, &fid.to_string())?,
+                None => {}
+            }
+            // Values are indexed per property list
+            for (_, value) in &properties {
+                os.write_bytes(13, &message(|os| value.write(os)))?;
+            }
+            if !properties.is_empty() {
+                let indexes = message(|os| {
+                    for (validx, (keyidx, _)) in properties.iter().enumerate() {
+                        os.write_uint32_no_tag(*keyidx)?;
+                        os.write_uint32_no_tag(validx as u32)?;
+                    }
+                    Ok(())
+                });
+                os.write_bytes(14, &indexes)?;
+            }
+            os.write_bytes(13, &message(|os| GeobufValue::String(layer).write(os)))?;
+            os.write_bytes(
+                15,
+                &message(|os| {
+                    os.write_uint32_no_tag(layer_key)?;
+                    os.write_uint32_no_tag(0)
+                }),
+            )
+        });
+        self.features.push(data);
+    }
+    fn coord(&self, p: &geom::Point) -> (i64, i64) {
+        let (x, y) = if self.to_wgs84 {
+            merc_to_lonlat(p.x, p.y)
+        } else {
+            (p.x, p.y)
+        };
+        let e = 10f64.powi(self.precision as i32);
+        ((x * e).round() as i64, (y * e).round() as i64)
+    }
+    /// Delta encoded coordinates of a line. The closing point of rings is omitted.
+    fn add_line(&self, coords: &mut Vec<i64>, points: &[geom::Point], closed: bool) -> u32 {
+        let len = if closed && !points.is_empty() {
+            points.len() - 1
+        } else {
+            points.len()
+        };
+        let mut sum = (0, 0);
+        for p in &points[..len] {
+            let (x, y) = self.coord(p);
+            coords.push(x - sum.0);
+            coords.push(y - sum.1);
+            sum = (x, y);
+        }
+        len as u32
+    }
+    /// Encode `Data.Geometry` message
+    fn encode_geom(&self, geom: &GeometryType) -> Vec<u8> {
+        let mut lengths = Vec::new();
+        let mut coords = Vec::new();
+        let geom_type = match geom {
+            GeometryType::Point(ref p) => {
+                let (x, y) = self.coord(p);
+                coords.push(x);
+                coords.push(y);
+                GeobufGeomType::Point
+            }
+            GeometryType::MultiPoint(ref g) => {
+                self.add_line(&mut coords, &g.points, false);
+                GeobufGeomType::MultiPoint
+            }
+            GeometryType::LineString(ref g) => {
+                self.add_line(&mut coords, &g.points, false);
+                GeobufGeomType::LineString
+            }
+            GeometryType::MultiLineString(ref g) => {
+                for line in &g.lines {
+                    lengths.push(self.add_line(&mut coords, &line.points, false));
+                }
+                if g.lines.len() == 1 {
+                    lengths.clear();
+                }
+                GeobufGeomType::MultiLineString
+            }
+            GeometryType::Polygon(ref g) => {
+                for ring in &g.rings {
+                    lengths.push(self.add_line(&mut coords, &ring.points, true));
+                }
+                if g.rings.len() == 1 {
+                    lengths.clear();
+                }
+                GeobufGeomType::Polygon
+            }
+            GeometryType::MultiPolygon(ref g) => {
+                lengths.push(g.polygons.len() as u32);
+                for polygon in &g.polygons {
+                    lengths.push(polygon.rings.len() as u32);
+                    for ring in &polygon.rings {
+                        lengths.push(self.add_line(&mut coords, &ring.points, true));
+                    }
+                }
+                if g.polygons.len() == 1 && g.polygons[0].rings.len() == 1 {
+                    lengths.clear();
+                }
+                GeobufGeomType::MultiPolygon
+            }
+            GeometryType::GeometryCollection(_) => unreachable!(),
+        };
+        message(|os| {
+            os.write_enum(1, geom_type as i32)?;
+            if !lengths.is_empty() {
+                let packed = message(|os| {
+                    for len in &lengths {
+                        os.write_uint32_no_tag(*len)?;
+                    }
+                    Ok(())
+                });
+                os.write_bytes(2, &packed)?;
+            }
+            if !coords.is_empty() {
+                let packed = message(|os| {
+                    for c in &coords {
+                        os.write_sint64_no_tag(*c)?;
+                    }
+                    Ok(())
+                });
+                os.write_bytes(3, &packed)?;
+            }
+            Ok(())
+        })
+    }
+    /// Encode `Data` message
+    pub fn encode(&self) -> Vec<u8> {
+        message(|os| {
+            for key in &self.keys {
+                os.write_string(1, key)?;
+            }
+            if self.precision != 6 {
+                os.write_uint32(3, self.precision)?;
+            }
+            let collection = message(|os| {
+                for feature in &self.features {
+                    os.write_bytes(1, feature)?;
+                }
+                Ok(())
+            });
+            os.write_bytes(4, &collection)
+        })
+    }
+}
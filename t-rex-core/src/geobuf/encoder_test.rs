@@ -0,0 +1,140 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use crate::core::feature::{FeatureAttr, FeatureAttrValType, FeatureStruct};
+use crate::core::geom::{GeometryType, LineString, Point, Polygon};
+use crate::geobuf::encoder::FeatureCollection;
+use tile_grid::lonlat_to_merc;
+
+#[test]
+fn test_feature_collection() {
+    let mut collection = FeatureCollection::new(false);
+    assert!(collection.is_empty());
+    let feature = FeatureStruct {
+        fid: Some(1),
+        attributes: vec![
+            FeatureAttr {
+                key: String::from("name"),
+                value: FeatureAttrValType::String(String::from("Bern")),
+            },
+            FeatureAttr {
+                key: String::from("pop"),
+                value: FeatureAttrValType::Int(-5),
+            },
+        ],
+        geometry: GeometryType::Point(Point::new(7.44, 46.95, None)),
+    };
+    collection.add_feature("places", &feature);
+    let ring = LineString {
+        points: [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 0.0)]
+            .iter()
+            .map(|&(x, y)| Point::new(x, y, None))
+            .collect(),
+        srid: None,
+    };
+    let feature = FeatureStruct {
+        fid: None,
+        attributes: Vec::new(),
+        geometry: GeometryType::Polygon(Polygon {
+            rings: vec![ring],
+            srid: None,
+        }),
+    };
+    collection.add_feature("places", &feature);
+    assert_eq!(collection.len(), 2);
+
+    // Reference encoding following geobuf.proto
+    let expected: Vec<u8> = vec![
+        10, 4, 110, 97, 109, 101, // key "name"
+        10, 3, 112, 111, 112, // key "pop"
+        10, 5, 108, 97, 121, 101, 114, // key "layer"
+        24, 2, // precision
+        34, 76, // feature collection
+        10, 44, // feature
+        10, 8, 8, 0, 26, 4, 208, 11, 174, 73, // Point(744, 4695)
+        96, 2, // int_id
+        106, 6, 10, 4, 66, 101, 114, 110, // "Bern"
+        106, 2, 32, 5, // -5
+        114, 4, 0, 0, 1, 1, // properties
+        106, 8, 10, 6, 112, 108, 97, 99, 101, 115, // "places"
+        122, 2, 2, 0, // custom properties
+        10, 28, // feature
+        10, 12, 8, 4, 26, 8, 0, 0, 200, 1, 0, 0, 200, 1, // Polygon without closing point
+        106, 8, 10, 6, 112, 108, 97, 99, 101, 115, // "places"
+        122, 2, 2, 0, // custom properties
+    ];
+    assert_eq!(collection.encode(), expected);
+}
+
+#[test]
+fn test_64bit_values() {
+    let mut collection = FeatureCollection::new(false);
+    let feature = FeatureStruct {
+        fid: Some(u64::max_value()),
+        attributes: vec![
+            FeatureAttr {
+                key: String::from("min"),
+                value: FeatureAttrValType::Int(i64::min_value()),
+            },
+            FeatureAttr {
+                key: String::from("max"),
+                value: FeatureAttrValType::UInt(u64::max_value()),
+            },
+        ],
+        geometry: GeometryType::Point(Point::new(0.0, 0.0, None)),
+    };
+    collection.add_feature("big", &feature);
+    let data = collection.encode();
+    let expected: Vec<u8> = vec![
+        10, 73, // feature
+        10, 6, 8, 0, 26, 2, 0, 0, // Point(0, 0)
+        90, 20, 49, 56, 52, 52, 54, 55, 52, 52, 48, 55, 51, 55, 48, 57, 53, 53, 49, 54, 49,
+        53, // id "18446744073709551615"
+        106, 11, 32, 128, 128, 128, 128, 128, 128, 128, 128, 128, 1, // i64::MIN
+        106, 11, 24, 255, 255, 255, 255, 255, 255, 255, 255, 255, 1, // u64::MAX
+        114, 4, 0, 0, 1, 1, // properties
+        106, 5, 10, 3, 98, 105, 103, // "big"
+        122, 2, 2, 0, // custom properties
+    ];
+    assert_eq!(&data[data.len() - expected.len()..], &expected[..]);
+}
+
+#[test]
+fn test_wgs84_coords() {
+    let mut collection = FeatureCollection::new(true);
+    let (x, y) = lonlat_to_merc(7.44, 46.95);
+    let feature = FeatureStruct {
+        fid: None,
+        attributes: Vec::new(),
+        geometry: GeometryType::Point(Point::new(x, y, Some(3857))),
+    };
+    collection.add_feature("places", &feature);
+    let data = collection.encode();
+    // No precision field for the default of 6 decimal places
+    assert_eq!(&data[..9], &[10, 5, 108, 97, 121, 101, 114, 34, 30]);
+    // Point(7440000, 46950000) as zigzag varints
+    assert_eq!(
+        &data[13..25],
+        &[8, 0, 26, 8, 128, 154, 140, 7, 224, 153, 227, 44]
+    );
+}
+
+#[test]
+fn test_geographic_coords() {
+    let mut collection = FeatureCollection::geographic();
+    let feature = FeatureStruct {
+        fid: None,
+        attributes: Vec::new(),
+        geometry: GeometryType::Point(Point::new(7.44, 46.95, Some(4326))),
+    };
+    collection.add_feature("places", &feature);
+    let data = collection.encode();
+    // Same encoding as converted Web Mercator coordinates
+    assert_eq!(&data[..9], &[10, 5, 108, 97, 121, 101, 114, 34, 30]);
+    assert_eq!(
+        &data[13..25],
+        &[8, 0, 26, 8, 128, 154, 140, 7, 224, 153, 227, 44]
+    );
+}
@@ -10,8 +10,12 @@ extern crate serde_derive;
 #[macro_use]
 extern crate serde_json;
 
+#[cfg(feature = "server")]
 pub mod cache;
 pub mod core;
+#[cfg(feature = "server")]
 pub mod datasource;
+pub mod flatgeobuf;
+pub mod geobuf;
 pub mod mvt;
 pub mod service;
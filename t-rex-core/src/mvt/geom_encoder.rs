@@ -23,11 +23,9 @@ impl CommandInteger {
     fn new(id: Command, count: u32) -> CommandInteger {
         CommandInteger(((id as u32) & 0x7) | (count << 3))
     }
-    #[cfg(test)]
     fn id(&self) -> u32 {
         self.0 & 0x7
     }
-    #[cfg(test)]
     fn count(&self) -> u32 {
         self.0 >> 3
     }
@@ -51,7 +49,6 @@ impl ParameterInteger {
     fn new(value: i32) -> ParameterInteger {
         ParameterInteger(((value << 1) ^ (value >> 31)) as u32)
     }
-    #[cfg(test)]
     fn value(&self) -> i32 {
         ((self.0 >> 1) as i32) ^ (-((self.0 & 1) as i32))
     }
@@ -95,6 +92,116 @@ fn test_sequence() {
     assert_eq!(seq.0, &[9, 50, 34, 9]);
 }
 
+/// Limit of transformed coordinates, keeping the differences between coordinates in i32 range
+const MAX_TRANSFORMED_COORD: i64 = 1 << 30;
+
+/// Transform encoded geometry coordinates with `x * factor - offset.0` and `y * factor - offset.1`.
+/// Returns the transformed command sequence and its bounding box (minx, miny, maxx, maxy),
+/// or None if transformed coordinates exceed the range of tile coordinates.
+pub fn transform_geometry(
+    geometry: &[u32],
+    factor: i64,
+    offset: (i64, i64),
+) -> Option<(CommandSequence, (i64, i64, i64, i64))> {
+    let mut seq = CommandSequence::new();
+    let mut bbox = (i64::MAX, i64::MAX, i64::MIN, i64::MIN);
+    let (mut x, mut y) = (0, 0); // cursor in source geometry
+    let (mut tx, mut ty) = (0, 0); // cursor in transformed geometry
+    let mut i = 0;
+    while i < geometry.len() {
+        let cmd = CommandInteger(geometry[i]);
+        seq.push(cmd.0);
+        i += 1;
+        if cmd.id() == Command::ClosePath as u32 {
+            continue;
+        }
+        for _ in 0..cmd.count() {
+            if i + 1 >= geometry.len() {
+                break;
+            }
+            x += i64::from(ParameterInteger(geometry[i]).value());
+            y += i64::from(ParameterInteger(geometry[i + 1]).value());
+            i += 2;
+            let (nx, ny) = (x * factor - offset.0, y * factor - offset.1);
+            if nx.abs() >= MAX_TRANSFORMED_COORD || ny.abs() >= MAX_TRANSFORMED_COORD {
+                return None;
+            }
+            seq.push(ParameterInteger::new((nx - tx) as i32).0);
+            seq.push(ParameterInteger::new((ny - ty) as i32).0);
+            tx = nx;
+            ty = ny;
+            bbox = (bbox.0.min(nx), bbox.1.min(ny), bbox.2.max(nx), bbox.3.max(ny));
+        }
+    }
+    Some((seq, bbox))
+}
+
+#[test]
+fn test_transform_geometry() {
+    // LineString (2,2) (10,4)
+    let geom = [9, 4, 4, 10, 16, 4];
+    let (seq, bbox) = transform_geometry(&geom, 2, (8, 0)).unwrap();
+    // (-4,4) (12,8)
+    assert_eq!(seq.0, &[9, 7, 8, 10, 32, 8]);
+    assert_eq!(bbox, (-4, 4, 12, 8));
+    let (seq, _) = transform_geometry(&geom, 1, (0, 0)).unwrap();
+    assert_eq!(seq.0, &geom);
+    // (2,2) * 2^16 + 2^30 is out of range
+    assert!(transform_geometry(&geom, 1 << 16, (0, -(1 << 30))).is_none());
+}
+
+/// Decode geometry commands into paths of tile coordinates.
+/// Every MoveTo point starts a new path, ClosePath repeats the first point of the path.
+pub fn decode_geometry(geometry: &[u32]) -> Vec<Vec<(i32, i32)>> {
+    let mut paths: Vec<Vec<(i32, i32)>> = Vec::new();
+    let (mut x, mut y) = (0, 0);
+    let mut i = 0;
+    while i < geometry.len() {
+        let cmd = CommandInteger(geometry[i]);
+        i += 1;
+        if cmd.id() == Command::ClosePath as u32 {
+            if let Some(path) = paths.last_mut() {
+                if let Some(&first) = path.first() {
+                    path.push(first);
+                }
+            }
+            continue;
+        }
+        for _ in 0..cmd.count() {
+            if i + 1 >= geometry.len() {
+                break;
+            }
+            x += ParameterInteger(geometry[i]).value();
+            y += ParameterInteger(geometry[i + 1]).value();
+            i += 2;
+            if cmd.id() == Command::MoveTo as u32 || paths.is_empty() {
+                paths.push(Vec::new());
+            }
+            paths.last_mut().unwrap().push((x, y));
+        }
+    }
+    paths
+}
+
+#[test]
+fn test_decode_geometry() {
+    // MultiPoint (5,7) (3,2)
+    assert_eq!(
+        decode_geometry(&[17, 10, 14, 3, 9]),
+        vec![vec![(5, 7)], vec![(3, 2)]]
+    );
+    // LineString (2,2) (2,10) (10,10)
+    assert_eq!(
+        decode_geometry(&[9, 4, 4, 18, 0, 16, 16, 0]),
+        vec![vec![(2, 2), (2, 10), (10, 10)]]
+    );
+    // Polygon (3,6) (8,12) (20,34) (3,6)
+    assert_eq!(
+        decode_geometry(&[9, 6, 12, 18, 10, 12, 24, 44, 15]),
+        vec![vec![(3, 6), (8, 12), (20, 34), (3, 6)]]
+    );
+}
+
 pub trait EncodableGeom {
     fn encode(&self) -> CommandSequence {
         let mut seq = CommandSequence::new();
@@ -3,27 +3,37 @@
 // Licensed under the MIT License. See LICENSE file in the project root for full license information.
 //
 
-use crate::core::feature::{Feature, FeatureAttrValType};
+use crate::core::feature::{Feature, FeatureAttrValType, FeatureStruct};
 use crate::core::geom;
 use crate::core::geom::GeometryType;
 use crate::core::layer::Layer;
 use crate::core::screen;
-use crate::mvt::geom_encoder::{CommandSequence, EncodableGeom};
+use crate::mvt::geom_encoder::{
+    decode_geometry, transform_geometry, CommandSequence, EncodableGeom,
+};
 use crate::mvt::vector_tile;
 use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
-use flate2::Compression;
+use flate2::{Compression, GzBuilder};
 use protobuf::error::ProtobufError;
 use protobuf::stream::CodedOutputStream;
-use protobuf::{parse_from_reader, Message};
+use protobuf::{parse_from_reader, Message, RepeatedField};
 use std::fs::File;
 use std::io::{BufReader, Read, Write};
 use tile_grid::Extent;
 
+/// Maximum number of zoom levels between a parent tile and tiles extracted with `overzoom`
+pub const MAX_OVERZOOM: u8 = 16;
+
+/// Subfield ID of the feature count in the extra field of the gzip header (RFC 1952)
+const FEATURE_COUNT_SUBFIELD: &[u8; 2] = b"FC";
+
 pub struct Tile<'a> {
     pub mvt_tile: vector_tile::Tile,
     extent: &'a Extent,
     reverse_y: bool,
+    /// `snap_to_grid` of the layer created last
+    snap_to_grid: Option<f64>,
 }
 
 impl GeometryType {
@@ -50,9 +60,11 @@ impl ScreenGeom<geom::Point> for screen::Point {
     fn from_geom(extent: &Extent, reverse_y: bool, tile_size: u32, point: &geom::Point) -> Self {
         let x_span = extent.maxx - extent.minx;
         let y_span = extent.maxy - extent.miny;
+        // Rounding down (instead of towards zero) keeps coordinates in the tile buffer
+        // consistent with the same coordinates in the adjacent tile
         let mut screen_geom = screen::Point {
-            x: ((point.x - extent.minx) * tile_size as f64 / x_span) as i32,
-            y: ((point.y - extent.miny) * tile_size as f64 / y_span) as i32,
+            x: ((point.x - extent.minx) * tile_size as f64 / x_span).floor() as i32,
+            y: ((point.y - extent.miny) * tile_size as f64 / y_span).floor() as i32,
         };
         if reverse_y {
             screen_geom.y = (tile_size as i32).saturating_sub(screen_geom.y)
@@ -158,10 +170,13 @@ impl<'a> Tile<'a> {
             mvt_tile: mvt_tile,
             extent: extent,
             reverse_y: reverse_y,
+            snap_to_grid: None,
         }
     }
 
+    /// New MVT layer. Features added afterwards are snapped to the grid of `layer`.
     pub fn new_layer(&mut self, layer: &Layer) -> vector_tile::Tile_Layer {
+        self.snap_to_grid = layer.snap_to_grid;
         let mut mvt_layer = vector_tile::Tile_Layer::new();
         mvt_layer.set_version(2);
         mvt_layer.set_name(layer.name.clone());
@@ -260,7 +275,10 @@ impl<'a> Tile<'a> {
                 mvt_value,
             );
         }
-        if let Ok(geom) = feature.geometry() {
+        if let Ok(mut geom) = feature.geometry() {
+            if let Some(size) = self.snap_to_grid {
+                geom = geom.snapped(size);
+            }
             let g_type = geom.mvt_field_type();
             let enc_geom = self.encode_geom(geom, mvt_layer.get_extent()).vec();
             if !enc_geom.is_empty() {
@@ -271,6 +289,53 @@ impl<'a> Tile<'a> {
         }
     }
 
+    /// Order features by id, geometry and attributes and rebuild the key and value tables
+    /// in order of use, making the encoded layer independent of the order features were added.
+    pub fn sort_features(mvt_layer: &mut vector_tile::Tile_Layer) {
+        let keys = mvt_layer.take_keys().into_vec();
+        let values = mvt_layer.take_values().into_vec();
+        let encoded_values: Vec<Vec<u8>> = values
+            .iter()
+            .map(|v| v.write_to_bytes().unwrap_or_default())
+            .collect();
+        let mut features: Vec<_> = mvt_layer
+            .take_features()
+            .into_iter()
+            .map(|feature| {
+                let tags: Vec<(usize, usize)> = feature
+                    .get_tags()
+                    .chunks_exact(2)
+                    .map(|kv| (kv[0] as usize, kv[1] as usize))
+                    .filter(|&(k, v)| k < keys.len() && v < values.len())
+                    .collect();
+                let attrs: Vec<(&String, &Vec<u8>)> = tags
+                    .iter()
+                    .map(|&(k, v)| (&keys[k], &encoded_values[v]))
+                    .collect();
+                let sort_key = (
+                    feature.get_id(),
+                    feature.get_geometry().to_vec(),
+                    feature.get_field_type() as i32,
+                    attrs,
+                );
+                (sort_key, tags, feature)
+            })
+            .collect();
+        features.sort_by(|a, b| a.0.cmp(&b.0));
+        for (_, tags, mut feature) in features {
+            feature.clear_tags();
+            for (k, v) in tags {
+                Tile::add_feature_attribute(
+                    mvt_layer,
+                    &mut feature,
+                    keys[k].clone(),
+                    values[v].clone(),
+                );
+            }
+            mvt_layer.mut_features().push(feature);
+        }
+    }
+
     pub fn add_layer(&mut self, mvt_layer: vector_tile::Tile_Layer) {
         self.mvt_tile.mut_layers().push(mvt_layer);
     }
@@ -281,8 +346,11 @@ impl<'a> Tile<'a> {
         os.flush().unwrap();
     }
 
+    /// Write gzip compressed tile with its feature count in the gzip header
     pub fn write_gz_to(out: &mut dyn Write, mvt_tile: &vector_tile::Tile) {
-        let mut gz = GzEncoder::new(out, Compression::default());
+        let mut gz = GzBuilder::new()
+            .extra(Self::feature_count_extra(Self::feature_count(mvt_tile)))
+            .write(out, Compression::default());
         {
             let mut os = CodedOutputStream::new(&mut gz);
             let _ = mvt_tile.write_to(&mut os);
@@ -314,15 +382,126 @@ impl<'a> Tile<'a> {
         v
     }
 
-    pub fn tile_content(tilegz: Vec<u8>, gzip: bool) -> Vec<u8> {
-        if gzip {
-            tilegz
-        } else {
-            let mut gz = GzDecoder::new(&tilegz[..]);
-            let mut unc_tile = Vec::with_capacity(tilegz.len());
-            let _ = gz.read_to_end(&mut unc_tile);
-            unc_tile
+    /// Gzip compressed encoded tile
+    pub fn compress(tiledata: &[u8]) -> Vec<u8> {
+        let mut gz = GzEncoder::new(Vec::with_capacity(tiledata.len()), Compression::default());
+        let _ = gz.write_all(tiledata);
+        gz.finish().unwrap_or_default()
+    }
+
+    /// Gzip compressed encoded tile with `feature_count` in the gzip header
+    pub fn compress_with_feature_count(tiledata: &[u8], feature_count: usize) -> Vec<u8> {
+        let mut gz = GzBuilder::new()
+            .extra(Self::feature_count_extra(feature_count))
+            .write(Vec::with_capacity(tiledata.len()), Compression::default());
+        let _ = gz.write_all(tiledata);
+        gz.finish().unwrap_or_default()
+    }
+
+    fn feature_count_extra(feature_count: usize) -> Vec<u8> {
+        let mut extra = FEATURE_COUNT_SUBFIELD.to_vec();
+        extra.extend_from_slice(&4u16.to_le_bytes());
+        extra.extend_from_slice(&(feature_count as u32).to_le_bytes());
+        extra
+    }
+
+    /// Number of features in all layers
+    pub fn feature_count(mvt_tile: &vector_tile::Tile) -> usize {
+        mvt_tile
+            .get_layers()
+            .iter()
+            .map(|layer| layer.get_features().len())
+            .sum()
+    }
+
+    /// Feature count recorded in the gzip header of compressed tile data,
+    /// read without decompressing the tile. None for tiles written by other tools.
+    pub fn gz_feature_count(data: &[u8]) -> Option<usize> {
+        // ID1 ID2 CM FLG MTIME(4) XFL OS XLEN(2), FLG.FEXTRA = 4
+        if !Tile::is_gzip(data) || data.len() < 12 || data[3] & 4 == 0 {
+            return None;
+        }
+        let xlen = u16::from_le_bytes([data[10], data[11]]) as usize;
+        let mut extra = data.get(12..12 + xlen)?;
+        while extra.len() >= 4 {
+            let len = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+            let value = extra.get(4..4 + len)?;
+            if &extra[..2] == FEATURE_COUNT_SUBFIELD && len == 4 {
+                let count = u32::from_le_bytes([value[0], value[1], value[2], value[3]]);
+                return Some(count as usize);
+            }
+            extra = &extra[4 + len..];
+        }
+        None
+    }
+
+    /// Check for gzip magic bytes
+    pub fn is_gzip(data: &[u8]) -> bool {
+        data.starts_with(&[0x1f, 0x8b])
+    }
+
+    /// Tile data gzip compressed or uncompressed as requested with `gzip`.
+    /// The compression of `data` is detected, e.g. for caches written by other tools.
+    pub fn tile_content(data: Vec<u8>, gzip: bool) -> Vec<u8> {
+        match (Tile::is_gzip(&data), gzip) {
+            (true, true) | (false, false) => data,
+            (false, true) => Tile::compress(&data),
+            (true, false) => {
+                let mut gz = GzDecoder::new(&data[..]);
+                let mut unc_tile = Vec::with_capacity(data.len());
+                let _ = gz.read_to_end(&mut unc_tile);
+                unc_tile
+            }
+        }
+    }
+
+    /// Extract child tile `dz` levels below a parent tile by scaling its geometries.
+    /// `dx` and `dy` are the child offsets within the parent, counted from the top left.
+    /// Features outside the child tile including a buffer are dropped, others are only clipped
+    /// when their coordinates exceed the range of tile coordinates.
+    /// Returns an empty tile for more than `MAX_OVERZOOM` levels.
+    pub fn overzoom(parent: &vector_tile::Tile, dz: u8, dx: u32, dy: u32) -> vector_tile::Tile {
+        let mut mvt_tile = vector_tile::Tile::new();
+        if dz > MAX_OVERZOOM {
+            return mvt_tile;
+        }
+        let factor = 1i64 << dz;
+        for layer in parent.get_layers() {
+            let extent = i64::from(layer.get_extent());
+            let buffer = extent / 8;
+            let offset = (i64::from(dx) * extent, i64::from(dy) * extent);
+            let mut mvt_layer = vector_tile::Tile_Layer::new();
+            mvt_layer.set_version(layer.get_version());
+            mvt_layer.set_name(layer.get_name().to_string());
+            mvt_layer.set_extent(layer.get_extent());
+            mvt_layer.set_keys(RepeatedField::from_vec(layer.get_keys().to_vec()));
+            mvt_layer.set_values(RepeatedField::from_vec(layer.get_values().to_vec()));
+            for feature in layer.get_features() {
+                let geometry = match transform_geometry(feature.get_geometry(), factor, offset) {
+                    Some((geometry, bbox)) => {
+                        if bbox.2 < -buffer
+                            || bbox.3 < -buffer
+                            || bbox.0 > extent + buffer
+                            || bbox.1 > extent + buffer
+                        {
+                            continue;
+                        }
+                        geometry
+                    }
+                    None => match clip_transformed(feature, factor, offset, extent, buffer) {
+                        Some(geometry) => geometry,
+                        None => continue,
+                    },
+                };
+                let mut mvt_feature = feature.clone();
+                mvt_feature.set_geometry(geometry.0);
+                mvt_layer.mut_features().push(mvt_feature);
+            }
+            if !mvt_layer.get_features().is_empty() {
+                mvt_tile.mut_layers().push(mvt_layer);
+            }
         }
+        mvt_tile
     }
 
     pub fn to_file(&self, fname: &str) {
@@ -333,4 +512,120 @@ impl<'a> Tile<'a> {
     pub fn size(mvt_tile: &vector_tile::Tile) -> u32 {
         mvt_tile.compute_size()
     }
+
+    /// Encoded size of a single layer (without the enclosing tile field header)
+    pub fn layer_size(mvt_layer: &vector_tile::Tile_Layer) -> u32 {
+        mvt_layer.compute_size()
+    }
+
+    /// Encoded size of a single feature
+    pub fn feature_size(mvt_feature: &vector_tile::Tile_Feature) -> u32 {
+        mvt_feature.compute_size()
+    }
+}
+
+/// Transform the geometry of an MVT feature like `transform_geometry` and clip it to the tile
+/// `extent` including `buffer`. Polygon rings are clipped separately, keeping their orientation.
+fn clip_transformed(
+    feature: &vector_tile::Tile_Feature,
+    factor: i64,
+    offset: (i64, i64),
+    extent: i64,
+    buffer: i64,
+) -> Option<CommandSequence> {
+    let paths: Vec<geom::LineString> = decode_geometry(feature.get_geometry())
+        .iter()
+        .map(|path| geom::LineString {
+            points: path
+                .iter()
+                .map(|&(x, y)| {
+                    geom::Point::new(
+                        (i64::from(x) * factor - offset.0) as f64,
+                        (i64::from(y) * factor - offset.1) as f64,
+                        None,
+                    )
+                })
+                .collect(),
+            srid: None,
+        })
+        .collect();
+    let geometry = match feature.get_field_type() {
+        vector_tile::Tile_GeomType::POINT => GeometryType::MultiPoint(geom::MultiPoint {
+            points: paths.into_iter().flat_map(|path| path.points).collect(),
+            srid: None,
+        }),
+        vector_tile::Tile_GeomType::LINESTRING => {
+            GeometryType::MultiLineString(geom::MultiLineString {
+                lines: paths,
+                srid: None,
+            })
+        }
+        vector_tile::Tile_GeomType::POLYGON => GeometryType::MultiPolygon(geom::MultiPolygon {
+            polygons: paths
+                .into_iter()
+                .map(|ring| geom::Polygon {
+                    rings: vec![ring],
+                    srid: None,
+                })
+                .collect(),
+            srid: None,
+        }),
+        vector_tile::Tile_GeomType::UNKNOWN => return None,
+    };
+    let tile_extent = Extent {
+        minx: 0.0,
+        miny: 0.0,
+        maxx: extent as f64,
+        maxy: extent as f64,
+    };
+    let buffered = Extent {
+        minx: -buffer as f64,
+        miny: -buffer as f64,
+        maxx: (extent + buffer) as f64,
+        maxy: (extent + buffer) as f64,
+    };
+    let clipped = geometry.clipped(&buffered)?;
+    Some(Tile::new(&tile_extent, false).encode_geom(clipped, extent as u32))
+}
+
+/// Features of a tile layer
+pub struct LayerFeatures {
+    pub name: String,
+    pub features: Vec<FeatureStruct>,
+}
+
+/// Encode layers into an uncompressed MVT tile covering `extent` (y axis pointing up).
+/// Features outside the tile including a `buffer` (in pixels of a 256 pixel tile) are dropped,
+/// others are not clipped. Empty layers are omitted.
+pub fn encode_tile(layers: Vec<LayerFeatures>, extent: &Extent, buffer: u32) -> Vec<u8> {
+    let buf = f64::from(buffer) * (extent.maxx - extent.minx) / 256.0;
+    let buffered = Extent {
+        minx: extent.minx - buf,
+        miny: extent.miny - buf,
+        maxx: extent.maxx + buf,
+        maxy: extent.maxy + buf,
+    };
+    let mut tile = Tile::new(extent, true);
+    for layer_features in layers {
+        let layer = Layer::new(&layer_features.name);
+        let mut mvt_layer = tile.new_layer(&layer);
+        for feature in &layer_features.features {
+            let inside = match feature.geometry.bbox() {
+                Some(bbox) => {
+                    bbox.maxx >= buffered.minx
+                        && bbox.maxy >= buffered.miny
+                        && bbox.minx <= buffered.maxx
+                        && bbox.miny <= buffered.maxy
+                }
+                None => false,
+            };
+            if inside {
+                tile.add_feature(&mut mvt_layer, feature);
+            }
+        }
+        if !mvt_layer.get_features().is_empty() {
+            tile.add_layer(mvt_layer);
+        }
+    }
+    Tile::tile_bytevec(&tile.mvt_tile)
 }
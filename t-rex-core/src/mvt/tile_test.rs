@@ -10,10 +10,10 @@ use crate::core::geom::GeometryType;
 use crate::core::layer::Layer;
 use crate::core::screen;
 use crate::mvt::geom_encoder::EncodableGeom;
-use crate::mvt::tile::{ScreenGeom, Tile};
+use crate::mvt::tile::{encode_tile, LayerFeatures, ScreenGeom, Tile};
 use crate::mvt::vector_tile;
 use std::fs::File;
-use tile_grid::Extent;
+use tile_grid::{Extent, Grid};
 
 #[test]
 fn test_point_to_screen_coords() {
@@ -53,6 +53,34 @@ fn test_point_to_screen_coords() {
     );
 }
 
+#[test]
+fn test_adjacent_tiles_screen_coords() {
+    // Line crossing the border of two adjacent tiles with vertices in the tile buffer
+    let grid = Grid::web_mercator();
+    let left = grid.tile_extent(0, 0, 1);
+    let right = grid.tile_extent(1, 0, 1);
+    let line = geom::LineString {
+        points: vec![
+            geom::Point::new(-200000.0, -1000000.0, Some(3857)),
+            geom::Point::new(-1500.3, -1000500.0, Some(3857)),
+            geom::Point::new(1200.7, -1000700.0, Some(3857)),
+            geom::Point::new(250000.0, -1001000.0, Some(3857)),
+        ],
+        srid: Some(3857),
+    };
+    let left_line = screen::LineString::from_geom(&left, true, 4096, &line);
+    let right_line = screen::LineString::from_geom(&right, true, 4096, &line);
+    assert_eq!(left_line.points.len(), 4);
+    assert_eq!(right_line.points.len(), 4);
+    assert_eq!(left_line.points[1], screen::Point { x: 4095, y: 205 });
+    assert_eq!(right_line.points[1], screen::Point { x: -1, y: 205 });
+    // Geometry is continuous across the shared edge
+    for (l, r) in left_line.points.iter().zip(right_line.points.iter()) {
+        assert_eq!(l.x - r.x, 4096);
+        assert_eq!(l.y, r.y);
+    }
+}
+
 #[test]
 fn test_tile_values() {
     let mut value = vector_tile::Tile_Value::new();
@@ -376,3 +404,295 @@ fn test_build_mvt_with_helpers() {
     path.push("out.pbf");
     tile.to_file(&format!("{}", &path.display()));
 }
+
+#[test]
+fn test_64bit_values() {
+    let extent = Extent {
+        minx: 0.0,
+        miny: 0.0,
+        maxx: 256000.0,
+        maxy: 256000.0,
+    };
+    let attr = |key: &str, value| FeatureAttr {
+        key: key.to_string(),
+        value,
+    };
+    let feature = FeatureStruct {
+        fid: Some(u64::max_value()),
+        attributes: vec![
+            attr("max", FeatureAttrValType::Int(i64::max_value())),
+            attr("min", FeatureAttrValType::Int(i64::min_value())),
+            attr("umax", FeatureAttrValType::UInt(u64::max_value())),
+            attr("smin", FeatureAttrValType::SInt(i64::min_value() + 1)),
+            // Above 2^53, not representable as double
+            attr("odd", FeatureAttrValType::Int(9007199254740993)),
+        ],
+        geometry: GeometryType::Point(geom::Point::new(1000.0, 1000.0, Some(3857))),
+    };
+    let layers = vec![LayerFeatures {
+        name: "big".to_string(),
+        features: vec![feature],
+    }];
+    let tiledata = encode_tile(layers, &extent, 8);
+    let mvt_tile = Tile::read_from(&mut &tiledata[..]).unwrap();
+    let layer = &mvt_tile.get_layers()[0];
+    assert_eq!(layer.get_features()[0].get_id(), u64::max_value());
+    let values = layer.get_values();
+    assert_eq!(values[0].get_int_value(), i64::max_value());
+    assert_eq!(values[1].get_int_value(), i64::min_value());
+    assert_eq!(values[2].get_uint_value(), u64::max_value());
+    assert_eq!(values[3].get_sint_value(), i64::min_value() + 1);
+    assert_eq!(values[4].get_int_value(), 9007199254740993);
+    assert!(values.iter().all(|v| !v.has_double_value()));
+}
+
+#[test]
+fn test_empty_tile() {
+    let mvt_tile = vector_tile::Tile::new();
+    let tilegz = Tile::tile_bytevec_gz(&mvt_tile);
+    assert!(tilegz.len() > 0);
+    assert_eq!(Tile::tile_content(tilegz.clone(), false).len(), 0);
+    let tile = Tile::read_gz_from(&mut &tilegz[..]).unwrap();
+    assert_eq!(tile.get_layers().len(), 0);
+}
+
+#[test]
+fn test_gz_feature_count() {
+    let mut mvt_layer = vector_tile::Tile_Layer::new();
+    mvt_layer.set_version(2);
+    mvt_layer.set_name("points".to_string());
+    for _ in 0..3 {
+        mvt_layer
+            .mut_features()
+            .push(vector_tile::Tile_Feature::new());
+    }
+    let mut mvt_tile = vector_tile::Tile::new();
+    mvt_tile.mut_layers().push(mvt_layer);
+    assert_eq!(Tile::feature_count(&mvt_tile), 3);
+
+    let tilegz = Tile::tile_bytevec_gz(&mvt_tile);
+    assert_eq!(Tile::gz_feature_count(&tilegz), Some(3));
+    let tiledata = Tile::tile_bytevec(&mvt_tile);
+    assert_eq!(Tile::tile_content(tilegz, false), tiledata);
+    let tilegz = Tile::compress_with_feature_count(&tiledata, 70000);
+    assert_eq!(Tile::gz_feature_count(&tilegz), Some(70000));
+    assert_eq!(
+        Tile::read_gz_from(&mut &tilegz[..]).unwrap().get_layers()[0]
+            .get_features()
+            .len(),
+        3
+    );
+    // Tiles without recorded count
+    assert_eq!(Tile::gz_feature_count(&Tile::compress(&tiledata)), None);
+    assert_eq!(Tile::gz_feature_count(&tiledata), None);
+    assert_eq!(Tile::gz_feature_count(&tilegz[..14]), None);
+}
+
+#[test]
+fn test_overzoom() {
+    let mut mvt_layer = vector_tile::Tile_Layer::new();
+    mvt_layer.set_version(2);
+    mvt_layer.set_name("points".to_string());
+    mvt_layer.set_extent(4096);
+    for geometry in &[vec![9, 200, 400], vec![9, 6000, 6000]] {
+        let mut mvt_feature = vector_tile::Tile_Feature::new();
+        mvt_feature.set_field_type(vector_tile::Tile_GeomType::POINT);
+        mvt_feature.set_geometry(geometry.clone());
+        mvt_layer.mut_features().push(mvt_feature);
+    }
+    let mut parent = vector_tile::Tile::new();
+    parent.mut_layers().push(mvt_layer);
+
+    // Top left child: (100,200) -> (200,400)
+    let child = Tile::overzoom(&parent, 1, 0, 0);
+    let layer = &child.get_layers()[0];
+    assert_eq!(layer.get_name(), "points");
+    assert_eq!(layer.get_features().len(), 1);
+    assert_eq!(layer.get_features()[0].get_geometry(), &[9, 400, 800]);
+
+    // Bottom right child: (3000,3000) -> (1904,1904)
+    let child = Tile::overzoom(&parent, 1, 1, 1);
+    let layer = &child.get_layers()[0];
+    assert_eq!(layer.get_features().len(), 1);
+    assert_eq!(layer.get_features()[0].get_geometry(), &[9, 3808, 3808]);
+
+    // Empty layers are dropped
+    let child = Tile::overzoom(&parent, 2, 3, 0);
+    assert_eq!(child.get_layers().len(), 0);
+
+    // Maximal overzoom levels exceeded
+    let child = Tile::overzoom(&parent, 17, 0, 0);
+    assert_eq!(child.get_layers().len(), 0);
+}
+
+#[test]
+fn test_overzoom_out_of_range() {
+    use crate::mvt::geom_encoder::decode_geometry;
+
+    let mut mvt_layer = vector_tile::Tile_Layer::new();
+    mvt_layer.set_version(2);
+    mvt_layer.set_name("lines".to_string());
+    mvt_layer.set_extent(4096);
+    // LineString (0,0) (100000,0) and Polygon (0,0) (100000,0) (100000,100000) (0,100000)
+    for &(geom_type, ref geometry) in &[
+        (
+            vector_tile::Tile_GeomType::LINESTRING,
+            vec![9, 0, 0, 10, 200000, 0],
+        ),
+        (
+            vector_tile::Tile_GeomType::POLYGON,
+            vec![9, 0, 0, 26, 200000, 0, 0, 200000, 199999, 0, 15],
+        ),
+    ] {
+        let mut mvt_feature = vector_tile::Tile_Feature::new();
+        mvt_feature.set_field_type(geom_type);
+        mvt_feature.set_geometry(geometry.clone());
+        mvt_layer.mut_features().push(mvt_feature);
+    }
+    let mut parent = vector_tile::Tile::new();
+    parent.mut_layers().push(mvt_layer);
+
+    // Coordinates scaled by 2^16 exceed i32, geometries are clipped to the buffered tile
+    let child = Tile::overzoom(&parent, 16, 0, 0);
+    let features = child.get_layers()[0].get_features();
+    assert_eq!(features.len(), 2);
+    assert_eq!(
+        decode_geometry(features[0].get_geometry()),
+        vec![vec![(0, 0), (4608, 0)]]
+    );
+    assert_eq!(
+        decode_geometry(features[1].get_geometry()),
+        vec![vec![(0, 4608), (0, 0), (4608, 0), (4608, 4608), (0, 4608)]]
+    );
+
+    // Line outside of the tile
+    let child = Tile::overzoom(&parent, 16, 0, 1);
+    let features = child.get_layers()[0].get_features();
+    assert_eq!(features.len(), 1);
+    assert_eq!(
+        features[0].get_field_type(),
+        vector_tile::Tile_GeomType::POLYGON
+    );
+}
+
+#[test]
+fn test_encode_tile() {
+    let extent = Extent {
+        minx: 0.0,
+        miny: 0.0,
+        maxx: 256000.0,
+        maxy: 256000.0,
+    };
+    let point = |x: f64, y: f64| FeatureStruct {
+        fid: None,
+        attributes: vec![FeatureAttr {
+            key: String::from("x"),
+            value: FeatureAttrValType::Double(x),
+        }],
+        geometry: GeometryType::Point(geom::Point::new(x, y, Some(3857))),
+    };
+    let layers = vec![
+        LayerFeatures {
+            name: "points".to_string(),
+            features: vec![
+                point(1000.0, 1000.0),
+                point(260000.0, 1000.0),
+                point(300000.0, 1000.0),
+            ],
+        },
+        LayerFeatures {
+            name: "outside".to_string(),
+            features: vec![point(-10000.0, 1000.0)],
+        },
+    ];
+    let tiledata = encode_tile(layers, &extent, 8);
+    let mvt_tile = Tile::read_from(&mut &tiledata[..]).unwrap();
+    assert_eq!(mvt_tile.get_layers().len(), 1);
+    let layer = &mvt_tile.get_layers()[0];
+    assert_eq!(layer.get_name(), "points");
+    assert_eq!(layer.get_extent(), 4096);
+    // Second point is within the tile buffer
+    assert_eq!(layer.get_features().len(), 2);
+    assert_eq!(layer.get_features()[0].get_geometry(), &[9, 32, 8160]);
+    assert_eq!(layer.get_features()[1].get_geometry(), &[9, 8320, 8160]);
+    assert_eq!(layer.get_values().len(), 2);
+}
+
+#[test]
+fn test_deterministic_tile() {
+    let extent = Extent {
+        minx: 0.0,
+        miny: 0.0,
+        maxx: 256000.0,
+        maxy: 256000.0,
+    };
+    let mut layer = Layer::new("parcels");
+    layer.tile_size = 1 << 14;
+    layer.snap_to_grid = Some(0.5);
+    let feature = |fid: Option<u64>, x: f64, attrs: &[(&str, FeatureAttrValType)]| FeatureStruct {
+        fid,
+        attributes: attrs
+            .iter()
+            .map(|(key, value)| FeatureAttr {
+                key: key.to_string(),
+                value: value.clone(),
+            })
+            .collect(),
+        geometry: GeometryType::LineString(geom::LineString {
+            points: vec![
+                geom::Point::new(x, 1015.7, None),
+                geom::Point::new(x + 3000.3, 5000.0, None),
+            ],
+            srid: None,
+        }),
+    };
+    let text = |s: &str| FeatureAttrValType::String(s.to_string());
+    let features = vec![
+        feature(None, 1000.0, &[("name", text("a"))]),
+        feature(None, 1000.0, &[("area", FeatureAttrValType::Double(1.5))]),
+        feature(None, 2000.0, &[("name", text("b"))]),
+        feature(Some(7), 9000.0, &[]),
+        feature(
+            Some(3),
+            4000.0,
+            &[
+                ("area", FeatureAttrValType::Double(2.5)),
+                ("name", text("a")),
+            ],
+        ),
+    ];
+    let encode = |order: &[usize], deterministic: bool| {
+        let mut tile = Tile::new(&extent, true);
+        let mut mvt_layer = tile.new_layer(&layer);
+        for &i in order {
+            tile.add_feature(&mut mvt_layer, &features[i]);
+        }
+        if deterministic {
+            Tile::sort_features(&mut mvt_layer);
+        }
+        tile.add_layer(mvt_layer);
+        Tile::tile_bytevec(&tile.mvt_tile)
+    };
+
+    let expected = encode(&[0, 1, 2, 3, 4], true);
+    for order in &[[4, 3, 2, 1, 0], [2, 0, 4, 1, 3], [1, 0, 3, 4, 2]] {
+        assert_eq!(encode(order, true), expected);
+        assert_ne!(encode(order, false), encode(&[0, 1, 2, 3, 4], false));
+    }
+
+    let mvt_tile = Tile::read_from(&mut &expected[..]).unwrap();
+    let mvt_layer = &mvt_tile.get_layers()[0];
+    assert_eq!(mvt_layer.get_extent(), 16384);
+    let ids: Vec<u64> = mvt_layer
+        .get_features()
+        .iter()
+        .map(|f| f.get_id())
+        .collect();
+    assert_eq!(ids, vec![0, 0, 0, 3, 7]);
+    assert_eq!(mvt_layer.get_keys(), &["area", "name"]);
+    // Coordinates snapped to 0.5 before quantization to 15.625 units (y = 1015.5)
+    assert_eq!(
+        mvt_layer.get_features()[0].get_geometry(),
+        &[9, 128, 32640, 10, 384, 511]
+    );
+}
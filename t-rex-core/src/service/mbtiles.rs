@@ -0,0 +1,114 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, OpenFlags, OptionalExtension, NO_PARAMS};
+use serde_json;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tile_grid::Extent;
+
+/// Read-only MBTiles archive (https://github.com/mapbox/mbtiles-spec)
+#[derive(Clone)]
+pub struct MbtilesArchive {
+    pub path: String,
+    /// Entries of the `metadata` table
+    pub metadata: BTreeMap<String, String>,
+    pool: Pool<SqliteConnectionManager>,
+}
+
+impl MbtilesArchive {
+    pub fn open(path: &str) -> Result<Self, String> {
+        if !Path::new(path).is_file() {
+            return Err(format!("MBTiles file '{}' not found", path));
+        }
+        let manager = SqliteConnectionManager::file(path)
+            .with_flags(OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX);
+        let pool = Pool::builder()
+            .max_size(4)
+            .build(manager)
+            .map_err(|e| format!("Error opening MBTiles file '{}': {}", path, e))?;
+        let metadata = {
+            let conn = pool.get().map_err(|e| e.to_string())?;
+            let mut stmt = conn
+                .prepare("SELECT name, value FROM metadata")
+                .map_err(|e| format!("Error reading metadata of '{}': {}", path, e))?;
+            let rows = stmt
+                .query_map(NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(|e| format!("Error reading metadata of '{}': {}", path, e))?;
+            rows.filter_map(|row| row.ok()).collect()
+        };
+        Ok(MbtilesArchive {
+            path: path.to_string(),
+            metadata,
+            pool,
+        })
+    }
+    fn metadata_zoom(&self, name: &str) -> Option<u8> {
+        self.metadata.get(name).and_then(|z| z.trim().parse().ok())
+    }
+    pub fn minzoom(&self) -> Option<u8> {
+        self.metadata_zoom("minzoom")
+    }
+    pub fn maxzoom(&self) -> Option<u8> {
+        self.metadata_zoom("maxzoom")
+    }
+    /// Bounds in WGS84 (`minx,miny,maxx,maxy`)
+    pub fn bounds(&self) -> Option<Extent> {
+        let coords = self
+            .metadata
+            .get("bounds")?
+            .split(',')
+            .map(|v| v.trim().parse::<f64>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        if coords.len() != 4 {
+            return None;
+        }
+        Some(Extent {
+            minx: coords[0],
+            miny: coords[1],
+            maxx: coords[2],
+            maxy: coords[3],
+        })
+    }
+    /// Center in WGS84 and zoom level (`lon,lat,zoom`)
+    pub fn center(&self) -> Option<(f64, f64, Option<u8>)> {
+        let mut parts = self.metadata.get("center")?.split(',').map(|v| v.trim());
+        let lon = parts.next()?.parse().ok()?;
+        let lat = parts.next()?.parse().ok()?;
+        let zoom = parts.next().and_then(|z| z.parse().ok());
+        Some((lon, lat, zoom))
+    }
+    pub fn attribution(&self) -> Option<String> {
+        self.metadata.get("attribution").cloned()
+    }
+    /// TileJSON `vector_layers` from the `json` metadata entry
+    pub fn vector_layers(&self) -> Option<serde_json::Value> {
+        let json: serde_json::Value = serde_json::from_str(self.metadata.get("json")?).ok()?;
+        json.get("vector_layers").cloned()
+    }
+    /// Tile data as stored in the archive (usually gzip compressed).
+    /// `ytile` is the tile row in TMS adressing scheme.
+    pub fn tile(&self, zoom: u8, xtile: u32, ytile: u32) -> Option<Vec<u8>> {
+        let conn = match self.pool.get() {
+            Ok(conn) => conn,
+            Err(e) => {
+                error!("MBTiles '{}': {}", self.path, e);
+                return None;
+            }
+        };
+        conn.query_row(
+            "SELECT tile_data FROM tiles WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+            params![zoom, xtile, ytile],
+            |row| row.get(0),
+        )
+        .optional()
+        .unwrap_or_else(|e| {
+            error!("MBTiles '{}': {}", self.path, e);
+            None
+        })
+    }
+}
@@ -0,0 +1,63 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use crate::service::mbtiles::MbtilesArchive;
+use rusqlite::{params, Connection};
+use std::env;
+use std::fs;
+
+fn create_mbtiles(name: &str) -> String {
+    let mut path = env::temp_dir();
+    path.push(name);
+    let path = format!("{}", path.display());
+    let _ = fs::remove_file(&path);
+    let conn = Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE metadata (name text, value text);
+         CREATE TABLE tiles (zoom_level integer, tile_column integer, tile_row integer, tile_data blob);",
+    )
+    .unwrap();
+    for (name, value) in &[
+        ("name", "contours"),
+        ("format", "pbf"),
+        ("minzoom", "2"),
+        ("maxzoom", "12"),
+        ("bounds", "5.9,45.8,10.5,47.8"),
+        ("center", "8.2,46.8,7"),
+        ("attribution", "© swisstopo"),
+        ("json", r#"{"vector_layers":[{"id":"contour","fields":{"ele":"Number"}}]}"#),
+    ] {
+        conn.execute(
+            "INSERT INTO metadata (name, value) VALUES (?1, ?2)",
+            params![name, value],
+        )
+        .unwrap();
+    }
+    conn.execute(
+        "INSERT INTO tiles VALUES (12, 2148, 2638, ?1)",
+        params![&b"tiledata"[..]],
+    )
+    .unwrap();
+    path
+}
+
+#[test]
+fn test_mbtiles_archive() {
+    let path = create_mbtiles("t_rex_test.mbtiles");
+    let archive = MbtilesArchive::open(&path).unwrap();
+    assert_eq!(archive.minzoom(), Some(2));
+    assert_eq!(archive.maxzoom(), Some(12));
+    let bounds = archive.bounds().unwrap();
+    assert_eq!((bounds.minx, bounds.maxy), (5.9, 47.8));
+    assert_eq!(archive.center(), Some((8.2, 46.8, Some(7))));
+    assert_eq!(archive.attribution(), Some("© swisstopo".to_string()));
+    assert_eq!(archive.vector_layers().unwrap()[0]["id"], json!("contour"));
+
+    // Tile rows in TMS scheme
+    assert_eq!(archive.tile(12, 2148, 2638), Some(b"tiledata".to_vec()));
+    assert_eq!(archive.tile(12, 2148, 1457), None);
+
+    assert!(MbtilesArchive::open("missing.mbtiles").is_err());
+}
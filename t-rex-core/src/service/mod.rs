@@ -6,4 +6,9 @@
 pub mod glstyle_converter;
 #[cfg(test)]
 mod glstyle_converter_test;
+#[cfg(feature = "server")]
+pub mod mbtiles;
+#[cfg(all(test, feature = "server"))]
+mod mbtiles_test;
+#[cfg(feature = "server")]
 pub mod tileset;
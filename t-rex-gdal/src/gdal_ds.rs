@@ -183,7 +183,7 @@ impl DatasourceType for GdalDatasource {
                 );
             }
         }
-        if layer.buffer_size.is_some() {
+        if layer.buffer_size.is_some() && layer.clip_geom {
             if layer.geometry_type != Some("POINT".to_string()) {
                 warn!(
                     "Layer '{}': Clipping with buffer_size not supported for GDAL layers",
@@ -209,18 +209,7 @@ impl DatasourceType for GdalDatasource {
         debug!("retrieve_features layer: {}", layer_name);
         let ogr_layer = dataset.layer_by_name(layer_name).unwrap();
 
-        let mut bbox_extent = if let Some(pixels) = layer.buffer_size {
-            let pixel_width = grid.pixel_width(zoom);
-            let buf = f64::from(pixels) * pixel_width;
-            Extent {
-                minx: extent.minx - buf,
-                miny: extent.miny - buf,
-                maxx: extent.maxx + buf,
-                maxy: extent.maxy + buf,
-            }
-        } else {
-            extent.clone()
-        };
+        let mut bbox_extent = layer.buffered_extent(extent, grid, zoom);
 
         // CoordTransform for features
         let mut transformation = None;
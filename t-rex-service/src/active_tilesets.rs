@@ -0,0 +1,107 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use crate::datasources::Datasources;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+
+/// Datasources with prepared queries of a tileset
+pub enum TilesetDatasources<'a> {
+    /// Datasources of the service with the queries of all tilesets
+    Shared(&'a Datasources),
+    /// Datasources of a lazily prepared tileset
+    Active(Arc<Datasources>),
+}
+
+impl<'a> Deref for TilesetDatasources<'a> {
+    type Target = Datasources;
+
+    fn deref(&self) -> &Datasources {
+        match self {
+            TilesetDatasources::Shared(datasources) => datasources,
+            TilesetDatasources::Active(datasources) => datasources,
+        }
+    }
+}
+
+struct Entry {
+    datasources: Arc<Datasources>,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    map: HashMap<String, Entry>,
+    /// Access counter for least recently used eviction
+    tick: u64,
+}
+
+/// Tilesets prepared on first request (lazy loading), bounded to `max_tilesets`
+/// and shared by clones. Evicted tilesets are prepared again when requested.
+#[derive(Clone)]
+pub struct ActiveTilesets {
+    max_tilesets: usize,
+    entries: Arc<Mutex<Entries>>,
+}
+
+impl ActiveTilesets {
+    pub fn new(max_tilesets: usize) -> ActiveTilesets {
+        ActiveTilesets {
+            max_tilesets,
+            entries: Arc::new(Mutex::new(Entries::default())),
+        }
+    }
+    pub fn max_tilesets(&self) -> usize {
+        self.max_tilesets
+    }
+    /// Number of prepared tilesets
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().map.len()
+    }
+    /// Datasources of a prepared tileset
+    pub fn get(&self, tileset: &str) -> Option<Arc<Datasources>> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.tick += 1;
+        let tick = entries.tick;
+        entries.map.get_mut(tileset).map(|entry| {
+            entry.last_used = tick;
+            entry.datasources.clone()
+        })
+    }
+    /// Insert datasources prepared for `tileset`, evicting the least recently used tileset
+    /// if the limit is reached. Returns the datasources of a concurrently inserted entry.
+    pub fn insert(&self, tileset: &str, datasources: Datasources) -> Arc<Datasources> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.tick += 1;
+        let tick = entries.tick;
+        if let Some(entry) = entries.map.get_mut(tileset) {
+            entry.last_used = tick;
+            return entry.datasources.clone();
+        }
+        if entries.map.len() >= self.max_tilesets {
+            let lru = entries
+                .map
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(name, _)| name.clone());
+            if let Some(lru) = lru {
+                debug!("Tileset '{}' evicted from active tilesets", lru);
+                entries.map.remove(&lru);
+            }
+        }
+        let datasources = Arc::new(datasources);
+        let entry = Entry {
+            datasources: datasources.clone(),
+            last_used: tick,
+        };
+        entries.map.insert(tileset.to_string(), entry);
+        datasources
+    }
+    /// Check whether a tileset is prepared (without updating its last use)
+    pub fn contains(&self, tileset: &str) -> bool {
+        self.entries.lock().unwrap().map.contains_key(tileset)
+    }
+}
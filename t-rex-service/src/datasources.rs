@@ -4,14 +4,16 @@
 //
 
 use clap::ArgMatches;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use t_rex_core::core::config::{ApplicationCfg, DatasourceCfg};
 use t_rex_core::core::feature::Feature;
 use t_rex_core::core::layer::Layer;
 use t_rex_core::core::Config;
 #[cfg(not(feature = "with-gdal"))]
 use t_rex_core::datasource::DummyDatasource as GdalDatasource;
-use t_rex_core::datasource::{DatasourceType, PostgisDatasource};
+use t_rex_core::datasource::{
+    BoundQuery, CsvDatasource, DatasourceType, PostgisDatasource, ReplicaStats,
+};
 #[cfg(feature = "with-gdal")]
 use t_rex_gdal::GdalDatasource;
 use tile_grid::{Extent, Grid};
@@ -20,6 +22,7 @@ use tile_grid::{Extent, Grid};
 pub enum Datasource {
     Postgis(PostgisDatasource),
     Gdal(GdalDatasource),
+    Csv(CsvDatasource),
 }
 
 impl DatasourceType for Datasource {
@@ -27,36 +30,49 @@ impl DatasourceType for Datasource {
         match self {
             &Datasource::Postgis(ref ds) => Datasource::Postgis(ds.connected()),
             &Datasource::Gdal(ref ds) => Datasource::Gdal(ds.connected()),
+            &Datasource::Csv(ref ds) => Datasource::Csv(ds.connected()),
         }
     }
     fn detect_layers(&self, detect_geometry_types: bool) -> Vec<Layer> {
         match self {
             &Datasource::Postgis(ref ds) => ds.detect_layers(detect_geometry_types),
             &Datasource::Gdal(ref ds) => ds.detect_layers(detect_geometry_types),
+            &Datasource::Csv(ref ds) => ds.detect_layers(detect_geometry_types),
         }
     }
     fn detect_data_columns(&self, layer: &Layer, sql: Option<&String>) -> Vec<(String, String)> {
         match self {
             &Datasource::Postgis(ref ds) => ds.detect_data_columns(layer, sql),
             &Datasource::Gdal(ref ds) => ds.detect_data_columns(layer, sql),
+            &Datasource::Csv(ref ds) => ds.detect_data_columns(layer, sql),
+        }
+    }
+    fn detect_field_types(&self, layer: &Layer, sql: Option<&String>) -> Vec<(String, String)> {
+        match self {
+            &Datasource::Postgis(ref ds) => ds.detect_field_types(layer, sql),
+            &Datasource::Gdal(ref ds) => ds.detect_field_types(layer, sql),
+            &Datasource::Csv(ref ds) => ds.detect_field_types(layer, sql),
         }
     }
     fn extent_from_wgs84(&self, extent: &Extent, dest_srid: i32) -> Option<Extent> {
         match self {
             &Datasource::Postgis(ref ds) => ds.extent_from_wgs84(extent, dest_srid),
             &Datasource::Gdal(ref ds) => ds.extent_from_wgs84(extent, dest_srid),
+            &Datasource::Csv(ref ds) => ds.extent_from_wgs84(extent, dest_srid),
         }
     }
     fn layer_extent(&self, layer: &Layer, grid_srid: i32) -> Option<Extent> {
         match self {
             &Datasource::Postgis(ref ds) => ds.layer_extent(layer, grid_srid),
             &Datasource::Gdal(ref ds) => ds.layer_extent(layer, grid_srid),
+            &Datasource::Csv(ref ds) => ds.layer_extent(layer, grid_srid),
         }
     }
     fn prepare_queries(&mut self, tileset: &str, layer: &Layer, grid_srid: i32) {
         match self {
             &mut Datasource::Postgis(ref mut ds) => ds.prepare_queries(tileset, layer, grid_srid),
             &mut Datasource::Gdal(ref mut ds) => ds.prepare_queries(tileset, layer, grid_srid),
+            &mut Datasource::Csv(ref mut ds) => ds.prepare_queries(tileset, layer, grid_srid),
         }
     }
     fn retrieve_features<F>(
@@ -78,6 +94,185 @@ impl DatasourceType for Datasource {
             &Datasource::Gdal(ref ds) => {
                 ds.retrieve_features(tileset, layer, extent, zoom, grid, read)
             }
+            &Datasource::Csv(ref ds) => {
+                ds.retrieve_features(tileset, layer, extent, zoom, grid, read)
+            }
+        }
+    }
+    fn retrieve_features_while<F>(
+        &self,
+        tileset: &str,
+        layer: &Layer,
+        extent: &Extent,
+        zoom: u8,
+        grid: &Grid,
+        read: F,
+    ) -> u64
+    where
+        F: FnMut(&dyn Feature) -> bool,
+    {
+        match self {
+            &Datasource::Postgis(ref ds) => {
+                ds.retrieve_features_while(tileset, layer, extent, zoom, grid, read)
+            }
+            &Datasource::Gdal(ref ds) => {
+                ds.retrieve_features_while(tileset, layer, extent, zoom, grid, read)
+            }
+            &Datasource::Csv(ref ds) => {
+                ds.retrieve_features_while(tileset, layer, extent, zoom, grid, read)
+            }
+        }
+    }
+    fn try_retrieve_features_while<F>(
+        &self,
+        tileset: &str,
+        layer: &Layer,
+        extent: &Extent,
+        zoom: u8,
+        grid: &Grid,
+        read: F,
+    ) -> Result<u64, String>
+    where
+        F: FnMut(&dyn Feature) -> bool,
+    {
+        match self {
+            &Datasource::Postgis(ref ds) => {
+                ds.try_retrieve_features_while(tileset, layer, extent, zoom, grid, read)
+            }
+            &Datasource::Gdal(ref ds) => {
+                ds.try_retrieve_features_while(tileset, layer, extent, zoom, grid, read)
+            }
+            &Datasource::Csv(ref ds) => {
+                ds.try_retrieve_features_while(tileset, layer, extent, zoom, grid, read)
+            }
+        }
+    }
+}
+
+impl Datasource {
+    /// Datasource type as in `/version.json`
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            &Datasource::Postgis(_) => "postgis",
+            &Datasource::Gdal(_) => "gdal",
+            &Datasource::Csv(_) => "csv",
+        }
+    }
+    /// Database version detected when connecting or configured (PostGIS only)
+    pub fn server_version(&self) -> Option<String> {
+        match self {
+            &Datasource::Postgis(ref ds) => ds.version.map(|version| version.to_string()),
+            _ => None,
+        }
+    }
+    /// Check whether layer geometries are clipped to the buffered tile extent (PostGIS only).
+    /// Other datasources don't support clipping with `buffer_size`.
+    pub fn clips_geometries(&self, layer: &Layer) -> bool {
+        match self {
+            &Datasource::Postgis(_) => layer.buffer_size.is_some() && layer.clip_geom,
+            _ => false,
+        }
+    }
+    /// Retrieve layer encoded by the datasource (PostGIS ST_AsMVT).
+    /// None, if the datasource doesn't encode the layer.
+    pub fn retrieve_mvt_layer(
+        &self,
+        tileset: &str,
+        layer: &Layer,
+        extent: &Extent,
+        zoom: u8,
+        grid: &Grid,
+    ) -> Result<Option<Vec<u8>>, String> {
+        match self {
+            &Datasource::Postgis(ref ds) => ds.retrieve_mvt_layer(tileset, layer, extent, zoom, grid),
+            _ => Ok(None),
+        }
+    }
+    /// SQL and query plan of the layer query for a tile (PostGIS only).
+    /// Executes the query with `EXPLAIN ANALYZE`.
+    pub fn explain_query(
+        &self,
+        tileset: &str,
+        layer: &Layer,
+        extent: &Extent,
+        zoom: u8,
+        grid: &Grid,
+    ) -> Result<Option<(String, serde_json::Value)>, String> {
+        match self {
+            &Datasource::Postgis(ref ds) => ds.explain_query(tileset, layer, extent, zoom, grid),
+            _ => Ok(None),
+        }
+    }
+    /// Check whether the `table_name` of a layer exists (PostGIS only)
+    pub fn table_exists(&self, layer: &Layer) -> Option<bool> {
+        match self {
+            &Datasource::Postgis(ref ds) => ds.table_exists(layer),
+            _ => None,
+        }
+    }
+    /// Layer query for a tile with its parameter values (PostGIS only)
+    pub fn bound_query(
+        &self,
+        tileset: &str,
+        layer: &Layer,
+        extent: &Extent,
+        zoom: u8,
+        grid: &Grid,
+    ) -> Option<BoundQuery> {
+        match self {
+            &Datasource::Postgis(ref ds) => ds.bound_query(tileset, layer, extent, zoom, grid),
+            _ => None,
+        }
+    }
+    /// Query plan of the layer query for a tile (PostGIS only)
+    pub fn explain_plan(
+        &self,
+        tileset: &str,
+        layer: &Layer,
+        extent: &Extent,
+        zoom: u8,
+        grid: &Grid,
+        analyze: bool,
+    ) -> Result<Option<String>, String> {
+        match self {
+            &Datasource::Postgis(ref ds) => {
+                ds.explain_plan(tileset, layer, extent, zoom, grid, analyze)
+            }
+            _ => Ok(None),
+        }
+    }
+    /// Distinct values of an attribute with feature counts (PostGIS only)
+    pub fn field_values(
+        &self,
+        tileset: &str,
+        layer: &Layer,
+        field: &str,
+        zoom: u8,
+        grid: &Grid,
+        limit: u32,
+        timeout_ms: u64,
+    ) -> Result<Option<Vec<(serde_json::Value, i64)>>, String> {
+        match self {
+            &Datasource::Postgis(ref ds) => {
+                ds.field_values(tileset, layer, field, zoom, grid, limit, timeout_ms)
+            }
+            _ => Err(format!(
+                "Layer '{}': field values are only supported for PostGIS layers",
+                layer.name
+            )),
+        }
+    }
+    /// Check declared layer geometry type against the data (PostGIS only)
+    pub fn check_queries(&self, layer: &Layer) -> Result<(), String> {
+        match self {
+            &Datasource::Postgis(ref ds) => ds.check_queries(layer),
+            _ => Ok(()),
+        }
+    }
+    pub fn check_geometry_types(&self, layer: &Layer) -> Result<(), String> {
+        match self {
+            &Datasource::Postgis(ref ds) => ds.check_geometry_types(layer),
+            _ => Ok(()),
         }
     }
 }
@@ -88,21 +283,25 @@ impl<'a> Config<'a, DatasourceCfg> for Datasource {
             PostgisDatasource::from_config(ds_cfg).and_then(|ds| Ok(Datasource::Postgis(ds)))
         } else if ds_cfg.path.is_some() {
             GdalDatasource::from_config(ds_cfg).and_then(|ds| Ok(Datasource::Gdal(ds)))
+        } else if ds_cfg.csv.is_some() {
+            CsvDatasource::from_config(ds_cfg).and_then(|ds| Ok(Datasource::Csv(ds)))
         } else {
             Err(format!("Unsupported datasource"))
         }
     }
     fn gen_config() -> String {
         format!(
-            "{}{}",
+            "{}{}{}",
             PostgisDatasource::gen_config(),
-            GdalDatasource::gen_config()
+            GdalDatasource::gen_config(),
+            CsvDatasource::gen_config()
         )
     }
     fn gen_runtime_config(&self) -> String {
         match self {
             &Datasource::Postgis(ref ds) => ds.gen_runtime_config(),
             &Datasource::Gdal(ref ds) => ds.gen_runtime_config(),
+            &Datasource::Csv(ref ds) => ds.gen_runtime_config(),
         }
     }
 }
@@ -111,6 +310,8 @@ impl<'a> Config<'a, DatasourceCfg> for Datasource {
 pub struct Datasources {
     pub datasources: HashMap<String, Datasource>,
     pub default: Option<String>,
+    /// Datasources with licenses requiring attribution
+    pub attribution_required: HashSet<String>,
 }
 
 impl<'a> Config<'a, ApplicationCfg> for Datasources {
@@ -124,6 +325,9 @@ impl<'a> Config<'a, ApplicationCfg> for Datasources {
             if ds_cfg.default.unwrap_or(false) {
                 datasources.default = Some(name.clone());
             }
+            if ds_cfg.attribution_required.unwrap_or(false) {
+                datasources.attribution_required.insert(name.clone());
+            }
         }
         datasources.setup();
         Ok(datasources)
@@ -141,6 +345,9 @@ impl<'a> Config<'a, ApplicationCfg> for Datasources {
             if self.default.is_some() && name == self.default.as_ref().unwrap() {
                 config.push_str("default = true\n");
             }
+            if self.attribution_required.contains(name) {
+                config.push_str("attribution_required = true\n");
+            }
         }
         config
     }
@@ -151,6 +358,7 @@ impl Datasources {
         Datasources {
             datasources: HashMap::new(),
             default: None,
+            attribution_required: HashSet::new(),
         }
     }
     pub fn add(&mut self, name: &String, ds: Datasource) {
@@ -159,9 +367,10 @@ impl Datasources {
     }
     pub fn from_args(args: &ArgMatches) -> Self {
         let mut datasources = Datasources::new();
-        if let Some(dbconn) = args.value_of("dbconn") {
+        for spec in args.values_of("dbconn").into_iter().flatten() {
+            let (name, dbconn) = dbconn_arg(spec);
             datasources.add(
-                &"dbconn".to_string(),
+                &name.unwrap_or("dbconn").to_string(),
                 Datasource::Postgis(PostgisDatasource::new(dbconn, None)),
             );
         }
@@ -178,6 +387,12 @@ impl Datasources {
                 datasources.add(&"datasource".to_string(), ds);
             }
         }
+        if let Some(csv) = args.value_of("csv") {
+            datasources.add(
+                &"csv".to_string(),
+                Datasource::Csv(CsvDatasource::new(csv)),
+            );
+        }
         datasources.setup();
         datasources
     }
@@ -188,10 +403,27 @@ impl Datasources {
             self.default = self.datasources.keys().cloned().next();
         }
     }
+    /// Connection counts and health of PostGIS replicas per datasource
+    pub fn replica_stats(&self) -> BTreeMap<String, Vec<ReplicaStats>> {
+        self.datasources
+            .iter()
+            .filter_map(|(name, ds)| match ds {
+                Datasource::Postgis(ref ds) => Some((name.clone(), ds.replica_stats())),
+                _ => None,
+            })
+            .collect()
+    }
     pub fn datasource(&self, name: &Option<String>) -> Option<&Datasource> {
         let key = name.as_ref().unwrap_or(self.default.as_ref().unwrap());
         self.datasources.get(key)
     }
+    /// Check whether the license of a datasource requires attribution
+    pub fn attribution_required(&self, name: &Option<String>) -> bool {
+        match name.as_ref().or(self.default.as_ref()) {
+            Some(name) => self.attribution_required.contains(name),
+            None => false,
+        }
+    }
     pub fn datasource_mut(&mut self, name: &Option<String>) -> Option<&mut Datasource> {
         let key = name.as_ref().unwrap_or(self.default.as_ref().unwrap());
         self.datasources.get_mut(key)
@@ -204,6 +436,44 @@ impl Datasources {
     }
 }
 
+/// Split `--dbconn` argument `NAME=URL` into datasource name and connection URL
+pub fn dbconn_arg(spec: &str) -> (Option<&str>, &str) {
+    match spec.find('=') {
+        Some(pos)
+            if pos > 0
+                && spec[..pos]
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '-') =>
+        {
+            (Some(&spec[..pos]), &spec[pos + 1..])
+        }
+        _ => (None, spec),
+    }
+}
+
+#[test]
+fn test_dbconn_arg() {
+    assert_eq!(
+        dbconn_arg("postgresql://pi@localhost/osm"),
+        (None, "postgresql://pi@localhost/osm")
+    );
+    assert_eq!(
+        dbconn_arg("osm=postgresql://pi@localhost/osm"),
+        (Some("osm"), "postgresql://pi@localhost/osm")
+    );
+    assert_eq!(
+        dbconn_arg("postgresql://pi@localhost/osm?sslmode=require"),
+        (None, "postgresql://pi@localhost/osm?sslmode=require")
+    );
+    assert_eq!(
+        dbconn_arg("osm_2=postgresql://pi@localhost/osm?sslmode=require"),
+        (
+            Some("osm_2"),
+            "postgresql://pi@localhost/osm?sslmode=require"
+        )
+    );
+}
+
 #[cfg(test)]
 fn ds_from_config(toml: &str) -> Result<Datasource, String> {
     use t_rex_core::core::parse_config;
@@ -226,6 +496,63 @@ fn test_datasource_from_config() {
         pg.connection_url,
         "postgresql://pi@localhost/natural_earth_vectors"
     );
+    assert!(pg.replica_urls.is_empty());
+    assert_eq!(pg.pool_timeout_ms, None);
+
+    let toml = r#"
+        #[[datasource]]
+        dbconn = ["postgresql://pi@replica1/osm", "postgresql://pi@replica2/osm"]
+        pool_timeout_ms = 1000
+        "#;
+    let pg = match ds_from_config(toml).unwrap() {
+        Datasource::Postgis(pg) => pg,
+        _ => panic!(),
+    };
+    assert_eq!(pg.connection_url, "postgresql://pi@replica1/osm");
+    assert_eq!(pg.replica_urls, vec!["postgresql://pi@replica2/osm"]);
+    assert_eq!(pg.pool_timeout_ms, Some(1000));
+    assert_eq!(
+        pg.gen_runtime_config(),
+        "\n[[datasource]]\ndbconn = [\"postgresql://pi@replica1/osm\", \"postgresql://pi@replica2/osm\"]\n"
+    );
+}
+
+#[test]
+fn test_attribution_required() {
+    use t_rex_core::core::parse_config;
+
+    let toml = r#"
+        [service.mvt]
+        viewer = false
+
+        [[datasource]]
+        name = "osm"
+        dbconn = "postgresql://pi@localhost/osm"
+        attribution_required = true
+
+        [[datasource]]
+        name = "natural_earth"
+        dbconn = "postgresql://pi@localhost/natural_earth_vectors"
+        default = true
+
+        [grid]
+        predefined = "web_mercator"
+
+        [[tileset]]
+        name = "osm"
+
+        [webserver]
+        bind = "127.0.0.1"
+        port = 6767
+        "#;
+    let config: ApplicationCfg = parse_config(toml.to_string(), "").unwrap();
+    let datasources = Datasources::from_config(&config).unwrap();
+    assert!(datasources.attribution_required(&Some("osm".to_string())));
+    assert!(!datasources.attribution_required(&Some("natural_earth".to_string())));
+    assert!(!datasources.attribution_required(&None));
+    assert!(datasources
+        .gen_runtime_config()
+        .contains("name = \"osm\"\nattribution_required = true\n"));
 }
 
 #[test]
@@ -250,7 +577,19 @@ fn test_datasource_config_errors() {
         "#;
     assert_eq!(
         ds_from_config(toml).err(),
-        Some(" - invalid type: boolean `true`, expected a string for key `dbconn`".to_string())
+        Some(
+            " - invalid type: boolean `true`, expected a string or an array of strings for key `dbconn`"
+                .to_string()
+        )
+    );
+
+    let toml = r#"
+        #[[datasource]]
+        dbconn = []
+        "#;
+    assert_eq!(
+        ds_from_config(toml).err(),
+        Some("Empty dbconn list".to_string())
     );
 }
 
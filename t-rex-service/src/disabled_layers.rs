@@ -0,0 +1,50 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// Layers disabled while their table is missing (`missing_table = "skip"`), shared by clones
+#[derive(Clone, Default)]
+pub struct DisabledLayers {
+    /// Tileset and layer names
+    layers: Arc<RwLock<HashSet<(String, String)>>>,
+    /// Number of state changes
+    generation: Arc<AtomicUsize>,
+}
+
+impl DisabledLayers {
+    pub fn contains(&self, tileset: &str, layer: &str) -> bool {
+        let layers = self.layers.read().unwrap();
+        !layers.is_empty() && layers.contains(&(tileset.to_string(), layer.to_string()))
+    }
+    /// Check whether a tileset has disabled layers
+    pub fn has_tileset(&self, tileset: &str) -> bool {
+        self.layers
+            .read()
+            .unwrap()
+            .iter()
+            .any(|(ts, _)| ts == tileset)
+    }
+    /// Disable or enable a layer. Returns true, if the state changed.
+    pub fn set(&self, tileset: &str, layer: &str, disabled: bool) -> bool {
+        let mut layers = self.layers.write().unwrap();
+        let key = (tileset.to_string(), layer.to_string());
+        let changed = if disabled {
+            layers.insert(key)
+        } else {
+            layers.remove(&key)
+        };
+        if changed {
+            self.generation.fetch_add(1, Ordering::Relaxed);
+        }
+        changed
+    }
+    /// Counter changing with each state change, e.g. for invalidating cached metadata
+    pub fn generation(&self) -> usize {
+        self.generation.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,256 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Processing state of a tile request
+#[derive(Clone, Copy, PartialEq, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RequestState {
+    ReadingCache,
+    WaitingForDb,
+    Encoding,
+    WritingCache,
+}
+
+struct Entry {
+    path: String,
+    tileset: String,
+    zxy: (u8, u32, u32),
+    started: Instant,
+    state: RequestState,
+    /// Reported as stuck in log
+    logged: bool,
+}
+
+#[derive(Default)]
+struct Registry {
+    entries: HashMap<u64, Entry>,
+    peak: usize,
+    tileset_peaks: HashMap<String, usize>,
+    layer_errors: u64,
+    /// Last logged error per (tileset, layer)
+    layer_errors_logged: HashMap<(String, String), Instant>,
+}
+
+/// Registry of tile requests in progress, shared by clones
+#[derive(Clone, Default)]
+pub struct InflightRequests {
+    next_id: Arc<AtomicU64>,
+    registry: Arc<Mutex<Registry>>,
+}
+
+/// Request in progress. Removed from the registry when dropped.
+pub struct InflightRequest {
+    requests: InflightRequests,
+    id: u64,
+}
+
+/// Tile request in progress as reported by `/debug/requests`
+#[derive(Serialize, Debug)]
+pub struct RequestInfo {
+    pub path: String,
+    pub tileset: String,
+    pub z: u8,
+    pub x: u32,
+    pub y: u32,
+    pub elapsed_ms: u64,
+    pub state: RequestState,
+}
+
+#[derive(Serialize, Default, PartialEq, Debug)]
+pub struct Concurrency {
+    pub in_flight: usize,
+    /// Maximal number of concurrent requests since startup
+    pub peak: usize,
+}
+
+/// Concurrency metrics and requests in progress, longest running first
+#[derive(Serialize, Debug)]
+pub struct RequestsSnapshot {
+    #[serde(flatten)]
+    pub total: Concurrency,
+    pub tilesets: BTreeMap<String, Concurrency>,
+    pub requests: Vec<RequestInfo>,
+    /// Number of layer query errors since startup
+    pub layer_errors: u64,
+}
+
+impl Registry {
+    fn tileset_count(&self, tileset: &str) -> usize {
+        self.entries
+            .values()
+            .filter(|e| e.tileset == tileset)
+            .count()
+    }
+}
+
+impl InflightRequests {
+    /// Register tile request in URL adressing scheme
+    pub fn start(
+        &self,
+        path: &str,
+        tileset: &str,
+        zoom: u8,
+        xtile: u32,
+        ytile: u32,
+    ) -> InflightRequest {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut registry = self.registry.lock().unwrap();
+        registry.entries.insert(
+            id,
+            Entry {
+                path: path.to_string(),
+                tileset: tileset.to_string(),
+                zxy: (zoom, xtile, ytile),
+                started: Instant::now(),
+                state: RequestState::ReadingCache,
+                logged: false,
+            },
+        );
+        registry.peak = registry.peak.max(registry.entries.len());
+        let count = registry.tileset_count(tileset);
+        let peak = registry
+            .tileset_peaks
+            .entry(tileset.to_string())
+            .or_insert(0);
+        *peak = (*peak).max(count);
+        InflightRequest {
+            requests: self.clone(),
+            id,
+        }
+    }
+    pub fn snapshot(&self) -> RequestsSnapshot {
+        let registry = self.registry.lock().unwrap();
+        let mut tilesets: BTreeMap<String, Concurrency> = registry
+            .tileset_peaks
+            .iter()
+            .map(|(tileset, peak)| {
+                (
+                    tileset.clone(),
+                    Concurrency {
+                        in_flight: 0,
+                        peak: *peak,
+                    },
+                )
+            })
+            .collect();
+        let mut requests = Vec::new();
+        for entry in registry.entries.values() {
+            if let Some(concurrency) = tilesets.get_mut(&entry.tileset) {
+                concurrency.in_flight += 1;
+            }
+            requests.push(RequestInfo {
+                path: entry.path.clone(),
+                tileset: entry.tileset.clone(),
+                z: entry.zxy.0,
+                x: entry.zxy.1,
+                y: entry.zxy.2,
+                elapsed_ms: entry.started.elapsed().as_millis() as u64,
+                state: entry.state,
+            });
+        }
+        requests.sort_by(|a, b| b.elapsed_ms.cmp(&a.elapsed_ms));
+        RequestsSnapshot {
+            total: Concurrency {
+                in_flight: registry.entries.len(),
+                peak: registry.peak,
+            },
+            tilesets,
+            requests,
+            layer_errors: registry.layer_errors,
+        }
+    }
+    /// Number of requests in progress
+    pub fn in_flight(&self) -> usize {
+        self.registry.lock().unwrap().entries.len()
+    }
+    /// Wait until no requests are in progress, at most for `timeout`.
+    /// Used by background work yielding to interactive requests.
+    pub fn wait_idle(&self, timeout: Duration) {
+        let start = Instant::now();
+        while self.in_flight() > 0 && start.elapsed() < timeout {
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+    /// Log requests running longer than `threshold` (once per request).
+    /// Returns the number of newly reported requests.
+    pub fn log_stuck(&self, threshold: Duration) -> usize {
+        let mut registry = self.registry.lock().unwrap();
+        let mut count = 0;
+        for entry in registry.entries.values_mut() {
+            let elapsed = entry.started.elapsed();
+            if !entry.logged && elapsed > threshold {
+                warn!(
+                    "{} - Request running for {} s in state {:?}",
+                    entry.path,
+                    elapsed.as_secs(),
+                    entry.state
+                );
+                entry.logged = true;
+                count += 1;
+            }
+        }
+        count
+    }
+    /// Count a layer query error and log it at most once per layer within `interval`.
+    /// Returns true, if the error was logged.
+    pub fn layer_error(&self, tileset: &str, layer: &str, err: &str, interval: Duration) -> bool {
+        let mut registry = self.registry.lock().unwrap();
+        registry.layer_errors += 1;
+        let now = Instant::now();
+        let key = (tileset.to_string(), layer.to_string());
+        match registry.layer_errors_logged.get(&key) {
+            Some(logged) if now.duration_since(*logged) < interval => return false,
+            _ => {}
+        }
+        registry.layer_errors_logged.insert(key, now);
+        error!("Tileset '{}': {}", tileset, err);
+        true
+    }
+    /// Number of layer query errors since startup
+    pub fn layer_errors(&self) -> u64 {
+        self.registry.lock().unwrap().layer_errors
+    }
+    /// Run `log_stuck` periodically in a background thread until `stopped` is set
+    pub fn spawn_watchdog(&self, threshold: Duration, stopped: Arc<AtomicBool>) {
+        let requests = self.clone();
+        let interval = (threshold / 2).max(Duration::from_secs(1));
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            if stopped.load(Ordering::Relaxed) {
+                break;
+            }
+            requests.log_stuck(threshold);
+        });
+    }
+}
+
+impl InflightRequest {
+    pub fn set_state(&self, state: RequestState) {
+        if let Some(entry) = self
+            .requests
+            .registry
+            .lock()
+            .unwrap()
+            .entries
+            .get_mut(&self.id)
+        {
+            entry.state = state;
+        }
+    }
+}
+
+impl Drop for InflightRequest {
+    fn drop(&mut self) {
+        if let Ok(mut registry) = self.requests.registry.lock() {
+            registry.entries.remove(&self.id);
+        }
+    }
+}
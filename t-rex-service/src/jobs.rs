@@ -0,0 +1,275 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+//! Seeding jobs executed by background workers of the web server
+
+use crate::mvt_service::MvtService;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Instant;
+use t_rex_core::cache::Tilecache;
+use t_rex_core::core::config::PrewarmCfg;
+
+/// Maximal number of error messages reported per job
+const MAX_ERRORS: usize = 100;
+
+/// Parameters of a seeding job (like the `generate` command)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JobParams {
+    pub tileset: Option<String>,
+    pub minzoom: Option<u8>,
+    pub maxzoom: Option<u8>,
+    /// Extent in WGS84 as minx, miny, maxx, maxy
+    pub extent: Option<(f64, f64, f64, f64)>,
+    /// Replace cached tiles (Default: true)
+    #[serde(default = "default_overwrite")]
+    pub overwrite: bool,
+    /// Write tiles without features
+    #[serde(default)]
+    pub write_empty: bool,
+}
+
+fn default_overwrite() -> bool {
+    true
+}
+
+/// Jobs generating the tiles of `webserver.prewarm` missing in the cache
+pub fn prewarm_jobs(service: &MvtService, cfg: &PrewarmCfg) -> Result<Vec<JobParams>, String> {
+    if let Tilecache::Nocache(_) = service.cache {
+        if !cfg.tilesets.is_empty() {
+            warn!("webserver.prewarm ignored without tile cache");
+        }
+        return Ok(Vec::new());
+    }
+    cfg.tilesets
+        .iter()
+        .map(|ts| {
+            if service.get_tileset(&ts.name).is_none() {
+                return Err(format!(
+                    "Unknown tileset '{}' in webserver.prewarm",
+                    ts.name
+                ));
+            }
+            if let (Some(minzoom), Some(maxzoom)) = (ts.minzoom, ts.maxzoom) {
+                if minzoom > maxzoom {
+                    return Err(format!(
+                        "webserver.prewarm of tileset '{}': minzoom {} > maxzoom {}",
+                        ts.name, minzoom, maxzoom
+                    ));
+                }
+            }
+            Ok(JobParams {
+                tileset: Some(ts.name.clone()),
+                minzoom: ts.minzoom,
+                maxzoom: ts.maxzoom,
+                extent: ts
+                    .extent
+                    .as_ref()
+                    .map(|ext| (ext.minx, ext.miny, ext.maxx, ext.maxy)),
+                overwrite: false,
+                write_empty: false,
+            })
+        })
+        .collect()
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Completed,
+    Cancelled,
+}
+
+/// Job status as reported by `GET /admin/jobs/{id}`
+#[derive(Serialize, Clone, Debug)]
+pub struct JobInfo {
+    pub id: u64,
+    pub state: JobState,
+    pub params: JobParams,
+    pub tiles_done: u64,
+    pub tiles_total: u64,
+    /// Estimated remaining time of a running job
+    pub eta_s: Option<u64>,
+    pub error_count: u64,
+    /// First error messages
+    pub errors: Vec<String>,
+}
+
+struct Job {
+    info: JobInfo,
+    started: Option<Instant>,
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: BTreeMap<u64, Job>,
+    pending: VecDeque<u64>,
+    stopped: bool,
+}
+
+/// Queue of seeding jobs, shared by clones
+#[derive(Clone, Default)]
+pub struct JobQueue {
+    next_id: Arc<AtomicU64>,
+    queue: Arc<(Mutex<Queue>, Condvar)>,
+}
+
+/// Job taken from the queue by a worker
+pub struct JobHandle {
+    jobs: JobQueue,
+    pub id: u64,
+    pub params: JobParams,
+}
+
+impl JobQueue {
+    pub fn new() -> JobQueue {
+        JobQueue::default()
+    }
+    /// Add job to queue. Returns the job id.
+    pub fn submit(&self, params: JobParams) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let (ref queue, ref available) = *self.queue;
+        let mut queue = queue.lock().unwrap();
+        queue.jobs.insert(
+            id,
+            Job {
+                info: JobInfo {
+                    id,
+                    state: JobState::Queued,
+                    params,
+                    tiles_done: 0,
+                    tiles_total: 0,
+                    eta_s: None,
+                    error_count: 0,
+                    errors: Vec::new(),
+                },
+                started: None,
+            },
+        );
+        queue.pending.push_back(id);
+        available.notify_one();
+        id
+    }
+    pub fn info(&self, id: u64) -> Option<JobInfo> {
+        let queue = self.queue.0.lock().unwrap();
+        let job = queue.jobs.get(&id)?;
+        let mut info = job.info.clone();
+        if let (JobState::Running, Some(started)) = (info.state, job.started) {
+            if info.tiles_done > 0 {
+                let elapsed = started.elapsed().as_secs_f64();
+                let remaining = info.tiles_total.saturating_sub(info.tiles_done) as f64;
+                info.eta_s = Some((elapsed / info.tiles_done as f64 * remaining) as u64);
+            }
+        }
+        Some(info)
+    }
+    /// Cancel queued or running job. Returns false for unknown or finished jobs.
+    pub fn cancel(&self, id: u64) -> bool {
+        let mut queue = self.queue.0.lock().unwrap();
+        queue.pending.retain(|pending| *pending != id);
+        match queue.jobs.get_mut(&id) {
+            Some(job)
+                if job.info.state == JobState::Queued || job.info.state == JobState::Running =>
+            {
+                job.info.state = JobState::Cancelled;
+                true
+            }
+            _ => false,
+        }
+    }
+    /// Cancel all jobs and stop the workers after their current tile
+    pub fn shutdown(&self) {
+        let (ref queue, ref available) = *self.queue;
+        let mut queue = queue.lock().unwrap();
+        queue.stopped = true;
+        queue.pending.clear();
+        for job in queue.jobs.values_mut() {
+            if job.info.state == JobState::Queued || job.info.state == JobState::Running {
+                job.info.state = JobState::Cancelled;
+            }
+        }
+        available.notify_all();
+    }
+    /// Wait for the next queued job. None after `shutdown`.
+    pub(crate) fn next(&self) -> Option<JobHandle> {
+        let (ref queue, ref available) = *self.queue;
+        let mut queue = queue.lock().unwrap();
+        loop {
+            if queue.stopped {
+                return None;
+            }
+            if let Some(id) = queue.pending.pop_front() {
+                let job = queue.jobs.get_mut(&id).unwrap();
+                job.info.state = JobState::Running;
+                job.started = Some(Instant::now());
+                return Some(JobHandle {
+                    jobs: self.clone(),
+                    id,
+                    params: job.info.params.clone(),
+                });
+            }
+            queue = available.wait(queue).unwrap();
+        }
+    }
+    /// Execute jobs with `workers` background threads
+    pub fn spawn_workers(&self, service: &MvtService, workers: u8) {
+        for _ in 0..workers {
+            let jobs = self.clone();
+            let service = service.clone();
+            thread::spawn(move || {
+                while let Some(job) = jobs.next() {
+                    info!("Starting job {}: {:?}", job.id, job.params);
+                    service.seed_job(&job);
+                    job.finish();
+                }
+            });
+        }
+    }
+    fn update<F: FnOnce(&mut JobInfo)>(&self, id: u64, f: F) {
+        if let Some(job) = self.queue.0.lock().unwrap().jobs.get_mut(&id) {
+            f(&mut job.info);
+        }
+    }
+}
+
+impl JobHandle {
+    pub fn set_total(&self, tiles: u64) {
+        self.jobs.update(self.id, |info| info.tiles_total = tiles);
+    }
+    pub fn tile_done(&self) {
+        self.jobs.update(self.id, |info| info.tiles_done += 1);
+    }
+    pub fn error(&self, msg: String) {
+        error!("Job {}: {}", self.id, msg);
+        self.jobs.update(self.id, |info| {
+            info.error_count += 1;
+            if info.errors.len() < MAX_ERRORS {
+                info.errors.push(msg);
+            }
+        });
+    }
+    pub fn is_cancelled(&self) -> bool {
+        let queue = self.jobs.queue.0.lock().unwrap();
+        queue
+            .jobs
+            .get(&self.id)
+            .map(|job| job.info.state == JobState::Cancelled)
+            .unwrap_or(true)
+    }
+    /// Mark job as completed, unless it was cancelled
+    pub fn finish(&self) {
+        self.jobs.update(self.id, |info| {
+            if info.state == JobState::Running {
+                info.state = JobState::Completed;
+                info.eta_s = None;
+            }
+        });
+        info!("Job {} finished", self.id);
+    }
+}
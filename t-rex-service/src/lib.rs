@@ -13,10 +13,20 @@ extern crate serde_json;
 #[cfg(feature = "with-gdal")]
 extern crate t_rex_gdal;
 
+pub mod active_tilesets;
 pub mod datasources;
+pub mod disabled_layers;
+pub mod inflight;
+pub mod jobs;
+pub mod manifest;
 pub mod metadata;
 pub mod mvt_service;
 #[cfg(test)]
 mod mvt_service_test;
+pub mod ogcapi;
 mod qgs_reader;
+pub mod seed_state;
+pub mod tile_diff;
+#[cfg(test)]
+mod tile_diff_test;
 pub use qgs_reader::read_qgs;
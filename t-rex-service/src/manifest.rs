@@ -0,0 +1,206 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+//! Manifest of tiles produced by `generate`
+//!
+//! Written as JSON lines: one record per tile, closed by a summary record.
+//! Every line is flushed immediately, so the manifest of an interrupted run
+//! covers all tiles written so far.
+
+use serde_json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::mem;
+
+/// Number of distinct tile hashes kept per generation for deduplication
+const DEDUP_CAPACITY: usize = 100_000;
+
+#[derive(Serialize, PartialEq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum TileStatus {
+    /// Tile written to the cache
+    Generated,
+    /// Existing cache entry kept
+    Skipped,
+    /// Tile written to the cache, with the same content as a tile generated before
+    Deduplicated,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TileRecord<'a> {
+    #[serde(rename = "type")]
+    pub type_: &'static str,
+    pub tileset: &'a str,
+    pub z: u8,
+    pub x: u32,
+    /// y in the adressing scheme of the cache path
+    pub y: u32,
+    pub path: &'a str,
+    pub status: TileStatus,
+    /// Size in bytes of the (compressed) cache entry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<usize>,
+    /// SHA-256 of the (compressed) cache entry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<String>,
+}
+
+#[derive(Serialize, Default, PartialEq, Debug)]
+pub struct ManifestSummary {
+    pub generated: u64,
+    pub skipped: u64,
+    pub deduplicated: u64,
+    /// Empty tiles not written to the cache
+    pub empty: u64,
+    /// Total size of generated and deduplicated tiles
+    pub bytes: u64,
+}
+
+pub struct Manifest {
+    writer: Box<dyn Write>,
+    hashes: RecentHashes,
+    summary: ManifestSummary,
+}
+
+/// Cache path of first tile with a given hash, keyed by the first 8 bytes of the SHA-256.
+///
+/// Memory is bounded by keeping two generations of at most `capacity` entries each:
+/// when the current generation is full, it replaces the previous one. Hashes found in
+/// the previous generation are moved to the current one, so frequent tiles (e.g. empty
+/// sea tiles) are kept while rare ones are forgotten.
+struct RecentHashes {
+    capacity: usize,
+    current: HashMap<u64, String>,
+    previous: HashMap<u64, String>,
+}
+
+impl RecentHashes {
+    fn new(capacity: usize) -> RecentHashes {
+        RecentHashes {
+            capacity,
+            current: HashMap::new(),
+            previous: HashMap::new(),
+        }
+    }
+    /// Path of a tile with the same hash, or insert `path`
+    fn get_or_insert(&mut self, digest: &[u8], path: &str) -> Option<String> {
+        let mut key = [0u8; 8];
+        key.copy_from_slice(&digest[..8]);
+        let key = u64::from_be_bytes(key);
+        if let Some(first) = self.current.get(&key) {
+            return Some(first.clone());
+        }
+        let first = self.previous.remove(&key);
+        if self.current.len() >= self.capacity {
+            self.previous = mem::take(&mut self.current);
+        }
+        let path = first.clone().unwrap_or_else(|| path.to_string());
+        self.current.insert(key, path);
+        first
+    }
+}
+
+impl Manifest {
+    /// Create manifest file
+    pub fn create(path: &str) -> io::Result<Manifest> {
+        let file = File::create(path)?;
+        Ok(Manifest::new(Box::new(LineWriter::new(file))))
+    }
+    pub fn new(writer: Box<dyn Write>) -> Manifest {
+        Manifest {
+            writer,
+            hashes: RecentHashes::new(DEDUP_CAPACITY),
+            summary: ManifestSummary::default(),
+        }
+    }
+    /// Record a tile written to the cache
+    pub fn tile_written(
+        &mut self,
+        tileset: &str,
+        z: u8,
+        x: u32,
+        y: u32,
+        path: &str,
+        data: &[u8],
+    ) {
+        let digest = Sha256::digest(data);
+        let hash = hex(&digest);
+        let duplicate_of = self.hashes.get_or_insert(&digest, path);
+        let status = if duplicate_of.is_some() {
+            self.summary.deduplicated += 1;
+            TileStatus::Deduplicated
+        } else {
+            self.summary.generated += 1;
+            TileStatus::Generated
+        };
+        self.summary.bytes += data.len() as u64;
+        self.write_record(&TileRecord {
+            type_: "tile",
+            tileset,
+            z,
+            x,
+            y,
+            path,
+            status,
+            size: Some(data.len()),
+            sha256: Some(hash),
+            duplicate_of,
+        });
+    }
+    /// Record an existing cache entry
+    pub fn tile_skipped(&mut self, tileset: &str, z: u8, x: u32, y: u32, path: &str) {
+        self.summary.skipped += 1;
+        self.write_record(&TileRecord {
+            type_: "tile",
+            tileset,
+            z,
+            x,
+            y,
+            path,
+            status: TileStatus::Skipped,
+            size: None,
+            sha256: None,
+            duplicate_of: None,
+        });
+    }
+    /// Count an empty tile, which is not written to the cache
+    pub fn tile_empty(&mut self) {
+        self.summary.empty += 1;
+    }
+    pub fn summary(&self) -> &ManifestSummary {
+        &self.summary
+    }
+    /// Override the number of hashes kept per generation for deduplication
+    #[cfg(test)]
+    pub(crate) fn set_dedup_capacity(&mut self, capacity: usize) {
+        self.hashes.capacity = capacity;
+    }
+    /// Write summary record and close manifest
+    pub fn finish(mut self) {
+        let mut json = serde_json::to_value(&self.summary).unwrap();
+        json.as_object_mut()
+            .unwrap()
+            .insert("type".to_string(), json!("summary"));
+        self.write_line(&json.to_string());
+        let _ = self.writer.flush();
+    }
+    fn write_record(&mut self, record: &TileRecord) {
+        let line = serde_json::to_string(record).unwrap();
+        self.write_line(&line);
+    }
+    fn write_line(&mut self, line: &str) {
+        if let Err(e) = writeln!(self.writer, "{}", line) {
+            error!("Error writing manifest: {}", e);
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
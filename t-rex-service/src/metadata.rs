@@ -6,57 +6,241 @@
 use crate::mvt_service::MvtService;
 use serde_json;
 use std::cmp;
+use std::time::{SystemTime, UNIX_EPOCH};
+use t_rex_core::cache::Cache;
+use t_rex_core::core::layer::Layer;
 use t_rex_core::datasource::DatasourceType;
+use t_rex_core::service::tileset::Tileset;
+use tile_grid::Grid;
 
 type JsonResult = Result<serde_json::Value, serde_json::error::Error>;
 
-impl MvtService {
-    /// Service metadata for backend web application
-    pub fn get_mvt_metadata(&self) -> JsonResult {
-        #[derive(Serialize)]
-        struct MvtInfo {
-            tilesets: Vec<TilesetInfo>,
+fn tileset_not_found(tileset: &str) -> serde_json::error::Error {
+    serde::de::Error::custom(format!("Tileset '{}' not found", tileset))
+}
+
+/// Unix timestamp in seconds
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Tile URL templates of `tileset` with base URLs `tile_urls` or `baseurl`
+fn tile_url_templates(baseurl: &str, tile_urls: &[String], tileset: &str) -> serde_json::Value {
+    let template = |url: &str| format!("{}/{}/{{z}}/{{x}}/{{y}}.pbf", url, tileset);
+    if tile_urls.is_empty() {
+        json!([template(baseurl)])
+    } else {
+        json!(tile_urls
+            .iter()
+            .map(|url| template(url))
+            .collect::<Vec<_>>())
+    }
+}
+
+/// Style shorthands with their property group and layer type
+const STYLE_SHORTHANDS: &[(&str, &str, &str)] = &[
+    ("fill-color", "paint", "fill"),
+    ("line-color", "paint", "line"),
+    ("line-width", "paint", "line"),
+    ("circle-color", "paint", "circle"),
+    ("circle-radius", "paint", "circle"),
+    ("text-field", "layout", "symbol"),
+];
+
+/// Zoom level of outlines added to fill layers
+const OUTLINE_MINZOOM: u8 = 14;
+/// Zoom level of generated label layers
+const LABEL_MINZOOM: u8 = 14;
+
+/// Attribute used for labels: `name`, a localized name like `name_de` or `label`
+fn label_field(fields: &[(String, String)]) -> Option<&str> {
+    let names = fields.iter().map(|(name, _)| name.as_str());
+    names
+        .clone()
+        .find(|name| *name == "name")
+        .or_else(|| {
+            names
+                .clone()
+                .find(|name| name.starts_with("name_") || name.starts_with("name:"))
+        })
+        .or_else(|| names.clone().find(|name| *name == "label"))
+}
+
+/// Add zoom dependent defaults for missing paint properties:
+/// line widths and circle radii increasing with zoom, outlines of fill layers at high zooms.
+fn add_zoom_defaults(layerjson: &mut serde_json::Value) {
+    let obj = layerjson.as_object_mut().unwrap();
+    let layer_type = obj["type"].as_str().unwrap_or("").to_string();
+    let paint = match obj
+        .entry("paint".to_string())
+        .or_insert(json!({}))
+        .as_object_mut()
+    {
+        Some(paint) => paint,
+        None => return,
+    };
+    match &layer_type as &str {
+        "line" => {
+            paint.entry("line-width".to_string()).or_insert(json!([
+                "interpolate",
+                ["exponential", 1.4],
+                ["zoom"],
+                5,
+                0.5,
+                18,
+                4
+            ]));
+        }
+        "circle" => {
+            paint.entry("circle-radius".to_string()).or_insert(json!([
+                "interpolate",
+                ["linear"],
+                ["zoom"],
+                5,
+                2,
+                18,
+                6
+            ]));
         }
-        #[derive(Serialize)]
-        struct TilesetInfo {
-            name: String,
-            tilejson: String,
-            tileurl: String,
-            bounds: [f64; 4],
-            layers: Vec<LayerInfo>,
-            supported: bool,
+        "fill" => {
+            // Outline in fill color (default of fill-outline-color) below OUTLINE_MINZOOM
+            let fill_color = paint.get("fill-color").cloned().unwrap_or(json!("#000000"));
+            if !fill_color.is_object() {
+                paint
+                    .entry("fill-outline-color".to_string())
+                    .or_insert(json!([
+                        "step",
+                        ["zoom"],
+                        fill_color,
+                        OUTLINE_MINZOOM,
+                        "rgba(0, 0, 0, 0.3)"
+                    ]));
+            }
+        }
+        _ => {}
+    }
+    if paint.is_empty() {
+        obj.remove("paint");
+    }
+}
+
+/// Symbol layer with labels from attribute `field`, shown above `LABEL_MINZOOM`
+fn label_layer(layerjson: &serde_json::Value, field: &str) -> serde_json::Value {
+    let mut label = json!({
+        "id": format!("{}_label", layerjson["id"].as_str().unwrap_or("")),
+        "type": "symbol",
+        "source": layerjson["source"],
+        "source-layer": layerjson["source-layer"],
+        "minzoom": cmp::max(LABEL_MINZOOM as u64, layerjson["minzoom"].as_u64().unwrap_or(0)),
+        "layout": {
+            "text-field": ["get", field],
+            "text-font": ["Roboto Regular"],
+            "text-size": 12
+        },
+        "paint": {
+            "text-color": "#333333",
+            "text-halo-color": "rgba(255, 255, 255, 0.8)",
+            "text-halo-width": 1
         }
-        #[derive(Serialize)]
-        struct LayerInfo {
-            name: String,
-            geometry_type: Option<String>,
+    });
+    if let Some(maxzoom) = layerjson.get("maxzoom") {
+        label["maxzoom"] = maxzoom.clone();
+    }
+    match layerjson["type"].as_str() {
+        Some("line") => label["layout"]["symbol-placement"] = json!("line"),
+        Some("circle") => {
+            label["layout"]["text-anchor"] = json!("top");
+            label["layout"]["text-offset"] = json!([0, 0.6]);
+        }
+        _ => {}
+    }
+    label
+}
+
+/// Move style shorthands like `line-color = "#888"` into `paint` or `layout`.
+/// Entries in `paint` and `layout` have precedence, other keys are passed through.
+fn merge_style_shorthands(layerjson: &mut serde_json::Value) {
+    let obj = layerjson.as_object_mut().unwrap();
+    for (key, group, layer_type) in STYLE_SHORTHANDS {
+        if let Some(value) = obj.remove(*key) {
+            if let Some(props) = obj
+                .entry(group.to_string())
+                .or_insert(json!({}))
+                .as_object_mut()
+            {
+                props.entry(key.to_string()).or_insert(value);
+            }
+            obj.entry("type".to_string()).or_insert(json!(layer_type));
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct MvtInfo {
+    tilesets: Vec<TilesetInfo>,
+}
+
+#[derive(Serialize)]
+struct TilesetInfo {
+    name: String,
+    tilejson: String,
+    tileurl: String,
+    bounds: [f64; 4],
+    /// Initial map position [lon, lat, zoom]
+    center: (f64, f64, u8),
+    layers: Vec<LayerInfo>,
+    supported: bool,
+    /// Tile matrix set identifier of the grid (e.g. for WMTS clients)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tile_matrix_set: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LayerInfo {
+    name: String,
+    geometry_type: Option<String>,
+}
+
+impl TilesetInfo {
+    fn new(set: &Tileset, layers: &[&Layer], grid: &Grid, path_prefix: &str) -> TilesetInfo {
+        let layerinfos = layers
+            .iter()
+            .map(|l| LayerInfo {
+                name: l.name.clone(),
+                geometry_type: l.geometry_type.clone(),
+            })
+            .collect();
+        let supported = layers.iter().any(|l| {
+            let geom_type = l.geometry_type.clone().unwrap_or("UNKNOWN".to_string());
+            ["POINT", "LINESTRING", "POLYGON"].contains(&(&geom_type as &str))
+        });
+        let ext = set.get_extent();
+        let center = set.get_center();
+        TilesetInfo {
+            name: set.name.clone(),
+            tilejson: format!("{}.json", set.name),
+            tileurl: format!("{}/{}/{{z}}/{{x}}/{{y}}.pbf", path_prefix, set.name),
+            bounds: [ext.minx, ext.miny, ext.maxx, ext.maxy],
+            center: (center.0, center.1, set.get_start_zoom()),
+            layers: layerinfos,
+            supported: supported,
+            tile_matrix_set: grid.tile_matrix_set.clone(),
         }
+    }
+}
 
+impl MvtService {
+    /// Service metadata for backend web application.
+    /// Tile URLs are prefixed with `path_prefix` ("" or "/prefix").
+    pub fn get_mvt_metadata(&self, path_prefix: &str) -> JsonResult {
         let mut tileset_infos: Vec<TilesetInfo> = self
             .tilesets
             .iter()
-            .map(|set| {
-                let layerinfos = set
-                    .layers
-                    .iter()
-                    .map(|l| LayerInfo {
-                        name: l.name.clone(),
-                        geometry_type: l.geometry_type.clone(),
-                    })
-                    .collect();
-                let supported = set.layers.iter().any(|l| {
-                    let geom_type = l.geometry_type.clone().unwrap_or("UNKNOWN".to_string());
-                    ["POINT", "LINESTRING", "POLYGON"].contains(&(&geom_type as &str))
-                });
-                let ext = set.get_extent();
-                TilesetInfo {
-                    name: set.name.clone(),
-                    tilejson: format!("{}.json", set.name),
-                    tileurl: format!("/{}/{{z}}/{{x}}/{{y}}.pbf", set.name),
-                    bounds: [ext.minx, ext.miny, ext.maxx, ext.maxy],
-                    layers: layerinfos,
-                    supported: supported,
-                }
+            .map(|ts| {
+                let layers = self.get_tileset_layers(&ts.name);
+                TilesetInfo::new(ts, &layers, &self.grid, path_prefix)
             })
             .collect();
         tileset_infos.sort_by_key(|ti| ti.name.clone());
@@ -65,10 +249,21 @@ impl MvtService {
         };
         serde_json::to_value(mvt_info)
     }
+    /// Service metadata restricted to a single tileset
+    pub fn get_tileset_mvt_metadata(&self, tileset: &str, path_prefix: &str) -> JsonResult {
+        let ts = self
+            .get_tileset(tileset)
+            .ok_or_else(|| tileset_not_found(tileset))?;
+        let layers = self.get_tileset_layers(&ts.name);
+        let mvt_info = MvtInfo {
+            tilesets: vec![TilesetInfo::new(ts, &layers, &self.grid, path_prefix)],
+        };
+        serde_json::to_value(mvt_info)
+    }
     fn get_tilejson_metadata(&self, tileset: &str) -> JsonResult {
         let ts = self
             .get_tileset(tileset)
-            .expect(&format!("Tileset '{}' not found", tileset));
+            .ok_or_else(|| tileset_not_found(tileset))?;
         let ext = ts.get_extent();
         let center = ts.get_center();
         let zoom = ts.get_start_zoom();
@@ -79,7 +274,7 @@ impl MvtService {
             "attribution": ts.attribution(),
             "format": "pbf",
             "version": "2.0.0",
-            "scheme": "xyz",
+            "scheme": self.tile_scheme(),
             "bounds": [ext.minx,
                        ext.miny,
                        ext.maxx,
@@ -90,7 +285,7 @@ impl MvtService {
             // Maximum zoom level for which tiles are available.
             // Data from tiles at the maxzoom are used when displaying the map at higher zoom levels.
             // Optional. Default: 30. >= 0, <= 30. (Mapbox Style default: 22)
-            "maxzoom": ts.maxzoom(),
+            "maxzoom": cmp::min(ts.maxzoom(), self.grid.maxzoom()),
             "center": [center.0, center.1, zoom],
             "basename": tileset
         }))
@@ -98,7 +293,7 @@ impl MvtService {
     fn get_tilejson_layers(&self, tileset: &str) -> JsonResult {
         let ts = self
             .get_tileset(tileset)
-            .expect(&format!("Tileset '{}' not found", tileset));
+            .ok_or_else(|| tileset_not_found(tileset))?;
         let layers = self.get_tileset_layers(tileset);
         let layers_metadata: Vec<serde_json::Value> = layers
             .iter()
@@ -135,7 +330,10 @@ impl MvtService {
     fn get_tilejson_vector_layers(&self, tileset: &str) -> JsonResult {
         let ts = self
             .get_tileset(tileset)
-            .expect(&format!("Tileset '{}' not found", tileset));
+            .ok_or_else(|| tileset_not_found(tileset))?;
+        if let Some(ref archive) = ts.mbtiles {
+            return Ok(archive.vector_layers().unwrap_or(json!([])));
+        }
         let layers = self.get_tileset_layers(tileset);
         let vector_layers: Vec<serde_json::Value> = layers
             .iter()
@@ -154,12 +352,23 @@ impl MvtService {
                     "fields": {}
                 });
                 //insert fields
-                let fields = self.ds(&layer).unwrap().detect_data_columns(&layer, query);
-                for (ref field, _) in fields {
+                let fields = self.ds(&layer).unwrap().detect_field_types(&layer, query);
+                let mut field_zooms = serde_json::Map::new();
+                for (ref field, ref field_type) in fields {
                     layer_json["fields"]
                         .as_object_mut()
                         .unwrap()
-                        .insert(field.clone(), json!(""));
+                        .insert(field.clone(), json!(field_type));
+                    if let Some((minzoom, maxzoom)) = layer.attribute_zoom_range(field) {
+                        field_zooms.insert(
+                            field.clone(),
+                            json!({"minzoom": minzoom, "maxzoom": maxzoom}),
+                        );
+                    }
+                }
+                // Vendor extension: zoom levels of fields not included in all tiles
+                if !field_zooms.is_empty() {
+                    layer_json["t-rex:field_zooms"] = json!(field_zooms);
                 }
                 layer_json
             })
@@ -167,17 +376,20 @@ impl MvtService {
         Ok(json!(vector_layers))
     }
     /// TileJSON metadata (https://github.com/mapbox/tilejson-spec)
-    pub fn get_tilejson(&self, baseurl: &str, tileset: &str) -> JsonResult {
+    /// Tile URLs are based on `tile_urls` if not empty, otherwise on `baseurl`.
+    pub fn get_tilejson(&self, baseurl: &str, tile_urls: &[String], tileset: &str) -> JsonResult {
         let mut metadata = self.get_tilejson_metadata(tileset)?;
         let vector_layers = self.get_tilejson_vector_layers(tileset)?;
-        let url = json!([format!("{}/{}/{{z}}/{{x}}/{{y}}.pbf", baseurl, tileset)]);
+        let url = tile_url_templates(baseurl, tile_urls, tileset);
         let obj = metadata.as_object_mut().unwrap();
         obj.insert("tiles".to_string(), url);
         obj.insert("vector_layers".to_string(), vector_layers);
         Ok(json!(obj))
     }
     /// MapboxGL Style JSON (https://www.mapbox.com/mapbox-gl-style-spec/)
-    pub fn get_stylejson(&self, baseurl: &str, tileset: &str) -> JsonResult {
+    /// The tileset source has tile URLs based on `tile_urls`, if not empty, and the
+    /// tileset attribution, also in sources of a default style.
+    pub fn get_stylejson(&self, baseurl: &str, tile_urls: &[String], tileset: &str) -> JsonResult {
         let mut stylejson = json!({
             "version": 8,
             "name": "t-rex",
@@ -194,6 +406,20 @@ impl MvtService {
                 }
             }
         });
+        if !tile_urls.is_empty() {
+            stylejson["sources"][tileset]["tiles"] =
+                tile_url_templates(baseurl, tile_urls, tileset);
+        }
+        let ts = self
+            .get_tileset(tileset)
+            .ok_or_else(|| tileset_not_found(tileset))?;
+        let attribution = ts.attribution();
+        if !attribution.is_empty() {
+            stylejson["sources"][tileset]["attribution"] = json!(attribution);
+        }
+        let center = ts.get_center();
+        stylejson["center"] = json!([center.0, center.1]);
+        stylejson["zoom"] = json!(ts.get_start_zoom());
         let background_layer = json!({
           "id": "background_",
           "type": "background",
@@ -210,6 +436,7 @@ impl MvtService {
                 } else {
                     json!({})
                 };
+                merge_style_shorthands(&mut layerjson);
                 layerjson
                     .as_object_mut()
                     .unwrap()
@@ -227,11 +454,26 @@ impl MvtService {
                 // minzoom:
                 // The minimum zoom level for the layer. At zoom levels less than the minzoom, the layer will be hidden.
                 // Optional number between 0 and 24 inclusive.
+                let minzoom = layer.minzoom();
+                if minzoom > 0 {
+                    layerjson
+                        .as_object_mut()
+                        .unwrap()
+                        .entry("minzoom".to_string())
+                        .or_insert(json!(minzoom));
+                }
                 // maxzoom:
                 // The maximum zoom level for the layer. At zoom levels equal to or greater than the maxzoom, the layer will be hidden.
                 // Optional number between 0 and 24 inclusive.
-                // Note: We could use source data min-/maxzoom as default to prevent overzooming
-                // or we could add style.minzoom, style.maxzoom elements
+                // Layers reaching the tileset maxzoom stay visible in overzoomed tiles.
+                let maxzoom = layer.maxzoom(22);
+                if maxzoom < ts.maxzoom() {
+                    layerjson
+                        .as_object_mut()
+                        .unwrap()
+                        .entry("maxzoom".to_string())
+                        .or_insert(json!(maxzoom + 1));
+                }
 
                 // Default paint type
                 let default_type = if let Some(ref geomtype) = layer.geometry_type {
@@ -248,22 +490,67 @@ impl MvtService {
                     .unwrap()
                     .entry("type".to_string())
                     .or_insert(json!(default_type));
+                add_zoom_defaults(&mut layerjson);
 
                 layerjson
             })
             .collect();
+        // Label layers above all other layers, for layers without configured style
+        let mut label_layers = Vec::new();
+        for (layer, layerjson) in layers.iter().zip(layer_styles.iter()) {
+            if layer.style.is_some() {
+                continue;
+            }
+            let query = layer.query(layer.maxzoom(22));
+            let fields = self
+                .ds(&layer)
+                .map(|ds| ds.detect_data_columns(&layer, query))
+                .unwrap_or_default();
+            if let Some(field) = label_field(&fields) {
+                let label = label_layer(layerjson, field);
+                if label["maxzoom"].as_u64().unwrap_or(24) > label["minzoom"].as_u64().unwrap_or(0)
+                {
+                    label_layers.push(label);
+                }
+            }
+        }
+        layer_styles.extend(label_layers);
         layer_styles.insert(0, background_layer);
         // Insert layers in stylejson
         let obj = stylejson.as_object_mut().unwrap();
         obj.insert("layers".to_string(), json!(layer_styles));
+        if let Some(ref style) = ts.default_style {
+            // Default style of tileset with generated entries for missing keys
+            let mut preset: serde_json::Value = serde_json::from_str(style)?;
+            if let Some(preset_obj) = preset.as_object_mut() {
+                for (key, value) in obj.iter() {
+                    preset_obj.entry(key.clone()).or_insert(value.clone());
+                }
+                if let Some(source) = preset_obj
+                    .get_mut("sources")
+                    .and_then(|sources| sources.get_mut(tileset))
+                    .and_then(|source| source.as_object_mut())
+                {
+                    if !attribution.is_empty() {
+                        source
+                            .entry("attribution".to_string())
+                            .or_insert(json!(attribution));
+                    }
+                }
+                return Ok(preset);
+            }
+        }
         Ok(json!(obj))
     }
 
     /// MBTiles metadata.json (https://github.com/mapbox/mbtiles-spec/blob/master/1.3/spec.md)
     pub fn get_mbtiles_metadata(&self, tileset: &str) -> JsonResult {
         let mut metadata = self.get_tilejson_metadata(tileset)?;
-        metadata["bounds"] = json!(metadata["bounds"].to_string());
-        metadata["center"] = json!(metadata["center"].to_string());
+        // Comma separated values without brackets
+        for key in &["bounds", "center"] {
+            let values = metadata[key].to_string();
+            metadata[key] = json!(values.trim_start_matches('[').trim_end_matches(']'));
+        }
         let layers = self.get_tilejson_layers(tileset)?;
         let vector_layers = self.get_tilejson_vector_layers(tileset)?;
         let metadata_vector_layers = json!({
@@ -277,6 +564,46 @@ impl MvtService {
         );
         Ok(json!(obj))
     }
+
+    /// Number of cached tiles with the creation time of the oldest and newest tile per zoom level
+    /// (Unix timestamps). Collected from the cache on request, which requires a directory scan
+    /// for file caches. Tiles of languages and layer parameters are not included.
+    /// `zoom_levels` is null for caches without creation times.
+    pub fn get_tileset_freshness(&self, tileset: &str) -> JsonResult {
+        let ts = self
+            .get_tileset(tileset)
+            .ok_or_else(|| tileset_not_found(tileset))?;
+        let times = match self.cache.tile_times(&ts.name) {
+            Some(times) => times,
+            None => {
+                return Ok(json!({
+                    "tileset": ts.name,
+                    "zoom_levels": null,
+                    "oldest": null,
+                    "newest": null
+                }))
+            }
+        };
+        let zoom_levels: Vec<serde_json::Value> = times
+            .iter()
+            .map(|(zoom, t)| {
+                json!({
+                    "zoom": zoom,
+                    "tiles": t.tiles,
+                    "oldest": unix_time(t.oldest),
+                    "newest": unix_time(t.newest)
+                })
+            })
+            .collect();
+        let oldest = times.values().map(|t| unix_time(t.oldest)).min();
+        let newest = times.values().map(|t| unix_time(t.newest)).max();
+        Ok(json!({
+            "tileset": ts.name,
+            "zoom_levels": zoom_levels,
+            "oldest": oldest,
+            "newest": newest
+        }))
+    }
 }
 
 #[cfg(test)]
@@ -289,7 +616,7 @@ fn test_mvt_metadata() {
     let config = read_config("src/test/example.toml").unwrap();
     let service = MvtService::from_config(&config).unwrap();
 
-    let metadata = format!("{:#}", service.get_mvt_metadata().unwrap());
+    let metadata = format!("{:#}", service.get_mvt_metadata("").unwrap());
     let expected = r#"{
   "tilesets": [
     {
@@ -299,6 +626,11 @@ fn test_mvt_metadata() {
         180.0,
         90.0
       ],
+      "center": [
+        0.0,
+        0.0,
+        2
+      ],
       "layers": [
         {
           "geometry_type": "POINT",
@@ -315,6 +647,7 @@ fn test_mvt_metadata() {
       ],
       "name": "osm",
       "supported": true,
+      "tile_matrix_set": "WebMercatorQuad",
       "tilejson": "osm.json",
       "tileurl": "/osm/{z}/{x}/{y}.pbf"
     }
@@ -337,7 +670,9 @@ fn test_tilejson() {
     service.prepare_feature_queries();
     let metadata = format!(
         "{:#}",
-        service.get_tilejson("http://127.0.0.1", "osm").unwrap()
+        service
+            .get_tilejson("http://127.0.0.1", &[], "osm")
+            .unwrap()
     );
     println!("{}", metadata);
     let expected = r#"{
@@ -397,10 +732,14 @@ fn test_stylejson() {
     use t_rex_core::core::read_config;
 
     let config = read_config("src/test/example.toml").unwrap();
-    let service = MvtService::from_config(&config).unwrap();
+    let mut service = MvtService::from_config(&config).unwrap();
+    service.tilesets[0].layers[0].minzoom = Some(4);
+    service.tilesets[0].layers[2].maxzoom = Some(8);
     let json = format!(
         "{:#}",
-        service.get_stylejson("http://127.0.0.1", "osm").unwrap()
+        service
+            .get_stylejson("http://127.0.0.1", &[], "osm")
+            .unwrap()
     );
     println!("{}", json);
     let expected = r#"
@@ -411,7 +750,8 @@ fn test_stylejson() {
       "url": "http://127.0.0.1/osm.json"
     }
   },
-  "version": 8
+  "version": 8,
+  "zoom": 2
 "#;
     assert!(json.contains(expected));
     let expected = r#"
@@ -430,13 +770,99 @@ fn test_stylejson() {
     let expected = r##"
       "paint": {
         "fill-color": "#d8e8c8",
-        "fill-opacity": 0.5
-      },"##;
+        "fill-opacity": 0.5,"##;
     assert!(json.contains(expected));
 
     let expected = r#"
       "id": "buildings","#;
     assert!(json.contains(expected));
+
+    // Layer zoom ranges (maxzoom is exclusive)
+    let style: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(style["center"], json!([0.0, 0.0]));
+    let layers = style["layers"].as_array().unwrap();
+    assert_eq!(layers[1]["minzoom"], json!(4));
+    assert_eq!(layers[1].get("maxzoom"), None);
+    assert_eq!(layers[2].get("minzoom"), None);
+    assert_eq!(layers[2].get("maxzoom"), None);
+    assert_eq!(layers[3]["maxzoom"], json!(9));
+
+    // Zoom dependent defaults
+    assert_eq!(layers[2]["type"], json!("line"));
+    assert_eq!(
+        layers[2]["paint"]["line-width"],
+        json!(["interpolate", ["exponential", 1.4], ["zoom"], 5, 0.5, 18, 4])
+    );
+    assert_eq!(
+        layers[3]["paint"]["fill-outline-color"],
+        json!(["step", ["zoom"], "#d8e8c8", 14, "rgba(0, 0, 0, 0.3)"])
+    );
+    // No label layers without detected fields
+    assert_eq!(layers.len(), 4);
+}
+
+#[test]
+fn test_default_stylejson() {
+    use t_rex_core::core::read_config;
+
+    let config = read_config("src/test/example.toml").unwrap();
+    let mut service = MvtService::from_config(&config).unwrap();
+    service.tilesets[0].default_style = Some(
+        r##"{"name": "OSM demo", "layers": [
+            {"id": "water", "type": "fill", "source": "osm", "source-layer": "water"}]}"##
+            .to_string(),
+    );
+    let style = service
+        .get_stylejson("http://127.0.0.1", &[], "osm")
+        .unwrap();
+    assert_eq!(style["name"], json!("OSM demo"));
+    assert_eq!(style["layers"].as_array().unwrap().len(), 1);
+    // Generated entries for missing keys
+    assert_eq!(style["version"], json!(8));
+    assert_eq!(
+        style["sources"]["osm"]["url"],
+        json!("http://127.0.0.1/osm.json")
+    );
+}
+
+#[test]
+fn test_stylejson_shorthands() {
+    use t_rex_core::core::read_config;
+
+    let config = read_config("src/test/example.toml").unwrap();
+    let mut service = MvtService::from_config(&config).unwrap();
+    service.tilesets[0].layers[0].style = Some(
+        r##"{"circle-color": "#e55e5e", "circle-radius": 4, "circle-blur": 0.5,
+            "paint": {"circle-radius": 6}}"##
+            .to_string(),
+    );
+    service.tilesets[0].layers[1].style =
+        Some(r##"{"line-color": "#888", "line-width": 2, "text-field": "{name}"}"##.to_string());
+    let style = service
+        .get_stylejson("http://127.0.0.1", &[], "osm")
+        .unwrap();
+    let layers = style["layers"].as_array().unwrap();
+
+    assert_eq!(layers[1]["type"], json!("circle"));
+    assert_eq!(
+        layers[1]["paint"],
+        json!({"circle-color": "#e55e5e", "circle-radius": 6})
+    );
+    // Unknown keys are passed through
+    assert_eq!(layers[1]["circle-blur"], json!(0.5));
+
+    // Type of the first shorthand
+    assert_eq!(layers[2]["type"], json!("line"));
+    assert_eq!(
+        layers[2]["paint"],
+        json!({"line-color": "#888", "line-width": 2})
+    );
+    assert_eq!(layers[2]["layout"], json!({"text-field": "{name}"}));
+
+    // Layer without shorthands
+    assert_eq!(layers[3]["type"], json!("fill"));
+    assert_eq!(layers[3]["paint"]["fill-color"], json!("#d8e8c8"));
+    assert_eq!(layers[3]["paint"]["fill-opacity"], json!(0.5));
 }
 
 #[test]
@@ -454,8 +880,8 @@ fn test_mbtiles_metadata() {
     let expected = r#"{
   "attribution": "",
   "basename": "osm",
-  "bounds": "[-180.0,-90.0,180.0,90.0]",
-  "center": "[0.0,0.0,2]",
+  "bounds": "-180.0,-90.0,180.0,90.0",
+  "center": "0.0,0.0,2",
   "description": "osm",
   "format": "pbf",
   "id": "osm",
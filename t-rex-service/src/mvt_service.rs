@@ -3,22 +3,42 @@
 // Licensed under the MIT License. See LICENSE file in the project root for full license information.
 //
 
+use crate::active_tilesets::{ActiveTilesets, TilesetDatasources};
 use crate::datasources::{Datasource, Datasources};
+use crate::disabled_layers::DisabledLayers;
+use crate::inflight::{InflightRequest, InflightRequests, RequestState};
+use crate::jobs::JobHandle;
+use crate::manifest::Manifest;
+use crate::seed_state::SeedState;
 use pbr::ProgressBar;
 use percent_encoding::percent_decode;
 use serde_json;
+use std::borrow::Cow;
 use std::cmp;
+use std::collections::HashMap;
 use std::io::{stderr, Stderr, Stdout};
-use std::time::Instant;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 use t_rex_core::cache::{Cache, Tilecache};
-use t_rex_core::core::layer::Layer;
+use t_rex_core::core::feature::{Feature, FeatureStruct};
+use t_rex_core::core::layer::{Layer, LocalizedFeature, ParamValues, PARAM_PREFIX};
 use t_rex_core::core::stats::Statistics;
 use t_rex_core::core::{ApplicationCfg, Config};
-use t_rex_core::datasource::DatasourceType;
-use t_rex_core::mvt::tile::Tile;
+use t_rex_core::datasource::{is_pool_timeout, DatasourceType};
+use t_rex_core::flatgeobuf::encoder as fgb;
+use t_rex_core::geobuf::encoder::FeatureCollection;
+use t_rex_core::mvt::tile::{Tile, MAX_OVERZOOM};
 use t_rex_core::mvt::vector_tile;
-use t_rex_core::service::tileset::{Tileset, WORLD_EXTENT};
-use tile_grid::{extent_to_merc, Extent, ExtentInt, Grid, GridIterator};
+use t_rex_core::service::mbtiles::MbtilesArchive;
+use t_rex_core::service::tileset::{
+    check_tileset_aliases, normalize_tileset_name, Tileset, WORLD_EXTENT,
+};
+use tile_grid::{
+    extent_to_merc, Extent, ExtentInt, Grid, GridIterator, Origin, TmsTile, Unit, XyzTile,
+};
 
 /// Mapbox Vector Tile Service
 #[derive(Clone)]
@@ -27,8 +47,108 @@ pub struct MvtService {
     pub grid: Grid,
     pub tilesets: Vec<Tileset>,
     pub cache: Tilecache,
+    /// Tile limits of tilesets with configured extent, buffered by one tile
+    pub tileset_limits: HashMap<String, Vec<ExtentInt>>,
+    /// Tile requests in progress
+    pub requests: InflightRequests,
+    /// Layers disabled while their table is missing
+    pub disabled_layers: DisabledLayers,
+    /// Tilesets prepared on first request (lazy loading), `None` if all are prepared at startup
+    pub active_tilesets: Option<ActiveTilesets>,
 }
 
+/// Durations of tile creation phases
+#[derive(Default, Debug)]
+pub struct TileTimings {
+    /// Cache lookup
+    pub cache: Duration,
+    /// Datasource queries
+    pub db: Duration,
+    /// Feature and protobuf encoding
+    pub encode: Duration,
+    /// Compression and decompression
+    pub gzip: Duration,
+}
+
+impl TileTimings {
+    /// Value of `Server-Timing` HTTP header (durations in milliseconds)
+    pub fn server_timing(&self) -> String {
+        let ms = |d: &Duration| d.as_secs_f64() * 1000.0;
+        format!(
+            "cache;dur={:.1}, db;dur={:.1}, encode;dur={:.1}, gzip;dur={:.1}",
+            ms(&self.cache),
+            ms(&self.db),
+            ms(&self.encode),
+            ms(&self.gzip)
+        )
+    }
+}
+
+/// Information about a tile returned with its data
+#[derive(Default, Debug)]
+pub struct TileInfo {
+    /// Simplification tolerance in pixels applied to fit into `max_tile_bytes`
+    pub tolerance: Option<f64>,
+    pub timings: TileTimings,
+    /// Layers omitted because of query errors (`on_layer_error = "skip"`)
+    pub layer_errors: Vec<String>,
+    /// Creation time of cached tiles
+    pub modified: Option<SystemTime>,
+    /// Cache-Control max-age of tiles with uncached layers (`cache = false`)
+    pub max_age: Option<u32>,
+    /// Number of features. None for cached tiles written by other tools.
+    pub feature_count: Option<usize>,
+}
+
+/// Options of a tile request
+#[derive(Default)]
+pub struct TileOptions<'a> {
+    /// Return gzip compressed tile data
+    pub gzip: bool,
+    /// Language of localized layers
+    pub lang: Option<&'a str>,
+    /// Query parameter values of the layer queries (Default: parameter defaults)
+    pub params: Option<&'a ParamValues>,
+    pub stats: Option<&'a mut Statistics>,
+    /// Return the tile info with the feature count without tile data.
+    /// Missing tiles are counted with the layer queries instead of being created.
+    pub count_only: bool,
+}
+
+/// Tileset layers included in a created tile
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TileLayers {
+    All,
+    /// Layers stored in the tile cache
+    Cached,
+    /// Layers with `cache = false`, queried for each request
+    Uncached,
+}
+
+impl TileLayers {
+    fn includes(self, layer: &Layer) -> bool {
+        match self {
+            TileLayers::All => true,
+            TileLayers::Cached => !layer.no_cache,
+            TileLayers::Uncached => layer.no_cache,
+        }
+    }
+}
+
+/// Simplification tolerance in pixels of first auto-simplification iteration
+const AUTO_SIMPLIFY_MIN_TOLERANCE: f64 = 0.5;
+/// Maximal number of auto-simplifications with doubled tolerance
+const AUTO_SIMPLIFY_MAX_ITERATIONS: u8 = 5;
+
+/// Maximal delay of a job tile waiting for interactive requests
+const JOB_MAX_WAIT: Duration = Duration::from_secs(1);
+
+/// Minimal interval between logged query errors of the same layer
+const LAYER_ERROR_LOG_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Number of tile requests outside of tileset extents
+static OUT_OF_BOUNDS_REQUESTS: AtomicUsize = AtomicUsize::new(0);
+
 impl MvtService {
     /// Connect all datasources
     // Needed before calling methods on PostGIS datasources like prepare_feature_queries or get_mbtiles_metadata
@@ -45,60 +165,364 @@ impl MvtService {
     pub(crate) fn ds(&self, layer: &Layer) -> Option<&Datasource> {
         self.datasources.datasource(&layer.datasource)
     }
-    pub(crate) fn get_tileset(&self, name: &str) -> Option<&Tileset> {
+    /// Datasources with prepared queries of a tileset. With lazy loading, the queries are
+    /// prepared on the first request of the tileset, evicting the least recently used tileset.
+    pub(crate) fn tileset_datasources(&self, tileset: &str) -> TilesetDatasources<'_> {
+        let active_tilesets = match self.active_tilesets {
+            Some(ref active_tilesets) => active_tilesets,
+            None => return TilesetDatasources::Shared(&self.datasources),
+        };
+        let ts = match self.get_tileset(tileset) {
+            Some(ts) => ts,
+            None => return TilesetDatasources::Shared(&self.datasources),
+        };
+        if let Some(datasources) = active_tilesets.get(&ts.name) {
+            return TilesetDatasources::Active(datasources);
+        }
+        let now = Instant::now();
+        let mut datasources = self.datasources.clone();
+        prepare_tileset_queries(&mut datasources, ts, self.grid.srid);
+        self.check_tileset_queries(&datasources, &ts.name);
+        let datasources = active_tilesets.insert(&ts.name, datasources);
+        info!(
+            "Tileset '{}': queries prepared in {}ms ({} of max. {} active tilesets)",
+            ts.name,
+            now.elapsed().as_millis(),
+            active_tilesets.len(),
+            active_tilesets.max_tilesets()
+        );
+        TilesetDatasources::Active(datasources)
+    }
+    /// Lookup tileset by name as used in URLs and cache paths or by one of its aliases
+    pub fn get_tileset(&self, name: &str) -> Option<&Tileset> {
         // URL decode tileset names from http requests
-        let dec_name = percent_decode(name.as_bytes()).decode_utf8().unwrap();
-        self.tilesets.iter().find(|t| t.name == dec_name)
+        let dec_name = percent_decode(name.as_bytes()).decode_utf8().ok()?;
+        let norm_name = normalize_tileset_name(&dec_name);
+        self.tilesets
+            .iter()
+            .find(|t| t.name == norm_name)
+            .or_else(|| self.tilesets.iter().find(|t| t.aliases.contains(&norm_name)))
     }
-    /// Get layers (as reference) of given tileset
+    /// Tileset name of a tileset alias (other names unchanged)
+    fn canonical_tileset_name<'a>(&'a self, name: &'a str) -> &'a str {
+        self.get_tileset(name)
+            .map(|ts| ts.name.as_str())
+            .unwrap_or(name)
+    }
+    /// Get active layers (as reference) of given tileset. Layers disabled
+    /// because of a missing table are omitted.
     pub(crate) fn get_tileset_layers(&self, name: &str) -> Vec<&Layer> {
         match self.get_tileset(name) {
-            Some(set) => set.layers.iter().map(|l| l).collect(),
+            Some(set) => set
+                .layers
+                .iter()
+                .filter(|l| !self.disabled_layers.contains(&set.name, &l.name))
+                .collect(),
             None => Vec::new(),
         }
     }
+    /// Active layers of given tileset in `layers` with the requested query parameter values bound.
+    /// Layers without parameters are borrowed.
+    fn get_tileset_layers_with_params(
+        &self,
+        name: &str,
+        params: &ParamValues,
+        layers: TileLayers,
+    ) -> Result<Vec<Cow<'_, Layer>>, String> {
+        self.get_tileset_layers(name)
+            .into_iter()
+            .filter(|layer| layers.includes(layer))
+            .map(|layer| {
+                if params.is_empty() || layer.params.is_empty() {
+                    Ok(Cow::Borrowed(layer))
+                } else {
+                    layer.with_param_values(params).map(Cow::Owned)
+                }
+            })
+            .collect()
+    }
+    /// Disable layers with `missing_table = "skip"` while their table is missing
+    /// and enable them again when it reappears.
+    pub fn update_disabled_layers(&self) {
+        for tileset in &self.tilesets {
+            for layer in tileset.layers.iter().filter(|l| l.skip_missing_table) {
+                let exists = match self.ds(layer).and_then(|ds| ds.table_exists(layer)) {
+                    Some(exists) => exists,
+                    None => continue,
+                };
+                if !self
+                    .disabled_layers
+                    .set(&tileset.name, &layer.name, !exists)
+                {
+                    continue;
+                }
+                let table = layer.table_name.as_ref().unwrap();
+                if exists {
+                    info!(
+                        "Tileset '{}': table {} found - layer '{}' enabled",
+                        tileset.name, table, layer.name
+                    );
+                } else {
+                    warn!(
+                        "Tileset '{}': table {} not found - LAYER '{}' DISABLED",
+                        tileset.name, table, layer.name
+                    );
+                }
+            }
+        }
+    }
+    /// Run `update_disabled_layers` periodically in a background thread until `stopped` is set.
+    /// No thread is started without layers having `missing_table = "skip"`.
+    pub fn spawn_missing_table_check(&self, interval: Duration, stopped: Arc<AtomicBool>) {
+        let check = self
+            .tilesets
+            .iter()
+            .any(|ts| ts.layers.iter().any(|l| l.skip_missing_table));
+        if !check {
+            return;
+        }
+        let service = self.clone();
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            if stopped.load(Ordering::Relaxed) {
+                break;
+            }
+            service.update_disabled_layers();
+        });
+    }
     /// Prepare datasource queries. Must be called before requesting tiles.
-    pub fn prepare_feature_queries(&mut self) {
+    /// Returns configuration warnings like mismatching layer geometry types, which are also logged.
+    pub fn prepare_feature_queries(&mut self) -> Vec<String> {
+        let mut tileset_limits = HashMap::new();
         for tileset in &self.tilesets {
-            for layer in &tileset.layers {
-                let ds = self
-                    .datasources
-                    .datasource_mut(&layer.datasource)
-                    .expect(&format!("Datasource of layer `{}` not found", layer.name));
-                ds.prepare_queries(&tileset.name, &layer, self.grid.srid);
+            match tileset.extent {
+                // (-180 -90) throws error when projecting
+                Some(ref ext_wgs84) if *ext_wgs84 != WORLD_EXTENT => {
+                    let ext_proj = self.builtin_extent_from_wgs84(ext_wgs84).or_else(|| {
+                        self.datasources
+                            .default()
+                            .and_then(|ds| ds.extent_from_wgs84(ext_wgs84, self.grid.srid))
+                    });
+                    match ext_proj {
+                        Some(ext_proj) => {
+                            tileset_limits
+                                .insert(tileset.name.clone(), self.grid.tile_limits(ext_proj, 1));
+                        }
+                        None => warn!(
+                            "Tileset '{}': Couldn't transform extent to grid SRS - bounds not enforced",
+                            tileset.name
+                        ),
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.tileset_limits = tileset_limits;
+        self.update_disabled_layers();
+        let mut warnings = self.attribution_warnings();
+        if let Some(ref active_tilesets) = self.active_tilesets {
+            info!(
+                "Lazy loading of tilesets: queries prepared on first request (max. {} active tilesets)",
+                active_tilesets.max_tilesets()
+            );
+            return warnings;
+        }
+        for tileset in &self.tilesets {
+            prepare_tileset_queries(&mut self.datasources, tileset, self.grid.srid);
+        }
+        for tileset in &self.tilesets {
+            warnings.extend(self.check_tileset_queries(&self.datasources, &tileset.name));
+        }
+        warnings
+    }
+    /// Check attribution of tilesets with layers of datasources requiring attribution
+    /// (`attribution_required`). Returns the logged warnings.
+    pub fn attribution_warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for tileset in &self.tilesets {
+            if !tileset.attribution().trim().is_empty() {
+                continue;
+            }
+            let mut datasources: Vec<String> = tileset
+                .layers
+                .iter()
+                .filter(|layer| self.datasources.attribution_required(&layer.datasource))
+                .map(|layer| {
+                    layer
+                        .datasource
+                        .clone()
+                        .or(self.datasources.default.clone())
+                        .unwrap_or_default()
+                })
+                .collect();
+            if datasources.is_empty() {
+                continue;
             }
+            datasources.sort();
+            datasources.dedup();
+            let warning = format!(
+                "Tileset '{}': attribution required by license of datasource '{}' missing",
+                tileset.name,
+                datasources.join("', '")
+            );
+            warn!("{}", warning);
+            warnings.push(warning);
         }
+        warnings
     }
-    /// Create vector tile from input at x, y, z in TMS adressing scheme
+    /// Check prepared queries and geometry types of the active layers of a tileset.
+    /// Returns the logged warnings.
+    fn check_tileset_queries(&self, datasources: &Datasources, tileset: &str) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for layer in self.get_tileset_layers(tileset) {
+            if let Some(ds) = datasources.datasource(&layer.datasource) {
+                if let Err(err) = ds
+                    .check_queries(layer)
+                    .and_then(|_| ds.check_geometry_types(layer))
+                {
+                    warn!("{}", err);
+                    warnings.push(err);
+                }
+            }
+        }
+        warnings
+    }
+    /// Create vector tile from input at x, y, z in grid adressing scheme.
+    /// Tiles failing because of layer query errors are empty.
     pub fn tile(
         &self,
         tileset: &str,
         xtile: u32,
         ytile: u32,
         zoom: u8,
-        mut stats: Option<&mut Statistics>,
+        stats: Option<&mut Statistics>,
     ) -> vector_tile::Tile {
+        let mut info = TileInfo::default();
+        let params = ParamValues::new();
+        self.tile_timed(
+            tileset,
+            xtile,
+            ytile,
+            zoom,
+            None,
+            &params,
+            stats,
+            &mut info,
+            None,
+            TileLayers::All,
+        )
+        .unwrap_or_else(|_| vector_tile::Tile::new())
+    }
+    /// Create vector tile like `tile`, adding durations of datasource queries and encoding to `info`.
+    /// Localized names are resolved for `lang` and layer queries use the parameter values `params`.
+    /// Only the tileset layers selected by `layers` are included.
+    /// Returns an error, if a layer query fails and the tileset doesn't skip layers with errors,
+    /// or if the encoded features exceed `max_tile_memory`.
+    fn tile_timed(
+        &self,
+        tileset: &str,
+        xtile: u32,
+        ytile: u32,
+        zoom: u8,
+        lang: Option<&str>,
+        params: &ParamValues,
+        mut stats: Option<&mut Statistics>,
+        info: &mut TileInfo,
+        request: Option<&InflightRequest>,
+        layers: TileLayers,
+    ) -> Result<vector_tile::Tile, String> {
         let extent = self.grid.tile_extent(xtile, ytile, zoom);
         debug!(
             "{}/{}/{}/{} retrieving with {:?}",
             tileset, zoom, xtile, ytile, extent
         );
         let mut tile = Tile::new(&extent, true);
-        for layer in self.get_tileset_layers(tileset) {
+        let max_memory = self
+            .get_tileset(tileset)
+            .and_then(|ts| ts.max_tile_memory)
+            .map(|bytes| bytes as u64);
+        let mut tile_bytes: u64 = 0;
+        let datasources = self.tileset_datasources(tileset);
+        for layer in self
+            .get_tileset_layers_with_params(tileset, params, layers)?
+            .iter()
+        {
+            let layer: &Layer = layer;
             if zoom >= layer.minzoom() && zoom <= layer.maxzoom(self.grid.maxzoom()) {
+                if layer.use_st_asmvt
+                    && self.add_mvt_layer(
+                        &mut tile, tileset, layer, &extent, zoom, &mut stats, info, request,
+                    )?
+                {
+                    continue;
+                }
                 let mut mvt_layer = tile.new_layer(layer);
                 let now = Instant::now();
-                let num_features = self.ds(&layer).unwrap().retrieve_features(
+                let mut encode = Duration::default();
+                let mut encoding = false;
+                if let Some(request) = request {
+                    request.set_state(RequestState::WaitingForDb);
+                }
+                let mut memory_exceeded = false;
+                let ds = datasources.datasource(&layer.datasource).unwrap();
+                let result = ds.try_retrieve_features_while(
                     tileset,
                     &layer,
                     &extent,
                     zoom,
                     &self.grid,
                     |feat| {
-                        tile.add_feature(&mut mvt_layer, feat);
+                        if !encoding {
+                            if let Some(request) = request {
+                                request.set_state(RequestState::Encoding);
+                            }
+                            encoding = true;
+                        }
+                        let start = Instant::now();
+                        let count = mvt_layer.get_features().len();
+                        let feat = LocalizedFeature {
+                            layer,
+                            feature: feat,
+                            lang,
+                        };
+                        tile.add_feature(&mut mvt_layer, &feat);
+                        encode += start.elapsed();
+                        if let Some(max_memory) = max_memory {
+                            if mvt_layer.get_features().len() > count {
+                                let mvt_feature = &mvt_layer.get_features()[count];
+                                tile_bytes += Tile::feature_size(mvt_feature) as u64;
+                            }
+                            memory_exceeded = tile_bytes > max_memory;
+                        }
+                        !memory_exceeded
                     },
                 );
+                let num_features = match result {
+                    Ok(num_features) => num_features,
+                    Err(err) => {
+                        self.layer_error(tileset, layer, err, info)?;
+                        continue;
+                    }
+                };
+                if memory_exceeded {
+                    let err = format!(
+                        "{}/{}/{}/{} exceeds max_tile_memory of {} bytes in layer {}",
+                        tileset,
+                        zoom,
+                        xtile,
+                        ytile,
+                        max_memory.unwrap_or(0),
+                        layer.name
+                    );
+                    error!("{} - tile not created", err);
+                    if let Some(ref mut stats) = stats {
+                        stats.add(format!("max_tile_memory_exceeded.{}.{}", tileset, zoom), 1);
+                    }
+                    return Err(err);
+                }
                 let elapsed = now.elapsed();
+                info.timings.db += elapsed.checked_sub(encode).unwrap_or_default();
+                info.timings.encode += encode;
                 if let Some(ref mut stats) = stats {
                     stats.add(
                         format!("tile_ms.{}.{}.{}", tileset, layer.name, zoom),
@@ -114,13 +538,468 @@ impl MvtService {
                     tileset, zoom, xtile, ytile, layer.name, num_features
                 );
                 if num_features > 0 {
+                    if layer.deterministic {
+                        Tile::sort_features(&mut mvt_layer);
+                    }
+                    tile.add_layer(mvt_layer);
+                }
+            }
+        }
+        Ok(tile.mvt_tile)
+    }
+    /// Handle a layer query error according to the `on_layer_error` policy of the tileset.
+    /// Errors are counted and logged at most once per layer within `LAYER_ERROR_LOG_INTERVAL`.
+    /// Returns the error, if the tile should fail. Tiles always fail without a free connection.
+    fn layer_error(
+        &self,
+        tileset: &str,
+        layer: &Layer,
+        err: String,
+        info: &mut TileInfo,
+    ) -> Result<(), String> {
+        if is_pool_timeout(&err) {
+            return Err(err);
+        }
+        self.requests
+            .layer_error(tileset, &layer.name, &err, LAYER_ERROR_LOG_INTERVAL);
+        let skip = self
+            .get_tileset(tileset)
+            .map(|ts| ts.skip_layer_errors)
+            .unwrap_or(false);
+        if !skip {
+            return Err(err);
+        }
+        info.layer_errors.push(layer.name.clone());
+        Ok(())
+    }
+    /// Add layer encoded by the datasource (PostGIS ST_AsMVT) to `tile`.
+    /// Returns false, if the datasource doesn't encode the layer.
+    fn add_mvt_layer(
+        &self,
+        tile: &mut Tile,
+        tileset: &str,
+        layer: &Layer,
+        extent: &Extent,
+        zoom: u8,
+        stats: &mut Option<&mut Statistics>,
+        info: &mut TileInfo,
+        request: Option<&InflightRequest>,
+    ) -> Result<bool, String> {
+        if let Some(request) = request {
+            request.set_state(RequestState::WaitingForDb);
+        }
+        let now = Instant::now();
+        let data = self
+            .tileset_datasources(tileset)
+            .datasource(&layer.datasource)
+            .unwrap()
+            .retrieve_mvt_layer(tileset, &layer, extent, zoom, &self.grid);
+        let elapsed = now.elapsed();
+        info.timings.db += elapsed;
+        let data = match data {
+            Ok(Some(data)) => data,
+            Ok(None) => return Ok(false),
+            Err(err) => {
+                self.layer_error(tileset, layer, err, info)?;
+                return Ok(true);
+            }
+        };
+        let mut num_features = 0;
+        match Tile::read_from(&mut &data[..]) {
+            Ok(mut mvt_tile) => {
+                for mut mvt_layer in mvt_tile.take_layers().into_iter() {
+                    num_features += mvt_layer.get_features().len();
+                    if mvt_layer.get_features().len() > 0 {
+                        if layer.deterministic {
+                            Tile::sort_features(&mut mvt_layer);
+                        }
+                        tile.add_layer(mvt_layer);
+                    }
+                }
+            }
+            Err(e) => error!("Layer '{}': invalid ST_AsMVT result: {}", layer.name, e),
+        }
+        if let Some(ref mut stats) = stats {
+            stats.add(
+                format!("tile_ms.{}.{}.{}", tileset, layer.name, zoom),
+                elapsed.as_secs() * 1000 + elapsed.subsec_millis() as u64,
+            );
+            stats.add(
+                format!("feature_count.{}.{}.{}", tileset, layer.name, zoom),
+                num_features as u64,
+            );
+        }
+        Ok(true)
+    }
+    /// Check whether an encoded tile exceeds the `max_tile_bytes` budget of the tileset
+    fn exceeds_tile_budget(&self, tileset: &str, mvt_tile: &vector_tile::Tile) -> bool {
+        match self.get_tileset(tileset).and_then(|ts| ts.max_tile_bytes) {
+            Some(max_bytes) => Tile::size(mvt_tile) > max_bytes,
+            None => false,
+        }
+    }
+    /// Create vector tile like `tile`. If the tile exceeds the `max_tile_bytes` budget
+    /// of the tileset, geometries are simplified with increasing tolerance until it fits.
+    /// The applied tolerance in pixels is set in `info` (None without simplification).
+    /// Returns an error, if a layer query fails and the tileset doesn't skip layers with errors.
+    pub fn tile_auto_simplified(
+        &self,
+        tileset: &str,
+        xtile: u32,
+        ytile: u32,
+        zoom: u8,
+        lang: Option<&str>,
+        params: &ParamValues,
+        mut stats: Option<&mut Statistics>,
+        info: &mut TileInfo,
+        request: Option<&InflightRequest>,
+        layers: TileLayers,
+    ) -> Result<vector_tile::Tile, String> {
+        let mvt_tile = self.tile_timed(
+            tileset,
+            xtile,
+            ytile,
+            zoom,
+            lang,
+            params,
+            stats.as_mut().map(|s| &mut **s),
+            info,
+            request,
+            layers,
+        )?;
+        if !self.exceeds_tile_budget(tileset, &mvt_tile) {
+            return Ok(mvt_tile);
+        }
+        let extent = self.grid.tile_extent(xtile, ytile, zoom);
+        let mut layer_features = Vec::new();
+        if let Some(request) = request {
+            request.set_state(RequestState::WaitingForDb);
+        }
+        let now = Instant::now();
+        let layers = self.get_tileset_layers_with_params(tileset, params, layers)?;
+        let datasources = self.tileset_datasources(tileset);
+        for layer in layers.iter() {
+            let layer: &Layer = layer;
+            if zoom >= layer.minzoom()
+                && zoom <= layer.maxzoom(self.grid.maxzoom())
+                && !info.layer_errors.contains(&layer.name)
+            {
+                let mut features = Vec::new();
+                let ds = datasources.datasource(&layer.datasource).unwrap();
+                ds.retrieve_features(tileset, &layer, &extent, zoom, &self.grid, |feat| {
+                    if let Ok(geometry) = feat.geometry() {
+                        features.push(FeatureStruct {
+                            fid: feat.fid(),
+                            attributes: layer.localize_attributes(feat.attributes(), lang),
+                            geometry,
+                        });
+                    }
+                });
+                layer_features.push((layer, features));
+            }
+        }
+        info.timings.db += now.elapsed();
+        if let Some(request) = request {
+            request.set_state(RequestState::Encoding);
+        }
+        let now = Instant::now();
+        let pixel_width = self.grid.pixel_width(zoom);
+        let mut tolerance = AUTO_SIMPLIFY_MIN_TOLERANCE;
+        let mut iteration = 1;
+        loop {
+            let mut tile = Tile::new(&extent, true);
+            for &(layer, ref features) in &layer_features {
+                let mut mvt_layer = tile.new_layer(layer);
+                let mut num_features = 0;
+                for feature in features {
+                    let simplified = FeatureStruct {
+                        fid: feature.fid,
+                        attributes: feature.attributes.clone(),
+                        geometry: feature.geometry.simplified(tolerance * pixel_width),
+                    };
+                    if !simplified.geometry.is_empty() {
+                        tile.add_feature(&mut mvt_layer, &simplified as &dyn Feature);
+                        num_features += 1;
+                    }
+                }
+                if num_features > 0 {
+                    if layer.deterministic {
+                        Tile::sort_features(&mut mvt_layer);
+                    }
                     tile.add_layer(mvt_layer);
                 }
             }
+            let fits = !self.exceeds_tile_budget(tileset, &tile.mvt_tile);
+            if fits || iteration == AUTO_SIMPLIFY_MAX_ITERATIONS {
+                info.timings.encode += now.elapsed();
+                if !fits {
+                    warn!(
+                        "{}/{}/{}/{} exceeds max_tile_bytes with simplification tolerance {} px",
+                        tileset, zoom, xtile, ytile, tolerance
+                    );
+                }
+                if let Some(ref mut stats) = stats {
+                    stats.add(
+                        format!("simplify_iterations.{}.{}", tileset, zoom),
+                        iteration as u64,
+                    );
+                }
+                debug!(
+                    "{}/{}/{}/{} simplified with tolerance {} px",
+                    tileset, zoom, xtile, ytile, tolerance
+                );
+                info.tolerance = Some(tolerance);
+                return Ok(tile.mvt_tile);
+            }
+            tolerance *= 2.0;
+            iteration += 1;
+        }
+    }
+    /// Create vector tiles for a block of tiles (metatile) with one datasource query per layer.
+    /// `limits` are tile indices in grid adressing scheme (maximum exclusive).
+    ///
+    /// Each feature is added to all tiles whose extent buffered by the layer `buffer_size`
+    /// it intersects, clipped to this extent if the datasource clips geometries.
+    /// A `query_limit` applies to each tile. Layers with `cache = false` are omitted.
+    /// Returns an error like `tile_timed`, if a layer query fails or a tile exceeds `max_tile_memory`.
+    pub fn metatile(
+        &self,
+        tileset: &str,
+        limits: &ExtentInt,
+        zoom: u8,
+        mut stats: Option<&mut Statistics>,
+    ) -> Result<Vec<(u32, u32, vector_tile::Tile)>, String> {
+        let mut tile_extents = Vec::new();
+        for xtile in limits.minx..limits.maxx {
+            for ytile in limits.miny..limits.maxy {
+                tile_extents.push((xtile, ytile, self.grid.tile_extent(xtile, ytile, zoom)));
+            }
+        }
+        let mut extent = match tile_extents.first() {
+            Some(&(_, _, ref tile_extent)) => tile_extent.clone(),
+            None => return Ok(Vec::new()),
+        };
+        for &(_, _, ref tile_extent) in &tile_extents {
+            extent.minx = extent.minx.min(tile_extent.minx);
+            extent.miny = extent.miny.min(tile_extent.miny);
+            extent.maxx = extent.maxx.max(tile_extent.maxx);
+            extent.maxy = extent.maxy.max(tile_extent.maxy);
+        }
+        debug!(
+            "{}/{}/{:?} retrieving metatile with {:?}",
+            tileset, zoom, limits, extent
+        );
+        let mut tiles: Vec<Tile> = tile_extents
+            .iter()
+            .map(|&(_, _, ref tile_extent)| Tile::new(tile_extent, true))
+            .collect();
+        let max_memory = self
+            .get_tileset(tileset)
+            .and_then(|ts| ts.max_tile_memory)
+            .map(|bytes| bytes as u64);
+        let mut tile_bytes = vec![0u64; tiles.len()];
+        let mut info = TileInfo::default();
+        let datasources = self.tileset_datasources(tileset);
+        for layer in self.get_tileset_layers(tileset) {
+            if zoom >= layer.minzoom()
+                && zoom <= layer.maxzoom(self.grid.maxzoom())
+                && !layer.no_cache
+            {
+                // query_limit is applied per tile
+                let mut query_layer = layer.clone();
+                query_layer.query_limit = None;
+                let mut features = Vec::new();
+                let now = Instant::now();
+                let ds = datasources.datasource(&layer.datasource).unwrap();
+                // Geometries are clipped to the metatile by the datasource
+                let clip = ds.clips_geometries(&layer);
+                let result = ds.try_retrieve_features_while(
+                    tileset,
+                    &query_layer,
+                    &extent,
+                    zoom,
+                    &self.grid,
+                    |feat| {
+                        if let Ok(geometry) = feat.geometry() {
+                            if let Some(bbox) = geometry.bbox() {
+                                features.push((
+                                    bbox,
+                                    FeatureStruct {
+                                        fid: feat.fid(),
+                                        attributes: layer
+                                            .localize_attributes(feat.attributes(), None),
+                                        geometry: geometry,
+                                    },
+                                ));
+                            }
+                        }
+                        true
+                    },
+                );
+                let num_features = match result {
+                    Ok(num_features) => num_features,
+                    Err(err) => {
+                        self.layer_error(tileset, &layer, err, &mut info)?;
+                        continue;
+                    }
+                };
+                let elapsed = now.elapsed();
+                if let Some(ref mut stats) = stats {
+                    stats.add(
+                        format!("tile_ms.{}.{}.{}", tileset, layer.name, zoom),
+                        elapsed.as_secs() * 1000 + elapsed.subsec_millis() as u64,
+                    );
+                    stats.add(
+                        format!("feature_count.{}.{}.{}", tileset, layer.name, zoom),
+                        num_features as u64,
+                    );
+                }
+                debug!(
+                    "{}/{}/{:?} layer {}: {} features",
+                    tileset, zoom, limits, layer.name, num_features
+                );
+
+                let query_limit = layer.query_limit.unwrap_or(0) as usize;
+                for (idx, (tile, &(xtile, ytile, ref tile_extent))) in
+                    tiles.iter_mut().zip(tile_extents.iter()).enumerate()
+                {
+                    let bbox = layer.buffered_extent(tile_extent, &self.grid, zoom);
+                    let mut mvt_layer = tile.new_layer(layer);
+                    let mut tile_features = 0;
+                    for &(ref feat_bbox, ref feature) in &features {
+                        if !intersects(feat_bbox, &bbox) {
+                            continue;
+                        }
+                        let count = mvt_layer.get_features().len();
+                        if clip {
+                            let geometry = match feature.geometry.clipped(&bbox) {
+                                Some(geometry) => geometry,
+                                None => continue,
+                            };
+                            let clipped = FeatureStruct {
+                                fid: feature.fid,
+                                attributes: feature.attributes.clone(),
+                                geometry,
+                            };
+                            tile.add_feature(&mut mvt_layer, &clipped as &dyn Feature);
+                        } else {
+                            tile.add_feature(&mut mvt_layer, feature as &dyn Feature);
+                        }
+                        tile_features += 1;
+                        if let Some(max_memory) = max_memory {
+                            if mvt_layer.get_features().len() > count {
+                                let mvt_feature = &mvt_layer.get_features()[count];
+                                tile_bytes[idx] += Tile::feature_size(mvt_feature) as u64;
+                            }
+                            if tile_bytes[idx] > max_memory {
+                                let err = format!(
+                                    "{}/{}/{}/{} exceeds max_tile_memory of {} bytes in layer {}",
+                                    tileset, zoom, xtile, ytile, max_memory, layer.name
+                                );
+                                error!("{} - metatile not created", err);
+                                if let Some(ref mut stats) = stats {
+                                    stats.add(
+                                        format!("max_tile_memory_exceeded.{}.{}", tileset, zoom),
+                                        1,
+                                    );
+                                }
+                                return Err(err);
+                            }
+                        }
+                        if tile_features == query_limit {
+                            info!(
+                                "Features of layer {} limited to {} (tile query_limit reached, zoom level {})",
+                                layer.name, tile_features, zoom
+                            );
+                            break;
+                        }
+                    }
+                    if tile_features > 0 {
+                        if layer.deterministic {
+                            Tile::sort_features(&mut mvt_layer);
+                        }
+                        tile.add_layer(mvt_layer);
+                    }
+                }
+            }
         }
-        tile.mvt_tile
+        Ok(tile_extents
+            .iter()
+            .zip(tiles.into_iter())
+            .map(|(&(xtile, ytile, _), tile)| (xtile, ytile, tile.mvt_tile))
+            .collect())
     }
-    /// Fetch or create vector tile from input at x, y, z
+    /// Check whether tile in grid adressing scheme is within the tileset extent buffered by one tile.
+    /// Always true for tilesets without extent or before `prepare_feature_queries`.
+    pub fn tile_in_bounds(&self, tileset: &str, xtile: u32, ytile: u32, zoom: u8) -> bool {
+        match self
+            .tileset_limits
+            .get(tileset)
+            .and_then(|limits| limits.get(zoom as usize))
+        {
+            Some(limit) => {
+                xtile >= limit.minx && xtile < limit.maxx && ytile >= limit.miny && ytile < limit.maxy
+            }
+            None => true,
+        }
+    }
+    /// Number of tile requests outside of tileset extents since startup
+    pub fn out_of_bounds_requests() -> usize {
+        OUT_OF_BOUNDS_REQUESTS.load(Ordering::Relaxed)
+    }
+    /// Cache path of tile
+    fn cache_path(&self, tileset: &str, tile: &XyzTile) -> String {
+        let y = self.cache_ytile(tile);
+        format!("{}/{}/{}/{}.pbf", tileset, tile.z, tile.x, y)
+    }
+    /// Tile URLs and cache paths of Web Mercator and top-left grids are in XYZ scheme,
+    /// others in TMS scheme (the grid scheme of bottom-left grids).
+    fn xyz_scheme(&self) -> bool {
+        self.grid.srid == 3857 || self.grid.origin == Origin::TopLeft
+    }
+    /// Adressing scheme of tile URLs as declared in TileJSON ("xyz" or "tms")
+    pub fn tile_scheme(&self) -> &'static str {
+        if self.xyz_scheme() {
+            "xyz"
+        } else {
+            "tms"
+        }
+    }
+    /// Tile at x, y, z in URL and cache adressing scheme. None outside of the grid.
+    pub fn url_tile(&self, xtile: u32, ytile: u32, zoom: u8) -> Option<XyzTile> {
+        // Tile counts of custom grids per zoom level are not necessarily powers of two
+        if !self.grid.tile_in_grid(xtile, ytile, zoom) {
+            return None;
+        }
+        let tile = if self.xyz_scheme() {
+            XyzTile {
+                x: xtile,
+                y: ytile,
+                z: zoom,
+            }
+        } else {
+            self.grid.tms_to_xyz(&TmsTile {
+                x: xtile,
+                y: ytile,
+                z: zoom,
+            })
+        };
+        Some(tile)
+    }
+    /// Tile row in grid adressing scheme
+    pub fn grid_ytile(&self, tile: &XyzTile) -> u32 {
+        self.grid.ytile_from_xyz(tile.y, tile.z)
+    }
+    /// Tile row in URL and cache adressing scheme
+    pub fn cache_ytile(&self, tile: &XyzTile) -> u32 {
+        if self.xyz_scheme() {
+            tile.y
+        } else {
+            self.grid.xyz_to_tms(tile).y
+        }
+    }
+    /// Fetch or create vector tile from input at x, y, z.
+    /// Failing tiles are returned as None.
     pub fn tile_cached(
         &self,
         tileset: &str,
@@ -130,23 +1009,470 @@ impl MvtService {
         gzip: bool,
         stats: Option<&mut Statistics>,
     ) -> Option<Vec<u8>> {
-        // Reverse y for XYZ scheme (TODO: protocol instead of CRS dependent?)
-        let y = if self.grid.srid == 3857 {
-            self.grid.ytile_from_xyz(ytile, zoom)
+        let tile = match self.url_tile(xtile, ytile, zoom) {
+            Some(tile) => tile,
+            None => {
+                debug!(
+                    "{}/{}/{}/{} - Skipping tile outside of grid",
+                    tileset, zoom, xtile, ytile
+                );
+                return None;
+            }
+        };
+        let options = TileOptions {
+            gzip,
+            stats,
+            ..Default::default()
+        };
+        self.tile_cached_with_info(tileset, &tile, options)
+            .unwrap_or(None)
+            .map(|(data, _)| data)
+    }
+    /// Tile from MBTiles archive, passed through without re-encoding.
+    /// Tiles above the archive maxzoom are extracted from the parent tile with `overzoom`.
+    fn mbtiles_tile(
+        &self,
+        archive: &MbtilesArchive,
+        overzoom: bool,
+        tile: &TmsTile,
+        gzip: bool,
+    ) -> Option<(Vec<u8>, TileInfo)> {
+        let TmsTile {
+            x: xtile,
+            y: ytile,
+            z: zoom,
+        } = *tile;
+        let mut info = TileInfo::default();
+        let now = Instant::now();
+        let maxzoom = archive.maxzoom().unwrap_or(zoom);
+        if zoom <= maxzoom || !overzoom {
+            let data = archive.tile(zoom, xtile, ytile)?;
+            info.timings.cache = now.elapsed();
+            info.feature_count = Tile::gz_feature_count(&data);
+            let now = Instant::now();
+            let content = Tile::tile_content(data, gzip);
+            info.timings.gzip = now.elapsed();
+            return Some((content, info));
+        }
+        let dz = zoom - maxzoom;
+        if dz > MAX_OVERZOOM {
+            return None;
+        }
+        let data = archive.tile(maxzoom, xtile >> dz, ytile >> dz)?;
+        info.timings.cache = now.elapsed();
+        let parent = if Tile::is_gzip(&data) {
+            Tile::read_gz_from(&mut &data[..])
         } else {
-            ytile
+            Tile::read_from(&mut &data[..])
+        }
+        .map_err(|e| error!("Error decoding MBTiles tile: {}", e))
+        .ok()?;
+        let mask = (1u32 << dz) - 1;
+        let now = Instant::now();
+        let mvt_tile = Tile::overzoom(&parent, dz, xtile & mask, mask - (ytile & mask));
+        if mvt_tile.get_layers().is_empty() {
+            return None;
+        }
+        info.feature_count = Some(Tile::feature_count(&mvt_tile));
+        let tiledata = Tile::tile_bytevec(&mvt_tile);
+        info.timings.encode = now.elapsed();
+        if gzip {
+            let now = Instant::now();
+            let tilegz = Tile::compress(&tiledata);
+            info.timings.gzip = now.elapsed();
+            Some((tilegz, info))
+        } else {
+            Some((tiledata, info))
+        }
+    }
+    /// Call `add` with the features of all layers at x, y, z (XYZ adressing scheme).
+    /// Returns false for unknown tilesets, MBTiles tilesets and tiles out of range.
+    fn read_tile_features<F>(
+        &self,
+        tileset: &str,
+        xtile: u32,
+        ytile: u32,
+        zoom: u8,
+        mut add: F,
+    ) -> bool
+    where
+        F: FnMut(&Layer, &dyn Feature),
+    {
+        let y = match self.url_tile(xtile, ytile, zoom) {
+            Some(tile) => self.grid_ytile(&tile),
+            None => return false,
         };
-        let path = format!("{}/{}/{}/{}.pbf", tileset, zoom, xtile, ytile);
-
+        let ts = match self.get_tileset(tileset) {
+            Some(ts) => ts,
+            None => return false,
+        };
+        if ts.mbtiles.is_some()
+            || zoom < ts.minzoom()
+            || zoom > ts.maxzoom()
+            || !self.tile_in_bounds(&ts.name, xtile, y, zoom)
+        {
+            return false;
+        }
+        let extent = self.grid.tile_extent(xtile, y, zoom);
+        let datasources = self.tileset_datasources(tileset);
+        for layer in self.get_tileset_layers(tileset) {
+            if zoom >= layer.minzoom() && zoom <= layer.maxzoom(self.grid.maxzoom()) {
+                let ds = datasources.datasource(&layer.datasource).unwrap();
+                ds.retrieve_features(tileset, &layer, &extent, zoom, &self.grid, |feat| {
+                    add(&layer, feat)
+                });
+            }
+        }
+        true
+    }
+    /// Create Geobuf FeatureCollection with the features of all layers at x, y, z
+    /// (XYZ adressing scheme). The layer name is added as feature member `layer`.
+    /// Coordinates are converted to WGS84 for the Web Mercator grid.
+    /// Returns None for empty tiles, unknown tilesets and MBTiles tilesets.
+    pub fn tile_geobuf(
+        &self,
+        tileset: &str,
+        xtile: u32,
+        ytile: u32,
+        zoom: u8,
+        gzip: bool,
+    ) -> Option<Vec<u8>> {
+        let mut collection = if self.grid.units == Unit::Degrees {
+            FeatureCollection::geographic()
+        } else {
+            FeatureCollection::new(self.grid.srid == 3857)
+        };
+        if !self.read_tile_features(tileset, xtile, ytile, zoom, |layer, feat| {
+            collection.add_feature(&layer.name, feat)
+        }) || collection.is_empty()
+        {
+            return None;
+        }
+        let data = collection.encode();
+        if gzip {
+            Some(Tile::compress(&data))
+        } else {
+            Some(data)
+        }
+    }
+    /// Create FlatGeobuf with the features of all layers at x, y, z (XYZ adressing scheme).
+    /// The header contains the attribute columns of all layers and the column `layer`
+    /// with the layer name. Coordinates are converted to WGS84 for the Web Mercator grid.
+    /// Returns None for empty tiles, unknown tilesets and MBTiles tilesets.
+    pub fn tile_fgb(
+        &self,
+        tileset: &str,
+        xtile: u32,
+        ytile: u32,
+        zoom: u8,
+        gzip: bool,
+    ) -> Option<Vec<u8>> {
+        let mut collection = fgb::FeatureCollection::new(self.grid.srid);
+        if !self.read_tile_features(tileset, xtile, ytile, zoom, |layer, feat| {
+            collection.add_feature(&layer.name, feat)
+        }) || collection.is_empty()
+        {
+            return None;
+        }
+        let data = collection.encode();
+        if gzip {
+            Some(Tile::compress(&data))
+        } else {
+            Some(data)
+        }
+    }
+    /// Query plans of the layer queries for a tile at x, y, z (XYZ adressing scheme).
+    /// Queries are executed with `EXPLAIN ANALYZE`, layers of other datasources than PostGIS
+    /// are omitted. Returns None for unknown tilesets.
+    pub fn explain_tile(
+        &self,
+        tileset: &str,
+        xtile: u32,
+        ytile: u32,
+        zoom: u8,
+    ) -> Option<Result<serde_json::Value, String>> {
+        let ts = self.get_tileset(tileset)?;
+        let y = match self.url_tile(xtile, ytile, zoom) {
+            Some(tile) => self.grid_ytile(&tile),
+            None => {
+                return Some(Err(format!(
+                    "Tile {}/{}/{} is outside of the grid",
+                    zoom, xtile, ytile
+                )))
+            }
+        };
+        let extent = self.grid.tile_extent(xtile, y, zoom);
+        let mut plans = Vec::new();
+        let datasources = self.tileset_datasources(&ts.name);
+        for layer in &ts.layers {
+            if zoom < layer.minzoom() || zoom > layer.maxzoom(self.grid.maxzoom()) {
+                continue;
+            }
+            let ds = datasources.datasource(&layer.datasource).unwrap();
+            match ds.explain_query(&ts.name, layer, &extent, zoom, &self.grid) {
+                Ok(Some((sql, plan))) => plans.push(json!({
+                    "layer": layer.name,
+                    "sql": sql,
+                    "plan": plan,
+                })),
+                Ok(None) => {}
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        Some(Ok(json!({
+            "tileset": ts.name,
+            "z": zoom,
+            "x": xtile,
+            "y": ytile,
+            "layers": plans,
+        })))
+    }
+    /// Effective query of a layer at zoom level for debugging, e.g. in psql.
+    /// The SQL has parameters substituted with the values of tile x, y (XYZ adressing scheme)
+    /// or the grid extent as bbox. With `explain` (analyze or not), the query plan is appended.
+    pub fn layer_query_report(
+        &self,
+        tileset: &str,
+        layer: &str,
+        zoom: u8,
+        tile: Option<(u32, u32)>,
+        explain: Option<bool>,
+    ) -> Result<String, String> {
         let ts = self
             .get_tileset(tileset)
-            .expect(&format!("Tileset '{}' not found", tileset));
+            .ok_or(format!("Unknown tileset '{}'", tileset))?;
+        let layer = self
+            .get_layer(&ts.name, layer)
+            .ok_or(format!("Unknown layer '{}' in tileset '{}'", layer, ts.name))?;
+        if zoom < layer.minzoom() || zoom > layer.maxzoom(self.grid.maxzoom()) {
+            return Err(format!(
+                "Layer '{}' is not included at zoom level {}",
+                layer.name, zoom
+            ));
+        }
+        let (extent, bbox) = match tile {
+            Some((xtile, ytile)) => {
+                let y = match self.url_tile(xtile, ytile, zoom) {
+                    Some(tile) => self.grid_ytile(&tile),
+                    None => {
+                        return Err(format!(
+                            "Tile {}/{}/{} is outside of the grid",
+                            zoom, xtile, ytile
+                        ))
+                    }
+                };
+                let bbox = format!("tile {}/{}/{}", zoom, xtile, ytile);
+                (self.grid.tile_extent(xtile, y, zoom), bbox)
+            }
+            None => (self.grid.extent.clone(), "grid extent".to_string()),
+        };
+        let datasources = self.tileset_datasources(&ts.name);
+        let ds = datasources
+            .datasource(&layer.datasource)
+            .ok_or(format!("Layer '{}': datasource not found", layer.name))?;
+        let query = ds
+            .bound_query(&ts.name, layer, &extent, zoom, &self.grid)
+            .ok_or(format!(
+                "Layer '{}': no PostGIS query at zoom level {}",
+                layer.name, zoom
+            ))?;
+        let mut report = format!(
+            "-- Layer '{}' of tileset '{}' at zoom level {}, bbox of {}\n",
+            layer.name, ts.name, zoom, bbox
+        );
+        for (i, value) in query.params.iter().enumerate() {
+            report.push_str(&format!("-- ${} = {}\n", i + 1, value));
+        }
+        report.push_str(&format!("{};\n", query.substituted_sql()));
+        if let Some(analyze) = explain {
+            let plan = ds
+                .explain_plan(&ts.name, layer, &extent, zoom, &self.grid, analyze)?
+                .unwrap_or_default();
+            report.push_str("\n-- Query plan:\n");
+            for line in plan.lines() {
+                report.push_str(&format!("-- {}\n", line));
+            }
+        }
+        Ok(report)
+    }
+    /// Tile requests in progress and database connections as reported by `/debug/requests`
+    pub fn debug_requests(&self) -> serde_json::Value {
+        let mut json = json!(self.requests.snapshot());
+        json["replicas"] = json!(self.datasources.replica_stats());
+        json
+    }
+    /// Lookup layer of a tileset
+    pub fn get_layer(&self, tileset: &str, layer: &str) -> Option<&Layer> {
+        self.get_tileset(tileset)?
+            .layers
+            .iter()
+            .find(|l| l.name == layer)
+    }
+    /// Attributes of a layer included at zoom level
+    pub fn layer_fields(&self, tileset: &str, layer: &str, zoom: u8) -> Option<Vec<String>> {
+        let layer = self.get_layer(tileset, layer)?;
+        let fields = match self.ds(layer) {
+            Some(ds) => ds
+                .detect_data_columns(layer, layer.query(zoom))
+                .into_iter()
+                .map(|(name, _)| name)
+                .filter(|name| layer.attribute_in_zoom(name, zoom))
+                .collect(),
+            None => Vec::new(),
+        };
+        Some(fields)
+    }
+    /// Distinct values of a layer attribute with feature counts, most frequent first
+    pub fn field_values(
+        &self,
+        tileset: &str,
+        layer: &str,
+        field: &str,
+        zoom: u8,
+        limit: u32,
+        timeout_ms: u64,
+    ) -> Option<Result<serde_json::Value, String>> {
+        let ts = self.get_tileset(tileset)?;
+        let layer = self.get_layer(tileset, layer)?;
+        let datasources = self.tileset_datasources(&ts.name);
+        let ds = datasources.datasource(&layer.datasource)?;
+        let values =
+            match ds.field_values(&ts.name, layer, field, zoom, &self.grid, limit, timeout_ms) {
+                Ok(values) => values.unwrap_or_default(),
+                Err(err) => return Some(Err(err)),
+            };
+        let values = values
+            .into_iter()
+            .map(|(value, count)| json!({"value": value, "count": count}))
+            .collect::<Vec<_>>();
+        Some(Ok(json!({
+            "tileset": ts.name,
+            "layer": layer.name,
+            "field": field,
+            "zoom": zoom,
+            "values": values,
+        })))
+    }
+    /// Check whether tiles of a tileset depend on the requested language
+    pub fn has_localized_layers(&self, tileset: &str) -> bool {
+        self.get_tileset_layers(tileset)
+            .iter()
+            .any(|layer| layer.localized_name.is_some())
+    }
+    /// Check requested query parameter values of the tileset layers.
+    /// Returns the cache path component of the values differing from the parameter defaults
+    /// (None if all values are defaults). Unknown parameters and invalid values are errors.
+    pub fn param_values_key(
+        &self,
+        tileset: &str,
+        params: &ParamValues,
+    ) -> Result<Option<String>, String> {
+        if params.is_empty() {
+            return Ok(None);
+        }
+        let layers = match self.get_tileset(tileset) {
+            Some(ts) => &ts.layers,
+            None => return Ok(None),
+        };
+        let mut canonical = Vec::new();
+        for (name, value) in params {
+            let declared: Vec<_> = layers
+                .iter()
+                .filter_map(|layer| layer.params.iter().find(|p| &p.name == name))
+                .collect();
+            if declared.is_empty() {
+                return Err(format!("Unknown parameter '{}{}'", PARAM_PREFIX, name));
+            }
+            let mut is_default = true;
+            let mut normalized = value.clone();
+            for param in declared {
+                let parsed = param.check(value)?;
+                is_default &= parsed == param.default;
+                normalized = parsed.to_string();
+            }
+            if !is_default {
+                canonical.push(format!("{}={}", name, normalized));
+            }
+        }
+        if canonical.is_empty() {
+            return Ok(None);
+        }
+        // Stable hash (FNV-1a) of the values
+        let hash = canonical
+            .join("&")
+            .bytes()
+            .fold(0xcbf29ce484222325u64, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            });
+        Ok(Some(format!("params-{:016x}", hash)))
+    }
+    /// Fetch or create vector tile from input at `tile` (see `url_tile`).
+    /// Localized names are resolved for `options.lang`, which is part of the cache path.
+    /// Layer queries are executed with the query parameter values `options.params`,
+    /// which are part of the cache path, if they differ from the defaults.
+    /// Returns the applied simplification tolerance and the durations of the
+    /// processing phases with the tile data.
+    /// Returns None for empty tiles and unknown tilesets.
+    /// Tiles with omitted layers (`on_layer_error = "skip"` or disabled because of
+    /// a missing table) are not cached.
+    /// Layers with `cache = false` are queried for each request and appended to the
+    /// cached layers, with their Cache-Control max-age in the returned info.
+    pub fn tile_cached_with_info(
+        &self,
+        tileset: &str,
+        tile: &XyzTile,
+        options: TileOptions,
+    ) -> Result<Option<(Vec<u8>, TileInfo)>, String> {
+        let TileOptions {
+            gzip,
+            lang,
+            params,
+            stats,
+            count_only,
+        } = options;
+        let default_params = ParamValues::new();
+        let params = params.unwrap_or(&default_params);
+        let (xtile, zoom) = (tile.x, tile.z);
+        // Row in URL and cache adressing scheme
+        let ytile = self.cache_ytile(tile);
+        // TODO: protocol instead of CRS dependent adressing scheme?
+        let y = self.grid_ytile(tile);
+        let ts = match self.get_tileset(tileset) {
+            Some(ts) => ts,
+            None => return Ok(None),
+        };
+        // Tiles without localized layers are the same for all languages
+        let lang = lang.filter(|_| self.has_localized_layers(&ts.name));
+        let mut prefix = ts.name.clone();
+        if let Some(lang) = lang {
+            prefix = format!("{}/{}", prefix, lang);
+        }
+        if let Some(key) = self.param_values_key(&ts.name, params)? {
+            prefix = format!("{}/{}", prefix, key);
+        }
+        let path = format!("{}/{}/{}/{}.pbf", prefix, zoom, xtile, ytile);
 
         if zoom < ts.minzoom() || zoom > ts.maxzoom() {
-            return None;
+            return Ok(None);
+        }
+        if !self.tile_in_bounds(&ts.name, xtile, y, zoom) {
+            let count = OUT_OF_BOUNDS_REQUESTS.fetch_add(1, Ordering::Relaxed) + 1;
+            debug!("{} - Skipping tile outside of tileset extent", path);
+            if count == 1 || count % 1000 == 0 {
+                info!("{} tile requests outside of tileset extents", count);
+            }
+            return Ok(None);
         }
 
+        if let Some(ref archive) = ts.mbtiles {
+            let tms = self.grid.tms_tile(xtile, y, zoom);
+            return Ok(self.mbtiles_tile(archive, ts.overzoom, &tms, gzip));
+        }
+
+        let request = self.requests.start(&path, &ts.name, zoom, xtile, ytile);
+        let mut info = TileInfo::default();
         let mut tile: Option<Vec<u8>> = None;
+        let now = Instant::now();
         if ts.is_cachable_at(zoom) {
             self.cache.read(&path, |f| {
                 let mut data = Vec::new();
@@ -160,19 +1486,92 @@ impl MvtService {
             );
         }
 
-        // Return tile from cache
-        if let Some(tilegz) = tile {
-            return Some(Tile::tile_content(tilegz, gzip));
+        info.timings.cache = now.elapsed();
+
+        // Layers with `cache = false` are added to the cached layers
+        let max_age = self.uncached_max_age(&ts.name, zoom);
+
+        // Return tile from cache, which may contain uncompressed tiles of other tools
+        if let Some(data) = tile {
+            info.feature_count = Tile::gz_feature_count(&data);
+            if count_only {
+                if max_age.is_none() {
+                    info.modified = self.cache.modified(&path);
+                } else {
+                    let count = self.count_features(
+                        &ts.name,
+                        xtile,
+                        y,
+                        zoom,
+                        params,
+                        &mut info,
+                        TileLayers::Uncached,
+                    )?;
+                    info.feature_count = info.feature_count.map(|n| n + count);
+                    info.max_age = max_age;
+                }
+                return Ok(Some((Vec::new(), info)));
+            }
+            let now = Instant::now();
+            if max_age.is_none() {
+                info.modified = self.cache.modified(&path);
+                let content = Tile::tile_content(data, gzip);
+                info.timings.gzip = now.elapsed();
+                return Ok(Some((content, info)));
+            }
+            let tiledata = Tile::tile_content(data, false);
+            info.timings.gzip = now.elapsed();
+            return self.add_uncached_layers(
+                &ts.name, tiledata, xtile, y, zoom, gzip, lang, params, stats, info, &request,
+            );
+        }
+
+        if count_only {
+            let count =
+                self.count_features(&ts.name, xtile, y, zoom, params, &mut info, TileLayers::All)?;
+            if count == 0 {
+                return Ok(None);
+            }
+            info.feature_count = Some(count);
+            info.max_age = max_age;
+            return Ok(Some((Vec::new(), info)));
         }
 
         // Request tile and write into cache
-        let mvt_tile = self.tile(tileset, xtile, y, zoom, stats);
+        let mut stats = stats;
+        let mvt_tile = self.tile_auto_simplified(
+            &ts.name,
+            xtile,
+            y,
+            zoom,
+            lang,
+            params,
+            stats.as_mut().map(|s| &mut **s),
+            &mut info,
+            Some(&request),
+            TileLayers::Cached,
+        )?;
+        let feature_count = Tile::feature_count(&mvt_tile);
+        info.feature_count = Some(feature_count);
         // Spec: A Vector Tile SHOULD contain at least one layer.
         if mvt_tile.get_layers().len() > 0 {
-            let tilegz = Tile::tile_bytevec_gz(&mvt_tile);
-            if ts.is_cachable_at(zoom) {
-                if let Err(ioerr) = self.cache.write(&path, &tilegz) {
-                    error!("Error writing {}: {}", path, ioerr);
+            request.set_state(RequestState::Encoding);
+            let now = Instant::now();
+            let tiledata = Tile::tile_bytevec(&mvt_tile);
+            info.timings.encode += now.elapsed();
+            let now = Instant::now();
+            // The feature count is stored with the cached tile for HEAD requests
+            let tilegz = Tile::compress_with_feature_count(&tiledata, feature_count);
+            info.timings.gzip = now.elapsed();
+            if ts.is_cachable_at(zoom)
+                && info.layer_errors.is_empty()
+                && !self.disabled_layers.has_tileset(&ts.name)
+            {
+                request.set_state(RequestState::WritingCache);
+                match self.cache.write(&path, &tilegz) {
+                    // Creation time as recorded by the cache
+                    Ok(_) => info.modified = self.cache.modified(&path),
+                    Err(ioerr) => error!("Error writing {}: {}", path, ioerr),
                 }
             } else {
                 debug!(
@@ -180,15 +1579,137 @@ impl MvtService {
                     ts.name, zoom
                 );
             }
-            Some(Tile::tile_content(tilegz, gzip))
+            if max_age.is_some() {
+                return self.add_uncached_layers(
+                    &ts.name, tiledata, xtile, y, zoom, gzip, lang, params, stats, info, &request,
+                );
+            }
+            Ok(Some((if gzip { tilegz } else { tiledata }, info)))
+        } else if max_age.is_some() {
+            self.add_uncached_layers(
+                &ts.name,
+                Vec::new(),
+                xtile,
+                y,
+                zoom,
+                gzip,
+                lang,
+                params,
+                stats,
+                info,
+                &request,
+            )
         } else {
             // We don't save empty tiles
             // When serving from file cache return 204 No Content
             // Nginx: try_files $uri = 204;
             debug!("{} - Skipping empty tile", path);
-            None
+            Ok(None)
         }
     }
+    /// Number of features of the `layers` of a tile returned by the layer queries,
+    /// without encoding them
+    fn count_features(
+        &self,
+        tileset: &str,
+        xtile: u32,
+        ytile: u32,
+        zoom: u8,
+        params: &ParamValues,
+        info: &mut TileInfo,
+        layers: TileLayers,
+    ) -> Result<usize, String> {
+        let extent = self.grid.tile_extent(xtile, ytile, zoom);
+        let datasources = self.tileset_datasources(tileset);
+        let now = Instant::now();
+        let mut count = 0;
+        for layer in self
+            .get_tileset_layers_with_params(tileset, params, layers)?
+            .iter()
+        {
+            let layer: &Layer = layer;
+            if zoom < layer.minzoom() || zoom > layer.maxzoom(self.grid.maxzoom()) {
+                continue;
+            }
+            let ds = datasources.datasource(&layer.datasource).unwrap();
+            let result =
+                ds.try_retrieve_features_while(tileset, layer, &extent, zoom, &self.grid, |_| true);
+            match result {
+                Ok(num_features) => count += num_features as usize,
+                Err(err) => self.layer_error(tileset, layer, err, info)?,
+            }
+        }
+        info.timings.db += now.elapsed();
+        Ok(count)
+    }
+    /// Cache-Control max-age of tiles with layers with `cache = false` at `zoom`
+    /// (minimum of the layers, default 0). None if all layers are cached.
+    pub fn uncached_max_age(&self, tileset: &str, zoom: u8) -> Option<u32> {
+        self.get_tileset_layers(tileset)
+            .iter()
+            .filter(|layer| {
+                layer.no_cache
+                    && zoom >= layer.minzoom()
+                    && zoom <= layer.maxzoom(self.grid.maxzoom())
+            })
+            .map(|layer| layer.cache_control_max_age.unwrap_or(0))
+            .min()
+    }
+    /// Append the layers with `cache = false` to the uncompressed data of the cached layers.
+    /// Layers of an MVT tile are a repeated protobuf field, so the encoded layers are
+    /// concatenated without decoding the cached part.
+    fn add_uncached_layers(
+        &self,
+        tileset: &str,
+        mut tiledata: Vec<u8>,
+        xtile: u32,
+        ytile: u32,
+        zoom: u8,
+        gzip: bool,
+        lang: Option<&str>,
+        params: &ParamValues,
+        stats: Option<&mut Statistics>,
+        mut info: TileInfo,
+        request: &InflightRequest,
+    ) -> Result<Option<(Vec<u8>, TileInfo)>, String> {
+        let mvt_tile = self.tile_auto_simplified(
+            tileset,
+            xtile,
+            ytile,
+            zoom,
+            lang,
+            params,
+            stats,
+            &mut info,
+            Some(request),
+            TileLayers::Uncached,
+        )?;
+        info.feature_count = info
+            .feature_count
+            .map(|n| n + Tile::feature_count(&mvt_tile));
+        if mvt_tile.get_layers().len() > 0 {
+            request.set_state(RequestState::Encoding);
+            let now = Instant::now();
+            tiledata.extend(Tile::tile_bytevec(&mvt_tile));
+            info.timings.encode += now.elapsed();
+        }
+        if tiledata.is_empty() {
+            debug!(
+                "{}/{}/{}/{} - Skipping empty tile",
+                tileset, zoom, xtile, ytile
+            );
+            return Ok(None);
+        }
+        // Changes with every request
+        info.modified = None;
+        info.max_age = self.uncached_max_age(tileset, zoom);
+        if gzip {
+            let now = Instant::now();
+            tiledata = Tile::compress(&tiledata);
+            info.timings.gzip += now.elapsed();
+        }
+        Ok(Some((tiledata, info)))
+    }
     fn progress_bar(&self, msg: &str, limits: &ExtentInt) -> ProgressBar<Stdout> {
         let tiles =
             (limits.maxx as u64 - limits.minx as u64) * (limits.maxy as u64 - limits.miny as u64);
@@ -200,12 +1721,20 @@ impl MvtService {
         pb.show_time_left = false;
         pb
     }
+    /// Extent in grid SRS from WGS84 for grids not requiring a datasource transformation
+    /// (Web Mercator and WGS84)
+    fn builtin_extent_from_wgs84(&self, extent: &Extent) -> Option<Extent> {
+        match self.grid.srid {
+            3857 => Some(extent_to_merc(extent)),
+            4326 => Some(extent.clone()),
+            _ => None,
+        }
+    }
     /// Projected extent in grid SRS from WGS84
     pub fn extent_from_wgs84(&self, extent: &Extent) -> Extent {
         // TODO: use proj4 (directly)
-        if self.grid.srid == 3857 {
-            // shortcut for Web Mercator
-            extent_to_merc(extent)
+        if let Some(ext) = self.builtin_extent_from_wgs84(extent) {
+            ext
         } else {
             let ds = self.datasources.default().unwrap();
             ds.extent_from_wgs84(extent, self.grid.srid)
@@ -215,7 +1744,56 @@ impl MvtService {
                 ))
         }
     }
-    /// Populate tile cache
+    /// Tile limits and zoom range for seeding a tileset within `extent` (WGS84)
+    fn seed_limits(
+        &self,
+        tileset: &Tileset,
+        extent: Option<&Extent>,
+        minzoom: Option<u8>,
+        maxzoom: Option<u8>,
+    ) -> (Vec<ExtentInt>, u8, u8) {
+        // Convert extent to grid SRS
+        let extent = extent.or(tileset.extent.as_ref());
+        debug!("wgs84 extent: {:?}", extent);
+        let ext_proj = match extent {
+            // (-180 -90) throws error when projecting
+            Some(ext_wgs84) if *ext_wgs84 != WORLD_EXTENT => self.extent_from_wgs84(ext_wgs84),
+            _ => {
+                warn!("Building cache for the full globe, please fill in the tileset extent");
+                self.grid.tile_extent(0, 0, 0)
+            }
+        };
+        debug!("tile limits: {:?}", ext_proj);
+
+        let tolerance = 0;
+        let limits = self.grid.tile_limits(ext_proj, tolerance);
+
+        let ts_minzoom = cmp::max(tileset.minzoom(), minzoom.unwrap_or(0));
+        let ts_maxzoom = *[
+            tileset.maxzoom(),
+            maxzoom.unwrap_or(99),
+            self.grid.maxzoom(),
+        ]
+        .iter()
+        .min()
+        .unwrap_or(&22);
+        if minzoom.is_some() && minzoom.unwrap() < ts_minzoom {
+            warn!("Skipping zoom levels <{}", ts_minzoom);
+        }
+        if maxzoom.is_some() && maxzoom.unwrap() > ts_maxzoom {
+            warn!("Skipping zoom levels >{}", ts_maxzoom);
+        }
+        (limits, ts_minzoom, ts_maxzoom)
+    }
+    /// Populate tile cache. Layers with `cache = false` are not included.
+    ///
+    /// With `write_empty`, tiles without features are stored as valid MVT without layers,
+    /// so that the cache is complete within the seeded extent ("seeded and empty"
+    /// vs. "not seeded"). Every empty tile costs a cache entry (a file system block
+    /// for the file cache), which can be a lot for sparse data on high zoom levels.
+    ///
+    /// With `metatile_size` > 1, blocks of `metatile_size` x `metatile_size` tiles are
+    /// generated with one datasource query per layer (see `metatile`).
     pub fn generate(
         &self,
         tileset_name: Option<&str>,
@@ -226,51 +1804,170 @@ impl MvtService {
         nodeno: Option<u8>,
         progress: bool,
         overwrite: bool,
+        write_empty: bool,
+        metatile_size: u8,
+        mut manifest: Option<Manifest>,
+        mut seed_state: Option<SeedState>,
     ) -> Statistics {
         self.init_cache();
         let mut stats = Statistics::new();
         let nodes = nodes.unwrap_or(1) as u64;
         let nodeno = nodeno.unwrap_or(0) as u64;
         let mut tileno: u64 = 0;
+        let tileset_name = tileset_name.map(|name| self.canonical_tileset_name(name));
         for tileset in &self.tilesets {
             if tileset_name.is_some() && tileset_name.unwrap() != &tileset.name {
                 continue;
             }
+            if tileset.mbtiles.is_some() {
+                info!("Skipping tileset '{}' served from MBTiles", tileset.name);
+                continue;
+            }
             if progress {
                 println!("Generating tileset '{}'...", tileset.name);
             }
 
-            // Convert extent to grid SRS
-            let extent = extent.as_ref().or(tileset.extent.as_ref());
-            debug!("wgs84 extent: {:?}", extent);
-            let ext_proj = match extent {
-                // (-180 -90) throws error when projecting
-                Some(ext_wgs84) if *ext_wgs84 != WORLD_EXTENT => self.extent_from_wgs84(ext_wgs84),
-                _ => {
-                    warn!("Building cache for the full globe, please fill in the tileset extent");
-                    self.grid.tile_extent(0, 0, 0)
+            let (limits, ts_minzoom, ts_maxzoom) =
+                self.seed_limits(tileset, extent.as_ref(), minzoom, maxzoom);
+            if metatile_size > 1 {
+                let maxzoom = cmp::min(ts_maxzoom, limits.len() as u8 - 1);
+                for zoom in ts_minzoom..=maxzoom {
+                    let ref limit = limits[zoom as usize];
+                    let mut pb = ProgressBar::new(0);
+                    if progress {
+                        debug!("level {}: {:?}", zoom, limit);
+                        pb = self.progress_bar(&format!("Level {}: ", zoom), &limit);
+                        pb.tick();
+                    }
+                    let size = metatile_size as u32;
+                    for minx in (limit.minx..limit.maxx).step_by(size as usize) {
+                        for miny in (limit.miny..limit.maxy).step_by(size as usize) {
+                            let skip = tileno % nodes != nodeno;
+                            tileno += 1;
+                            if skip {
+                                continue;
+                            }
+                            let metatile = ExtentInt {
+                                minx: minx,
+                                miny: miny,
+                                maxx: cmp::min(minx + size, limit.maxx),
+                                maxy: cmp::min(miny + size, limit.maxy),
+                            };
+                            let numtiles = ((metatile.maxx - metatile.minx)
+                                * (metatile.maxy - metatile.miny))
+                                as u64;
+                            if let Some(ref state) = seed_state {
+                                if state.is_completed(tileno) {
+                                    if progress {
+                                        pb.add(numtiles);
+                                    }
+                                    continue;
+                                }
+                            }
+                            let missing = overwrite
+                                || (metatile.minx..metatile.maxx).any(|xtile| {
+                                    (metatile.miny..metatile.maxy).any(|ytile| {
+                                        let tile = self.grid.xyz_tile(xtile, ytile, zoom);
+                                        !self.cache.exists(&self.cache_path(&tileset.name, &tile))
+                                    })
+                                });
+                            let mut failed = false;
+                            if missing {
+                                let tiles = match self.metatile(
+                                    &tileset.name,
+                                    &metatile,
+                                    zoom,
+                                    Some(&mut stats),
+                                ) {
+                                    Ok(tiles) => tiles,
+                                    Err(_) => {
+                                        // Error is logged, metatile is regenerated when resuming
+                                        failed = true;
+                                        Vec::new()
+                                    }
+                                };
+                                for (xtile, ytile, mut mvt_tile) in tiles {
+                                    let tile = self.grid.xyz_tile(xtile, ytile, zoom);
+                                    let path = self.cache_path(&tileset.name, &tile);
+                                    if !overwrite && self.cache.exists(&path) {
+                                        if let Some(ref mut manifest) = manifest {
+                                            let y = self.cache_ytile(&tile);
+                                            manifest.tile_skipped(
+                                                &tileset.name,
+                                                zoom,
+                                                xtile,
+                                                y,
+                                                &path,
+                                            );
+                                        }
+                                        continue;
+                                    }
+                                    if self.exceeds_tile_budget(&tileset.name, &mvt_tile) {
+                                        match self.tile_auto_simplified(
+                                            &tileset.name,
+                                            xtile,
+                                            ytile,
+                                            zoom,
+                                            None,
+                                            &ParamValues::new(),
+                                            Some(&mut stats),
+                                            &mut TileInfo::default(),
+                                            None,
+                                            TileLayers::Cached,
+                                        ) {
+                                            Ok(simplified) => mvt_tile = simplified,
+                                            // Layer error is logged
+                                            Err(_) => {
+                                                failed = true;
+                                                continue;
+                                            }
+                                        }
+                                    }
+                                    self.write_tile(
+                                        &tileset.name,
+                                        xtile,
+                                        ytile,
+                                        zoom,
+                                        &mvt_tile,
+                                        write_empty,
+                                        &mut manifest,
+                                    );
+                                }
+                            } else if let Some(ref mut manifest) = manifest {
+                                for xtile in metatile.minx..metatile.maxx {
+                                    for ytile in metatile.miny..metatile.maxy {
+                                        let tile = self.grid.xyz_tile(xtile, ytile, zoom);
+                                        let path = self.cache_path(&tileset.name, &tile);
+                                        let y = self.cache_ytile(&tile);
+                                        manifest.tile_skipped(
+                                            &tileset.name,
+                                            zoom,
+                                            xtile,
+                                            y,
+                                            &path,
+                                        );
+                                    }
+                                }
+                            }
+                            if let Some(ref mut state) = seed_state {
+                                if failed {
+                                    state.failed(tileno);
+                                } else {
+                                    let tile =
+                                        self.grid.xyz_tile(metatile.minx, metatile.miny, zoom);
+                                    let y = self.cache_ytile(&tile);
+                                    state.completed(tileno, &tileset.name, zoom, metatile.minx, y);
+                                }
+                            }
+                            if progress {
+                                pb.add(numtiles);
+                            }
+                        }
+                    }
                 }
-            };
-            debug!("tile limits: {:?}", ext_proj);
-
-            let tolerance = 0;
-            let limits = self.grid.tile_limits(ext_proj, tolerance);
-
-            let ts_minzoom = cmp::max(tileset.minzoom(), minzoom.unwrap_or(0));
-            let ts_maxzoom = *[
-                tileset.maxzoom(),
-                maxzoom.unwrap_or(99),
-                self.grid.maxzoom(),
-            ]
-            .iter()
-            .min()
-            .unwrap_or(&22);
-            if minzoom.is_some() && minzoom.unwrap() < ts_minzoom {
-                warn!("Skipping zoom levels <{}", ts_minzoom);
-            }
-            if maxzoom.is_some() && maxzoom.unwrap() > ts_maxzoom {
-                warn!("Skipping zoom levels >{}", ts_maxzoom);
+                continue;
             }
+
             let griditer = GridIterator::new(ts_minzoom, ts_maxzoom, limits.clone());
             let mut pb = ProgressBar::new(0);
             let mut pb_z = !ts_minzoom;
@@ -288,30 +1985,60 @@ impl MvtService {
                 if skip {
                     continue;
                 }
+                if let Some(ref state) = seed_state {
+                    if state.is_completed(tileno) {
+                        if progress {
+                            pb.inc();
+                        }
+                        continue;
+                    }
+                }
 
-                // Store Mercator tiles in xyz scheme, others in TMS scheme.
-                let y = if self.grid.srid == 3857 {
-                    self.grid.ytile_from_xyz(ytile, zoom)
-                } else {
-                    ytile
-                };
-                let path = format!("{}/{}/{}/{}.pbf", &tileset.name, zoom, xtile, y);
+                let tile = self.grid.xyz_tile(xtile, ytile, zoom);
+                let path = self.cache_path(&tileset.name, &tile);
 
                 if overwrite || !self.cache.exists(&path) {
                     // Entry doesn't exist, or we're ignoring it, so generate it
-                    let mvt_tile = self.tile(
+                    let mvt_tile = match self.tile_auto_simplified(
                         &tileset.name,
                         xtile as u32,
                         ytile as u32,
                         zoom,
+                        None,
+                        &ParamValues::new(),
                         Some(&mut stats),
-                    );
-                    if mvt_tile.get_layers().len() > 0 {
-                        let tilegz = Tile::tile_bytevec_gz(&mvt_tile);
-                        if let Err(ioerr) = self.cache.write(&path, &tilegz) {
-                            error!("Error writing {}: {}", path, ioerr);
+                        &mut TileInfo::default(),
+                        None,
+                        TileLayers::Cached,
+                    ) {
+                        Ok(mvt_tile) => mvt_tile,
+                        Err(_) => {
+                            // Layer error is logged, tile is regenerated when resuming
+                            if let Some(ref mut state) = seed_state {
+                                state.failed(tileno);
+                            }
+                            if progress {
+                                pb.inc();
+                            }
+                            continue;
                         }
-                    }
+                    };
+                    self.write_tile(
+                        &tileset.name,
+                        xtile,
+                        ytile,
+                        zoom,
+                        &mvt_tile,
+                        write_empty,
+                        &mut manifest,
+                    );
+                } else if let Some(ref mut manifest) = manifest {
+                    let y = self.cache_ytile(&tile);
+                    manifest.tile_skipped(&tileset.name, zoom, xtile, y, &path);
+                }
+                if let Some(ref mut state) = seed_state {
+                    let y = self.cache_ytile(&tile);
+                    state.completed(tileno, &tileset.name, zoom, xtile, y);
                 }
 
                 if progress {
@@ -322,14 +2049,130 @@ impl MvtService {
         if progress {
             println!("");
         }
+        if let Some(manifest) = manifest {
+            manifest.finish();
+        }
+        if let Some(state) = seed_state {
+            state.finish();
+        }
         stats
     }
+    /// Populate tile cache for a job of the job queue (without layers with `cache = false`).
+    /// Job tiles wait for interactive tile requests in progress, with a maximal delay
+    /// of `JOB_MAX_WAIT` per tile.
+    pub fn seed_job(&self, job: &JobHandle) {
+        let params = &job.params;
+        let extent = params.extent.map(|(minx, miny, maxx, maxy)| Extent {
+            minx,
+            miny,
+            maxx,
+            maxy,
+        });
+        let tilesets = self
+            .tilesets
+            .iter()
+            .filter(|ts| {
+                params
+                    .tileset
+                    .as_ref()
+                    .map(|name| self.canonical_tileset_name(name) == ts.name)
+                    .unwrap_or(true)
+            })
+            .filter(|ts| ts.mbtiles.is_none())
+            .map(|ts| {
+                let (limits, minzoom, maxzoom) =
+                    self.seed_limits(ts, extent.as_ref(), params.minzoom, params.maxzoom);
+                let maxzoom = cmp::min(maxzoom, limits.len() as u8 - 1);
+                (ts, limits, minzoom, maxzoom)
+            })
+            .collect::<Vec<_>>();
+        let total = tilesets
+            .iter()
+            .map(|(_, limits, minzoom, maxzoom)| {
+                (*minzoom..=*maxzoom)
+                    .filter_map(|zoom| limits.get(zoom as usize))
+                    .map(|l| (l.maxx - l.minx) as u64 * (l.maxy - l.miny) as u64)
+                    .sum::<u64>()
+            })
+            .sum();
+        job.set_total(total);
+        for (tileset, limits, minzoom, maxzoom) in tilesets {
+            if minzoom > maxzoom {
+                continue;
+            }
+            for (zoom, xtile, ytile) in GridIterator::new(minzoom, maxzoom, limits) {
+                if job.is_cancelled() {
+                    info!("Job {} cancelled", job.id);
+                    return;
+                }
+                let path = self.cache_path(&tileset.name, &self.grid.xyz_tile(xtile, ytile, zoom));
+                if params.overwrite || !self.cache.exists(&path) {
+                    self.requests.wait_idle(JOB_MAX_WAIT);
+                    let tile = panic::catch_unwind(AssertUnwindSafe(|| {
+                        self.tile_auto_simplified(
+                            &tileset.name,
+                            xtile,
+                            ytile,
+                            zoom,
+                            None,
+                            &ParamValues::new(),
+                            None,
+                            &mut TileInfo::default(),
+                            None,
+                            TileLayers::Cached,
+                        )
+                    }));
+                    match tile {
+                        Ok(Ok(mvt_tile)) => self.write_tile(
+                            &tileset.name,
+                            xtile,
+                            ytile,
+                            zoom,
+                            &mvt_tile,
+                            params.write_empty,
+                            &mut None,
+                        ),
+                        Ok(Err(err)) => job.error(format!("{} - {}", path, err)),
+                        Err(_) => job.error(format!("{} - Tile creation failed", path)),
+                    }
+                }
+                job.tile_done();
+            }
+        }
+    }
+    /// Write generated tile into cache. Empty tiles are only written with `write_empty`.
+    fn write_tile(
+        &self,
+        tileset: &str,
+        xtile: u32,
+        ytile: u32,
+        zoom: u8,
+        mvt_tile: &vector_tile::Tile,
+        write_empty: bool,
+        manifest: &mut Option<Manifest>,
+    ) {
+        if mvt_tile.get_layers().is_empty() && !write_empty {
+            if let Some(ref mut manifest) = manifest {
+                manifest.tile_empty();
+            }
+            return;
+        }
+        let tile = self.grid.xyz_tile(xtile, ytile, zoom);
+        let path = self.cache_path(tileset, &tile);
+        let tilegz = Tile::tile_bytevec_gz(mvt_tile);
+        if let Err(ioerr) = self.cache.write(&path, &tilegz) {
+            error!("Error writing {}: {}", path, ioerr);
+        } else if let Some(ref mut manifest) = manifest {
+            let y = self.cache_ytile(&tile);
+            manifest.tile_written(tileset, zoom, xtile, y, &path, &tilegz);
+        }
+    }
     pub fn init_cache(&self) {
         info!("{}", &self.cache.info());
         for tileset in &self.tilesets {
             // :tileset.json
             let json = self
-                .get_tilejson(&self.cache.baseurl(), &tileset.name)
+                .get_tilejson(&self.cache.baseurl(), &[], &tileset.name)
                 .unwrap();
             let _ = self.cache.write(
                 &format!("{}.json", &tileset.name),
@@ -338,7 +2181,7 @@ impl MvtService {
 
             // :tileset.style.json
             let json = self
-                .get_stylejson(&self.cache.baseurl(), &tileset.name)
+                .get_stylejson(&self.cache.baseurl(), &[], &tileset.name)
                 .unwrap();
             let _ = self.cache.write(
                 &format!("{}.style.json", &tileset.name),
@@ -362,6 +2205,31 @@ impl MvtService {
         pb.show_time_left = false;
         pb
     }
+    /// Check whether a WGS84 point is within the grid extent
+    pub fn point_in_grid(&self, x: f64, y: f64) -> bool {
+        if !((-180.0..=180.0).contains(&x) && (-90.0..=90.0).contains(&y)) {
+            return false;
+        }
+        let ext_wgs84 = Extent {
+            minx: x,
+            miny: y,
+            maxx: x,
+            maxy: y,
+        };
+        let ext_proj = match self.builtin_extent_from_wgs84(&ext_wgs84).or_else(|| {
+            self.datasources
+                .default()
+                .and_then(|ds| ds.extent_from_wgs84(&ext_wgs84, self.grid.srid))
+        }) {
+            Some(ext) => ext,
+            None => return false,
+        };
+        let grid_ext = &self.grid.extent;
+        ext_proj.minx >= grid_ext.minx
+            && ext_proj.maxx <= grid_ext.maxx
+            && ext_proj.miny >= grid_ext.miny
+            && ext_proj.maxy <= grid_ext.maxy
+    }
     /// Get statistics from drilldown
     pub fn drilldown(
         &self,
@@ -370,8 +2238,22 @@ impl MvtService {
         maxzoom: Option<u8>,
         points: Vec<f64>,
         progress: bool,
+    ) -> Statistics {
+        let cancelled = AtomicBool::new(false);
+        self.drilldown_cancellable(tileset_name, minzoom, maxzoom, points, progress, &cancelled)
+    }
+    /// Get statistics from drilldown. Stops with partial statistics when `cancelled` is set.
+    pub fn drilldown_cancellable(
+        &self,
+        tileset_name: Option<&str>,
+        minzoom: Option<u8>,
+        maxzoom: Option<u8>,
+        points: Vec<f64>,
+        progress: bool,
+        cancelled: &AtomicBool,
     ) -> Statistics {
         let mut stats = Statistics::new();
+        let tileset_name = tileset_name.map(|name| self.canonical_tileset_name(name));
         for tileset in &self.tilesets {
             if tileset_name.is_some() && tileset_name.unwrap() != &tileset.name {
                 continue;
@@ -404,6 +2286,10 @@ impl MvtService {
                 let tolerance = 0;
                 let limits = self.grid.tile_limits(ext_proj, tolerance);
                 for zoom in ts_minzoom..=ts_maxzoom {
+                    if cancelled.load(Ordering::Relaxed) {
+                        info!("Drilldown cancelled");
+                        return stats;
+                    }
                     let ref limit = limits[zoom as usize];
                     debug!("level {}: {:?}", zoom, limit);
                     let xtile = limit.minx;
@@ -419,6 +2305,27 @@ impl MvtService {
                         format!("tile_bytes.{}.total.{}", &tileset.name, zoom),
                         Tile::size(&mvt_tile) as u64,
                     );
+                    for mvt_layer in mvt_tile.get_layers() {
+                        stats.add(
+                            format!(
+                                "layer_bytes.{}.{}.{}",
+                                &tileset.name,
+                                mvt_layer.get_name(),
+                                zoom
+                            ),
+                            Tile::layer_size(mvt_layer) as u64,
+                        );
+                    }
+                    // Tile coordinates in URL adressing scheme for linking tiles
+                    let ytile_url = self.cache_ytile(&self.grid.xyz_tile(xtile, ytile, zoom));
+                    stats.add(
+                        format!("tile_x.{}.{}", &tileset.name, zoom),
+                        xtile as u64,
+                    );
+                    stats.add(
+                        format!("tile_y.{}.{}", &tileset.name, zoom),
+                        ytile_url as u64,
+                    );
                     if progress {
                         pb.inc();
                     }
@@ -466,6 +2373,16 @@ impl MvtService {
     }
 }
 
+/// Prepare datasource queries of all layers of a tileset
+fn prepare_tileset_queries(datasources: &mut Datasources, tileset: &Tileset, grid_srid: i32) {
+    for layer in &tileset.layers {
+        let ds = datasources
+            .datasource_mut(&layer.datasource)
+            .expect(&format!("Datasource of layer `{}` not found", layer.name));
+        ds.prepare_queries(&tileset.name, &layer, grid_srid);
+    }
+}
+
 impl<'a> Config<'a, ApplicationCfg> for MvtService {
     fn from_config(config: &ApplicationCfg) -> Result<Self, String> {
         let datasources = Datasources::from_config(config)?;
@@ -473,14 +2390,24 @@ impl<'a> Config<'a, ApplicationCfg> for MvtService {
         let tilesets = config
             .tilesets
             .iter()
-            .map(|ts_cfg| Tileset::from_config(ts_cfg).unwrap())
-            .collect();
+            .map(|ts_cfg| Tileset::from_config(ts_cfg))
+            .collect::<Result<Vec<_>, _>>()?;
+        check_tileset_aliases(&tilesets)?;
         let cache = Tilecache::from_config(&config)?;
+        let active_tilesets = match config.service.mvt.max_active_tilesets {
+            Some(0) => return Err("service.mvt.max_active_tilesets must be greater than 0".to_string()),
+            Some(max_tilesets) => Some(ActiveTilesets::new(max_tilesets)),
+            None => None,
+        };
         Ok(MvtService {
             datasources: datasources,
             grid: grid,
             tilesets: tilesets,
             cache: cache,
+            tileset_limits: HashMap::new(),
+            requests: InflightRequests::default(),
+            disabled_layers: DisabledLayers::default(),
+            active_tilesets,
         })
     }
     fn gen_config() -> String {
@@ -511,4 +2438,9 @@ const TOML_SERVICES: &'static str = r#"# t-rex configuration
 
 [service.mvt]
 viewer = true
+#max_active_tilesets = 100 # Prepare tilesets on first request, keeping the 100 most recently used (Default: all at startup)
 "#;
+
+fn intersects(a: &Extent, b: &Extent) -> bool {
+    a.minx <= b.maxx && a.maxx >= b.minx && a.miny <= b.maxy && a.maxy >= b.miny
+}
@@ -0,0 +1,202 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+//! OGC API - Tiles metadata (http://docs.opengeospatial.org/is/20-057/20-057.html)
+//!
+//! Tilesets are published as collections with vector tiles in the tile matrix set of the grid.
+
+use crate::mvt_service::MvtService;
+use serde_json;
+use std::cmp;
+use t_rex_core::service::tileset::Tileset;
+
+/// Tile matrix set identifier of grids without `tile_matrix_set`
+pub const CUSTOM_TILE_MATRIX_SET: &str = "Custom";
+
+/// Tile matrix sets with registered URI (OGC Two Dimensional Tile Matrix Set)
+const OGC_TILE_MATRIX_SETS: &[&str] = &["WebMercatorQuad", "WorldCRS84Quad"];
+
+const CONFORMANCE: &[&str] = &[
+    "http://www.opengis.net/spec/ogcapi-common-1/1.0/conf/core",
+    "http://www.opengis.net/spec/ogcapi-common-1/1.0/conf/landing-page",
+    "http://www.opengis.net/spec/ogcapi-common-1/1.0/conf/json",
+    "http://www.opengis.net/spec/ogcapi-common-2/1.0/conf/collections",
+    "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/core",
+    "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/tileset",
+    "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/tilesets-list",
+    "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/geodata-tilesets",
+    "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/mvt",
+];
+
+const CRS84: &str = "http://www.opengis.net/def/crs/OGC/1.3/CRS84";
+
+const MVT_MEDIA_TYPE: &str = "application/vnd.mapbox-vector-tile";
+
+fn link(href: String, rel: &str, media_type: &str, title: &str) -> serde_json::Value {
+    json!({"href": href, "rel": rel, "type": media_type, "title": title})
+}
+
+/// OGC API geometry type of layer
+fn geometry_type(geometry_type: &Option<String>) -> Option<&'static str> {
+    match geometry_type.as_ref().map(|t| t.as_str()) {
+        Some("POINT") | Some("MULTIPOINT") => Some("points"),
+        Some("LINESTRING") | Some("MULTILINESTRING") => Some("lines"),
+        Some("POLYGON") | Some("MULTIPOLYGON") => Some("polygons"),
+        _ => None,
+    }
+}
+
+impl MvtService {
+    /// Tile matrix set identifier of the grid
+    pub fn tile_matrix_set_id(&self) -> &str {
+        self.grid
+            .tile_matrix_set
+            .as_ref()
+            .map(|id| id.as_str())
+            .unwrap_or(CUSTOM_TILE_MATRIX_SET)
+    }
+    /// Tile row (counted from the top) in the tile adressing scheme of `tile_cached`
+    pub fn ytile_from_tile_row(&self, row: u32, zoom: u8) -> u32 {
+        if self.grid.srid == 3857 {
+            row
+        } else {
+            self.grid.ytile_from_xyz(row, zoom)
+        }
+    }
+    fn crs_uri(&self) -> String {
+        match self.grid.srid {
+            4326 => CRS84.to_string(),
+            srid => format!("http://www.opengis.net/def/crs/EPSG/0/{}", srid),
+        }
+    }
+    /// Landing page
+    pub fn get_ogcapi_landing_page(&self, baseurl: &str) -> serde_json::Value {
+        json!({
+            "title": "t-rex",
+            "description": "Vector tiles (OGC API - Tiles)",
+            "links": [
+                link(format!("{}/", baseurl), "self", "application/json", "This document"),
+                link(format!("{}/conformance", baseurl), "conformance", "application/json", "Conformance declaration"),
+                link(format!("{}/collections", baseurl), "data", "application/json", "Collections"),
+            ]
+        })
+    }
+    pub fn get_ogcapi_conformance(&self) -> serde_json::Value {
+        json!({ "conformsTo": CONFORMANCE })
+    }
+    fn ogcapi_collection(&self, baseurl: &str, ts: &Tileset) -> serde_json::Value {
+        let ext = ts.get_extent();
+        let url = format!("{}/collections/{}", baseurl, ts.name);
+        json!({
+            "id": ts.name,
+            "title": ts.name,
+            "attribution": ts.attribution(),
+            "extent": {
+                "spatial": {
+                    "bbox": [[ext.minx, ext.miny, ext.maxx, ext.maxy]],
+                    "crs": CRS84
+                }
+            },
+            "links": [
+                link(url.clone(), "self", "application/json", "This collection"),
+                link(
+                    format!("{}/tiles", url),
+                    "http://www.opengis.net/def/rel/ogc/1.0/tilesets-vector",
+                    "application/json",
+                    "Vector tilesets"
+                ),
+            ]
+        })
+    }
+    /// Collections of all tilesets
+    pub fn get_ogcapi_collections(&self, baseurl: &str) -> serde_json::Value {
+        let mut tilesets = self.tilesets.iter().collect::<Vec<_>>();
+        tilesets.sort_by(|a, b| a.name.cmp(&b.name));
+        let collections = tilesets
+            .iter()
+            .map(|ts| self.ogcapi_collection(baseurl, ts))
+            .collect::<Vec<_>>();
+        json!({
+            "links": [link(format!("{}/collections", baseurl), "self", "application/json", "Collections")],
+            "collections": collections
+        })
+    }
+    pub fn get_ogcapi_collection(&self, baseurl: &str, tileset: &str) -> Option<serde_json::Value> {
+        let ts = self.get_tileset(tileset)?;
+        Some(self.ogcapi_collection(baseurl, ts))
+    }
+    /// Tileset description with links to the tile matrix set definition
+    fn ogcapi_tileset_info(&self, url: &str) -> serde_json::Value {
+        let tms = self.tile_matrix_set_id();
+        let mut links = vec![link(url.to_string(), "self", "application/json", tms)];
+        let mut info = json!({
+            "title": tms,
+            "dataType": "vector",
+            "crs": self.crs_uri(),
+        });
+        if OGC_TILE_MATRIX_SETS.contains(&tms) {
+            let tms_uri = format!("http://www.opengis.net/def/tilematrixset/OGC/1.0/{}", tms);
+            info["tileMatrixSetURI"] = json!(tms_uri);
+            links.push(link(
+                tms_uri,
+                "http://www.opengis.net/def/rel/ogc/1.0/tiling-scheme",
+                "application/json",
+                "Tile matrix set definition",
+            ));
+        }
+        info["links"] = json!(links);
+        info
+    }
+    /// Tilesets of a collection
+    pub fn get_ogcapi_tilesets(&self, baseurl: &str, tileset: &str) -> Option<serde_json::Value> {
+        let ts = self.get_tileset(tileset)?;
+        let url = format!("{}/collections/{}/tiles", baseurl, ts.name);
+        let tms_url = format!("{}/{}", url, self.tile_matrix_set_id());
+        Some(json!({
+            "links": [link(url, "self", "application/json", "Vector tilesets")],
+            "tilesets": [self.ogcapi_tileset_info(&tms_url)]
+        }))
+    }
+    /// Tileset metadata with tile URL template and layers
+    pub fn get_ogcapi_tileset(
+        &self,
+        baseurl: &str,
+        tileset: &str,
+        tile_matrix_set: &str,
+    ) -> Option<serde_json::Value> {
+        let ts = self.get_tileset(tileset)?;
+        if tile_matrix_set != self.tile_matrix_set_id() {
+            return None;
+        }
+        let url = format!("{}/collections/{}/tiles/{}", baseurl, ts.name, tile_matrix_set);
+        let mut info = self.ogcapi_tileset_info(&url);
+        info["title"] = json!(ts.name);
+        info["links"].as_array_mut().unwrap().push(json!({
+            "href": format!("{}/{{tileMatrix}}/{{tileRow}}/{{tileCol}}", url),
+            "rel": "item",
+            "type": MVT_MEDIA_TYPE,
+            "title": "Vector tiles",
+            "templated": true
+        }));
+        let layers = ts
+            .layers
+            .iter()
+            .map(|layer| {
+                let mut layer_json = json!({
+                    "id": layer.name,
+                    "dataType": "vector",
+                    "minTileMatrix": cmp::max(ts.minzoom(), layer.minzoom()).to_string(),
+                    "maxTileMatrix": cmp::min(ts.maxzoom(), layer.maxzoom(22)).to_string(),
+                });
+                if let Some(geometry_type) = geometry_type(&layer.geometry_type) {
+                    layer_json["geometryType"] = json!(geometry_type);
+                }
+                layer_json
+            })
+            .collect::<Vec<_>>();
+        info["layers"] = json!(layers);
+        Some(info)
+    }
+}
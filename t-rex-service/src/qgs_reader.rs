@@ -165,6 +165,15 @@ pub fn read_qgs(fname: &str) -> (Datasources, Tileset) {
         start_zoom: None,
         layers: Vec::new(),
         cache_limits: None,
+        max_tile_bytes: None,
+        max_tile_memory: None,
+        mbtiles: None,
+        overzoom: false,
+        default_style: None,
+        skip_layer_errors: false,
+        always_gzip: false,
+        static_dir: None,
+        aliases: Vec::new(),
     };
     for qgslayer in projectlayers.find_all("maplayer") {
         let layertype = qgslayer.get_attr("type").expect("Missing attribute 'type'");
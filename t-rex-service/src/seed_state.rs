@@ -0,0 +1,183 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+//! Progress of `generate` for resuming interrupted runs
+//!
+//! Tiles (or metatiles) are generated in a deterministic order. The state file records
+//! the number of processed tiles in this order and the ranges of failed tiles together
+//! with the generate parameters. A resumed run with the same parameters skips the
+//! completed tiles and regenerates the failed ones.
+
+use serde_json;
+use std::cmp;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Interval for writing the state file
+const WRITE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Parameters of a generate run which must not change when resuming
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct SeedParams {
+    pub tileset: Option<String>,
+    pub minzoom: Option<u8>,
+    pub maxzoom: Option<u8>,
+    /// Extent as minx, miny, maxx, maxy
+    pub extent: Option<(f64, f64, f64, f64)>,
+    pub nodes: Option<u8>,
+    pub nodeno: Option<u8>,
+    pub metatile_size: u8,
+}
+
+/// Last completed tile in the adressing scheme of the cache path
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct SeedPosition {
+    pub tileset: String,
+    pub z: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+/// Content of the state file
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct SeedProgress {
+    pub params: SeedParams,
+    /// Number of processed tiles (or metatiles) in generation order, including tiles of other nodes
+    pub completed: u64,
+    /// Ranges of failed tile numbers (inclusive), which are not completed
+    #[serde(default)]
+    pub failed: Vec<(u64, u64)>,
+    pub last: Option<SeedPosition>,
+    pub finished: bool,
+}
+
+pub struct SeedState {
+    path: String,
+    progress: SeedProgress,
+    /// Completed tiles of the previous run
+    resume_from: u64,
+    last_write: Instant,
+}
+
+impl SeedState {
+    /// Open state file. An existing state file is resumed, if it has the same parameters.
+    pub fn open(path: &str, params: SeedParams) -> Result<SeedState, String> {
+        let mut progress = SeedProgress {
+            params: params.clone(),
+            completed: 0,
+            failed: Vec::new(),
+            last: None,
+            finished: false,
+        };
+        if Path::new(path).exists() {
+            let content = fs::read_to_string(path)
+                .map_err(|e| format!("Error reading state file '{}': {}", path, e))?;
+            let previous: SeedProgress = serde_json::from_str(&content)
+                .map_err(|e| format!("Error reading state file '{}': {}", path, e))?;
+            if previous.params != params {
+                return Err(format!(
+                    "State file '{}' was written with different parameters: {:?}",
+                    path, previous.params
+                ));
+            }
+            if let Some(ref last) = previous.last {
+                info!(
+                    "Resuming after tile {}/{}/{}/{} ({} completed)",
+                    last.tileset, last.z, last.x, last.y, previous.completed
+                );
+            }
+            if !previous.failed.is_empty() {
+                info!(
+                    "Regenerating {} ranges of failed tiles",
+                    previous.failed.len()
+                );
+            }
+            progress = previous;
+        }
+        let mut state = SeedState {
+            path: path.to_string(),
+            resume_from: progress.completed,
+            progress,
+            last_write: Instant::now(),
+        };
+        state.write();
+        Ok(state)
+    }
+    /// Check whether tile number `tileno` (counted from 1) was completed in a previous run
+    pub fn is_completed(&self, tileno: u64) -> bool {
+        tileno <= self.resume_from && self.failed_range(tileno).is_none()
+    }
+    /// Index of failed range containing `tileno`
+    fn failed_range(&self, tileno: u64) -> Option<usize> {
+        self.progress
+            .failed
+            .iter()
+            .position(|&(first, last)| first <= tileno && tileno <= last)
+    }
+    /// Record completion of tile number `tileno`. The state file is written periodically.
+    pub fn completed(&mut self, tileno: u64, tileset: &str, z: u8, x: u32, y: u32) {
+        // Regenerated tile of a previous run
+        if let Some(idx) = self.failed_range(tileno) {
+            let (first, last) = self.progress.failed.remove(idx);
+            if tileno < last {
+                self.progress.failed.insert(idx, (tileno + 1, last));
+            }
+            if first < tileno {
+                self.progress.failed.insert(idx, (first, tileno - 1));
+            }
+        }
+        self.progress.completed = cmp::max(self.progress.completed, tileno);
+        self.progress.last = Some(SeedPosition {
+            tileset: tileset.to_string(),
+            z,
+            x,
+            y,
+        });
+        if self.last_write.elapsed() >= WRITE_INTERVAL {
+            self.write();
+        }
+    }
+    /// Record failure of tile number `tileno`, which is regenerated when resuming
+    pub fn failed(&mut self, tileno: u64) {
+        if self.failed_range(tileno).is_some() {
+            return;
+        }
+        // Extend range, if the previous processed tile failed too
+        let completed = self.progress.completed;
+        match self.progress.failed.last_mut() {
+            Some(range) if range.1 == completed => range.1 = tileno,
+            _ => self.progress.failed.push((tileno, tileno)),
+        }
+        self.progress.completed = cmp::max(completed, tileno);
+        if self.last_write.elapsed() >= WRITE_INTERVAL {
+            self.write();
+        }
+    }
+    pub fn progress(&self) -> &SeedProgress {
+        &self.progress
+    }
+    /// Mark run as finished. The state file is written when dropped.
+    pub fn finish(mut self) {
+        self.progress.finished = true;
+    }
+    /// Write state file atomically
+    fn write(&mut self) {
+        let tmp_path = format!("{}.tmp", self.path);
+        let json = serde_json::to_vec_pretty(&self.progress).unwrap();
+        if let Err(e) = fs::write(&tmp_path, json).and_then(|_| fs::rename(&tmp_path, &self.path))
+        {
+            error!("Error writing state file '{}': {}", self.path, e);
+        }
+        self.last_write = Instant::now();
+    }
+}
+
+impl Drop for SeedState {
+    /// Write final state, also for runs aborted by an error
+    fn drop(&mut self) {
+        self.write();
+    }
+}
@@ -0,0 +1,497 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+//! Comparison of vector tiles and tile cache directories
+//!
+//! Features are matched by id, geometry and attributes. Changed features
+//! are reported as removed from the first and added to the second tile.
+
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+use t_rex_core::mvt::geom_encoder::decode_geometry;
+use t_rex_core::mvt::tile::Tile;
+use t_rex_core::mvt::vector_tile;
+
+/// Upper bounds of the size delta buckets (in bytes) of changed tiles.
+/// The last bucket contains all tiles growing by more than 10000 bytes.
+pub const SIZE_BUCKETS: [i64; 9] = [-10001, -1001, -101, -1, 0, 100, 1000, 10000, i64::MAX];
+
+/// Comparison of a layer in two tiles
+#[derive(Default, Debug)]
+pub struct LayerDiff {
+    pub name: String,
+    pub features_a: usize,
+    pub features_b: usize,
+    /// Encoded layer size in bytes
+    pub bytes_a: u32,
+    pub bytes_b: u32,
+    /// Number of features in `b` without matching feature in `a`
+    pub added: usize,
+    /// Number of features in `a` without matching feature in `b`
+    pub removed: usize,
+    pub keys_added: Vec<String>,
+    pub keys_removed: Vec<String>,
+    /// Added and removed features as GeoJSON (tile coordinates)
+    pub features: Vec<Value>,
+}
+
+impl LayerDiff {
+    pub fn is_identical(&self) -> bool {
+        self.added == 0
+            && self.removed == 0
+            && self.keys_added.is_empty()
+            && self.keys_removed.is_empty()
+            && self.bytes_a == self.bytes_b
+    }
+}
+
+/// Comparison of two tiles
+#[derive(Default, Debug)]
+pub struct TileDiff {
+    /// Uncompressed tile size in bytes
+    pub bytes_a: u32,
+    pub bytes_b: u32,
+    pub layers: Vec<LayerDiff>,
+}
+
+impl TileDiff {
+    pub fn is_identical(&self) -> bool {
+        self.layers.iter().all(|layer| layer.is_identical())
+    }
+    /// Human readable report with one line per layer
+    pub fn report(&self) -> String {
+        let mut lines = vec![format!(
+            "Tile size: {} -> {} bytes ({:+})",
+            self.bytes_a,
+            self.bytes_b,
+            i64::from(self.bytes_b) - i64::from(self.bytes_a)
+        )];
+        for layer in &self.layers {
+            if layer.is_identical() {
+                lines.push(format!("Layer '{}': identical", layer.name));
+                continue;
+            }
+            lines.push(format!(
+                "Layer '{}': features {} -> {} ({:+}), {} added, {} removed, size {} -> {} bytes ({:+})",
+                layer.name,
+                layer.features_a,
+                layer.features_b,
+                layer.features_b as i64 - layer.features_a as i64,
+                layer.added,
+                layer.removed,
+                layer.bytes_a,
+                layer.bytes_b,
+                i64::from(layer.bytes_b) - i64::from(layer.bytes_a)
+            ));
+            if !layer.keys_added.is_empty() {
+                lines.push(format!("  keys added: {}", layer.keys_added.join(", ")));
+            }
+            if !layer.keys_removed.is_empty() {
+                lines.push(format!("  keys removed: {}", layer.keys_removed.join(", ")));
+            }
+        }
+        lines.push(String::new());
+        lines.join("\n")
+    }
+    /// Added and removed features of all layers with `tile` as feature member
+    pub fn geojson_features(&self, tile: &str) -> Vec<Value> {
+        self.layers
+            .iter()
+            .flat_map(|layer| layer.features.iter())
+            .map(|feature| {
+                let mut feature = feature.clone();
+                feature["tile"] = json!(tile);
+                feature
+            })
+            .collect()
+    }
+}
+
+/// Write features into a GeoJSON FeatureCollection file
+pub fn write_geojson(fname: &str, features: Vec<Value>) -> io::Result<()> {
+    let collection = json!({
+        "type": "FeatureCollection",
+        "features": features,
+    });
+    fs::write(fname, collection.to_string())
+}
+
+/// Decode a (gzip compressed) tile
+pub fn read_tile(data: &[u8]) -> Result<vector_tile::Tile, String> {
+    if data.starts_with(&[0x1f, 0x8b]) {
+        Tile::read_gz_from(&mut &data[..])
+    } else {
+        Tile::read_from(&mut &data[..])
+    }
+    .map_err(|e| format!("Error decoding tile: {}", e))
+}
+
+fn value_json(value: &vector_tile::Tile_Value) -> Value {
+    if value.has_string_value() {
+        json!(value.get_string_value())
+    } else if value.has_float_value() {
+        json!(value.get_float_value())
+    } else if value.has_double_value() {
+        json!(value.get_double_value())
+    } else if value.has_int_value() {
+        json!(value.get_int_value())
+    } else if value.has_uint_value() {
+        json!(value.get_uint_value())
+    } else if value.has_sint_value() {
+        json!(value.get_sint_value())
+    } else if value.has_bool_value() {
+        json!(value.get_bool_value())
+    } else {
+        Value::Null
+    }
+}
+
+fn feature_properties(
+    layer: &vector_tile::Tile_Layer,
+    feature: &vector_tile::Tile_Feature,
+) -> BTreeMap<String, Value> {
+    let keys = layer.get_keys();
+    let values = layer.get_values();
+    feature
+        .get_tags()
+        .chunks(2)
+        .filter(|tag| tag.len() == 2)
+        .filter_map(|tag| {
+            let key = keys.get(tag[0] as usize)?;
+            let value = values.get(tag[1] as usize)?;
+            Some((key.clone(), value_json(value)))
+        })
+        .collect()
+}
+
+/// Signed area of a ring (positive for exterior rings in tile coordinates)
+fn ring_area(ring: &[(i32, i32)]) -> f64 {
+    ring.windows(2)
+        .map(|w| f64::from(w[0].0) * f64::from(w[1].1) - f64::from(w[1].0) * f64::from(w[0].1))
+        .sum::<f64>()
+        / 2.0
+}
+
+fn geometry_json(feature: &vector_tile::Tile_Feature) -> Value {
+    let paths = decode_geometry(feature.get_geometry());
+    let coords = |path: &Vec<(i32, i32)>| -> Vec<Vec<i32>> {
+        path.iter().map(|&(x, y)| vec![x, y]).collect()
+    };
+    match feature.get_field_type() {
+        vector_tile::Tile_GeomType::POINT => {
+            let points: Vec<Vec<i32>> = paths.iter().flat_map(|path| coords(path)).collect();
+            if points.len() == 1 {
+                json!({"type": "Point", "coordinates": points[0]})
+            } else {
+                json!({"type": "MultiPoint", "coordinates": points})
+            }
+        }
+        vector_tile::Tile_GeomType::LINESTRING => {
+            if paths.len() == 1 {
+                json!({"type": "LineString", "coordinates": coords(&paths[0])})
+            } else {
+                let lines: Vec<_> = paths.iter().map(coords).collect();
+                json!({"type": "MultiLineString", "coordinates": lines})
+            }
+        }
+        vector_tile::Tile_GeomType::POLYGON => {
+            let mut polygons: Vec<Vec<Vec<Vec<i32>>>> = Vec::new();
+            for ring in &paths {
+                if ring_area(ring) > 0.0 || polygons.is_empty() {
+                    polygons.push(Vec::new());
+                }
+                polygons.last_mut().unwrap().push(coords(ring));
+            }
+            if polygons.len() == 1 {
+                json!({"type": "Polygon", "coordinates": polygons[0]})
+            } else {
+                json!({"type": "MultiPolygon", "coordinates": polygons})
+            }
+        }
+        vector_tile::Tile_GeomType::UNKNOWN => Value::Null,
+    }
+}
+
+fn feature_json(
+    layer: &vector_tile::Tile_Layer,
+    feature: &vector_tile::Tile_Feature,
+    change: &str,
+) -> Value {
+    let mut json = json!({
+        "type": "Feature",
+        "geometry": geometry_json(feature),
+        "properties": feature_properties(layer, feature),
+        "layer": layer.get_name(),
+        "change": change,
+    });
+    if feature.has_id() {
+        json["id"] = json!(feature.get_id());
+    }
+    json
+}
+
+/// Key for matching features independent of the key and value tables of their layer
+fn feature_key(layer: &vector_tile::Tile_Layer, feature: &vector_tile::Tile_Feature) -> String {
+    json!([
+        feature.get_id(),
+        feature.get_field_type() as i32,
+        feature.get_geometry(),
+        feature_properties(layer, feature),
+    ])
+    .to_string()
+}
+
+/// Features of `layer` without matching feature in `other`
+fn unmatched_features<'a>(
+    layer: &'a vector_tile::Tile_Layer,
+    other: Option<&vector_tile::Tile_Layer>,
+) -> Vec<&'a vector_tile::Tile_Feature> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    if let Some(other) = other {
+        for feature in other.get_features() {
+            *counts.entry(feature_key(other, feature)).or_insert(0) += 1;
+        }
+    }
+    layer
+        .get_features()
+        .iter()
+        .filter(
+            |feature| match counts.get_mut(&feature_key(layer, feature)) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    false
+                }
+                _ => true,
+            },
+        )
+        .collect()
+}
+
+fn diff_layers(
+    name: &str,
+    a: Option<&vector_tile::Tile_Layer>,
+    b: Option<&vector_tile::Tile_Layer>,
+    geojson: bool,
+) -> LayerDiff {
+    let keys = |layer: Option<&vector_tile::Tile_Layer>| -> BTreeSet<String> {
+        layer.map_or(BTreeSet::new(), |layer| {
+            layer.get_keys().iter().cloned().collect()
+        })
+    };
+    let (keys_a, keys_b) = (keys(a), keys(b));
+    let mut diff = LayerDiff {
+        name: name.to_string(),
+        features_a: a.map_or(0, |layer| layer.get_features().len()),
+        features_b: b.map_or(0, |layer| layer.get_features().len()),
+        bytes_a: a.map_or(0, Tile::layer_size),
+        bytes_b: b.map_or(0, Tile::layer_size),
+        keys_added: keys_b.difference(&keys_a).cloned().collect(),
+        keys_removed: keys_a.difference(&keys_b).cloned().collect(),
+        ..Default::default()
+    };
+    if let Some(layer) = a {
+        let removed = unmatched_features(layer, b);
+        diff.removed = removed.len();
+        if geojson {
+            diff.features
+                .extend(removed.iter().map(|f| feature_json(layer, f, "removed")));
+        }
+    }
+    if let Some(layer) = b {
+        let added = unmatched_features(layer, a);
+        diff.added = added.len();
+        if geojson {
+            diff.features
+                .extend(added.iter().map(|f| feature_json(layer, f, "added")));
+        }
+    }
+    diff
+}
+
+/// Compare two decoded tiles. Added and removed features are
+/// converted to GeoJSON, if `geojson` is set.
+pub fn diff_tiles(a: &vector_tile::Tile, b: &vector_tile::Tile, geojson: bool) -> TileDiff {
+    let find = |tile: &vector_tile::Tile, name: &str| -> Option<vector_tile::Tile_Layer> {
+        tile.get_layers()
+            .iter()
+            .find(|layer| layer.get_name() == name)
+            .cloned()
+    };
+    let mut names: Vec<&str> = a.get_layers().iter().map(|l| l.get_name()).collect();
+    for layer in b.get_layers() {
+        if !names.contains(&layer.get_name()) {
+            names.push(layer.get_name());
+        }
+    }
+    let layers = names
+        .iter()
+        .map(|name| {
+            let (layer_a, layer_b) = (find(a, name), find(b, name));
+            diff_layers(name, layer_a.as_ref(), layer_b.as_ref(), geojson)
+        })
+        .collect();
+    TileDiff {
+        bytes_a: Tile::size(a),
+        bytes_b: Tile::size(b),
+        layers,
+    }
+}
+
+/// Tile counts of a directory comparison
+#[derive(Default, PartialEq, Debug)]
+pub struct TileCounts {
+    pub identical: u64,
+    pub changed: u64,
+    /// Tiles only in the second directory
+    pub added: u64,
+    /// Tiles only in the first directory
+    pub removed: u64,
+}
+
+impl TileCounts {
+    fn report(&self) -> String {
+        format!(
+            "{} identical, {} changed, {} added, {} removed",
+            self.identical, self.changed, self.added, self.removed
+        )
+    }
+}
+
+/// Comparison of two tile cache directories
+#[derive(Default, Debug)]
+pub struct DirDiff {
+    pub total: TileCounts,
+    /// Tile counts per zoom level of cache paths like `tileset/z/x/y.pbf`
+    pub levels: BTreeMap<u8, TileCounts>,
+    /// Number of changed tiles per size delta bucket (see `SIZE_BUCKETS`)
+    pub size_histogram: [u64; 9],
+    /// Sum of tile file sizes in bytes
+    pub bytes_a: u64,
+    pub bytes_b: u64,
+}
+
+impl DirDiff {
+    fn count(&mut self, path: &str, count: fn(&mut TileCounts)) {
+        count(&mut self.total);
+        let zoom = path.rsplit('/').nth(2).and_then(|z| z.parse::<u8>().ok());
+        if let Some(zoom) = zoom {
+            count(self.levels.entry(zoom).or_insert_with(TileCounts::default));
+        }
+    }
+    /// Human readable summary
+    pub fn report(&self) -> String {
+        let mut lines = vec![
+            format!("Tiles: {}", self.total.report()),
+            format!(
+                "Size: {} -> {} bytes ({:+})",
+                self.bytes_a,
+                self.bytes_b,
+                self.bytes_b as i64 - self.bytes_a as i64
+            ),
+        ];
+        for (zoom, counts) in &self.levels {
+            lines.push(format!("Zoom level {}: {}", zoom, counts.report()));
+        }
+        lines.push("Size delta of changed tiles (bytes):".to_string());
+        let mut lower = i64::MIN;
+        for (bound, count) in SIZE_BUCKETS.iter().zip(self.size_histogram.iter()) {
+            let range = match (lower, *bound) {
+                (i64::MIN, upper) => format!("..{}", upper),
+                (lower, i64::MAX) => format!("{}..", lower),
+                (lower, upper) if lower == upper => format!("{}", lower),
+                (lower, upper) => format!("{}..{}", lower, upper),
+            };
+            lines.push(format!("  {:>12}: {}", range, count));
+            lower = bound.saturating_add(1);
+        }
+        lines.push(String::new());
+        lines.join("\n")
+    }
+}
+
+/// Index of the `SIZE_BUCKETS` bucket containing `delta`
+pub fn size_bucket(delta: i64) -> usize {
+    SIZE_BUCKETS
+        .iter()
+        .position(|bound| delta <= *bound)
+        .unwrap_or(SIZE_BUCKETS.len() - 1)
+}
+
+/// Collect relative paths of all tiles (`*.pbf`) below `dir`
+fn tile_paths(dir: &Path, prefix: &str, paths: &mut BTreeMap<String, u64>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let path = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}/{}", prefix, name)
+        };
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            tile_paths(&entry.path(), &path, paths)?;
+        } else if name.ends_with(".pbf") {
+            paths.insert(path, metadata.len());
+        }
+    }
+    Ok(())
+}
+
+/// Compare all tiles of two cache directories. `changed` is called with the path and the
+/// comparison of every changed tile. Added and removed features are converted to GeoJSON,
+/// if `geojson` is set.
+pub fn diff_dirs<F>(a: &Path, b: &Path, geojson: bool, mut changed: F) -> io::Result<DirDiff>
+where
+    F: FnMut(&str, &TileDiff),
+{
+    let mut paths_a = BTreeMap::new();
+    tile_paths(a, "", &mut paths_a)?;
+    let mut paths_b = BTreeMap::new();
+    tile_paths(b, "", &mut paths_b)?;
+    let mut stats = DirDiff::default();
+    for (path, size) in &paths_a {
+        stats.bytes_a += size;
+        if !paths_b.contains_key(path) {
+            stats.count(path, |c| c.removed += 1);
+        }
+    }
+    for (path, size) in &paths_b {
+        stats.bytes_b += size;
+        let size_a = match paths_a.get(path) {
+            Some(size_a) => *size_a,
+            None => {
+                stats.count(path, |c| c.added += 1);
+                continue;
+            }
+        };
+        let data_a = fs::read(a.join(path))?;
+        let data_b = fs::read(b.join(path))?;
+        if data_a == data_b {
+            stats.count(path, |c| c.identical += 1);
+            continue;
+        }
+        // Tiles which can't be decoded are counted as changed
+        let diff = match (read_tile(&data_a), read_tile(&data_b)) {
+            (Ok(tile_a), Ok(tile_b)) => Some(diff_tiles(&tile_a, &tile_b, geojson)),
+            (Err(e), _) | (_, Err(e)) => {
+                warn!("Tile '{}': {}", path, e);
+                None
+            }
+        };
+        if diff.as_ref().map_or(false, |diff| diff.is_identical()) {
+            stats.count(path, |c| c.identical += 1);
+        } else {
+            stats.count(path, |c| c.changed += 1);
+            stats.size_histogram[size_bucket(*size as i64 - size_a as i64)] += 1;
+            if let Some(ref diff) = diff {
+                changed(path, diff);
+            }
+        }
+    }
+    Ok(stats)
+}
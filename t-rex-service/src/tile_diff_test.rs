@@ -0,0 +1,197 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use crate::tile_diff::{diff_dirs, diff_tiles, read_tile, size_bucket};
+use std::env;
+use std::fs;
+use t_rex_core::mvt::tile::Tile;
+use t_rex_core::mvt::vector_tile;
+
+fn point_feature(id: u64, x: u32, tags: Vec<u32>) -> vector_tile::Tile_Feature {
+    let mut feature = vector_tile::Tile_Feature::new();
+    feature.set_id(id);
+    feature.set_field_type(vector_tile::Tile_GeomType::POINT);
+    feature.set_geometry(vec![9, x * 2, 4]);
+    feature.set_tags(tags);
+    feature
+}
+
+fn layer(
+    name: &str,
+    keys: &[&str],
+    features: Vec<vector_tile::Tile_Feature>,
+) -> vector_tile::Tile_Layer {
+    let mut layer = vector_tile::Tile_Layer::new();
+    layer.set_version(2);
+    layer.set_name(name.to_string());
+    layer.set_extent(4096);
+    for key in keys {
+        layer.mut_keys().push(key.to_string());
+    }
+    let mut value = vector_tile::Tile_Value::new();
+    value.set_string_value("a".to_string());
+    layer.mut_values().push(value);
+    for feature in features {
+        layer.mut_features().push(feature);
+    }
+    layer
+}
+
+fn tile(layers: Vec<vector_tile::Tile_Layer>) -> vector_tile::Tile {
+    let mut tile = vector_tile::Tile::new();
+    for layer in layers {
+        tile.mut_layers().push(layer);
+    }
+    tile
+}
+
+#[test]
+fn test_diff_tiles() {
+    let a = tile(vec![
+        layer(
+            "points",
+            &["name"],
+            vec![
+                point_feature(1, 10, vec![0, 0]),
+                point_feature(2, 20, vec![0, 0]),
+            ],
+        ),
+        layer("roads", &[], vec![point_feature(1, 10, vec![])]),
+    ]);
+    let diff = diff_tiles(&a, &a, true);
+    assert!(diff.is_identical());
+    assert_eq!(diff.layers.len(), 2);
+    assert!(diff.geojson_features("0/0/0").is_empty());
+
+    // Same features with different key table
+    let b = tile(vec![
+        layer(
+            "points",
+            &["other", "name", "class"],
+            vec![
+                point_feature(1, 10, vec![1, 0]),
+                point_feature(3, 30, vec![2, 0]),
+            ],
+        ),
+        layer("pois", &[], vec![point_feature(1, 10, vec![])]),
+    ]);
+    let diff = diff_tiles(&a, &b, true);
+    assert!(!diff.is_identical());
+    let names: Vec<&str> = diff.layers.iter().map(|l| l.name.as_str()).collect();
+    assert_eq!(names, vec!["points", "roads", "pois"]);
+    let points = &diff.layers[0];
+    assert_eq!((points.features_a, points.features_b), (2, 2));
+    assert_eq!((points.added, points.removed), (1, 1));
+    assert_eq!(points.keys_added, vec!["class", "other"]);
+    assert!(points.keys_removed.is_empty());
+    assert_eq!((diff.layers[1].features_b, diff.layers[1].removed), (0, 1));
+    assert_eq!((diff.layers[2].features_a, diff.layers[2].added), (0, 1));
+    assert_eq!(diff.layers[1].bytes_b, 0);
+
+    let features = diff.geojson_features("0/0/0");
+    assert_eq!(features.len(), 4);
+    assert_eq!(
+        features[0].to_string(),
+        r#"{"change":"removed","geometry":{"coordinates":[20,2],"type":"Point"},"id":2,"layer":"points","properties":{"name":"a"},"tile":"0/0/0","type":"Feature"}"#
+    );
+    assert_eq!(features[1]["change"], "added");
+    assert_eq!(features[1]["properties"]["class"], "a");
+
+    let report = diff.report();
+    assert!(report.contains("Layer 'points': features 2 -> 2 (+0), 1 added, 1 removed"));
+    assert!(report.contains("  keys added: class, other\n"));
+    assert!(report.contains("Layer 'roads': features 1 -> 0 (-1), 0 added, 1 removed"));
+}
+
+#[test]
+fn test_read_tile() {
+    let a = tile(vec![layer(
+        "points",
+        &[],
+        vec![point_feature(1, 10, vec![])],
+    )]);
+    assert_eq!(read_tile(&Tile::tile_bytevec(&a)), Ok(a.clone()));
+    assert_eq!(read_tile(&Tile::tile_bytevec_gz(&a)), Ok(a));
+}
+
+#[test]
+fn test_size_bucket() {
+    assert_eq!(size_bucket(-20000), 0);
+    assert_eq!(size_bucket(-10001), 0);
+    assert_eq!(size_bucket(-10000), 1);
+    assert_eq!(size_bucket(-1), 3);
+    assert_eq!(size_bucket(0), 4);
+    assert_eq!(size_bucket(1), 5);
+    assert_eq!(size_bucket(10001), 8);
+}
+
+#[test]
+fn test_diff_dirs() {
+    let mut basedir = env::temp_dir();
+    basedir.push("t_rex_test_diff");
+    let _ = fs::remove_dir_all(&basedir);
+    let dir_a = basedir.join("a");
+    let dir_b = basedir.join("b");
+    let a = tile(vec![layer(
+        "points",
+        &[],
+        vec![point_feature(1, 10, vec![])],
+    )]);
+    let b = tile(vec![layer(
+        "points",
+        &[],
+        vec![point_feature(1, 20, vec![])],
+    )]);
+    for (dir, tiles) in vec![
+        (
+            &dir_a,
+            vec![
+                ("ts/0/0/0.pbf", &a),
+                ("ts/1/0/0.pbf", &a),
+                ("ts/1/0/1.pbf", &a),
+            ],
+        ),
+        (
+            &dir_b,
+            vec![
+                ("ts/0/0/0.pbf", &a),
+                ("ts/1/0/0.pbf", &b),
+                ("ts/1/1/0.pbf", &b),
+            ],
+        ),
+    ] {
+        for (path, tile) in tiles {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, Tile::tile_bytevec_gz(tile)).unwrap();
+        }
+    }
+    fs::write(dir_a.join("ts/metadata.json"), "{}").unwrap();
+
+    let mut changed = Vec::new();
+    let stats = diff_dirs(&dir_a, &dir_b, false, |path, diff| {
+        changed.push((path.to_string(), diff.layers[0].added))
+    })
+    .unwrap();
+    assert_eq!(changed, vec![("ts/1/0/0.pbf".to_string(), 1)]);
+    assert_eq!(
+        (
+            stats.total.identical,
+            stats.total.changed,
+            stats.total.added,
+            stats.total.removed
+        ),
+        (1, 1, 1, 1)
+    );
+    assert_eq!(stats.levels.len(), 2);
+    assert_eq!(stats.levels[&0].identical, 1);
+    assert_eq!(stats.levels[&1].changed, 1);
+    assert_eq!(stats.size_histogram.iter().sum::<u64>(), 1);
+    let report = stats.report();
+    assert!(report.starts_with("Tiles: 1 identical, 1 changed, 1 added, 1 removed\n"));
+    assert!(report.contains("Zoom level 1: 0 identical, 1 changed, 1 added, 1 removed\n"));
+    assert!(report.contains("            0: "));
+    assert!(report.contains("       10001..: 0\n"));
+}
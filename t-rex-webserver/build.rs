@@ -2,51 +2,137 @@ use std::env;
 use std::fs;
 use std::fs::File;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+/// Static files of the built-in viewer with their media types
+const STATIC_FILES: &[(&str, &str)] = &[
+    ("favicon.ico", "image/x-icon"),
+    ("index.html", "text/html"),
+    ("viewer.js", "application/javascript"),
+    ("viewer.css", "text/css"),
+    ("drilldown.html", "text/html"),
+    ("maputnik.html", "text/html"),
+    ("maputnik.js", "application/javascript"),
+    ("maputnik-vendor.js", "application/javascript"),
+    ("img/logo-color.svg", "image/svg+xml"),
+    ("fonts/Roboto-Regular.ttf", "font/ttf"),
+    ("fonts/Roboto-Medium.ttf", "font/ttf"),
+];
+
+const STATIC_DIR: &str = "src/static";
+
+/// Embeddable path with forward slashes
+fn include_path(path: &Path) -> String {
+    path.canonicalize()
+        .unwrap()
+        .display()
+        .to_string()
+        .replace(std::path::MAIN_SEPARATOR, "/")
+        .replace("//?/", "")
+}
+
+/// Font glyph files as (key, path), e.g. ("fonts/Roboto Regular/0-255.pbf", ...)
+fn font_files(static_dir: &Path) -> Vec<(String, PathBuf)> {
+    let mut fonts = Vec::new();
+    let fontdir = static_dir.join("fonts");
+    if let Ok(entries) = fs::read_dir(&fontdir) {
+        for l1 in entries {
+            let l1path = l1.unwrap().path();
+            if l1path.is_dir() {
+                let fontname = l1path.file_name().unwrap().to_string_lossy().to_string();
+                for pbf in fs::read_dir(&l1path).unwrap() {
+                    let pbfpath = pbf.unwrap().path();
+                    if pbfpath.extension().map(|ext| ext == "pbf").unwrap_or(false) {
+                        let range = pbfpath.file_name().unwrap().to_string_lossy().to_string();
+                        fonts.push((format!("fonts/{}/{}", fontname, range), pbfpath));
+                    }
+                }
+            }
+        }
+    }
+    fonts.sort();
+    fonts
+}
+
+fn missing_assets_error(missing: &[String]) -> ! {
+    panic!(
+        "\n\nt-rex-webserver: missing static assets in {}:\n  {}\n\n\
+         Use a complete source checkout or download the Maputnik files with \
+         `make getmaputnik` in t-rex-webserver.\n\
+         To build without embedded assets, set TREX_ASSETS_DIR to an assets directory \
+         which is read at runtime.\n\n",
+        STATIC_DIR,
+        missing.join("\n  ")
+    )
+}
 
 fn main() {
+    println!("cargo:rerun-if-changed={}", STATIC_DIR);
+    println!("cargo:rerun-if-env-changed=TREX_ASSETS_DIR");
+    let assets_dir = env::var("TREX_ASSETS_DIR").ok();
+    let static_dir = Path::new(STATIC_DIR);
+    // Assets are only embedded with feature `webviewer`
+    let embed = assets_dir.is_none() && env::var("CARGO_FEATURE_WEBVIEWER").is_ok();
+
+    let mut files = Vec::new();
+    let mut fonts = Vec::new();
+    if embed {
+        let mut missing = STATIC_FILES
+            .iter()
+            .filter(|(name, _)| !static_dir.join(name).is_file())
+            .map(|(name, _)| name.to_string())
+            .collect::<Vec<_>>();
+        fonts = font_files(static_dir);
+        if fonts.is_empty() {
+            missing.push("fonts/<Font Name>/<range>.pbf".to_string());
+        }
+        if !missing.is_empty() {
+            missing_assets_error(&missing);
+        }
+        files = STATIC_FILES
+            .iter()
+            .map(|(name, _)| (name.to_string(), static_dir.join(name)))
+            .collect();
+    }
+
     let out_dir = env::var("OUT_DIR").unwrap();
-    let dest_path = Path::new(&out_dir).join("fonts.rs");
+    let dest_path = Path::new(&out_dir).join("assets.rs");
     let mut f = File::create(&dest_path).unwrap();
 
+    writeln!(f, "/// Static files (name, media type) of the built-in viewer").unwrap();
+    writeln!(f, "pub const STATIC_FILE_TYPES: &[(&str, &str)] = &[").unwrap();
+    for (name, media_type) in STATIC_FILES {
+        writeln!(f, "    ({:?}, {:?}),", name, media_type).unwrap();
+    }
+    writeln!(f, "];").unwrap();
+    writeln!(f, "/// Assets directory configured at build time (no embedded assets)").unwrap();
+    writeln!(f, "pub const BUILD_ASSETS_DIR: Option<&str> = {:?};", assets_dir).unwrap();
+    writeln!(f, "/// Assets embedded at build time").unwrap();
+    writeln!(f, "pub const EMBEDDED_ASSETS: bool = {:?};", embed).unwrap();
+    writeln!(f, "/// Embedded font glyph files").unwrap();
+    writeln!(f, "pub const EMBEDDED_FONTS: &[&str] = &[").unwrap();
+    for (key, _) in &fonts {
+        writeln!(f, "    {:?},", key).unwrap();
+    }
+    writeln!(f, "];").unwrap();
+    // Lookup without building a map at startup
+    writeln!(f, "/// Embedded static file or font glyph file").unwrap();
     writeln!(
         f,
-        "pub fn fonts() -> HashMap<&'static str, &'static [u8]> {{"
-    )
-    .unwrap();
-    writeln!(
-        f,
-        "let mut fonts = HashMap::<&'static str, &'static [u8]>::new();"
+        "pub fn embedded_file(key: &str) -> Option<&'static [u8]> {{"
     )
     .unwrap();
-    for l1 in fs::read_dir("./src/static/fonts/").unwrap() {
-        let l1fn = l1.unwrap().path();
-        if l1fn.is_dir() {
-            for pbf in fs::read_dir(l1fn).unwrap() {
-                let pbfpath = pbf.unwrap().path();
-                let inclpath = pbfpath.as_path().canonicalize().unwrap();
-                let mut pbfcomp = pbfpath.components();
-                pbfcomp.next();
-                pbfcomp.next();
-                pbfcomp.next();
-                let keypath = pbfcomp.as_path();
-                writeln!(
-                    f,
-                    "fonts.insert(\"{}\", include_bytes!(\"{}\"));",
-                    keypath
-                        .display()
-                        .to_string()
-                        .replace(std::path::MAIN_SEPARATOR, "/"),
-                    inclpath
-                        .display()
-                        .to_string()
-                        .replace(std::path::MAIN_SEPARATOR, "/")
-                        .replace("//?/", "")
-                )
-                .unwrap();
-            }
-        }
+    writeln!(f, "    match key {{").unwrap();
+    for (key, path) in files.iter().chain(fonts.iter()) {
+        writeln!(
+            f,
+            "        {:?} => Some(include_bytes!({:?})),",
+            key,
+            include_path(path)
+        )
+        .unwrap();
     }
-    writeln!(f, "fonts").unwrap();
+    writeln!(f, "        _ => None,").unwrap();
+    writeln!(f, "    }}").unwrap();
     writeln!(f, "}}").unwrap();
 }
@@ -0,0 +1,96 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use crate::datasources::Datasources;
+use std::collections::BTreeMap;
+
+/// Version and build details of the t-rex binary
+#[derive(Clone, Default, Serialize, Debug)]
+pub struct BuildInfo {
+    pub version: String,
+    /// Git commit hash of the source tree
+    pub git_commit: Option<String>,
+    /// Build time in RFC 3339 format
+    pub build_date: Option<String>,
+    /// Enabled cargo features
+    pub features: Vec<String>,
+    /// Versions of linked libraries like GDAL
+    pub dependencies: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+pub struct DatasourceVersion {
+    #[serde(rename = "type")]
+    ds_type: &'static str,
+    version: Option<String>,
+}
+
+/// Content of `/version.json`
+#[derive(Serialize)]
+pub struct VersionJson<'a> {
+    #[serde(flatten)]
+    build: &'a BuildInfo,
+    datasources: BTreeMap<String, DatasourceVersion>,
+}
+
+impl BuildInfo {
+    /// Version with build details in one line, e.g.
+    /// "t-rex 0.10.2 (commit 1a2b3c4d5e6f, built 2020-06-01T12:00:00Z, features: webviewer, with-gdal, GDAL 3.1.0)"
+    pub fn summary(&self) -> String {
+        let mut details = Vec::new();
+        if let Some(ref commit) = self.git_commit {
+            details.push(format!("commit {}", &commit[..commit.len().min(12)]));
+        }
+        if let Some(ref build_date) = self.build_date {
+            details.push(format!("built {}", build_date));
+        }
+        if !self.features.is_empty() {
+            details.push(format!("features: {}", self.features.join(", ")));
+        }
+        for (name, version) in &self.dependencies {
+            details.push(format!("{} {}", name, version));
+        }
+        if details.is_empty() {
+            format!("t-rex {}", self.version)
+        } else {
+            format!("t-rex {} ({})", self.version, details.join(", "))
+        }
+    }
+    /// Build details printed by `t_rex --version --verbose`
+    pub fn details(&self) -> String {
+        let unknown = "unknown".to_string();
+        let mut lines = vec![
+            format!("version: {}", self.version),
+            format!("commit: {}", self.git_commit.as_ref().unwrap_or(&unknown)),
+            format!(
+                "build date: {}",
+                self.build_date.as_ref().unwrap_or(&unknown)
+            ),
+            format!("features: {}", self.features.join(", ")),
+        ];
+        for (name, version) in &self.dependencies {
+            lines.push(format!("{}: {}", name, version));
+        }
+        lines.join("\n")
+    }
+    /// Build details with the database versions of the datasources
+    pub fn version_json(&self, datasources: &Datasources) -> VersionJson<'_> {
+        let datasources = datasources
+            .datasources
+            .iter()
+            .map(|(name, ds)| {
+                let version = DatasourceVersion {
+                    ds_type: ds.type_name(),
+                    version: ds.server_version(),
+                };
+                (name.clone(), version)
+            })
+            .collect();
+        VersionJson {
+            build: self,
+            datasources,
+        }
+    }
+}
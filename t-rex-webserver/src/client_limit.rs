@@ -0,0 +1,86 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+/// Limit of concurrent requests per client IP, shared by clones
+#[derive(Clone)]
+pub struct ClientLimit {
+    max_requests: u32,
+    /// Proxies representing many clients are not limited
+    trusted_proxies: Vec<IpAddr>,
+    active: Arc<Mutex<HashMap<IpAddr, u32>>>,
+}
+
+/// Request slot of a client. Released when dropped.
+pub struct ClientSlot {
+    limit: ClientLimit,
+    ip: Option<IpAddr>,
+}
+
+impl ClientLimit {
+    pub fn new(max_requests: u32, trusted_proxies: &[String]) -> Result<ClientLimit, String> {
+        let trusted_proxies = trusted_proxies
+            .iter()
+            .map(|ip| {
+                ip.parse()
+                    .map_err(|_| format!("Invalid trusted proxy IP address '{}'", ip))
+            })
+            .collect::<Result<Vec<IpAddr>, String>>()?;
+        Ok(ClientLimit {
+            max_requests,
+            trusted_proxies,
+            active: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+    /// Request slot for client, `None` if the limit is exceeded
+    pub fn acquire(&self, ip: Option<IpAddr>) -> Option<ClientSlot> {
+        let ip = match ip {
+            Some(ip) if !self.trusted_proxies.contains(&ip) => ip,
+            _ => {
+                return Some(ClientSlot {
+                    limit: self.clone(),
+                    ip: None,
+                })
+            }
+        };
+        let mut active = self.active.lock().unwrap();
+        let count = active.entry(ip).or_insert(0);
+        if *count >= self.max_requests {
+            return None;
+        }
+        *count += 1;
+        Some(ClientSlot {
+            limit: self.clone(),
+            ip: Some(ip),
+        })
+    }
+    /// Number of requests in progress of a client
+    #[cfg(test)]
+    pub fn active(&self, ip: &IpAddr) -> u32 {
+        *self.active.lock().unwrap().get(ip).unwrap_or(&0)
+    }
+}
+
+impl Drop for ClientSlot {
+    fn drop(&mut self) {
+        if let Some(ip) = self.ip {
+            if let Ok(mut active) = self.limit.active.lock() {
+                let remove = match active.get_mut(&ip) {
+                    Some(count) => {
+                        *count -= 1;
+                        *count == 0
+                    }
+                    None => false,
+                };
+                if remove {
+                    active.remove(&ip);
+                }
+            }
+        }
+    }
+}
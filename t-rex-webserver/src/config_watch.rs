@@ -0,0 +1,241 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use crate::client_limit::ClientLimit;
+use crate::core::config::{ApplicationCfg, TilesetCfg};
+use crate::core::Config;
+use crate::jobs::prewarm_jobs;
+use crate::mvt_service::MvtService;
+use crate::referer_check::RefererCheck;
+use crate::runtime_config::config_from_file;
+use crate::static_files::StaticFiles;
+use crate::user_agent_check::UserAgentCheck;
+use actix_web::dev::Server;
+use clap::ArgMatches;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Interval of file modification checks
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Time without further modifications before reloading (editors often write files several times)
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Restart of the web server after changes of the configuration file or included files
+/// (`serve --watch-config`)
+pub struct ConfigWatch {
+    args: ArgMatches<'static>,
+    cfgpath: String,
+    /// Validated configuration and connected service for the restart
+    reload: Mutex<Option<(ApplicationCfg, MvtService)>>,
+    reloads: AtomicUsize,
+}
+
+impl ConfigWatch {
+    pub fn new(args: ArgMatches<'static>) -> Result<ConfigWatch, String> {
+        let cfgpath = args
+            .value_of("config")
+            .ok_or("--watch-config requires --config")?
+            .to_string();
+        Ok(ConfigWatch {
+            args,
+            cfgpath,
+            reload: Mutex::new(None),
+            reloads: AtomicUsize::new(0),
+        })
+    }
+    /// Number of restarts with changed configuration
+    pub fn reloads(&self) -> usize {
+        self.reloads.load(Ordering::SeqCst)
+    }
+    /// Validated configuration and service, if the server was stopped for a restart
+    pub fn take_reload(&self) -> Option<(ApplicationCfg, MvtService)> {
+        self.reload.lock().unwrap().take()
+    }
+    /// Watch files of `config` in a background thread. After a change of a file, the new
+    /// configuration is validated and `server` is stopped for a restart with this configuration,
+    /// without reading the files again. Invalid configurations are reported and the server
+    /// keeps running with the current configuration.
+    pub fn spawn(self: &Arc<Self>, server: Server, config: ApplicationCfg) {
+        let watch = self.clone();
+        let mut files = vec![self.cfgpath.clone()];
+        files.extend(config.included_files());
+        info!("Watching configuration files {:?}", files);
+        thread::spawn(move || {
+            let mut mtimes = modification_times(&files);
+            loop {
+                thread::sleep(POLL_INTERVAL);
+                let mut current = modification_times(&files);
+                if current == mtimes {
+                    continue;
+                }
+                loop {
+                    thread::sleep(DEBOUNCE);
+                    let next = modification_times(&files);
+                    if next == current {
+                        break;
+                    }
+                    current = next;
+                }
+                mtimes = current;
+                match watch.validate() {
+                    Ok((new_config, service)) => {
+                        info!(
+                            "Configuration reloaded: {}",
+                            reload_summary(&config, &new_config)
+                        );
+                        watch.reloads.fetch_add(1, Ordering::SeqCst);
+                        *watch.reload.lock().unwrap() = Some((new_config, service));
+                        // Sends the stop command without waiting for completion
+                        let _ = server.stop(true);
+                        return;
+                    }
+                    Err(err) => {
+                        error!("Configuration not reloaded - {}", err);
+                    }
+                }
+            }
+        });
+    }
+    /// Read and check the configuration like at server startup.
+    /// Returns the configuration with the connected service.
+    fn validate(&self) -> Result<(ApplicationCfg, MvtService), String> {
+        let config = config_from_file(&self.cfgpath, &self.args)?;
+        let mut viewer = config.service.mvt.viewer;
+        if !self.args.is_present("no-viewer-assets") {
+            let static_files =
+                StaticFiles::init(config.webserver.assets_dir.as_ref().map(|d| d.as_str()))?;
+            viewer = viewer && !static_files.is_disabled();
+        } else {
+            viewer = false;
+        }
+        config.webserver.root_response(viewer)?;
+        if let Some(max_requests) = config.webserver.max_requests_per_ip {
+            ClientLimit::new(max_requests, &config.webserver.trusted_proxies)?;
+        }
+        if !config.webserver.allowed_referers.is_empty() {
+            let allow_missing = config.webserver.allow_missing_referer.unwrap_or(true);
+            RefererCheck::new(&config.webserver.allowed_referers, allow_missing)?;
+        }
+        if let Some(ref user_agent) = config.webserver.user_agent {
+            UserAgentCheck::new(user_agent)?;
+        }
+        let mut service = MvtService::from_config(&config)?;
+        if let Some(ref prewarm) = config.webserver.prewarm {
+            prewarm_jobs(&service, prewarm)?;
+        }
+        // Connection errors panic
+        let warnings = panic::catch_unwind(AssertUnwindSafe(|| {
+            service.connect();
+            service.prepare_feature_queries()
+        }))
+        .map_err(|_| "Datasource connection failed".to_string())?;
+        let strict =
+            bool::from_str(self.args.value_of("strict").unwrap_or("false")).unwrap_or(false);
+        if strict && !warnings.is_empty() {
+            return Err(format!("Warnings with --strict:\n{}", warnings.join("\n")));
+        }
+        Ok((config, service))
+    }
+}
+
+fn modification_times(files: &[String]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| fs::metadata(file).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+fn count(n: usize, item: &str) -> String {
+    if n == 1 {
+        format!("{} {}", n, item)
+    } else {
+        format!("{} {}s", n, item)
+    }
+}
+
+/// Tileset configuration without layers
+fn tileset_settings(tileset: &TilesetCfg) -> TilesetCfg {
+    let mut tileset = tileset.clone();
+    tileset.layers.clear();
+    tileset
+}
+
+/// Summary of configuration changes like "+1 tileset, ~2 layers changed"
+pub fn reload_summary(old: &ApplicationCfg, new: &ApplicationCfg) -> String {
+    let find = |config: &ApplicationCfg, name: &str| {
+        config.tilesets.iter().find(|ts| ts.name == name).cloned()
+    };
+    let added = new
+        .tilesets
+        .iter()
+        .filter(|ts| find(old, &ts.name).is_none())
+        .count();
+    let removed = old
+        .tilesets
+        .iter()
+        .filter(|ts| find(new, &ts.name).is_none())
+        .count();
+    let (mut changed, mut layers_added, mut layers_removed, mut layers_changed) = (0, 0, 0, 0);
+    for tileset in &new.tilesets {
+        let old_tileset = match find(old, &tileset.name) {
+            Some(ts) => ts,
+            None => continue,
+        };
+        if tileset_settings(tileset) != tileset_settings(&old_tileset) {
+            changed += 1;
+        }
+        for layer in &tileset.layers {
+            match old_tileset.layers.iter().find(|l| l.name == layer.name) {
+                Some(old_layer) if old_layer != layer => layers_changed += 1,
+                Some(_) => {}
+                None => layers_added += 1,
+            }
+        }
+        layers_removed += old_tileset
+            .layers
+            .iter()
+            .filter(|l| !tileset.layers.iter().any(|layer| layer.name == l.name))
+            .count();
+    }
+    let mut parts = Vec::new();
+    if added > 0 {
+        parts.push(format!("+{}", count(added, "tileset")));
+    }
+    if removed > 0 {
+        parts.push(format!("-{}", count(removed, "tileset")));
+    }
+    if changed > 0 {
+        parts.push(format!("~{} changed", count(changed, "tileset")));
+    }
+    if layers_added > 0 {
+        parts.push(format!("+{}", count(layers_added, "layer")));
+    }
+    if layers_removed > 0 {
+        parts.push(format!("-{}", count(layers_removed, "layer")));
+    }
+    if layers_changed > 0 {
+        parts.push(format!("~{} changed", count(layers_changed, "layer")));
+    }
+    let sections = [
+        ("service", old.service != new.service),
+        ("datasource", old.datasource != new.datasource),
+        ("grid", old.grid != new.grid),
+        ("cache", old.cache != new.cache),
+        ("webserver", old.webserver != new.webserver),
+    ];
+    for (section, _) in sections.iter().filter(|(_, changed)| *changed) {
+        parts.push(format!("~{} changed", section));
+    }
+    if parts.is_empty() {
+        "no changes".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
@@ -0,0 +1,47 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Responses of field value requests, shared by clones
+#[derive(Clone)]
+pub struct FieldValuesCache {
+    period: Duration,
+    entries: Arc<Mutex<HashMap<String, (Instant, String)>>>,
+}
+
+impl FieldValuesCache {
+    pub fn new(period: Duration) -> FieldValuesCache {
+        FieldValuesCache {
+            period,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+    /// Cache key of a request
+    pub fn key(tileset: &str, layer: &str, field: &str, zoom: u8, limit: u32) -> String {
+        format!("{}/{}/{}/{}/{}", tileset, layer, field, zoom, limit)
+    }
+    /// Cached response body, `None` if missing or expired
+    pub fn get(&self, key: &str) -> Option<String> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some((created, body)) if created.elapsed() < self.period => Some(body.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+    pub fn insert(&self, key: String, body: String) {
+        let mut entries = self.entries.lock().unwrap();
+        // Drop expired entries of other requests
+        let period = self.period;
+        entries.retain(|_, (created, _)| created.elapsed() < period);
+        entries.insert(key, (Instant::now(), body));
+    }
+}
@@ -0,0 +1,303 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+//! gRPC tile service (`tiles.proto`) over HTTP/2 without TLS
+//!
+//! The two calls are implemented directly on `h2` with hand-written messages instead of tonic,
+//! which would add hyper, tower and a prost code generation step to the build for two calls.
+//! Enabled with the `grpc` feature.
+
+use crate::mvt_service::MvtService;
+use actix_rt;
+use actix_web::web;
+use bytes::{Buf, Bytes, BytesMut};
+use h2::server::{self, SendResponse};
+use h2::RecvStream;
+use http::{HeaderMap, HeaderValue, Request, Response};
+use protobuf::wire_format::WireType;
+use protobuf::{CodedInputStream, CodedOutputStream, ProtobufResult};
+use std::io;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+
+pub const GET_TILE: &str = "/trex.TileService/GetTile";
+pub const GET_TILES: &str = "/trex.TileService/GetTiles";
+
+/// gRPC status codes
+pub const OK: u32 = 0;
+pub const INVALID_ARGUMENT: u32 = 3;
+pub const NOT_FOUND: u32 = 5;
+pub const RESOURCE_EXHAUSTED: u32 = 8;
+pub const UNIMPLEMENTED: u32 = 12;
+pub const INTERNAL: u32 = 13;
+
+/// Default maximum size of a request message (as in gRPC implementations)
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Tile in XYZ adressing scheme as in tile URLs
+#[derive(PartialEq, Default, Debug)]
+pub struct TileRequest {
+    pub tileset: String,
+    pub z: u32,
+    pub x: u32,
+    pub y: u32,
+    pub gzip: bool,
+}
+
+/// Tile data of a request, empty if the tile has no features
+#[derive(PartialEq, Default, Debug)]
+pub struct TileResponse {
+    pub tileset: String,
+    pub z: u32,
+    pub x: u32,
+    pub y: u32,
+    pub data: Vec<u8>,
+}
+
+impl TileRequest {
+    pub fn decode(msg: &[u8]) -> ProtobufResult<TileRequest> {
+        let mut req = TileRequest::default();
+        let mut is = CodedInputStream::from_bytes(msg);
+        while !is.eof()? {
+            match is.read_tag_unpack()? {
+                (1, WireType::WireTypeLengthDelimited) => req.tileset = is.read_string()?,
+                (2, WireType::WireTypeVarint) => req.z = is.read_uint32()?,
+                (3, WireType::WireTypeVarint) => req.x = is.read_uint32()?,
+                (4, WireType::WireTypeVarint) => req.y = is.read_uint32()?,
+                (5, WireType::WireTypeVarint) => req.gzip = is.read_bool()?,
+                (_, wire_type) => is.skip_field(wire_type)?,
+            }
+        }
+        Ok(req)
+    }
+    pub fn encode(&self) -> Vec<u8> {
+        let mut msg = Vec::new();
+        {
+            let mut os = CodedOutputStream::vec(&mut msg);
+            os.write_string(1, &self.tileset).unwrap();
+            os.write_uint32(2, self.z).unwrap();
+            os.write_uint32(3, self.x).unwrap();
+            os.write_uint32(4, self.y).unwrap();
+            os.write_bool(5, self.gzip).unwrap();
+            os.flush().unwrap();
+        }
+        msg
+    }
+}
+
+impl TileResponse {
+    pub fn decode(msg: &[u8]) -> ProtobufResult<TileResponse> {
+        let mut resp = TileResponse::default();
+        let mut is = CodedInputStream::from_bytes(msg);
+        while !is.eof()? {
+            match is.read_tag_unpack()? {
+                (1, WireType::WireTypeLengthDelimited) => resp.tileset = is.read_string()?,
+                (2, WireType::WireTypeVarint) => resp.z = is.read_uint32()?,
+                (3, WireType::WireTypeVarint) => resp.x = is.read_uint32()?,
+                (4, WireType::WireTypeVarint) => resp.y = is.read_uint32()?,
+                (5, WireType::WireTypeLengthDelimited) => resp.data = is.read_bytes()?,
+                (_, wire_type) => is.skip_field(wire_type)?,
+            }
+        }
+        Ok(resp)
+    }
+    pub fn encode(&self) -> Vec<u8> {
+        let mut msg = Vec::new();
+        {
+            let mut os = CodedOutputStream::vec(&mut msg);
+            os.write_string(1, &self.tileset).unwrap();
+            os.write_uint32(2, self.z).unwrap();
+            os.write_uint32(3, self.x).unwrap();
+            os.write_uint32(4, self.y).unwrap();
+            os.write_bytes(5, &self.data).unwrap();
+            os.flush().unwrap();
+        }
+        msg
+    }
+}
+
+/// gRPC message with length prefix
+pub fn frame(msg: &[u8]) -> Bytes {
+    let mut data = Vec::with_capacity(msg.len() + 5);
+    data.push(0); // uncompressed
+    data.extend_from_slice(&(msg.len() as u32).to_be_bytes());
+    data.extend_from_slice(msg);
+    Bytes::from(data)
+}
+
+/// Remove next complete message from `buf`.
+/// Messages larger than `max_size` are rejected as soon as their length prefix is read.
+pub fn next_message(buf: &mut BytesMut, max_size: usize) -> Option<Result<Bytes, (u32, String)>> {
+    if buf.len() < 5 {
+        return None;
+    }
+    let len = u32::from_be_bytes([buf[1], buf[2], buf[3], buf[4]]) as usize;
+    if len > max_size {
+        return Some(Err((
+            RESOURCE_EXHAUSTED,
+            format!("Message size {} exceeds maximum of {}", len, max_size),
+        )));
+    }
+    if buf.len() < len + 5 {
+        return None;
+    }
+    let compressed = buf[0] != 0;
+    buf.advance(5);
+    let msg = buf.split_to(len).freeze();
+    if compressed {
+        Some(Err((
+            UNIMPLEMENTED,
+            "Compressed messages are not supported".to_string(),
+        )))
+    } else {
+        Some(Ok(msg))
+    }
+}
+
+fn status_trailers(status: u32, message: &str) -> HeaderMap {
+    let mut trailers = HeaderMap::new();
+    trailers.insert("grpc-status", HeaderValue::from(status));
+    if let Ok(message) = HeaderValue::from_str(message) {
+        if !message.is_empty() {
+            trailers.insert("grpc-message", message);
+        }
+    }
+    trailers
+}
+
+async fn tile_response(
+    service: &Arc<MvtService>,
+    req: TileRequest,
+) -> Result<TileResponse, (u32, String)> {
+    let name = match service.get_tileset(&req.tileset) {
+        Some(ts) => ts.name.clone(),
+        None => return Err((NOT_FOUND, format!("Tileset '{}' not found", req.tileset))),
+    };
+    if req.z > u8::max_value() as u32 {
+        return Err((INVALID_ARGUMENT, format!("Invalid zoom level {}", req.z)));
+    }
+    let tile_service = service.clone();
+    let (x, y, z, gzip) = (req.x, req.y, req.z as u8, req.gzip);
+    let data =
+        web::block(move || Ok::<_, ()>(tile_service.tile_cached(&name, x, y, z, gzip, None)))
+            .await
+            .map_err(|e| (INTERNAL, e.to_string()))?;
+    Ok(TileResponse {
+        tileset: req.tileset,
+        z: req.z,
+        x: req.x,
+        y: req.y,
+        data: data.unwrap_or_default(),
+    })
+}
+
+/// Answer a call with tiles. Unary calls return the first requested tile.
+async fn call(
+    service: Arc<MvtService>,
+    max_message_size: usize,
+    request: Request<RecvStream>,
+    mut respond: SendResponse<Bytes>,
+) -> Result<(), h2::Error> {
+    let streaming = match request.uri().path() {
+        GET_TILE => false,
+        GET_TILES => true,
+        path => {
+            debug!("gRPC - Unknown method {}", path);
+            let response = Response::builder()
+                .header("content-type", "application/grpc")
+                .header("grpc-status", UNIMPLEMENTED)
+                .body(())
+                .unwrap();
+            respond.send_response(response, true)?;
+            return Ok(());
+        }
+    };
+    let response = Response::builder()
+        .header("content-type", "application/grpc")
+        .body(())
+        .unwrap();
+    let mut send = respond.send_response(response, false)?;
+    let mut body = request.into_body();
+    let mut buf = BytesMut::new();
+    let mut count = 0;
+    let mut status = (OK, String::new());
+    'stream: while let Some(data) = body.data().await {
+        let data = data?;
+        let _ = body.flow_control().release_capacity(data.len());
+        buf.extend_from_slice(&data);
+        while let Some(msg) = next_message(&mut buf, max_message_size) {
+            let req = match msg {
+                Ok(msg) => TileRequest::decode(&msg).map_err(|e| (INVALID_ARGUMENT, e.to_string())),
+                Err(e) => Err(e),
+            };
+            count += 1;
+            let resp = match req {
+                Ok(req) => tile_response(&service, req).await,
+                Err(e) => Err(e),
+            };
+            match resp {
+                Ok(resp) => send.send_data(frame(&resp.encode()), false)?,
+                Err(e) => {
+                    status = e;
+                    break 'stream;
+                }
+            }
+            if !streaming {
+                break 'stream;
+            }
+        }
+    }
+    if count == 0 && !streaming {
+        status = (INVALID_ARGUMENT, "Missing TileRequest".to_string());
+    }
+    send.send_trailers(status_trailers(status.0, &status.1))
+}
+
+/// Serve gRPC calls on `listener`
+pub async fn serve(mut listener: TcpListener, service: Arc<MvtService>, max_message_size: usize) {
+    loop {
+        let (socket, addr) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!("gRPC - {}", e);
+                continue;
+            }
+        };
+        let service = service.clone();
+        actix_rt::spawn(async move {
+            if let Err(e) = serve_connection(socket, service, max_message_size).await {
+                debug!("gRPC {} - {}", addr, e);
+            }
+        });
+    }
+}
+
+/// Serve gRPC calls on an accepted connection
+pub async fn serve_connection<T>(
+    io: T,
+    service: Arc<MvtService>,
+    max_message_size: usize,
+) -> Result<(), h2::Error>
+where
+    T: AsyncRead + AsyncWrite + Unpin + 'static,
+{
+    let mut connection = server::handshake(io).await?;
+    while let Some(request) = connection.accept().await {
+        let (request, respond) = request?;
+        let service = service.clone();
+        actix_rt::spawn(async move {
+            if let Err(e) = call(service, max_message_size, request, respond).await {
+                debug!("gRPC - {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Bind gRPC server to `addr`
+pub fn bind(addr: &str) -> io::Result<TcpListener> {
+    TcpListener::from_std(std::net::TcpListener::bind(addr)?)
+}
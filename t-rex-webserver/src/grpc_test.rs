@@ -0,0 +1,197 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use crate::cache::{Cache, Filecache, Tilecache};
+use crate::grpc::{
+    self, frame, next_message, TileRequest, TileResponse, DEFAULT_MAX_MESSAGE_SIZE, GET_TILE,
+    GET_TILES, NOT_FOUND, OK, RESOURCE_EXHAUSTED, UNIMPLEMENTED,
+};
+use crate::mvt_service::MvtService;
+use crate::server_test::test_service;
+use bytes::{Bytes, BytesMut};
+use h2::client;
+use http::Request;
+use std::env;
+use std::fs;
+use std::sync::Arc;
+use t_rex_core::mvt::tile::Tile;
+use tokio::net::UnixStream;
+
+#[test]
+fn test_messages() {
+    let req = TileRequest {
+        tileset: "osm".to_string(),
+        z: 12,
+        x: 2148,
+        y: 1457,
+        gzip: true,
+    };
+    assert_eq!(TileRequest::decode(&req.encode()).unwrap(), req);
+    let resp = TileResponse {
+        tileset: "osm".to_string(),
+        z: 12,
+        x: 2148,
+        y: 1457,
+        data: vec![0x1a, 0, 1],
+    };
+    assert_eq!(TileResponse::decode(&resp.encode()).unwrap(), resp);
+    // Default values and unknown fields
+    assert_eq!(
+        TileRequest::decode(&[0x30, 1, 0x10, 3]).unwrap(),
+        TileRequest {
+            z: 3,
+            ..Default::default()
+        }
+    );
+
+    let max = DEFAULT_MAX_MESSAGE_SIZE;
+    let mut buf = BytesMut::new();
+    buf.extend_from_slice(&frame(b"abc"));
+    buf.extend_from_slice(&frame(b"")[..3]);
+    assert_eq!(
+        next_message(&mut buf, max),
+        Some(Ok(Bytes::from_static(b"abc")))
+    );
+    assert_eq!(next_message(&mut buf, max), None);
+    buf.extend_from_slice(&frame(b"")[3..]);
+    assert_eq!(next_message(&mut buf, max), Some(Ok(Bytes::new())));
+    assert_eq!(buf.len(), 0);
+    buf.extend_from_slice(&[1, 0, 0, 0, 0]);
+    assert_eq!(
+        next_message(&mut buf, max).unwrap().unwrap_err().0,
+        UNIMPLEMENTED
+    );
+
+    // Oversized message is rejected on its length prefix
+    let mut buf = BytesMut::new();
+    buf.extend_from_slice(&frame(&[0; 11])[..5]);
+    assert_eq!(next_message(&mut buf, 11), None);
+    assert_eq!(
+        next_message(&mut buf, 10).unwrap().unwrap_err().0,
+        RESOURCE_EXHAUSTED
+    );
+}
+
+/// Call `method` with HTTP/2 client on a connection served by `grpc::serve_connection`
+/// and return response messages and grpc-status
+async fn grpc_call(
+    service: &Arc<MvtService>,
+    max_message_size: usize,
+    method: &str,
+    requests: &[TileRequest],
+) -> (Vec<TileResponse>, Option<String>) {
+    let (client_io, server_io) = UnixStream::pair().unwrap();
+    actix_rt::spawn({
+        let service = service.clone();
+        async move {
+            let _ = grpc::serve_connection(server_io, service, max_message_size).await;
+        }
+    });
+    let (client, connection) = client::handshake(client_io).await.unwrap();
+    actix_rt::spawn(async move {
+        let _ = connection.await;
+    });
+    let mut client = client.ready().await.unwrap();
+    let request = Request::post(format!("http://localhost{}", method))
+        .header("content-type", "application/grpc")
+        .header("te", "trailers")
+        .body(())
+        .unwrap();
+    let (response, mut send) = client.send_request(request, false).unwrap();
+    for req in requests {
+        send.send_data(frame(&req.encode()), false).unwrap();
+    }
+    send.send_data(Bytes::new(), true).unwrap();
+    let response = response.await.unwrap();
+    if let Some(status) = response.headers().get("grpc-status") {
+        // Trailers-only response
+        return (Vec::new(), Some(status.to_str().unwrap().to_string()));
+    }
+    let mut body = response.into_body();
+    let mut buf = BytesMut::new();
+    while let Some(data) = body.data().await {
+        let data = data.unwrap();
+        let _ = body.flow_control().release_capacity(data.len());
+        buf.extend_from_slice(&data);
+    }
+    let mut responses = Vec::new();
+    while let Some(msg) = next_message(&mut buf, DEFAULT_MAX_MESSAGE_SIZE) {
+        responses.push(TileResponse::decode(&msg.unwrap()).unwrap());
+    }
+    let trailers = body.trailers().await.unwrap().unwrap();
+    let status = trailers
+        .get("grpc-status")
+        .map(|status| status.to_str().unwrap().to_string());
+    (responses, status)
+}
+
+#[actix_rt::test]
+async fn test_grpc_server() {
+    let mut dir = env::temp_dir();
+    dir.push("t_rex_test_grpc");
+    let _ = fs::remove_dir_all(&dir);
+    let cache = Filecache {
+        basepath: dir.to_str().unwrap().to_string(),
+        baseurl: None,
+        extension: None,
+    };
+    cache
+        .write("a/1/0/1.pbf", &Tile::compress(b"tile 1/0/1"))
+        .unwrap();
+    cache
+        .write("a/1/1/0.pbf", &Tile::compress(b"tile 1/1/0"))
+        .unwrap();
+    let mut service = test_service();
+    service.cache = Tilecache::Filecache(cache);
+
+    let service = Arc::new(service);
+    let max = DEFAULT_MAX_MESSAGE_SIZE;
+
+    let tile = |tileset: &str, z, x, y| TileRequest {
+        tileset: tileset.to_string(),
+        z,
+        x,
+        y,
+        gzip: false,
+    };
+    let (responses, status) = grpc_call(&service, max, GET_TILE, &[tile("a", 1, 0, 1)]).await;
+    assert_eq!(status, Some(OK.to_string()));
+    assert_eq!(responses.len(), 1);
+    assert_eq!((responses[0].x, responses[0].y), (0, 1));
+    assert_eq!(responses[0].data, b"tile 1/0/1".to_vec());
+
+    let mut gzip = tile("a", 1, 0, 1);
+    gzip.gzip = true;
+    let requests = vec![tile("a", 1, 1, 0), gzip];
+    let (responses, status) = grpc_call(&service, max, GET_TILES, &requests).await;
+    assert_eq!(status, Some(OK.to_string()));
+    let data: Vec<&[u8]> = responses.iter().map(|r| r.data.as_slice()).collect();
+    assert_eq!(
+        data,
+        vec![&b"tile 1/1/0"[..], &Tile::compress(b"tile 1/0/1")[..]]
+    );
+
+    let requests = vec![
+        tile("a", 1, 1, 0),
+        tile("missing", 1, 0, 1),
+        tile("a", 1, 0, 1),
+    ];
+    let (responses, status) = grpc_call(&service, max, GET_TILES, &requests).await;
+    assert_eq!(status, Some(NOT_FOUND.to_string()));
+    assert_eq!(responses.len(), 1);
+
+    let (responses, status) = grpc_call(&service, max, "/trex.TileService/Other", &[]).await;
+    assert_eq!(status, Some(UNIMPLEMENTED.to_string()));
+    assert!(responses.is_empty());
+
+    let large = TileRequest {
+        tileset: "a".repeat(100),
+        ..tile("a", 1, 0, 1)
+    };
+    let requests = vec![tile("a", 1, 1, 0), large];
+    let (responses, status) = grpc_call(&service, 50, GET_TILES, &requests).await;
+    assert_eq!(status, Some(RESOURCE_EXHAUSTED.to_string()));
+    assert_eq!(responses.len(), 1);
+}
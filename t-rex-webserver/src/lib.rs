@@ -6,17 +6,31 @@
 #[macro_use]
 extern crate log;
 #[macro_use]
-extern crate lazy_static;
-#[macro_use]
 extern crate serde_derive;
 extern crate tile_grid;
 
 use t_rex_core::{cache, core, datasource, service};
-use t_rex_service::{datasources, mvt_service, read_qgs};
+use t_rex_service::{datasources, disabled_layers, inflight, jobs, mvt_service, read_qgs};
 
+mod build_info;
+mod client_limit;
+mod config_watch;
+#[cfg(test)]
+mod config_watch_test;
+mod field_values_cache;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(all(test, feature = "grpc"))]
+mod grpc_test;
+mod metadata_cache;
+mod referer_check;
 mod runtime_config;
 mod server;
+#[cfg(test)]
+mod server_test;
 mod static_files;
+mod user_agent_check;
 
+pub use crate::build_info::BuildInfo;
 pub use crate::runtime_config::*;
 pub use crate::server::webserver;
@@ -0,0 +1,114 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use bytes::Bytes;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// Serialized metadata response
+pub struct CachedJson {
+    pub body: Bytes,
+    pub etag: String,
+}
+
+impl CachedJson {
+    pub fn new(body: String) -> CachedJson {
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        CachedJson {
+            body: Bytes::from(body),
+            etag: format!("\"{:016x}\"", hasher.finish()),
+        }
+    }
+    /// Check whether an `If-None-Match` header matches the ETag
+    pub fn matches(&self, if_none_match: &str) -> bool {
+        if_none_match.split(',').any(|tag| {
+            let tag = tag.trim();
+            tag == "*" || tag.trim_start_matches("W/") == self.etag
+        })
+    }
+}
+
+struct Entry {
+    /// Generation of the disabled layers at creation time
+    generation: usize,
+    last_used: u64,
+    json: Arc<CachedJson>,
+}
+
+#[derive(Default)]
+struct Entries {
+    map: HashMap<String, Entry>,
+    /// Access counter for least recently used eviction
+    tick: u64,
+}
+
+/// Responses of metadata requests, bounded to `max_entries` and shared by clones
+#[derive(Clone)]
+pub struct MetadataCache {
+    max_entries: usize,
+    entries: Arc<Mutex<Entries>>,
+}
+
+impl MetadataCache {
+    pub fn new(max_entries: usize) -> MetadataCache {
+        MetadataCache {
+            max_entries,
+            entries: Arc::new(Mutex::new(Entries::default())),
+        }
+    }
+    /// Cache key of a request
+    pub fn key(route: &str, tileset: &str, baseurl: &str) -> String {
+        format!("{} {} {}", route, tileset, baseurl)
+    }
+    /// Cached response, `None` if missing or created with another `generation`
+    pub fn get(&self, key: &str, generation: usize) -> Option<Arc<CachedJson>> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.tick += 1;
+        let tick = entries.tick;
+        match entries.map.get_mut(key) {
+            Some(entry) if entry.generation == generation => {
+                entry.last_used = tick;
+                Some(entry.json.clone())
+            }
+            Some(_) => {
+                entries.map.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+    /// Insert response, evicting the least recently used entry if the cache is full
+    pub fn insert(&self, key: String, generation: usize, body: String) -> Arc<CachedJson> {
+        let json = Arc::new(CachedJson::new(body));
+        if self.max_entries == 0 {
+            return json;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        entries
+            .map
+            .retain(|_, entry| entry.generation == generation);
+        if entries.map.len() >= self.max_entries && !entries.map.contains_key(&key) {
+            let lru = entries
+                .map
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(lru) = lru {
+                entries.map.remove(&lru);
+            }
+        }
+        entries.tick += 1;
+        let entry = Entry {
+            generation,
+            last_used: entries.tick,
+            json: json.clone(),
+        };
+        entries.map.insert(key, entry);
+        json
+    }
+}
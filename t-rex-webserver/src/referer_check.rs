@@ -0,0 +1,120 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+/// Glob pattern with `*` matching any sequence of characters
+#[derive(Clone, Debug)]
+struct Glob {
+    parts: Vec<String>,
+}
+
+impl Glob {
+    fn new(pattern: &str) -> Glob {
+        Glob {
+            parts: pattern.split('*').map(|part| part.to_string()).collect(),
+        }
+    }
+    fn matches(&self, s: &str) -> bool {
+        let (first, last) = (&self.parts[0], &self.parts[self.parts.len() - 1]);
+        if self.parts.len() == 1 {
+            return s == first;
+        }
+        if s.len() < first.len() + last.len() || !s.starts_with(first.as_str()) {
+            return false;
+        }
+        let mut rest = &s[first.len()..];
+        for part in &self.parts[1..self.parts.len() - 1] {
+            match rest.find(part.as_str()) {
+                Some(pos) => rest = &rest[pos + part.len()..],
+                None => return false,
+            }
+        }
+        rest.len() >= last.len() && rest.ends_with(last.as_str())
+    }
+}
+
+/// Split URL into lowercase origin (`scheme://host[:port]`) and path (including query)
+fn split_url(url: &str) -> Option<(String, &str)> {
+    let host_start = url.find("://")? + 3;
+    let path_start = url[host_start..]
+        .find(|c| c == '/' || c == '?' || c == '#')
+        .map(|pos| host_start + pos)
+        .unwrap_or(url.len());
+    Some((url[..path_start].to_lowercase(), &url[path_start..]))
+}
+
+/// Allowed referer pattern, e.g. `https://*.example.com/*`.
+/// `*` in the origin doesn't match path separators, so the host can't be spoofed
+/// with a path or query. Without path, all paths of the origin are allowed.
+#[derive(Clone, Debug)]
+struct RefererPattern {
+    origin: Glob,
+    path: Glob,
+}
+
+impl RefererPattern {
+    fn new(pattern: &str) -> Option<RefererPattern> {
+        let (origin, path) = split_url(pattern)?;
+        if origin.ends_with("://") {
+            return None;
+        }
+        let path = if path.is_empty() { "/*" } else { path };
+        Some(RefererPattern {
+            origin: Glob::new(&origin),
+            path: Glob::new(path),
+        })
+    }
+    fn matches(&self, origin: &str, path: &str) -> bool {
+        if !self.origin.matches(origin) {
+            return false;
+        }
+        let path = if path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("/{}", path)
+        };
+        // Trailing slashes are optional, e.g. "/maps/*" also matches "/maps"
+        let trimmed = path.trim_end_matches('/');
+        self.path.matches(&path)
+            || self.path.matches(&format!("{}/", trimmed))
+            || (!trimmed.is_empty() && self.path.matches(trimmed))
+    }
+}
+
+/// Check of `Referer` headers against allowed patterns (hotlink protection)
+#[derive(Clone, Debug)]
+pub struct RefererCheck {
+    patterns: Vec<RefererPattern>,
+    /// Allow requests without `Referer` header (e.g. from native apps)
+    allow_missing: bool,
+}
+
+impl RefererCheck {
+    pub fn new(patterns: &[String], allow_missing: bool) -> Result<RefererCheck, String> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| {
+                RefererPattern::new(pattern).ok_or(format!(
+                    "Invalid allowed_referers pattern '{}' (expected e.g. \"https://*.example.com/*\")",
+                    pattern
+                ))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(RefererCheck {
+            patterns,
+            allow_missing,
+        })
+    }
+    /// Check whether a request with `referer` header is allowed
+    pub fn allows(&self, referer: Option<&str>) -> bool {
+        let referer = match referer {
+            Some(referer) if !referer.is_empty() => referer,
+            _ => return self.allow_missing,
+        };
+        match split_url(referer) {
+            Some((origin, path)) => self.patterns.iter().any(|p| p.matches(&origin, path)),
+            None => false,
+        }
+    }
+}
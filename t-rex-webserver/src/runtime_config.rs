@@ -4,16 +4,22 @@
 //
 
 use crate::cache::{Filecache, Nocache, Tilecache};
-use crate::core::config::{ApplicationCfg, DEFAULT_CONFIG};
+use crate::core::config::{
+    effective_config, extract_defaults, ApplicationCfg, DEFAULT_CONFIG, PROFILE_ENV,
+};
 use crate::core::layer::Layer;
 use crate::core::{parse_config, read_config, Config};
 use crate::datasource::DatasourceType;
-use crate::datasources::Datasources;
+use crate::datasources::{dbconn_arg, Datasources};
+use crate::disabled_layers::DisabledLayers;
+use crate::inflight::InflightRequests;
 use crate::mvt_service::MvtService;
 use crate::read_qgs;
 use crate::service::tileset::Tileset;
 use crate::tile_grid::Grid;
 use clap::ArgMatches;
+use std::collections::HashMap;
+use std::env;
 use std::process;
 use std::str::FromStr;
 
@@ -41,19 +47,51 @@ fn set_layer_buffer_defaults(layer: &mut Layer, simplify: bool, clip: bool) {
     };
 }
 
+/// Query profile from `--profile` or `TREX_PROFILE`
+fn profile_from_args(args: &ArgMatches) -> Option<String> {
+    args.value_of("profile")
+        .map(|profile| profile.to_string())
+        .or_else(|| env::var(PROFILE_ENV).ok())
+        .filter(|profile| !profile.is_empty())
+}
+
+/// Read configuration file with query files, the query profile and named datasource connections of `args`
+pub fn config_from_file(cfgpath: &str, args: &ArgMatches) -> Result<ApplicationCfg, String> {
+    // Connections of named datasources
+    let mut dbconns = Vec::new();
+    for spec in args.values_of("dbconn").into_iter().flatten() {
+        match dbconn_arg(spec) {
+            (Some(name), url) => dbconns.push((name.to_string(), url.to_string())),
+            (None, _) => warn!("Ignoring argument `dbconn` without datasource name"),
+        }
+    }
+    let mut config: ApplicationCfg = read_config(cfgpath)?;
+    let profile = profile_from_args(args);
+    if let Some(ref profile) = profile {
+        info!("Using query profile '{}'", profile);
+    }
+    config.load_query_files(cfgpath)?;
+    config.select_profile(profile.as_ref().map(|p| p.as_str()))?;
+    config.webserver.empty_tile_status()?;
+    config.webserver.tile_urls()?;
+    for resolution in config.resolve_dbconns(&dbconns)? {
+        info!("{}", resolution);
+    }
+    Ok(config)
+}
+
 pub fn config_from_args(args: &ArgMatches) -> ApplicationCfg {
     if let Some(cfgpath) = args.value_of("config") {
         info!("Reading configuration from '{}'", cfgpath);
-        for argname in vec!["dbconn", "datasource", "qgs"] {
+        for argname in vec!["datasource", "csv", "qgs"] {
             if args.value_of(argname).is_some() {
                 warn!("Ignoring argument `{}`", argname);
             }
         }
-        let config = read_config(cfgpath).unwrap_or_else(|err| {
+        config_from_file(cfgpath, args).unwrap_or_else(|err| {
             println!("Error reading configuration - {} ", err);
             process::exit(1)
-        });
-        config
+        })
     } else {
         let bind = args.value_of("bind").unwrap_or("127.0.0.1");
         let port =
@@ -79,6 +117,7 @@ pub fn service_from_args(config: &ApplicationCfg, args: &ArgMatches) -> MvtServi
             Some(dir) => Tilecache::Filecache(Filecache {
                 basepath: dir.to_string(),
                 baseurl: None,
+                extension: None,
             }),
         };
         let simplify = bool::from_str(args.value_of("simplify").unwrap_or("true")).unwrap_or(false);
@@ -98,7 +137,7 @@ pub fn service_from_args(config: &ApplicationCfg, args: &ArgMatches) -> MvtServi
         } else {
             let datasources = Datasources::from_args(args);
             if datasources.datasources.is_empty() {
-                println!("Either 'config', 'dbconn' or 'datasource' is required");
+                println!("Either 'config', 'dbconn', 'datasource' or 'csv' is required");
                 process::exit(1)
             }
             let detect_geometry_types =
@@ -121,6 +160,15 @@ pub fn service_from_args(config: &ApplicationCfg, args: &ArgMatches) -> MvtServi
                         start_zoom: None,
                         layers: vec![l],
                         cache_limits: None,
+                        max_tile_bytes: None,
+                        max_tile_memory: None,
+                        mbtiles: None,
+                        overzoom: false,
+                        default_style: None,
+                        skip_layer_errors: false,
+                        always_gzip: false,
+                        static_dir: None,
+                        aliases: Vec::new(),
                     };
                     tilesets.push(tileset);
                 }
@@ -132,12 +180,40 @@ pub fn service_from_args(config: &ApplicationCfg, args: &ArgMatches) -> MvtServi
             grid: grid,
             tilesets: tilesets,
             cache: cache,
+            tileset_limits: HashMap::new(),
+            requests: InflightRequests::default(),
+            disabled_layers: DisabledLayers::default(),
+            active_tilesets: None,
         };
         svc.connect(); //TODO: ugly - we connect twice
         svc
     }
 }
 
+/// Exit on configuration warnings (e.g. from `prepare_feature_queries`) with `--strict`
+pub fn exit_on_warnings(args: &ArgMatches, warnings: &[String]) {
+    let strict = bool::from_str(args.value_of("strict").unwrap_or("false")).unwrap_or(false);
+    if strict && !warnings.is_empty() {
+        println!(
+            "Error in configuration check (--strict):\n{}",
+            warnings.join("\n")
+        );
+        process::exit(1)
+    }
+}
+
+/// Configuration file with merged tileset and layer defaults (`--dump-config`)
+pub fn dump_config(args: &ArgMatches) -> String {
+    let cfgpath = args.value_of("config").unwrap_or_else(|| {
+        println!("--dump-config requires --config");
+        process::exit(1)
+    });
+    effective_config(cfgpath).unwrap_or_else(|err| {
+        println!("Error reading configuration - {} ", err);
+        process::exit(1)
+    })
+}
+
 pub fn gen_config(args: &ArgMatches) -> String {
     let toml = r#"
 [webserver]
@@ -148,14 +224,26 @@ port = 6767
 #[[webserver.static]]
 #path = "/static"
 #dir = "./public/"
+
+# gRPC tile service (see tiles.proto)
+#[webserver.grpc]
+#port = 6768
+#max_message_size = 4194304
 "#;
     let mut config;
     if args.value_of("dbconn").is_some()
         || args.value_of("datasource").is_some()
+        || args.value_of("csv").is_some()
         || args.value_of("qgs").is_some()
     {
         let service = service_from_args(&config_from_args(args), args);
         config = service.gen_runtime_config();
+        let defaults =
+            bool::from_str(args.value_of("defaults").unwrap_or("false")).unwrap_or(false);
+        if defaults {
+            config = extract_defaults(&config, "[[tileset]]", "tileset_defaults");
+            config = extract_defaults(&config, "[[tileset.layer]]", "layer_defaults");
+        }
     } else {
         config = MvtService::gen_config();
     }
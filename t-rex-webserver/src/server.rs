@@ -3,24 +3,47 @@
 // Licensed under the MIT License. See LICENSE file in the project root for full license information.
 //
 
-use crate::core::config::ApplicationCfg;
-use crate::mvt_service::MvtService;
-use crate::runtime_config::{config_from_args, service_from_args};
-use crate::static_files::StaticFiles;
+use crate::build_info::BuildInfo;
+use crate::client_limit::ClientLimit;
+use crate::config_watch::ConfigWatch;
+use crate::core::config::{ApplicationCfg, RootResponse};
+use crate::core::layer::{valid_lang, ParamValues, PARAM_PREFIX};
+use crate::datasource::is_pool_timeout;
+use crate::field_values_cache::FieldValuesCache;
+#[cfg(feature = "grpc")]
+use crate::grpc;
+use crate::jobs::{prewarm_jobs, JobParams, JobQueue};
+use crate::metadata_cache::MetadataCache;
+use crate::mvt_service::{MvtService, TileOptions};
+use crate::referer_check::RefererCheck;
+use crate::user_agent_check::UserAgentCheck;
+use crate::runtime_config::{config_from_args, exit_on_warnings, service_from_args};
+use crate::static_files::{dir_file, StaticFiles};
 use actix_cors::Cors;
 use actix_files as fs;
 use actix_rt;
-use actix_web::dev::BodyEncoding;
-use actix_web::http::{header, ContentEncoding};
+use actix_rt::time;
+use actix_web::dev::{Body, BodyEncoding, Service};
+use actix_web::http::header::HttpDate;
+use actix_web::http::{header, ContentEncoding, Method, StatusCode};
 use actix_web::middleware::Compress;
-use actix_web::{middleware, web, App, HttpRequest, HttpResponse, HttpServer, Result};
+use actix_web::{error, middleware, web, App, HttpRequest, HttpResponse, HttpServer, Result};
 use clap::ArgMatches;
+use futures::future::{ok, Either};
+use futures::stream::{self, StreamExt};
+use futures::Future;
 use log::Level;
+use net2::TcpBuilder;
 use num_cpus;
 use open;
-use std::collections::HashMap;
+use std::cmp;
+use std::net::{self, ToSocketAddrs};
+use std::process;
 use std::str;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 static DINO: &'static str = "             xxxxxxxxx
         xxxxxxxxxxxxxxxxxxxxxxxx
@@ -47,64 +70,247 @@ xxxxxxx
 xxxxxx
 xxxxxxx";
 
-async fn mvt_metadata(service: web::Data<MvtService>) -> Result<HttpResponse> {
-    let json = service.get_mvt_metadata().unwrap();
-    Ok(HttpResponse::Ok().json(json))
+/// Default of `webserver.robots_txt`
+const ROBOTS_TXT: &str = "User-agent: *\nDisallow: /\n";
+
+/// Robots exclusion policy for crawlers
+pub(crate) async fn robots_txt(config: web::Data<ApplicationCfg>) -> Result<HttpResponse> {
+    let content = config
+        .webserver
+        .robots_txt
+        .clone()
+        .unwrap_or_else(|| ROBOTS_TXT.to_string());
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(content))
 }
 
-/// Font list for Maputnik
-async fn fontstacks() -> Result<HttpResponse> {
-    Ok(HttpResponse::Ok().json(["Roboto Medium", "Roboto Regular"]))
+/// Default of `webserver.metadata_cache_size`
+const METADATA_CACHE_SIZE: usize = 1000;
+
+/// JSON response with ETag. The JSON is generated with `json` if not cached yet.
+/// Responds with 304 Not Modified if the client has a matching ETag.
+fn cached_json<F, J, E>(
+    req: &HttpRequest,
+    service: &MvtService,
+    cache: &MetadataCache,
+    key: String,
+    json: F,
+) -> Result<HttpResponse>
+where
+    F: FnOnce() -> std::result::Result<J, E>,
+    J: ToString,
+    E: ToString,
+{
+    let generation = service.disabled_layers.generation();
+    let cached = match cache.get(&key, generation) {
+        Some(cached) => cached,
+        None => {
+            let json = json().map_err(|e| error::ErrorInternalServerError(e.to_string()))?;
+            cache.insert(key, generation, json.to_string())
+        }
+    };
+    let not_modified = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| cached.matches(value))
+        .unwrap_or(false);
+    if not_modified {
+        return Ok(HttpResponse::NotModified()
+            .header(header::ETAG, cached.etag.clone())
+            .finish());
+    }
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .header(header::ETAG, cached.etag.clone())
+        .body(cached.body.clone()))
 }
 
-// Include method fonts() which returns HashMap with embedded font files
-include!(concat!(env!("OUT_DIR"), "/fonts.rs"));
+async fn mvt_metadata(
+    config: web::Data<ApplicationCfg>,
+    service: web::Data<MvtService>,
+    cache: web::Data<MetadataCache>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let path_prefix = config.webserver.path_prefix();
+    let key = MetadataCache::key("index.json", "", &path_prefix);
+    cached_json(&req, &service, &cache, key, || {
+        service.get_mvt_metadata(&path_prefix)
+    })
+}
+
+/// Version and build details with the database versions of the datasources
+pub(crate) async fn version_json(
+    build_info: web::Data<BuildInfo>,
+    service: web::Data<MvtService>,
+) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok()
+        .header(header::CACHE_CONTROL, "no-store")
+        .json(build_info.version_json(&service.datasources)))
+}
+
+/// Font list for Maputnik
+async fn fontstacks(static_files: web::Data<StaticFiles>) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(static_files.fontstacks()))
+}
 
 /// Fonts for Maputnik
 /// Example: /fonts/Open%20Sans%20Regular,Arial%20Unicode%20MS%20Regular/0-255.pbf
-async fn fonts_pbf(params: web::Path<(String, String)>) -> Result<HttpResponse> {
-    let fontpbfs = fonts();
+async fn fonts_pbf(
+    static_files: web::Data<StaticFiles>,
+    params: web::Path<(String, String)>,
+) -> Result<HttpResponse> {
     let fontlist = &params.0;
     let range = &params.1;
     let mut fonts = fontlist.split(",").collect::<Vec<_>>();
     fonts.push("Roboto Regular"); // Fallback
     let mut resp = HttpResponse::NotFound().finish();
     for font in fonts {
-        let key = format!("fonts/{}/{}.pbf", font.replace("%20", " "), range);
-        debug!("Font lookup: {}", key);
-        if let Some(pbf) = fontpbfs.get(&key as &str) {
+        let font = font.replace("%20", " ");
+        debug!("Font lookup: {}/{}", font, range);
+        if let Some(pbf) = static_files.font(&font, range) {
             resp = HttpResponse::Ok()
                 .content_type("application/x-protobuf")
                 // data is already gzip compressed
                 .encoding(ContentEncoding::Identity)
                 .header(header::CONTENT_ENCODING, "gzip")
-                .body(*pbf); // TODO: chunked response
+                .body(pbf.into_owned()); // TODO: chunked response
             break;
         }
     }
     Ok(resp)
 }
 
-fn req_baseurl(req: &HttpRequest) -> String {
+fn req_baseurl(req: &HttpRequest, config: &ApplicationCfg) -> String {
     let conninfo = req.connection_info();
-    format!("{}://{}", conninfo.scheme(), conninfo.host())
+    format!(
+        "{}://{}{}",
+        conninfo.scheme(),
+        conninfo.host(),
+        config.webserver.path_prefix()
+    )
+}
+
+/// Base URLs of tile URL templates (validated when loading the configuration)
+fn tile_urls(config: &ApplicationCfg) -> Vec<String> {
+    config.webserver.tile_urls().unwrap_or_default()
+}
+
+/// 404 response for unknown tileset names
+fn tileset_not_found(service: &MvtService, tileset: &str) -> HttpResponse {
+    let mut names = service
+        .tilesets
+        .iter()
+        .map(|ts| ts.name.as_str())
+        .collect::<Vec<_>>();
+    names.sort();
+    HttpResponse::NotFound()
+        .content_type("text/plain")
+        .body(format!(
+            "Tileset '{}' not found. Available tilesets: {}",
+            tileset,
+            names.join(", ")
+        ))
+}
+
+/// File from the `static_dir` of a tileset
+async fn tileset_static_file(
+    service: web::Data<MvtService>,
+    params: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let (tileset, path) = params.into_inner();
+    let ts = match service.get_tileset(&tileset) {
+        Some(ts) => ts,
+        None => return Ok(tileset_not_found(&service, &tileset)),
+    };
+    match ts.static_dir.as_ref().and_then(|dir| dir_file(dir, &path)) {
+        Some(file) => fs::NamedFile::open(file)?.into_response(&req),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+/// Preview page of a tileset: `viewer.html` from its `static_dir` or the built-in viewer
+async fn tileset_viewer(
+    config: web::Data<ApplicationCfg>,
+    service: web::Data<MvtService>,
+    static_files: web::Data<StaticFiles>,
+    tileset: web::Path<String>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let ts = match service.get_tileset(&tileset) {
+        Some(ts) => ts,
+        None => return Ok(tileset_not_found(&service, &tileset)),
+    };
+    if let Some(file) = ts
+        .static_dir
+        .as_ref()
+        .and_then(|dir| dir_file(dir, "viewer.html"))
+    {
+        return fs::NamedFile::open(file)?.into_response(&req);
+    }
+    let viewer = if config.service.mvt.viewer {
+        static_files.content(None, "index.html".to_string())
+    } else {
+        None
+    };
+    match viewer {
+        Some((data, media_type)) => Ok(HttpResponse::Ok()
+            .content_type(media_type)
+            .body(data.into_owned())),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
 }
 
 async fn tileset_tilejson(
+    config: web::Data<ApplicationCfg>,
     service: web::Data<MvtService>,
+    cache: web::Data<MetadataCache>,
     tileset: web::Path<String>,
     req: HttpRequest,
 ) -> Result<HttpResponse> {
-    let json = service.get_tilejson(&req_baseurl(&req), &tileset).unwrap();
-    Ok(HttpResponse::Ok().json(json))
+    let tileset = match service.get_tileset(&tileset) {
+        Some(ts) => ts.name.clone(),
+        None => return Ok(tileset_not_found(&service, &tileset)),
+    };
+    let baseurl = req_baseurl(&req, &config);
+    let key = MetadataCache::key("tilejson", &tileset, &baseurl);
+    cached_json(&req, &service, &cache, key, || {
+        service.get_tilejson(&baseurl, &tile_urls(&config), &tileset)
+    })
 }
 
 async fn tileset_style_json(
+    config: web::Data<ApplicationCfg>,
     service: web::Data<MvtService>,
+    cache: web::Data<MetadataCache>,
     tileset: web::Path<String>,
     req: HttpRequest,
 ) -> Result<HttpResponse> {
-    let json = service.get_stylejson(&req_baseurl(&req), &tileset).unwrap();
+    let tileset = match service.get_tileset(&tileset) {
+        Some(ts) => ts.name.clone(),
+        None => return Ok(tileset_not_found(&service, &tileset)),
+    };
+    let baseurl = req_baseurl(&req, &config);
+    let key = MetadataCache::key("style.json", &tileset, &baseurl);
+    cached_json(&req, &service, &cache, key, || {
+        service.get_stylejson(&baseurl, &tile_urls(&config), &tileset)
+    })
+}
+
+async fn tileset_mvt_metadata(
+    config: web::Data<ApplicationCfg>,
+    service: web::Data<MvtService>,
+    tileset: web::Path<String>,
+) -> Result<HttpResponse> {
+    let tileset = match service.get_tileset(&tileset) {
+        Some(ts) => ts.name.clone(),
+        None => return Ok(tileset_not_found(&service, &tileset)),
+    };
+    let json = service
+        .get_tileset_mvt_metadata(&tileset, &config.webserver.path_prefix())
+        .map_err(|e| error::ErrorInternalServerError(e.to_string()))?;
     Ok(HttpResponse::Ok().json(json))
 }
 
@@ -112,22 +318,32 @@ async fn tileset_metadata_json(
     service: web::Data<MvtService>,
     tileset: web::Path<String>,
 ) -> Result<HttpResponse> {
-    let json = service.get_mbtiles_metadata(&tileset).unwrap();
+    let tileset = match service.get_tileset(&tileset) {
+        Some(ts) => ts.name.clone(),
+        None => return Ok(tileset_not_found(&service, &tileset)),
+    };
+    let json = service
+        .get_mbtiles_metadata(&tileset)
+        .map_err(|e| error::ErrorInternalServerError(e.to_string()))?;
     Ok(HttpResponse::Ok().json(json))
 }
 
-async fn tile_pbf(
-    config: web::Data<ApplicationCfg>,
+async fn tileset_freshness(
     service: web::Data<MvtService>,
-    params: web::Path<(String, u8, u32, u32)>,
-    req: HttpRequest,
+    tileset: web::Path<String>,
 ) -> Result<HttpResponse> {
-    let tileset = &params.0;
-    let z = params.1;
-    let x = params.2;
-    let y = params.3;
-    let gzip = req
-        .headers()
+    let tileset = match service.get_tileset(&tileset) {
+        Some(ts) => ts.name.clone(),
+        None => return Ok(tileset_not_found(&service, &tileset)),
+    };
+    let json = web::block(move || service.get_tileset_freshness(&tileset))
+        .await
+        .map_err(|e| error::ErrorInternalServerError(e.to_string()))?;
+    Ok(HttpResponse::Ok().json(json))
+}
+
+fn accepts_gzip(req: &HttpRequest) -> bool {
+    req.headers()
         .get(header::ACCEPT_ENCODING)
         .and_then(|headerval| {
             headerval
@@ -135,37 +351,970 @@ async fn tile_pbf(
                 .ok()
                 .and_then(|headerstr| Some(headerstr.contains("gzip")))
         })
-        .unwrap_or(false);
-    let tile = service.tile_cached(tileset, x, y, z, gzip, None);
-    let cache_max_age = config.webserver.cache_control_max_age.unwrap_or(300);
+        .unwrap_or(false)
+}
+
+#[derive(Deserialize)]
+struct TileParams {
+    /// Language of localized names
+    lang: Option<String>,
+}
+
+impl TileParams {
+    /// Requested language, if valid
+    fn lang(&self) -> Result<Option<&str>, HttpResponse> {
+        match self.lang {
+            Some(ref lang) if !valid_lang(lang) => {
+                Err(HttpResponse::BadRequest().body(format!("Invalid language '{}'", lang)))
+            }
+            ref lang => Ok(lang.as_ref().map(|lang| lang.as_str())),
+        }
+    }
+}
 
-    let resp = if let Some(tile) = tile {
+/// Layer query parameter values of a tile request (`?param_<name>=<value>`).
+/// Unknown parameters and invalid values are rejected.
+fn tile_param_values(
+    service: &MvtService,
+    tileset: &str,
+    req: &HttpRequest,
+) -> Result<ParamValues, HttpResponse> {
+    let query = web::Query::<Vec<(String, String)>>::from_query(req.query_string())
+        .map_err(|e| HttpResponse::BadRequest().body(e.to_string()))?;
+    let params: ParamValues = query
+        .into_inner()
+        .into_iter()
+        .filter(|(key, _)| key.starts_with(PARAM_PREFIX))
+        .map(|(key, value)| (key[PARAM_PREFIX.len()..].to_string(), value))
+        .collect();
+    match service.param_values_key(tileset, &params) {
+        Ok(_) => Ok(params),
+        Err(e) => Err(HttpResponse::BadRequest().body(e)),
+    }
+}
+
+/// Rejection of tile requests not passing the `allowed_referers` check
+fn referer_forbidden(req: &HttpRequest) -> Option<HttpResponse> {
+    let check = req.app_data::<RefererCheck>()?;
+    let referer = req
+        .headers()
+        .get(header::REFERER)
+        .map(|referer| referer.to_str().unwrap_or("invalid"));
+    if check.allows(referer) {
+        None
+    } else {
+        debug!("{} - Referer {:?} not allowed", req.path(), referer);
+        Some(HttpResponse::Forbidden().finish())
+    }
+}
+
+/// Rejection of tile requests not passing the `user_agent` policy
+fn user_agent_forbidden(req: &HttpRequest) -> Option<HttpResponse> {
+    let check = req.app_data::<UserAgentCheck>()?;
+    let user_agent = req
+        .headers()
+        .get(header::USER_AGENT)
+        .map(|user_agent| user_agent.to_str().unwrap_or("invalid"));
+    if check.allows(user_agent) {
+        None
+    } else {
+        debug!("{} - User-Agent {:?} not allowed", req.path(), user_agent);
+        Some(HttpResponse::Forbidden().finish())
+    }
+}
+
+/// Await tile creation with the `request_timeout_ms` deadline. Returns None on timeout.
+async fn with_request_timeout<F: Future>(config: &ApplicationCfg, fut: F) -> Option<F::Output> {
+    match config.webserver.request_timeout_ms {
+        Some(ms) => time::timeout(Duration::from_millis(ms), fut).await.ok(),
+        None => Some(fut.await),
+    }
+}
+
+fn request_timed_out(config: &ApplicationCfg, tile: &str) -> HttpResponse {
+    warn!(
+        "{} - Request timed out after {} ms",
+        tile,
+        config.webserver.request_timeout_ms.unwrap_or(0)
+    );
+    HttpResponse::GatewayTimeout().finish()
+}
+
+/// `Retry-After` of tile requests failing without a free datasource connection
+const POOL_TIMEOUT_RETRY_AFTER_S: u32 = 1;
+
+fn pool_timed_out(tile: &str, err: &str) -> HttpResponse {
+    warn!("{} - {}", tile, err);
+    HttpResponse::ServiceUnavailable()
+        .header(header::RETRY_AFTER, POOL_TIMEOUT_RETRY_AFTER_S.to_string())
+        .finish()
+}
+
+async fn tile_pbf(
+    config: web::Data<ApplicationCfg>,
+    service: web::Data<MvtService>,
+    params: web::Path<(String, u8, u32, u32)>,
+    query: web::Query<TileParams>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let tile = params.into_inner();
+    tile_response(
+        &config,
+        &service,
+        tile,
+        &query,
+        &req,
+        "application/x-protobuf",
+    )
+    .await
+}
+
+/// Check whether a tile created at `modified` is unchanged since the `If-Modified-Since` date
+fn not_modified_since(req: &HttpRequest, modified: SystemTime) -> bool {
+    // HTTP dates have a resolution of seconds
+    let secs = |time: SystemTime| time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).ok();
+    req.headers()
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<HttpDate>().ok())
+        .and_then(|since| secs(since.into()))
+        .map(|since| {
+            secs(modified)
+                .map(|modified| modified <= since)
+                .unwrap_or(false)
+        })
+        .unwrap_or(false)
+}
+
+/// MVT response of tile `(tileset, z, x, y)`, shared by tile routes.
+/// Responses to HEAD requests include the number of features in `X-Feature-Count`
+/// without tile data. The count of cached tiles is stored with the tile, missing tiles
+/// are counted with the layer queries without creating them.
+/// Cached tiles are returned with their creation time in `Last-Modified`, answering
+/// `If-Modified-Since` requests with 304 Not Modified.
+/// Tiles with layers with `cache = false` use the shorter max-age of these layers.
+async fn tile_response(
+    config: &web::Data<ApplicationCfg>,
+    service: &web::Data<MvtService>,
+    (tileset, z, x, y): (String, u8, u32, u32),
+    query: &TileParams,
+    req: &HttpRequest,
+    content_type: &str,
+) -> Result<HttpResponse> {
+    if let Some(resp) = referer_forbidden(req).or_else(|| user_agent_forbidden(req)) {
+        return Ok(resp);
+    }
+    let (tileset, always_gzip) = match service.get_tileset(&tileset) {
+        Some(ts) => (ts.name.clone(), ts.always_gzip),
+        None => return Ok(tileset_not_found(service, &tileset)),
+    };
+    let lang = match query.lang() {
+        Ok(lang) => lang,
+        Err(resp) => return Ok(resp),
+    };
+    let params = match tile_param_values(service, &tileset, req) {
+        Ok(params) => params,
+        Err(resp) => return Ok(resp),
+    };
+    let gzip = always_gzip || accepts_gzip(req);
+    let head = req.method() == Method::HEAD;
+    let xyz = match service.url_tile(x, y, z) {
+        Some(xyz) => xyz,
+        None => {
+            debug!(
+                "{}/{}/{}/{} - Skipping tile outside of grid",
+                tileset, z, x, y
+            );
+            return Ok(empty_tile_response(config, head));
+        }
+    };
+    let tile = if config.webserver.request_timeout_ms.is_some() {
+        // Tile creation continues in the thread pool after a timeout (and fills the cache)
+        let tile_service = service.clone();
+        let (name, lang) = (tileset.clone(), lang.map(|lang| lang.to_string()));
+        let tile = web::block(move || {
+            let options = TileOptions {
+                gzip,
+                lang: lang.as_ref().map(|lang| lang.as_str()),
+                params: Some(&params),
+                stats: None,
+                count_only: head,
+            };
+            Ok::<_, ()>(tile_service.tile_cached_with_info(&name, &xyz, options))
+        });
+        match with_request_timeout(config, tile).await {
+            Some(tile) => tile.map_err(|e| error::ErrorInternalServerError(e.to_string()))?,
+            None => {
+                let tile = format!("{}/{}/{}/{}.pbf", tileset, z, x, y);
+                return Ok(request_timed_out(config, &tile));
+            }
+        }
+    } else {
+        let options = TileOptions {
+            gzip,
+            lang,
+            params: Some(&params),
+            stats: None,
+            count_only: head,
+        };
+        service.tile_cached_with_info(&tileset, &xyz, options)
+    };
+    let tile = match tile {
+        Ok(tile) => tile,
+        Err(e) if is_pool_timeout(&e) => {
+            let tile = format!("{}/{}/{}/{}.pbf", tileset, z, x, y);
+            return Ok(pool_timed_out(&tile, &e));
+        }
+        // Layer errors are logged
+        Err(_) => return Err(error::ErrorInternalServerError("Tile creation failed")),
+    };
+    let mut cache_max_age = config.webserver.cache_control_max_age.unwrap_or(300);
+    // Tiles with uncached layers
+    if let Some(max_age) = tile.as_ref().and_then(|(_, info)| info.max_age) {
+        cache_max_age = cmp::min(cache_max_age, max_age);
+    }
+
+    if let Some((_, ref info)) = tile {
+        if let Some(modified) = info.modified.filter(|m| not_modified_since(req, *m)) {
+            return Ok(HttpResponse::NotModified()
+                .header(header::LAST_MODIFIED, HttpDate::from(modified))
+                .if_true(!always_gzip, |r| {
+                    r.header(header::VARY, "Accept-Encoding");
+                })
+                .header(header::CACHE_CONTROL, format!("max-age={}", cache_max_age))
+                .finish());
+        }
+    }
+    let resp = if let Some((tile, info)) = tile {
         HttpResponse::Ok()
-            .content_type("application/x-protobuf")
+            .content_type(content_type)
+            // Served as is, without recompression into a stream without Content-Length
+            .encoding(ContentEncoding::Identity)
             .if_true(gzip, |r| {
                 // data is already gzip compressed
-                r.encoding(ContentEncoding::Identity)
-                    .header(header::CONTENT_ENCODING, "gzip");
+                r.header(header::CONTENT_ENCODING, "gzip");
+            })
+            .if_true(!always_gzip, |r| {
+                // Encoding depends on the request header
+                r.header(header::VARY, "Accept-Encoding");
+            })
+            .if_some(info.modified, |modified, r| {
+                r.header(header::LAST_MODIFIED, HttpDate::from(modified));
+            })
+            .if_some(info.tolerance, |tolerance, r| {
+                r.header("X-Simplification-Tolerance", tolerance.to_string());
             })
+            .if_some(info.feature_count.filter(|_| head), |feature_count, r| {
+                r.header("X-Feature-Count", feature_count.to_string());
+            })
+            .if_true(config.webserver.server_timing, |r| {
+                r.header("Server-Timing", info.timings.server_timing());
+            })
+            .if_true(
+                config.webserver.debug_headers && !info.layer_errors.is_empty(),
+                |r| {
+                    r.header("X-Tile-Layer-Errors", info.layer_errors.join(","));
+                },
+            )
             .header(header::CACHE_CONTROL, format!("max-age={}", cache_max_age))
-            .body(tile) // TODO: chunked response
+            .body(if head {
+                // Without Content-Length of the omitted tile data
+                Body::None
+            } else {
+                Body::from(tile) // TODO: chunked response
+            })
+    } else {
+        empty_tile_response(config, head)
+    };
+    Ok(resp)
+}
+
+/// Response for tiles without features (`webserver.empty_tile_status`)
+fn empty_tile_response(config: &ApplicationCfg, head: bool) -> HttpResponse {
+    if config.webserver.empty_tile_status() == Ok(404) {
+        HttpResponse::NotFound()
+    } else {
+        HttpResponse::NoContent()
+    }
+    .if_true(head, |r| {
+        r.header("X-Feature-Count", "0");
+    })
+    .finish()
+}
+
+/// Features of a tile encoded as Geobuf or FlatGeobuf (not cached)
+async fn tile_features(
+    config: web::Data<ApplicationCfg>,
+    service: web::Data<MvtService>,
+    params: web::Path<(String, u8, u32, u32)>,
+    req: HttpRequest,
+    format: &'static str,
+) -> Result<HttpResponse> {
+    let (tileset, z, x, y) = params.into_inner();
+    if let Some(resp) = referer_forbidden(&req).or_else(|| user_agent_forbidden(&req)) {
+        return Ok(resp);
+    }
+    let tileset = match service.get_tileset(&tileset) {
+        Some(ts) => ts.name.clone(),
+        None => return Ok(tileset_not_found(&service, &tileset)),
+    };
+    let gzip = accepts_gzip(&req);
+    let name = tileset.clone();
+    let data = web::block(move || {
+        Ok::<_, ()>(match format {
+            "fgb" => service.tile_fgb(&name, x, y, z, gzip),
+            _ => service.tile_geobuf(&name, x, y, z, gzip),
+        })
+    });
+    let data = match with_request_timeout(&config, data).await {
+        Some(data) => data.map_err(|e| error::ErrorInternalServerError(e.to_string()))?,
+        None => {
+            let tile = format!("{}/{}/{}/{}.{}", tileset, z, x, y, format);
+            return Ok(request_timed_out(&config, &tile));
+        }
+    };
+    let cache_max_age = config.webserver.cache_control_max_age.unwrap_or(300);
+    let resp = if let Some(data) = data {
+        HttpResponse::Ok()
+            .content_type(match format {
+                "fgb" => "application/flatgeobuf",
+                _ => "application/octet-stream",
+            })
+            .encoding(ContentEncoding::Identity)
+            .if_true(gzip, |r| {
+                // data is already gzip compressed
+                r.header(header::CONTENT_ENCODING, "gzip");
+            })
+            .header(header::CACHE_CONTROL, format!("max-age={}", cache_max_age))
+            .body(data)
+    } else if config.webserver.empty_tile_status() == Ok(404) {
+        HttpResponse::NotFound().finish()
     } else {
         HttpResponse::NoContent().finish()
     };
     Ok(resp)
 }
 
-lazy_static! {
-    static ref STATIC_FILES: StaticFiles = StaticFiles::init();
+/// Features of a tile encoded as Geobuf (not cached)
+async fn tile_geobuf(
+    config: web::Data<ApplicationCfg>,
+    service: web::Data<MvtService>,
+    params: web::Path<(String, u8, u32, u32)>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    tile_features(config, service, params, req, "geobuf").await
+}
+
+/// Features of a tile encoded as FlatGeobuf (not cached)
+async fn tile_fgb(
+    config: web::Data<ApplicationCfg>,
+    service: web::Data<MvtService>,
+    params: web::Path<(String, u8, u32, u32)>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    tile_features(config, service, params, req, "fgb").await
+}
+
+/// Default of `webserver.batch_max_tiles`
+const BATCH_MAX_TILES: u32 = 100;
+
+/// Append entry of a batch response: status (u16), data length (u32), data
+fn write_batch_entry(body: &mut Vec<u8>, status: u16, data: &[u8]) {
+    body.extend_from_slice(&status.to_be_bytes());
+    body.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    body.extend_from_slice(data);
+}
+
+/// Batch tile request with a JSON array of `[z, x, y]` triples.
+///
+/// The response (`application/octet-stream`) contains one entry per requested tile in
+/// request order. Each entry consists of a big-endian u16 status, a big-endian u32
+/// data length and the gzip compressed tile data. Status 200 is a tile, 204 an empty
+/// tile and 500 a failed tile.
+/// Tiles are created concurrently in the thread pool, one per CPU at a time.
+async fn tiles_batch(
+    config: web::Data<ApplicationCfg>,
+    service: web::Data<MvtService>,
+    tileset: web::Path<String>,
+    query: web::Query<TileParams>,
+    tiles: web::Json<Vec<(u8, u32, u32)>>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    if !config.webserver.batch_tiles {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    if let Some(resp) = referer_forbidden(&req).or_else(|| user_agent_forbidden(&req)) {
+        return Ok(resp);
+    }
+    let tileset = match service.get_tileset(&tileset) {
+        Some(ts) => ts.name.clone(),
+        None => return Ok(tileset_not_found(&service, &tileset)),
+    };
+    let lang = match query.lang() {
+        Ok(lang) => lang.map(|lang| lang.to_string()),
+        Err(resp) => return Ok(resp),
+    };
+    let params = match tile_param_values(&service, &tileset, &req) {
+        Ok(params) => params,
+        Err(resp) => return Ok(resp),
+    };
+    let max_tiles = config.webserver.batch_max_tiles.unwrap_or(BATCH_MAX_TILES);
+    if tiles.len() > max_tiles as usize {
+        return Ok(HttpResponse::BadRequest().body(format!(
+            "Too many tiles ({}, maximum: {})",
+            tiles.len(),
+            max_tiles
+        )));
+    }
+    let tiles = tiles.into_inner();
+    let (tileset, params) = (Arc::new(tileset), Arc::new(params));
+    // One tile per worker thread, in request order
+    let results = stream::iter(tiles.iter().map(|&(z, x, y)| {
+        let (service, tileset) = (service.clone(), tileset.clone());
+        let (lang, params) = (lang.clone(), params.clone());
+        web::block(move || {
+            let tile = match service.url_tile(x, y, z) {
+                Some(xyz) => {
+                    let options = TileOptions {
+                        gzip: true,
+                        lang: lang.as_ref().map(|lang| lang.as_str()),
+                        params: Some(&params),
+                        ..Default::default()
+                    };
+                    service.tile_cached_with_info(&tileset, &xyz, options)
+                }
+                None => Ok(None),
+            };
+            Ok::<_, ()>(tile)
+        })
+    }))
+    .buffered(num_cpus::get())
+    .collect::<Vec<_>>()
+    .await;
+    let mut body = Vec::new();
+    for (&(z, x, y), tile) in tiles.iter().zip(results) {
+        let tile = tile.map_err(|e| error::ErrorInternalServerError(e.to_string()))?;
+        match tile {
+            Ok(Some((data, _))) => write_batch_entry(&mut body, 200, &data),
+            Ok(None) => write_batch_entry(&mut body, 204, &[]),
+            Err(e) if is_pool_timeout(&e) => {
+                warn!("{}/{}/{}/{}.pbf - {}", tileset, z, x, y, e);
+                write_batch_entry(&mut body, 503, &[])
+            }
+            Err(_) => write_batch_entry(&mut body, 500, &[]),
+        }
+    }
+    Ok(HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .encoding(ContentEncoding::Identity)
+        .body(body))
+}
+
+/// PostgreSQL query plans of the layer queries of a tile
+async fn tile_explain(
+    config: web::Data<ApplicationCfg>,
+    service: web::Data<MvtService>,
+    params: web::Path<(String, u8, u32, u32)>,
+) -> Result<HttpResponse> {
+    if !config.webserver.debug_explain {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    let (tileset, z, x, y) = params.into_inner();
+    let tileset = match service.get_tileset(&tileset) {
+        Some(ts) => ts.name.clone(),
+        None => return Ok(tileset_not_found(&service, &tileset)),
+    };
+    if !service.grid.tile_in_grid(x, y, z) {
+        return Ok(HttpResponse::BadRequest()
+            .body(format!("Tile {}/{}/{} is outside of the grid", z, x, y)));
+    }
+    let explain = web::block(move || {
+        service
+            .explain_tile(&tileset, x, y, z)
+            .unwrap_or(Err("Tileset not found".to_string()))
+    })
+    .await;
+    let resp = match explain {
+        Ok(json) => HttpResponse::Ok()
+            .header(header::CACHE_CONTROL, "no-store")
+            .json(json),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    Ok(resp)
+}
+
+/// Defaults of field value requests
+const FIELD_VALUES_LIMIT: u32 = 100;
+const FIELD_VALUES_MAX_LIMIT: u32 = 1000;
+/// Default of `webserver.field_values_cache_s`
+const FIELD_VALUES_CACHE_S: u64 = 300;
+/// Default of `webserver.field_values_timeout_ms`
+const FIELD_VALUES_TIMEOUT_MS: u64 = 5000;
+
+#[derive(Deserialize)]
+struct FieldValuesParams {
+    limit: Option<u32>,
+    zoom: Option<u8>,
+}
+
+/// Distinct values of a layer attribute with feature counts, e.g. for styling with Maputnik
+async fn field_values(
+    config: web::Data<ApplicationCfg>,
+    service: web::Data<MvtService>,
+    cache: web::Data<FieldValuesCache>,
+    params: web::Path<(String, String, String)>,
+    query: web::Query<FieldValuesParams>,
+) -> Result<HttpResponse> {
+    if !config.service.mvt.viewer {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    let (tileset, layer, field) = params.into_inner();
+    let tileset = match service.get_tileset(&tileset) {
+        Some(ts) => ts.name.clone(),
+        None => return Ok(tileset_not_found(&service, &tileset)),
+    };
+    let (minzoom, maxzoom) = match service.get_layer(&tileset, &layer) {
+        Some(l) => (l.minzoom(), l.maxzoom(service.grid.maxzoom())),
+        None => {
+            return Ok(HttpResponse::NotFound()
+                .content_type("text/plain")
+                .body(format!("Layer '{}' not found", layer)))
+        }
+    };
+    let zoom = query.zoom.unwrap_or(maxzoom);
+    if zoom < minzoom || zoom > maxzoom {
+        return Ok(HttpResponse::BadRequest().body(format!(
+            "Layer '{}' is not available at zoom level {} ({}-{})",
+            layer, zoom, minzoom, maxzoom
+        )));
+    }
+    let limit = query
+        .limit
+        .unwrap_or(FIELD_VALUES_LIMIT)
+        .min(FIELD_VALUES_MAX_LIMIT);
+    let key = FieldValuesCache::key(&tileset, &layer, &field, zoom, limit);
+    if let Some(body) = cache.get(&key) {
+        return Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body(body));
+    }
+
+    let fields = {
+        let service = service.clone();
+        let (tileset, layer) = (tileset.clone(), layer.clone());
+        web::block(move || {
+            service
+                .layer_fields(&tileset, &layer, zoom)
+                .ok_or("Layer not found")
+        })
+        .await
+        .map_err(|e| error::ErrorInternalServerError(e.to_string()))?
+    };
+    if !fields.contains(&field) {
+        return Ok(HttpResponse::BadRequest().body(format!(
+            "Field '{}' not found in layer '{}' at zoom level {}",
+            field, layer, zoom
+        )));
+    }
+    let timeout_ms = config
+        .webserver
+        .field_values_timeout_ms
+        .unwrap_or(FIELD_VALUES_TIMEOUT_MS);
+    let values = web::block(move || {
+        service
+            .field_values(&tileset, &layer, &field, zoom, limit, timeout_ms)
+            .unwrap_or(Err("Layer not found".to_string()))
+    })
+    .await;
+    let resp = match values {
+        Ok(json) => {
+            let body = json.to_string();
+            cache.insert(key, body.clone());
+            HttpResponse::Ok()
+                .content_type("application/json")
+                .body(body)
+        }
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    Ok(resp)
+}
+
+/// Compare secrets in constant time
+fn secure_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Rejection of admin requests without `Authorization: Bearer <admin_token>` header
+fn admin_unauthorized(config: &ApplicationCfg, req: &HttpRequest) -> Option<HttpResponse> {
+    if config.webserver.read_only {
+        return Some(HttpResponse::Forbidden().body("Server is read-only"));
+    }
+    let token = match config.webserver.admin_token {
+        Some(ref token) => token,
+        None => return Some(HttpResponse::NotFound().finish()),
+    };
+    let expected = format!("Bearer {}", token);
+    let authorized = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .map(|auth| secure_eq(auth.as_bytes(), expected.as_bytes()))
+        .unwrap_or(false);
+    if authorized {
+        None
+    } else {
+        Some(
+            HttpResponse::Unauthorized()
+                .header(header::WWW_AUTHENTICATE, "Bearer")
+                .finish(),
+        )
+    }
+}
+
+/// Enqueue seeding job with parameters of the `generate` command
+async fn admin_submit_job(
+    config: web::Data<ApplicationCfg>,
+    service: web::Data<MvtService>,
+    jobs: web::Data<JobQueue>,
+    params: web::Json<JobParams>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    if let Some(resp) = admin_unauthorized(&config, &req) {
+        return Ok(resp);
+    }
+    let mut params = params.into_inner();
+    if let Some(tileset) = params.tileset.take() {
+        match service.get_tileset(&tileset) {
+            Some(ts) => params.tileset = Some(ts.name.clone()),
+            None => return Ok(tileset_not_found(&service, &tileset)),
+        }
+    }
+    if let Some((minx, miny, maxx, maxy)) = params.extent {
+        if minx >= maxx || miny >= maxy {
+            return Ok(HttpResponse::BadRequest().body("Invalid extent"));
+        }
+    }
+    let id = jobs.submit(params);
+    info!("Job {} queued", id);
+    Ok(HttpResponse::Accepted().json(jobs.info(id)))
+}
+
+/// Progress of seeding job
+async fn admin_job(
+    config: web::Data<ApplicationCfg>,
+    jobs: web::Data<JobQueue>,
+    id: web::Path<u64>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    if let Some(resp) = admin_unauthorized(&config, &req) {
+        return Ok(resp);
+    }
+    Ok(match jobs.info(*id) {
+        Some(info) => HttpResponse::Ok().json(info),
+        None => HttpResponse::NotFound().finish(),
+    })
+}
+
+/// Cancel queued or running seeding job
+async fn admin_cancel_job(
+    config: web::Data<ApplicationCfg>,
+    jobs: web::Data<JobQueue>,
+    id: web::Path<u64>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    if let Some(resp) = admin_unauthorized(&config, &req) {
+        return Ok(resp);
+    }
+    let cancelled = jobs.cancel(*id);
+    Ok(match jobs.info(*id) {
+        Some(info) if cancelled => HttpResponse::Ok().json(info),
+        Some(info) => HttpResponse::Conflict().json(info),
+        None => HttpResponse::NotFound().finish(),
+    })
 }
 
-async fn static_file_handler(req: HttpRequest) -> Result<HttpResponse> {
-    let key = req.path()[1..].to_string();
-    let resp = if let Some(ref content) = STATIC_FILES.content(None, key) {
+pub(crate) fn admin_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/admin/jobs").route(web::post().to(admin_submit_job)))
+        .service(
+            web::resource("/admin/jobs/{id}")
+                .route(web::get().to(admin_job))
+                .route(web::delete().to(admin_cancel_job)),
+        );
+}
+
+/// Tile requests in progress with concurrency metrics
+/// JSON requested with `f=json` or `Accept` header
+fn wants_json(req: &HttpRequest) -> bool {
+    if req.query_string().split('&').any(|param| param == "f=json") {
+        return true;
+    }
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .map(|accept| accept.contains("application/json") && !accept.contains("text/html"))
+        .unwrap_or(false)
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// HTML page listing the tilesets with their URLs
+fn tilesets_page(service: &MvtService, baseurl: &str, viewer: bool) -> String {
+    let mut names: Vec<&str> = service.tilesets.iter().map(|ts| ts.name.as_str()).collect();
+    names.sort();
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>t-rex</title>\n</head>\n<body>\n<h1>t-rex</h1>\n<table>\n<tr><th>Tileset</th><th>TileJSON</th><th>Style JSON</th><th>Tiles</th></tr>\n",
+    );
+    for name in names {
+        let url = html_escape(&format!("{}/{}", baseurl, name));
+        let name = html_escape(name);
+        let title = if viewer {
+            format!("<a href=\"{}/\">{}</a>", url, name)
+        } else {
+            name
+        };
+        html.push_str(&format!(
+            "<tr><td>{}</td><td><a href=\"{url}.json\">{url}.json</a></td><td><a href=\"{url}.style.json\">{url}.style.json</a></td><td>{url}/{{z}}/{{x}}/{{y}}.pbf</td></tr>\n",
+            title,
+            url = url
+        ));
+    }
+    html.push_str(&format!(
+        "</table>\n<p><a href=\"{url}/index.json\">index.json</a> | <a href=\"{url}/collections\">OGC API - Tiles</a></p>\n</body>\n</html>\n",
+        url = html_escape(baseurl)
+    ));
+    html
+}
+
+/// Response of `/` configured with `webserver.root`. JSON requests to the viewer or the
+/// tileset list get the OGC API landing page.
+async fn ogcapi_landing_page(
+    config: web::Data<ApplicationCfg>,
+    service: web::Data<MvtService>,
+    static_files: web::Data<StaticFiles>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let viewer = config.service.mvt.viewer;
+    // Validated on startup
+    let root = config
+        .webserver
+        .root_response(viewer)
+        .unwrap_or(RootResponse::Tilesets);
+    match root {
+        RootResponse::Redirect(location) => {
+            return Ok(HttpResponse::Found()
+                .header(header::LOCATION, location)
+                .finish());
+        }
+        RootResponse::Status(status, body) => {
+            let status = StatusCode::from_u16(status).unwrap_or(StatusCode::OK);
+            return Ok(HttpResponse::build(status)
+                .content_type("text/plain; charset=utf-8")
+                .body(body));
+        }
+        _ if wants_json(&req) => {}
+        RootResponse::Viewer => return static_file_handler(config, static_files, req).await,
+        RootResponse::Tilesets => {
+            let html = tilesets_page(&service, &req_baseurl(&req, &config), viewer);
+            return Ok(HttpResponse::Ok()
+                .content_type("text/html; charset=utf-8")
+                .body(html));
+        }
+    }
+    let json = service.get_ogcapi_landing_page(&req_baseurl(&req, &config));
+    Ok(HttpResponse::Ok().json(json))
+}
+
+async fn ogcapi_conformance(service: web::Data<MvtService>) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(service.get_ogcapi_conformance()))
+}
+
+async fn ogcapi_collections(
+    config: web::Data<ApplicationCfg>,
+    service: web::Data<MvtService>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let json = service.get_ogcapi_collections(&req_baseurl(&req, &config));
+    Ok(HttpResponse::Ok().json(json))
+}
+
+async fn ogcapi_collection(
+    config: web::Data<ApplicationCfg>,
+    service: web::Data<MvtService>,
+    tileset: web::Path<String>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let resp = match service.get_ogcapi_collection(&req_baseurl(&req, &config), &tileset) {
+        Some(json) => HttpResponse::Ok().json(json),
+        None => tileset_not_found(&service, &tileset),
+    };
+    Ok(resp)
+}
+
+async fn ogcapi_tilesets(
+    config: web::Data<ApplicationCfg>,
+    service: web::Data<MvtService>,
+    tileset: web::Path<String>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let resp = match service.get_ogcapi_tilesets(&req_baseurl(&req, &config), &tileset) {
+        Some(json) => HttpResponse::Ok().json(json),
+        None => tileset_not_found(&service, &tileset),
+    };
+    Ok(resp)
+}
+
+/// 404 response for tile matrix sets other than the one of the grid
+fn tile_matrix_set_not_found(service: &MvtService, tile_matrix_set: &str) -> HttpResponse {
+    HttpResponse::NotFound()
+        .content_type("text/plain")
+        .body(format!(
+            "Tile matrix set '{}' not found. Available tile matrix sets: {}",
+            tile_matrix_set,
+            service.tile_matrix_set_id()
+        ))
+}
+
+async fn ogcapi_tileset(
+    config: web::Data<ApplicationCfg>,
+    service: web::Data<MvtService>,
+    params: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let (tileset, tile_matrix_set) = params.into_inner();
+    let tileset = match service.get_tileset(&tileset) {
+        Some(ts) => ts.name.clone(),
+        None => return Ok(tileset_not_found(&service, &tileset)),
+    };
+    let baseurl = req_baseurl(&req, &config);
+    let resp = match service.get_ogcapi_tileset(&baseurl, &tileset, &tile_matrix_set) {
+        Some(json) => HttpResponse::Ok().json(json),
+        None => tile_matrix_set_not_found(&service, &tile_matrix_set),
+    };
+    Ok(resp)
+}
+
+/// OGC API tile with path `{tileMatrix}/{tileRow}/{tileCol}`
+async fn ogcapi_tile(
+    config: web::Data<ApplicationCfg>,
+    service: web::Data<MvtService>,
+    params: web::Path<(String, String, u8, u32, u32)>,
+    query: web::Query<TileParams>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let (tileset, tile_matrix_set, z, row, col) = params.into_inner();
+    if tile_matrix_set != service.tile_matrix_set_id() {
+        return Ok(tile_matrix_set_not_found(&service, &tile_matrix_set));
+    }
+    if !service.grid.tile_in_grid(col, row, z) {
+        return Ok(HttpResponse::NotFound()
+            .content_type("text/plain")
+            .body(format!(
+                "Tile {}/{}/{} is outside of the tile matrix",
+                z, row, col
+            )));
+    }
+    let y = service.ytile_from_tile_row(row, z);
+    let media_type = "application/vnd.mapbox-vector-tile";
+    tile_response(
+        &config,
+        &service,
+        (tileset, z, col, y),
+        &query,
+        &req,
+        media_type,
+    )
+    .await
+}
+
+/// OGC API - Tiles endpoints with tilesets as collections
+pub(crate) fn ogcapi_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/").route(web::get().to(ogcapi_landing_page)))
+        .service(web::resource("/conformance").route(web::get().to(ogcapi_conformance)))
+        .service(web::resource("/collections").route(web::get().to(ogcapi_collections)))
+        .service(web::resource("/collections/{tileset}").route(web::get().to(ogcapi_collection)))
+        .service(
+            web::resource("/collections/{tileset}/tiles").route(web::get().to(ogcapi_tilesets)),
+        )
+        .service(
+            web::resource("/collections/{tileset}/tiles/{tile_matrix_set}")
+                .route(web::get().to(ogcapi_tileset)),
+        )
+        .service(
+            web::resource("/collections/{tileset}/tiles/{tile_matrix_set}/{z}/{row}/{col}")
+                .route(web::get().to(ogcapi_tile))
+                .route(web::head().to(ogcapi_tile)),
+        );
+}
+
+/// Font routes for Maputnik and the built-in viewer
+pub(crate) fn font_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/fontstacks.json").route(web::get().to(fontstacks)))
+        .service(web::resource("/fonts.json").route(web::get().to(fontstacks)))
+        .service(web::resource("/fonts/{fonts}/{range}.pbf").route(web::get().to(fonts_pbf)));
+}
+
+pub(crate) async fn debug_requests(service: web::Data<MvtService>) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok()
+        .header(header::CACHE_CONTROL, "no-store")
+        .json(service.debug_requests()))
+}
+
+/// Tileset routes
+// Path segments are matched greedily, i.e. `/a.b.c.json` resolves to tileset `a.b.c`.
+// Tileset names ending with `.style` are rejected when loading the configuration,
+// so `/{tileset}.style.json` is never ambiguous.
+pub(crate) fn tileset_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/{tileset}.style.json").route(web::get().to(tileset_style_json)))
+        // Before tile routes, which would also match paths like /{tileset}/static/1/2/3.pbf
+        .service(
+            web::resource("/{tileset}/static/{path:.*}").route(web::get().to(tileset_static_file)),
+        )
+        .service(web::resource("/{tileset}/").route(web::get().to(tileset_viewer)))
+        .service(
+            web::resource("/{tileset}/metadata.json").route(web::get().to(tileset_metadata_json)),
+        )
+        .service(web::resource("/{tileset}/index.json").route(web::get().to(tileset_mvt_metadata)))
+        .service(web::resource("/{tileset}/freshness.json").route(web::get().to(tileset_freshness)))
+        .service(web::resource("/{tileset}.json").route(web::get().to(tileset_tilejson)))
+        .service(web::resource("/{tileset}/tiles").route(web::post().to(tiles_batch)))
+        .service(
+            web::resource("/{tileset}/{z}/{x}/{y}.pbf")
+                .route(web::get().to(tile_pbf))
+                .route(web::head().to(tile_pbf)),
+        )
+        .service(
+            web::resource("/{tileset}/{z}/{x}/{y}.geobuf").route(web::get().to(tile_geobuf)),
+        )
+        .service(web::resource("/{tileset}/{z}/{x}/{y}.fgb").route(web::get().to(tile_fgb)))
+        .service(
+            web::resource("/{tileset}/{z}/{x}/{y}/explain.json")
+                .route(web::get().to(tile_explain)),
+        )
+        .service(
+            web::resource("/{tileset}/{layer}/fields/{field}/values")
+                .route(web::get().to(field_values)),
+        );
+}
+
+pub(crate) async fn static_file_handler(
+    config: web::Data<ApplicationCfg>,
+    static_files: web::Data<StaticFiles>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let prefix = config.webserver.path_prefix();
+    if !req.path().starts_with(&prefix) {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    let key = req.path()[prefix.len()..].trim_start_matches('/').to_string();
+    let resp = if let Some((data, media_type)) = static_files.content(None, key) {
         HttpResponse::Ok()
             .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*") // TOOD: use Actix middleware
-            .content_type(content.1)
-            .body(content.0) // TODO: chunked response
+            .content_type(media_type)
+            .body(data.into_owned()) // TODO: chunked response
     } else {
         HttpResponse::NotFound().finish()
     };
@@ -173,35 +1322,266 @@ async fn static_file_handler(req: HttpRequest) -> Result<HttpResponse> {
 }
 
 #[derive(Deserialize)]
-struct DrilldownParams {
+pub(crate) struct DrilldownParams {
+    tileset: Option<String>,
     minzoom: Option<u8>,
     maxzoom: Option<u8>,
-    points: String, //x1,y1,x2,y2,..
+    points: Option<String>, //x1,y1,x2,y2,..
+    bbox: Option<String>,   //minx,miny,maxx,maxy
+    step: Option<f64>,
 }
 
-async fn drilldown_handler(
+/// Default of `webserver.drilldown_max_points`
+const DRILLDOWN_MAX_POINTS: u32 = 100;
+
+fn parse_numlist(numlist: &str, name: &str) -> Result<Vec<f64>, String> {
+    numlist
+        .split(',')
+        .map(|v| {
+            v.trim()
+                .parse::<f64>()
+                .map_err(|_| format!("Error parsing '{}' as list of float values", name))
+        })
+        .collect()
+}
+
+/// WGS84 drilldown points from `points` list or sampled from `bbox` with `step`
+fn drilldown_points<F>(
+    params: &DrilldownParams,
+    max_points: u32,
+    point_in_grid: F,
+) -> Result<Vec<f64>, String>
+where
+    F: Fn(f64, f64) -> bool,
+{
+    let outside_err = |x: f64, y: f64| format!("Point {},{} is outside of the grid extent", x, y);
+    let points = match (&params.points, &params.bbox) {
+        (Some(numlist), None) => {
+            let points = parse_numlist(numlist, "points")?;
+            if points.len() % 2 != 0 {
+                return Err("Error parsing 'points' as pairs of float values".to_string());
+            }
+            if points.len() / 2 > max_points as usize {
+                return Err(format!(
+                    "Too many points ({}, maximum: {})",
+                    points.len() / 2,
+                    max_points
+                ));
+            }
+            if let Some(point) = points
+                .chunks(2)
+                .find(|point| !point_in_grid(point[0], point[1]))
+            {
+                return Err(outside_err(point[0], point[1]));
+            }
+            points
+        }
+        (None, Some(numlist)) => {
+            let bbox = parse_numlist(numlist, "bbox")?;
+            if bbox.len() != 4
+                || !bbox.iter().all(|v| v.is_finite())
+                || bbox[0] > bbox[2]
+                || bbox[1] > bbox[3]
+            {
+                return Err("Expected 'bbox' as minx,miny,maxx,maxy".to_string());
+            }
+            let step = match params.step {
+                Some(step) if step > 0.0 && step.is_finite() => step,
+                _ => return Err("Expected positive 'step' with 'bbox'".to_string()),
+            };
+            // Sampled points are within the bbox corners
+            for (x, y) in &[(bbox[0], bbox[1]), (bbox[2], bbox[3])] {
+                if !point_in_grid(*x, *y) {
+                    return Err(outside_err(*x, *y));
+                }
+            }
+            // Count in f64 to avoid integer overflow with tiny steps
+            let nx = ((bbox[2] - bbox[0]) / step).floor() + 1.0;
+            let ny = ((bbox[3] - bbox[1]) / step).floor() + 1.0;
+            if nx * ny > max_points as f64 {
+                return Err(format!(
+                    "Too many points ({}, maximum: {})",
+                    nx * ny,
+                    max_points
+                ));
+            }
+            let (nx, ny) = (nx as usize, ny as usize);
+            let mut points = Vec::with_capacity(nx * ny * 2);
+            for iy in 0..ny {
+                for ix in 0..nx {
+                    points.push(bbox[0] + ix as f64 * step);
+                    points.push(bbox[1] + iy as f64 * step);
+                }
+            }
+            points
+        }
+        _ => return Err("Either 'points' or 'bbox' is required".to_string()),
+    };
+    Ok(points)
+}
+
+/// Sets flag when dropped
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+pub(crate) async fn drilldown_handler(
+    config: web::Data<ApplicationCfg>,
     service: web::Data<MvtService>,
     params: web::Query<DrilldownParams>,
 ) -> Result<HttpResponse> {
-    let tileset = None; // all tilesets
+    let max_points = config
+        .webserver
+        .drilldown_max_points
+        .unwrap_or(DRILLDOWN_MAX_POINTS);
+    let points = match drilldown_points(&params, max_points, |x, y| service.point_in_grid(x, y)) {
+        Ok(points) => points,
+        Err(e) => return Ok(HttpResponse::BadRequest().body(e)),
+    };
+    // default: all tilesets
+    let tileset = match params.tileset {
+        Some(ref tileset) => match service.get_tileset(tileset) {
+            Some(ts) => Some(ts.name.clone()),
+            None => return Ok(HttpResponse::NotFound().finish()),
+        },
+        None => None,
+    };
+    // The handler future is dropped when the client disconnects
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let _guard = CancelOnDrop(cancelled.clone());
+    let (minzoom, maxzoom) = (params.minzoom, params.maxzoom);
     let progress = false;
-    let points: Vec<f64> = params
-        .points
-        .split(",")
-        .map(|v| {
-            v.parse()
-                .expect("Error parsing 'point' as pair of float values")
-            //FIXME: map_err(|_| error::ErrorInternalServerError("...")
-        })
-        .collect();
-    let stats = service.drilldown(tileset, params.minzoom, params.maxzoom, points, progress);
-    let json = stats.as_json().unwrap();
+    let json = web::block(move || {
+        let stats = service.drilldown_cancellable(
+            tileset.as_ref().map(|ts| ts.as_str()),
+            minzoom,
+            maxzoom,
+            points,
+            progress,
+            &cancelled,
+        );
+        stats.as_json()
+    })
+    .await
+    .map_err(|e| error::ErrorInternalServerError(e.to_string()))?;
     Ok(HttpResponse::Ok().json(json))
 }
 
+/// Defaults of `HttpServer` connection settings
+const KEEP_ALIVE_S: u32 = 5;
+const CLIENT_TIMEOUT_MS: u64 = 5000;
+const CLIENT_SHUTDOWN_MS: u64 = 5000;
+const MAX_CONNECTIONS: u32 = 25600;
+const BACKLOG: u32 = 2048;
+/// Default of `webserver.stuck_request_s`
+const STUCK_REQUEST_S: u64 = 30;
+/// Default of `webserver.missing_table_check_s`
+const MISSING_TABLE_CHECK_S: u64 = 60;
+
+/// Listening sockets of the web server, kept open across restarts
+#[derive(Default)]
+pub(crate) struct Listeners {
+    /// Bind address with sockets
+    bound: Mutex<Option<(String, Vec<net::TcpListener>)>>,
+}
+
+impl Listeners {
+    /// Sockets for `bind_addr`. Sockets bound in a previous run are reused, so that
+    /// pending connections are kept and ephemeral ports (port 0) stay the same.
+    pub(crate) fn sockets(
+        &self,
+        bind_addr: &str,
+        backlog: i32,
+    ) -> std::io::Result<Vec<net::TcpListener>> {
+        let mut bound = self.bound.lock().unwrap();
+        if bound.as_ref().map(|(addr, _)| addr != bind_addr) == Some(true) {
+            info!("Bind address changed to {}", bind_addr);
+            // Release the current address before binding, e.g. for a changed host with the same port
+            *bound = None;
+        }
+        if bound.is_none() {
+            *bound = Some((bind_addr.to_string(), bind_sockets(bind_addr, backlog)?));
+        }
+        let (_, sockets) = bound.as_ref().unwrap();
+        sockets.iter().map(|socket| socket.try_clone()).collect()
+    }
+}
+
+/// Bind all addresses of `bind_addr` like `HttpServer::bind`
+fn bind_sockets(bind_addr: &str, backlog: i32) -> std::io::Result<Vec<net::TcpListener>> {
+    let mut sockets = Vec::new();
+    let mut err = None;
+    for addr in bind_addr.to_socket_addrs()? {
+        let builder = match addr {
+            net::SocketAddr::V4(_) => TcpBuilder::new_v4(),
+            net::SocketAddr::V6(_) => TcpBuilder::new_v6(),
+        };
+        let socket = builder.and_then(|builder| {
+            builder.reuse_address(true)?;
+            builder.bind(addr)?;
+            builder.listen(backlog)
+        });
+        match socket {
+            Ok(socket) => sockets.push(socket),
+            Err(e) => err = Some(e),
+        }
+    }
+    if sockets.is_empty() {
+        return Err(err.unwrap_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::Other, "Can not bind to address")
+        }));
+    }
+    Ok(sockets)
+}
+
+/// Start web server. With `--watch-config`, the server is restarted after valid changes
+/// of the configuration file or included files. The listening sockets stay open during a
+/// restart.
+pub fn webserver(args: ArgMatches<'static>, build_info: BuildInfo) -> std::io::Result<()> {
+    info!("{}", build_info.summary());
+    let listeners = Arc::new(Listeners::default());
+    if !args.is_present("watch-config") {
+        return run_webserver(args, build_info, listeners, None, None);
+    }
+    let watch = Arc::new(ConfigWatch::new(args.clone()).unwrap_or_else(|err| {
+        error!("{}", err);
+        process::exit(1)
+    }));
+    let mut reloaded = None;
+    loop {
+        run_webserver(
+            args.clone(),
+            build_info.clone(),
+            listeners.clone(),
+            Some(watch.clone()),
+            reloaded,
+        )?;
+        reloaded = watch.take_reload();
+        if reloaded.is_none() {
+            return Ok(());
+        }
+        info!("Restarting web server");
+    }
+}
+
 #[actix_rt::main]
-pub async fn webserver(args: ArgMatches<'static>) -> std::io::Result<()> {
-    let config = config_from_args(&args);
+async fn run_webserver(
+    args: ArgMatches<'static>,
+    build_info: BuildInfo,
+    listeners: Arc<Listeners>,
+    watch: Option<Arc<ConfigWatch>>,
+    reloaded: Option<(ApplicationCfg, MvtService)>,
+) -> std::io::Result<()> {
+    // Configuration and service validated by the config watch are used without reading again
+    let (mut config, reloaded_service) = match reloaded {
+        Some((config, service)) => (config, Some(service)),
+        None => (config_from_args(&args), None),
+    };
+    let watched_config = config.clone();
     let host = config
         .webserver
         .bind
@@ -210,69 +1590,335 @@ pub async fn webserver(args: ArgMatches<'static>) -> std::io::Result<()> {
     let port = config.webserver.port.unwrap_or(6767);
     let bind_addr = format!("{}:{}", host, port);
     let workers = config.webserver.threads.unwrap_or(num_cpus::get() as u8);
+    let keep_alive = config.webserver.keep_alive_s.unwrap_or(KEEP_ALIVE_S);
+    let client_timeout = config
+        .webserver
+        .client_timeout_ms
+        .unwrap_or(CLIENT_TIMEOUT_MS);
+    let client_shutdown = config
+        .webserver
+        .client_shutdown_ms
+        .unwrap_or(CLIENT_SHUTDOWN_MS);
+    let max_connections = config.webserver.max_connections.unwrap_or(MAX_CONNECTIONS);
+    let backlog = config.webserver.backlog.unwrap_or(BACKLOG);
+    let static_files = if args.is_present("no-viewer-assets") {
+        StaticFiles::Disabled
+    } else {
+        StaticFiles::init(config.webserver.assets_dir.as_ref().map(|d| d.as_str()))
+            .unwrap_or_else(|err| {
+                error!("{}", err);
+                process::exit(1)
+            })
+    };
+    info!("{}", static_files.info());
+    if static_files.is_disabled() {
+        // Viewer and landing page need the assets
+        config.service.mvt.viewer = false;
+    }
     let mvt_viewer = config.service.mvt.viewer;
+    if let Err(err) = config.webserver.root_response(mvt_viewer) {
+        error!("{}", err);
+        process::exit(1)
+    }
     let openbrowser =
         bool::from_str(args.value_of("openbrowser").unwrap_or("true")).unwrap_or(false);
     let static_dirs = config.webserver.static_.clone();
+    let path_prefix = config.webserver.path_prefix();
+    if !path_prefix.is_empty() {
+        info!("Serving routes under path prefix '{}'", path_prefix);
+    }
 
-    let mut service = service_from_args(&config, &args);
-    service.prepare_feature_queries();
+    let service = reloaded_service.unwrap_or_else(|| {
+        let mut service = service_from_args(&config, &args);
+        let warnings = service.prepare_feature_queries();
+        exit_on_warnings(&args, &warnings);
+        service
+    });
     service.init_cache();
+    // Background tasks of this run are stopped with the server (restart with --watch-config)
+    let stopped = Arc::new(AtomicBool::new(false));
+    for cache in service.cache.sqlite_caches() {
+        let interval = cache.vacuum_interval.unwrap_or(300);
+        cache.spawn_vacuum_task(Duration::from_secs(interval), stopped.clone());
+    }
+    let stuck_request = config.webserver.stuck_request_s.unwrap_or(STUCK_REQUEST_S);
+    service
+        .requests
+        .spawn_watchdog(Duration::from_secs(stuck_request), stopped.clone());
+    let missing_table_check = config
+        .webserver
+        .missing_table_check_s
+        .unwrap_or(MISSING_TABLE_CHECK_S);
+    service.spawn_missing_table_check(Duration::from_secs(missing_table_check), stopped.clone());
+    for tileset in &service.tilesets {
+        if let Some(ref dir) = tileset.static_dir {
+            if std::path::Path::new(dir).is_dir() {
+                info!(
+                    "Tileset '{}': serving static files from directory '{}'",
+                    tileset.name, dir
+                );
+            } else {
+                warn!(
+                    "Tileset '{}': static file directory '{}' not found",
+                    tileset.name, dir
+                );
+            }
+        }
+    }
 
-    let server = HttpServer::new(move || {
+    let client_limit = match config.webserver.max_requests_per_ip {
+        Some(max_requests) => {
+            let limit = ClientLimit::new(max_requests, &config.webserver.trusted_proxies)
+                .unwrap_or_else(|err| {
+                    error!("{}", err);
+                    process::exit(1)
+                });
+            info!("Limiting concurrent requests per client IP to {}", max_requests);
+            Some(limit)
+        }
+        None => None,
+    };
+
+    let referer_check = if config.webserver.allowed_referers.is_empty() {
+        None
+    } else {
+        let allow_missing = config.webserver.allow_missing_referer.unwrap_or(true);
+        let check = RefererCheck::new(&config.webserver.allowed_referers, allow_missing)
+            .unwrap_or_else(|err| {
+                error!("{}", err);
+                process::exit(1)
+            });
+        info!(
+            "Restricting tile requests to referers {:?}",
+            config.webserver.allowed_referers
+        );
+        Some(check)
+    };
+
+    let user_agent_check = config.webserver.user_agent.as_ref().map(|user_agent| {
+        info!("Applying User-Agent policy to tile requests");
+        UserAgentCheck::new(user_agent).unwrap_or_else(|err| {
+            error!("{}", err);
+            process::exit(1)
+        })
+    });
+
+    let field_values_cache = FieldValuesCache::new(Duration::from_secs(
+        config
+            .webserver
+            .field_values_cache_s
+            .unwrap_or(FIELD_VALUES_CACHE_S),
+    ));
+    let metadata_cache = MetadataCache::new(
+        config
+            .webserver
+            .metadata_cache_size
+            .unwrap_or(METADATA_CACHE_SIZE),
+    );
+    let jobs = JobQueue::new();
+    let prewarm = match config.webserver.prewarm {
+        Some(ref cfg) => prewarm_jobs(&service, cfg).unwrap_or_else(|err| {
+            error!("{}", err);
+            process::exit(1)
+        }),
+        None => Vec::new(),
+    };
+    let admin = config.webserver.admin_token.is_some() && !config.webserver.read_only;
+    if config.webserver.read_only {
+        info!("Read-only mode - admin endpoints disabled");
+    }
+    if admin || !prewarm.is_empty() {
+        let workers = config.webserver.job_workers.unwrap_or(1);
+        jobs.spawn_workers(&service, workers);
+        if admin {
+            info!("Admin endpoints enabled ({} job workers)", workers);
+        }
+    }
+
+    if config.webserver.debug_explain {
+        warn!("Query plan endpoint enabled - tile queries are executed with EXPLAIN ANALYZE");
+    }
+
+    let compress = config.webserver.compress.unwrap_or(true);
+    if !compress {
+        info!("Response compression disabled");
+    }
+
+    let cors_methods = if config.webserver.batch_tiles {
+        info!("Batch tile endpoint enabled");
+        vec!["GET", "POST"]
+    } else {
+        vec!["GET"]
+    };
+
+    #[cfg(feature = "grpc")]
+    {
+        if let Some(ref grpc_cfg) = config.webserver.grpc {
+            let grpc_bind = grpc_cfg.bind.as_ref().unwrap_or(&host);
+            let grpc_addr = format!("{}:{}", grpc_bind, grpc_cfg.port);
+            let listener = grpc::bind(&grpc_addr).unwrap_or_else(|err| {
+                error!("Can not start gRPC server on {}: {}", grpc_addr, err);
+                process::exit(1)
+            });
+            info!("gRPC tile service listening on {}", grpc_addr);
+            let max_message_size = grpc_cfg
+                .max_message_size
+                .unwrap_or(grpc::DEFAULT_MAX_MESSAGE_SIZE);
+            actix_rt::spawn(grpc::serve(
+                listener,
+                Arc::new(service.clone()),
+                max_message_size,
+            ));
+        }
+    }
+    #[cfg(not(feature = "grpc"))]
+    {
+        if config.webserver.grpc.is_some() {
+            warn!("gRPC tile service not available (t-rex built without feature `grpc`)");
+        }
+    }
+
+    let route_prefix = path_prefix.clone();
+    let prewarm_queue = jobs.clone();
+    let app = move || {
         let mut app = App::new()
             .data(config.clone())
             .data(service.clone())
+            .data(static_files.clone())
+            .data(jobs.clone())
+            .data(field_values_cache.clone())
+            .data(metadata_cache.clone())
+            .data(build_info.clone())
+            .wrap_fn({
+                let client_limit = client_limit.clone();
+                move |req, srv| {
+                    let ip = req.peer_addr().map(|addr| addr.ip());
+                    let slot = match client_limit {
+                        Some(ref limit) => match limit.acquire(ip) {
+                            Some(slot) => Some(slot),
+                            None => {
+                                debug!("{} - Too many concurrent requests", req.path());
+                                return Either::Right(ok(
+                                    req.into_response(HttpResponse::TooManyRequests().finish())
+                                ));
+                            }
+                        },
+                        None => None,
+                    };
+                    let fut = srv.call(req);
+                    Either::Left(async move {
+                        let res = fut.await;
+                        drop(slot);
+                        res
+                    })
+                }
+            })
             .wrap(middleware::Logger::new("%r %s %b %Dms %a"))
-            .wrap(Compress::default())
             .wrap(
                 Cors::new()
                     .send_wildcard()
-                    .allowed_methods(vec!["GET"])
+                    .allowed_methods(cors_methods.clone())
                     .finish(),
-            )
+            );
+        if let Some(ref check) = referer_check {
+            app = app.app_data(check.clone());
+        }
+        if let Some(ref check) = user_agent_check {
+            app = app.app_data(check.clone());
+        }
+        let mut scope = web::scope(&route_prefix)
             .service(web::resource("/index.json").route(web::get().to(mvt_metadata)))
-            .service(web::resource("/fontstacks.json").route(web::get().to(fontstacks)))
-            .service(web::resource("/fonts.json").route(web::get().to(fontstacks)))
-            .service(web::resource("/fonts/{fonts}/{range}.pbf").route(web::get().to(fonts_pbf)));
+            .service(web::resource("/version.json").route(web::get().to(version_json)))
+            .configure(font_routes);
         for static_dir in &static_dirs {
             let dir = &static_dir.dir;
             if std::path::Path::new(dir).is_dir() {
                 info!("Serving static files from directory '{}'", dir);
-                app = app.service(fs::Files::new(&static_dir.path, dir));
+                scope = scope.service(fs::Files::new(&static_dir.path, dir));
             } else {
                 warn!("Static file directory '{}' not found", dir);
             }
         }
-        app = app
-            .service(
-                web::resource("/{tileset}.style.json").route(web::get().to(tileset_style_json)),
-            )
-            .service(
-                web::resource("/{tileset}/metadata.json")
-                    .route(web::get().to(tileset_metadata_json)),
-            )
-            .service(web::resource("/{tileset}.json").route(web::get().to(tileset_tilejson)))
-            .service(web::resource("/{tileset}/{z}/{x}/{y}.pbf").route(web::get().to(tile_pbf)));
+        if admin {
+            scope = scope.configure(admin_routes);
+        }
+        scope = scope.configure(ogcapi_routes).configure(tileset_routes);
         if mvt_viewer {
-            app = app.service(web::resource("/drilldown").route(web::get().to(drilldown_handler)));
+            scope = scope
+                .service(web::resource("/drilldown").route(web::get().to(drilldown_handler)))
+                .service(web::resource("/debug/requests").route(web::get().to(debug_requests)));
             app = app.default_service(web::to(static_file_handler));
         }
-        app
-    })
-    .workers(workers as usize)
-    .bind(&bind_addr)
-    .expect("Can not start server on given IP/Port")
-    .shutdown_timeout(3) // default: 30s
-    .run();
+        // Crawlers request robots.txt at the root, independent of the path prefix
+        app.service(web::resource("/robots.txt").route(web::get().to(robots_txt)))
+            .service(scope)
+    };
+    let bind_error = |err: std::io::Error| -> ! {
+        error!("Can not start server on {}: {}", bind_addr, err);
+        process::exit(1)
+    };
+    let sockets = listeners
+        .sockets(&bind_addr, backlog as i32)
+        .unwrap_or_else(|err| bind_error(err));
+    // `Compress` changes the response body type and can't be wrapped in
+    // `middleware::Condition`, so it is only added to the app with `compress`
+    let (addrs, server) = if compress {
+        let mut server = HttpServer::new(move || app().wrap(Compress::default()))
+            .workers(workers as usize)
+            .keep_alive(keep_alive as usize)
+            .client_timeout(client_timeout)
+            .client_shutdown(client_shutdown)
+            .maxconn(max_connections as usize);
+        for socket in sockets {
+            server = server.listen(socket).unwrap_or_else(|err| bind_error(err));
+        }
+        (server.addrs(), server.shutdown_timeout(3).run()) // default: 30s
+    } else {
+        let mut server = HttpServer::new(app)
+            .workers(workers as usize)
+            .keep_alive(keep_alive as usize)
+            .client_timeout(client_timeout)
+            .client_shutdown(client_shutdown)
+            .maxconn(max_connections as usize);
+        for socket in sockets {
+            server = server.listen(socket).unwrap_or_else(|err| bind_error(err));
+        }
+        (server.addrs(), server.shutdown_timeout(3).run())
+    };
+    // Bound address, e.g. the ephemeral port assigned for port 0
+    let addr = addrs
+        .first()
+        .map(|addr| addr.to_string())
+        .unwrap_or(bind_addr.clone());
+    if let Some(ref watch) = watch {
+        watch.spawn(server.clone(), watched_config);
+    }
+    info!(
+        "HTTP server settings: workers={} keep_alive_s={} client_timeout_ms={} client_shutdown_ms={} max_connections={} backlog={}",
+        workers, keep_alive, client_timeout, client_shutdown, max_connections, backlog
+    );
+    info!("Listening on http://{}{}/", addr, path_prefix);
+    // Jobs yield to tile requests and skip cached tiles
+    for params in prewarm {
+        info!("Prewarming cache of tileset '{}'", params.tileset.as_ref().unwrap());
+        prewarm_queue.submit(params);
+    }
 
     if log_enabled!(Level::Info) {
         println!("{}", DINO);
     }
+    if port == 0 {
+        // Machine-readable line for test harnesses
+        println!("T_REX_LISTEN=http://{}{}/", addr, path_prefix);
+    }
 
-    if openbrowser && mvt_viewer {
-        let _res = open::that(format!("http://{}:{}", &host, port));
+    let restarted = watch.map(|watch| watch.reloads() > 0).unwrap_or(false);
+    if openbrowser && mvt_viewer && !restarted {
+        let _res = open::that(format!("http://{}{}/", &addr, &path_prefix));
     }
 
-    server.await
+    let result = server.await;
+    stopped.store(true, Ordering::Relaxed);
+    prewarm_queue.shutdown();
+    result
 }
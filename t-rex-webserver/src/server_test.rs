@@ -0,0 +1,1821 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use crate::cache::{Nocache, Tilecache};
+use crate::core::config::{ApplicationCfg, DEFAULT_CONFIG};
+use crate::core::parse_config;
+use crate::datasources::Datasources;
+use crate::disabled_layers::DisabledLayers;
+use crate::inflight::InflightRequests;
+use crate::metadata_cache::MetadataCache;
+use crate::mvt_service::MvtService;
+use crate::server::{ogcapi_routes, robots_txt, tileset_routes, Listeners};
+use crate::service::tileset::Tileset;
+use actix_web::http::StatusCode;
+use actix_web::{test, web, App};
+use std::collections::HashMap;
+use tile_grid::Grid;
+
+pub fn empty_tileset(name: &str) -> Tileset {
+    Tileset {
+        name: name.to_string(),
+        minzoom: None,
+        maxzoom: None,
+        attribution: None,
+        extent: None,
+        center: None,
+        start_zoom: None,
+        layers: Vec::new(),
+        cache_limits: None,
+        max_tile_bytes: None,
+        max_tile_memory: None,
+        mbtiles: None,
+        overzoom: false,
+        default_style: None,
+        skip_layer_errors: false,
+        always_gzip: false,
+        static_dir: None,
+        aliases: Vec::new(),
+    }
+}
+
+pub fn test_service() -> MvtService {
+    MvtService {
+        datasources: Datasources::new(),
+        grid: Grid::web_mercator(),
+        tilesets: vec![empty_tileset("a"), empty_tileset("a.b.c")],
+        cache: Tilecache::Nocache(Nocache),
+        tileset_limits: HashMap::new(),
+        requests: InflightRequests::default(),
+        disabled_layers: DisabledLayers::default(),
+        active_tilesets: None,
+    }
+}
+
+async fn get_json(uri: &str) -> serde_json::Value {
+    let config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(test_service())
+            .data(MetadataCache::new(10))
+            .configure(tileset_routes),
+    )
+    .await;
+    let req = test::TestRequest::get().uri(uri).to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = test::read_body(resp).await;
+    serde_json::from_slice(&body).unwrap()
+}
+
+async fn get_status(uri: &str) -> StatusCode {
+    let config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(test_service())
+            .data(MetadataCache::new(10))
+            .configure(tileset_routes),
+    )
+    .await;
+    let req = test::TestRequest::get().uri(uri).to_request();
+    test::call_service(&mut app, req).await.status()
+}
+
+#[actix_rt::test]
+async fn test_dotted_tileset_routes() {
+    let json = get_json("/a.b.c.json").await;
+    assert_eq!(json["id"], "a.b.c");
+    assert!(json["tiles"][0]
+        .as_str()
+        .unwrap()
+        .ends_with("/a.b.c/{z}/{x}/{y}.pbf"));
+
+    let json = get_json("/a.json").await;
+    assert_eq!(json["id"], "a");
+
+    let json = get_json("/a.b.c.style.json").await;
+    assert!(json["sources"]["a.b.c"].is_object());
+
+    assert_eq!(get_status("/a.b.c/0/0/0.pbf").await, StatusCode::NO_CONTENT);
+    assert_eq!(
+        get_status("/a.b.c/0/0/0.geobuf").await,
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(get_status("/a.b.c/0/0/0.fgb").await, StatusCode::NO_CONTENT);
+    assert_eq!(get_status("/a.b/0/0/0.pbf").await, StatusCode::NOT_FOUND);
+    assert_eq!(get_status("/b.json").await, StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn test_unknown_tileset() {
+    let config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(test_service())
+            .data(MetadataCache::new(10))
+            .configure(tileset_routes),
+    )
+    .await;
+    for uri in &[
+        "/x.json",
+        "/x.style.json",
+        "/x/metadata.json",
+        "/x/index.json",
+        "/x/0/0/0.pbf",
+        "/x/0/0/0.geobuf",
+        "/x/0/0/0.fgb",
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND, "{}", uri);
+        let body = test::read_body(resp).await;
+        assert_eq!(
+            std::str::from_utf8(&body).unwrap(),
+            "Tileset 'x' not found. Available tilesets: a, a.b.c"
+        );
+    }
+
+    let service = test_service();
+    assert!(service.get_tilejson("", &[], "x").is_err());
+    assert!(service.get_mbtiles_metadata("x").is_err());
+    assert!(service.get_tileset_mvt_metadata("x", "").is_err());
+    assert!(service.tile_cached("x", 0, 0, 0, false, None).is_none());
+}
+
+#[actix_rt::test]
+async fn test_tileset_index() {
+    let json = test_service().get_mvt_metadata("").unwrap();
+    assert_eq!(json["tilesets"].as_array().unwrap().len(), 2);
+
+    let json = get_json("/a.b.c/index.json").await;
+    let tilesets = json["tilesets"].as_array().unwrap();
+    assert_eq!(tilesets.len(), 1);
+    assert_eq!(tilesets[0]["name"], "a.b.c");
+    assert_eq!(tilesets[0]["tileurl"], "/a.b.c/{z}/{x}/{y}.pbf");
+    assert_eq!(tilesets[0]["tile_matrix_set"], "WebMercatorQuad");
+
+    assert_eq!(get_status("/b/index.json").await, StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn test_path_prefix() {
+    use actix_web::web;
+
+    let mut config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    config.webserver.path_prefix = Some("/tiles/".to_string());
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(test_service())
+            .data(MetadataCache::new(10))
+            .service(web::scope("/tiles").configure(tileset_routes)),
+    )
+    .await;
+    let req = test::TestRequest::get().uri("/tiles/a.json").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    assert!(json["tiles"][0]
+        .as_str()
+        .unwrap()
+        .ends_with("/tiles/a/{z}/{x}/{y}.pbf"));
+
+    let req = test::TestRequest::get()
+        .uri("/tiles/a/index.json")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    let json: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    assert_eq!(json["tilesets"][0]["tileurl"], "/tiles/a/{z}/{x}/{y}.pbf");
+
+    let req = test::TestRequest::get().uri("/a.json").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+async fn drilldown_status(uri: &str) -> StatusCode {
+    use crate::server::drilldown_handler;
+    use actix_web::web;
+
+    let mut config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    config.webserver.drilldown_max_points = Some(10);
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(test_service())
+            .service(web::resource("/drilldown").route(web::get().to(drilldown_handler))),
+    )
+    .await;
+    let req = test::TestRequest::get().uri(uri).to_request();
+    test::call_service(&mut app, req).await.status()
+}
+
+#[actix_rt::test]
+async fn test_drilldown_params() {
+    let ok = StatusCode::OK;
+    let bad = StatusCode::BAD_REQUEST;
+    assert_eq!(drilldown_status("/drilldown?points=7.44,46.95").await, ok);
+    assert_eq!(
+        drilldown_status("/drilldown?points=7.44,46.95&tileset=a&minzoom=1&maxzoom=2").await,
+        ok
+    );
+    assert_eq!(
+        drilldown_status("/drilldown?points=7.44,46.95&tileset=b").await,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(drilldown_status("/drilldown").await, bad);
+    assert_eq!(drilldown_status("/drilldown?points=7.44").await, bad);
+    assert_eq!(drilldown_status("/drilldown?points=7.44,x").await, bad);
+    // Outside of Web Mercator grid
+    assert_eq!(drilldown_status("/drilldown?points=7.44,89.0").await, bad);
+    assert_eq!(drilldown_status("/drilldown?points=190.0,46.95").await, bad);
+    let points = vec!["7.44,46.95"; 11].join(",");
+    assert_eq!(
+        drilldown_status(&format!("/drilldown?points={}", points)).await,
+        bad
+    );
+
+    // 3 x 3 points
+    assert_eq!(
+        drilldown_status("/drilldown?bbox=7.0,46.0,8.0,47.0&step=0.5").await,
+        ok
+    );
+    // 5 x 5 points
+    assert_eq!(
+        drilldown_status("/drilldown?bbox=7.0,46.0,8.0,47.0&step=0.25").await,
+        bad
+    );
+    assert_eq!(
+        drilldown_status("/drilldown?bbox=7.0,46.0,8.0,47.0").await,
+        bad
+    );
+    assert_eq!(
+        drilldown_status("/drilldown?bbox=8.0,46.0,7.0,47.0&step=0.5").await,
+        bad
+    );
+    assert_eq!(
+        drilldown_status("/drilldown?bbox=7.0,46.0,8.0,47.0&points=7.44,46.95&step=0.5").await,
+        bad
+    );
+    // Point count overflowing u64
+    assert_eq!(
+        drilldown_status("/drilldown?bbox=7.0,46.0,8.0,47.0&step=1e-12").await,
+        bad
+    );
+    assert_eq!(
+        drilldown_status("/drilldown?bbox=-inf,0,inf,0&step=1").await,
+        bad
+    );
+    assert_eq!(
+        drilldown_status("/drilldown?bbox=7.0,46.0,8.0,NaN&step=0.5").await,
+        bad
+    );
+    // Outside of Web Mercator grid
+    assert_eq!(
+        drilldown_status("/drilldown?bbox=0,0,4294967295,4294967295&step=1").await,
+        bad
+    );
+}
+
+#[actix_rt::test]
+async fn test_empty_tile_status() {
+    let mut config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    config.webserver.empty_tile_status = Some(404);
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(test_service())
+            .configure(tileset_routes),
+    )
+    .await;
+    let req = test::TestRequest::get().uri("/a/0/0/0.pbf").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn test_request_timeout() {
+    let mut config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    config.webserver.request_timeout_ms = Some(10000);
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(test_service())
+            .configure(tileset_routes),
+    )
+    .await;
+    let req = test::TestRequest::get().uri("/a/0/0/0.pbf").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let req = test::TestRequest::get().uri("/a/0/0/0.geobuf").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let req = test::TestRequest::get().uri("/a/0/0/0.fgb").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+}
+
+#[actix_rt::test]
+async fn test_tile_lang() {
+    let config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(test_service())
+            .configure(tileset_routes),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri("/a/0/0/0.pbf?lang=de")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let req = test::TestRequest::get()
+        .uri("/a/0/0/0.pbf?lang=..%2Fde")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn test_tile_params() {
+    use crate::core::layer::Layer;
+    use crate::core::Config;
+
+    let config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    let toml = r#"
+        name = "events"
+        minzoom = 10
+        [[param]]
+        name = "date"
+        type = "date"
+        default = "2023-01-01"
+        max = "2023-12-31"
+        "#;
+    let layer = Layer::from_config(&parse_config(toml.to_string(), "").unwrap()).unwrap();
+    let mut service = test_service();
+    service.tilesets[0].layers.push(layer);
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(service)
+            .configure(tileset_routes),
+    )
+    .await;
+    for (uri, status) in &[
+        ("/a/0/0/0.pbf?param_date=2023-06-01", StatusCode::NO_CONTENT),
+        ("/a/0/0/0.pbf?param_date=2023-01-01", StatusCode::NO_CONTENT),
+        ("/a/0/0/0.pbf?param_date=2024", StatusCode::BAD_REQUEST),
+        ("/a/0/0/0.pbf?param_date=yesterday", StatusCode::BAD_REQUEST),
+        ("/a/0/0/0.pbf?param_limit=10", StatusCode::BAD_REQUEST),
+        // Tileset without parameters
+        ("/a.b.c/0/0/0.pbf?param_date=2023", StatusCode::BAD_REQUEST),
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), *status, "{}", uri);
+    }
+    let req = test::TestRequest::get()
+        .uri("/a/0/0/0.pbf?param_date=2024-01-01")
+        .to_request();
+    let body = test::read_response(&mut app, req).await;
+    assert_eq!(
+        body,
+        "value '2024-01-01' of parameter 'date' is out of range (..2023-12-31)"
+    );
+}
+
+#[actix_rt::test]
+async fn test_ogcapi() {
+    use crate::static_files::StaticFiles;
+
+    let config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(test_service())
+            .data(StaticFiles::embedded())
+            .configure(ogcapi_routes)
+            .configure(tileset_routes),
+    )
+    .await;
+    let get = |uri: &str| {
+        test::TestRequest::get()
+            .uri(uri)
+            .header("accept", "application/json")
+            .to_request()
+    };
+    let resp = test::call_service(&mut app, get("/")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    assert_eq!(json["links"][1]["rel"], "conformance");
+
+    let resp = test::call_service(&mut app, get("/conformance")).await;
+    let json: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    let mvt = "http://www.opengis.net/spec/ogcapi-tiles-1/1.0/conf/mvt";
+    assert!(json["conformsTo"].as_array().unwrap().contains(&mvt.into()));
+
+    let resp = test::call_service(&mut app, get("/collections")).await;
+    let json: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    assert_eq!(json["collections"][1]["id"], "a.b.c");
+
+    let resp = test::call_service(&mut app, get("/collections/a.b.c/tiles")).await;
+    let json: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    assert_eq!(
+        json["tilesets"][0]["tileMatrixSetURI"],
+        "http://www.opengis.net/def/tilematrixset/OGC/1.0/WebMercatorQuad"
+    );
+
+    let resp = test::call_service(&mut app, get("/collections/a/tiles/WebMercatorQuad")).await;
+    let json: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    assert!(json["links"][2]["href"]
+        .as_str()
+        .unwrap()
+        .ends_with("/collections/a/tiles/WebMercatorQuad/{tileMatrix}/{tileRow}/{tileCol}"));
+
+    let req = get("/collections/a/tiles/WebMercatorQuad/0/0/0");
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let req = get("/collections/a/tiles/WorldCRS84Quad/0/0/0");
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    // Outside of the tile matrix
+    for uri in &[
+        "/collections/a/tiles/WebMercatorQuad/1/2/0",
+        "/collections/a/tiles/WebMercatorQuad/1/0/2",
+        "/collections/a/tiles/WebMercatorQuad/23/0/0",
+    ] {
+        let resp = test::call_service(&mut app, get(uri)).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND, "{}", uri);
+    }
+    let resp = test::call_service(&mut app, get("/collections/x")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn test_root_response() {
+    use crate::core::config::RootCfg;
+    use crate::static_files::StaticFiles;
+
+    let root_response = |root: Option<&str>| {
+        let mut config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+        config.service.mvt.viewer = false;
+        config.webserver.root =
+            root.map(|toml| parse_config::<RootCfg>(toml.to_string(), "").unwrap());
+        async move {
+            let mut app = test::init_service(
+                App::new()
+                    .data(config)
+                    .data(test_service())
+                    .data(StaticFiles::Disabled)
+                    .configure(ogcapi_routes),
+            )
+            .await;
+            let req = test::TestRequest::get().uri("/").to_request();
+            test::call_service(&mut app, req).await
+        }
+    };
+
+    // Tileset list without viewer
+    let resp = root_response(None).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get("content-type").unwrap(),
+        "text/html; charset=utf-8"
+    );
+    let html = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(html.contains(
+        r#"<tr><td>a.b.c</td><td><a href="http://localhost:8080/a.b.c.json">http://localhost:8080/a.b.c.json</a></td>"#
+    ));
+    assert!(html.contains("<td>http://localhost:8080/a/{z}/{x}/{y}.pbf</td>"));
+
+    let resp = root_response(Some(
+        r#"
+        response = "redirect"
+        location = "/a/"
+        "#,
+    ))
+    .await;
+    assert_eq!(resp.status(), StatusCode::FOUND);
+    assert_eq!(resp.headers().get("location").unwrap(), "/a/");
+
+    let resp = root_response(Some(
+        r#"
+        response = "status"
+        status = 503
+        body = "maintenance"
+        "#,
+    ))
+    .await;
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(test::read_body(resp).await, "maintenance");
+}
+
+#[actix_rt::test]
+async fn test_tiles_batch() {
+    use crate::cache::{Cache, Filecache};
+    use std::env;
+    use std::fs;
+    use t_rex_core::mvt::tile::Tile;
+
+    let mut config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    let post = |uri: &str, body: &str| {
+        test::TestRequest::post()
+            .uri(uri)
+            .header("content-type", "application/json")
+            .set_payload(body.to_string())
+            .to_request()
+    };
+
+    // Disabled by default
+    let mut app = test::init_service(
+        App::new()
+            .data(config.clone())
+            .data(test_service())
+            .configure(tileset_routes),
+    )
+    .await;
+    let resp = test::call_service(&mut app, post("/a/tiles", "[[0,0,0]]")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    config.webserver.batch_tiles = true;
+    config.webserver.batch_max_tiles = Some(2);
+    let mut app = test::init_service(
+        App::new()
+            .data(config.clone())
+            .data(test_service())
+            .configure(tileset_routes),
+    )
+    .await;
+    let resp = test::call_service(&mut app, post("/a/tiles", "[[0,0,0],[1,1,0]]")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = test::read_body(resp).await;
+    // Two empty tiles: status 204, length 0
+    assert_eq!(&body[..], &[0, 204, 0, 0, 0, 0, 0, 204, 0, 0, 0, 0]);
+
+    let resp = test::call_service(&mut app, post("/a/tiles", "[[0,0,0],[1,0,0],[1,1,0]]")).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = test::call_service(&mut app, post("/x/tiles", "[[0,0,0]]")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = test::call_service(&mut app, post("/a/tiles", "[[0,0]]")).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // Entries in request order
+    let mut dir = env::temp_dir();
+    dir.push("t_rex_test_tiles_batch");
+    let _ = fs::remove_dir_all(&dir);
+    let cache = Filecache {
+        basepath: dir.to_str().unwrap().to_string(),
+        baseurl: None,
+        extension: None,
+    };
+    let tiles = ["a/0/0/0.pbf", "a/1/1/0.pbf", "a/1/1/1.pbf"];
+    for tile in &tiles {
+        cache.write(tile, &Tile::compress(tile.as_bytes())).unwrap();
+    }
+    let mut service = test_service();
+    service.cache = Tilecache::Filecache(cache);
+    config.webserver.batch_max_tiles = None;
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(service)
+            .configure(tileset_routes),
+    )
+    .await;
+    let requested = "[[1,1,1],[1,0,0],[0,0,0],[1,1,0],[1,1,1]]";
+    let resp = test::call_service(&mut app, post("/a/tiles", requested)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = test::read_body(resp).await;
+    let mut entries = Vec::new();
+    let mut pos = 0;
+    while pos < body.len() {
+        let status = u16::from_be_bytes([body[pos], body[pos + 1]]);
+        let len = u32::from_be_bytes([body[pos + 2], body[pos + 3], body[pos + 4], body[pos + 5]]);
+        let data = body[pos + 6..pos + 6 + len as usize].to_vec();
+        entries.push((status, Tile::tile_content(data, false)));
+        pos += 6 + len as usize;
+    }
+    let tile = |path: &str| (200, path.as_bytes().to_vec());
+    assert_eq!(
+        entries,
+        vec![
+            tile("a/1/1/1.pbf"),
+            (204, Vec::new()),
+            tile("a/0/0/0.pbf"),
+            tile("a/1/1/0.pbf"),
+            tile("a/1/1/1.pbf"),
+        ]
+    );
+}
+
+#[actix_rt::test]
+async fn test_robots_txt() {
+    let mut config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    let mut app = test::init_service(
+        App::new()
+            .data(config.clone())
+            .service(web::resource("/robots.txt").route(web::get().to(robots_txt))),
+    )
+    .await;
+    let req = test::TestRequest::get().uri("/robots.txt").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get("content-type").unwrap(),
+        "text/plain; charset=utf-8"
+    );
+    assert_eq!(
+        test::read_body(resp).await,
+        "User-agent: *\nDisallow: /\n".as_bytes()
+    );
+
+    config.webserver.robots_txt = Some("User-agent: *\nAllow: /\n".to_string());
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .service(web::resource("/robots.txt").route(web::get().to(robots_txt))),
+    )
+    .await;
+    let req = test::TestRequest::get().uri("/robots.txt").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(test::read_body(resp).await, "User-agent: *\nAllow: /\n".as_bytes());
+}
+
+#[actix_rt::test]
+async fn test_explain() {
+    let mut config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+
+    // Disabled by default
+    let mut app = test::init_service(
+        App::new()
+            .data(config.clone())
+            .data(test_service())
+            .configure(tileset_routes),
+    )
+    .await;
+    let req = test::TestRequest::get().uri("/a/0/0/0/explain.json").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    config.webserver.debug_explain = true;
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(test_service())
+            .configure(tileset_routes),
+    )
+    .await;
+    let req = test::TestRequest::get().uri("/a/0/0/0/explain.json").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    assert_eq!(json["tileset"], "a");
+    assert_eq!(json["layers"], serde_json::json!([]));
+    let req = test::TestRequest::get().uri("/x/0/0/0/explain.json").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn test_field_values() {
+    use crate::core::layer::Layer;
+    use crate::field_values_cache::FieldValuesCache;
+    use std::time::Duration;
+
+    let mut config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    let mut service = test_service();
+    service.datasources.default = Some("db".to_string());
+    let mut layer = Layer::new("roads");
+    layer.minzoom = Some(5);
+    layer.maxzoom = Some(14);
+    service.tilesets[0].layers.push(layer);
+    let cache = FieldValuesCache::new(Duration::from_secs(60));
+    let body = r#"{"values":[{"count":3,"value":"primary"}]}"#;
+    cache.insert(
+        FieldValuesCache::key("a", "roads", "class", 10, 5),
+        body.to_string(),
+    );
+    let mut app = test::init_service(
+        App::new()
+            .data(config.clone())
+            .data(service.clone())
+            .data(cache.clone())
+            .configure(tileset_routes),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri("/a/roads/fields/class/values?zoom=10&limit=5")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(test::read_body(resp).await, body.as_bytes());
+    for (uri, status) in &[
+        // Field not included in layer
+        ("/a/roads/fields/class/values?zoom=10", StatusCode::BAD_REQUEST),
+        // Outside of layer zoom range
+        ("/a/roads/fields/class/values?zoom=4", StatusCode::BAD_REQUEST),
+        ("/a/rivers/fields/class/values", StatusCode::NOT_FOUND),
+        ("/x/roads/fields/class/values", StatusCode::NOT_FOUND),
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), *status, "{}", uri);
+    }
+
+    // Disabled without viewer
+    config.service.mvt.viewer = false;
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(service)
+            .data(cache)
+            .configure(tileset_routes),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri("/a/roads/fields/class/values?zoom=10&limit=5")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn test_field_values_cache() {
+    use crate::field_values_cache::FieldValuesCache;
+    use std::time::Duration;
+
+    let cache = FieldValuesCache::new(Duration::from_secs(60));
+    assert_eq!(cache.get("a/roads/class/10/100"), None);
+    cache.insert("a/roads/class/10/100".to_string(), "{}".to_string());
+    assert_eq!(cache.get("a/roads/class/10/100"), Some("{}".to_string()));
+    let cache = FieldValuesCache::new(Duration::from_secs(0));
+    cache.insert("a/roads/class/10/100".to_string(), "{}".to_string());
+    assert_eq!(cache.get("a/roads/class/10/100"), None);
+}
+
+#[test]
+fn test_metadata_cache() {
+    let cache = MetadataCache::new(2);
+    assert!(cache.get("a", 0).is_none());
+    let json = cache.insert("a".to_string(), 0, "{}".to_string());
+    assert_eq!(json.body, "{}");
+    assert!(json.matches(&json.etag));
+    assert!(json.matches(&format!("\"x\", W/{}", json.etag)));
+    assert!(json.matches("*"));
+    assert!(!json.matches("\"x\""));
+    assert_eq!(cache.get("a", 0).unwrap().etag, json.etag);
+    // Least recently used entry is evicted
+    cache.insert("b".to_string(), 0, "[]".to_string());
+    cache.get("a", 0);
+    cache.insert("c".to_string(), 0, "[]".to_string());
+    assert!(cache.get("a", 0).is_some());
+    assert!(cache.get("b", 0).is_none());
+    assert!(cache.get("c", 0).is_some());
+    // Entries of other generations are invalid
+    assert!(cache.get("a", 1).is_none());
+    cache.insert("b".to_string(), 1, "[]".to_string());
+    assert!(cache.get("c", 1).is_none());
+    // Disabled
+    let cache = MetadataCache::new(0);
+    cache.insert("a".to_string(), 0, "{}".to_string());
+    assert!(cache.get("a", 0).is_none());
+}
+
+#[actix_rt::test]
+async fn test_metadata_etag() {
+    use actix_web::http::header;
+
+    let config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    let service = test_service();
+    let cache = MetadataCache::new(10);
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(service.clone())
+            .data(cache.clone())
+            .configure(tileset_routes),
+    )
+    .await;
+    let req = test::TestRequest::get().uri("/a.style.json").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let etag = resp.headers().get(header::ETAG).unwrap().clone();
+    let body = test::read_body(resp).await;
+    let key = MetadataCache::key("style.json", "a", "http://localhost:8080");
+    assert_eq!(cache.get(&key, 0).unwrap().body, body);
+
+    let req = test::TestRequest::get()
+        .uri("/a.style.json")
+        .header(header::IF_NONE_MATCH, etag.clone())
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(resp.headers().get(header::ETAG), Some(&etag));
+
+    // Other base URL
+    let req = test::TestRequest::get()
+        .uri("/a.style.json")
+        .header(header::HOST, "example.com")
+        .header(header::IF_NONE_MATCH, etag.clone())
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_ne!(resp.headers().get(header::ETAG), Some(&etag));
+
+    // Regenerated after layer state changes
+    service.disabled_layers.set("a", "roads", true);
+    assert!(cache.get(&key, 1).is_none());
+    let req = test::TestRequest::get()
+        .uri("/a.style.json")
+        .header(header::IF_NONE_MATCH, etag.clone())
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+    assert!(cache.get(&key, 1).is_some());
+}
+
+#[actix_rt::test]
+async fn test_admin_jobs() {
+    use crate::jobs::JobQueue;
+    use crate::server::admin_routes;
+
+    let mut config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    config.webserver.admin_token = Some("secret".to_string());
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(test_service())
+            .data(JobQueue::new())
+            .configure(admin_routes),
+    )
+    .await;
+    let request = |req: test::TestRequest, token: &str| {
+        req.header("authorization", format!("Bearer {}", token))
+            .to_request()
+    };
+    let submit = |body: &str| {
+        test::TestRequest::post()
+            .uri("/admin/jobs")
+            .header("content-type", "application/json")
+            .set_payload(body.to_string())
+    };
+
+    let resp = test::call_service(&mut app, submit(r#"{"tileset": "a"}"#).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = test::call_service(&mut app, request(submit(r#"{"tileset": "a"}"#), "wrong")).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = test::call_service(&mut app, request(submit(r#"{"tileset": "x"}"#), "secret")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = test::call_service(
+        &mut app,
+        request(submit(r#"{"extent": [10, 0, 0, 10]}"#), "secret"),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let body =
+        r#"{"tileset": "a", "minzoom": 10, "maxzoom": 14, "extent": [7.0, 46.0, 8.0, 47.0]}"#;
+    let resp = test::call_service(&mut app, request(submit(body), "secret")).await;
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    let job: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    assert_eq!(job["id"], 1);
+    assert_eq!(job["state"], "queued");
+    assert_eq!(job["params"]["overwrite"], true);
+
+    let resp = test::call_service(
+        &mut app,
+        request(test::TestRequest::get().uri("/admin/jobs/1"), "secret"),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = test::call_service(
+        &mut app,
+        request(test::TestRequest::delete().uri("/admin/jobs/1"), "secret"),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let job: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    assert_eq!(job["state"], "cancelled");
+    let resp = test::call_service(
+        &mut app,
+        request(test::TestRequest::delete().uri("/admin/jobs/1"), "secret"),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let resp = test::call_service(
+        &mut app,
+        request(test::TestRequest::get().uri("/admin/jobs/2"), "secret"),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn test_read_only() {
+    use crate::jobs::JobQueue;
+    use crate::server::admin_routes;
+
+    let mut config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    config.webserver.admin_token = Some("secret".to_string());
+    config.webserver.read_only = true;
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(test_service())
+            .data(JobQueue::new())
+            .configure(admin_routes),
+    )
+    .await;
+    let req = test::TestRequest::post()
+        .uri("/admin/jobs")
+        .header("authorization", "Bearer secret")
+        .header("content-type", "application/json")
+        .set_payload(r#"{"tileset": "a"}"#)
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let req = test::TestRequest::delete()
+        .uri("/admin/jobs/1")
+        .header("authorization", "Bearer secret")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_rt::test]
+async fn test_debug_requests() {
+    use crate::server::debug_requests;
+    use actix_web::web;
+
+    let service = test_service();
+    let _request = service.requests.start("a/1/0/0.pbf", "a", 1, 0, 0);
+    let mut app = test::init_service(
+        App::new()
+            .data(service)
+            .service(web::resource("/debug/requests").route(web::get().to(debug_requests))),
+    )
+    .await;
+    let req = test::TestRequest::get().uri("/debug/requests").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    assert_eq!(json["in_flight"], 1);
+    assert_eq!(json["tilesets"]["a"]["in_flight"], 1);
+    assert_eq!(json["requests"][0]["path"], "a/1/0/0.pbf");
+    assert_eq!(json["requests"][0]["state"], "reading-cache");
+    assert_eq!(json["replicas"], serde_json::json!({}));
+}
+
+#[test]
+fn test_static_files() {
+    use crate::static_files::StaticFiles;
+
+    let mut sources = vec![StaticFiles::from_dir("src/static").unwrap()];
+    #[cfg(feature = "webviewer")]
+    sources.push(StaticFiles::embedded());
+    for static_files in sources {
+        let (data, media_type) = static_files.content(None, "".to_string()).unwrap();
+        assert!(data.len() > 0);
+        assert_eq!(media_type, "text/html");
+        assert!(static_files
+            .content(None, "fonts/Roboto-Medium.ttf".to_string())
+            .is_some());
+        assert!(static_files
+            .content(None, "../build.rs".to_string())
+            .is_none());
+        assert_eq!(
+            static_files.fontstacks(),
+            vec!["Roboto Medium".to_string(), "Roboto Regular".to_string()]
+        );
+        assert!(static_files.font("Roboto Regular", "0-255").is_some());
+        assert!(static_files.font("Roboto Regular", "0-1").is_none());
+        assert!(static_files.font("..", "index").is_none());
+    }
+
+    let err = StaticFiles::from_dir("src/static/img").err().unwrap();
+    assert!(err.contains("missing: favicon.ico, index.html"), "{}", err);
+
+    let static_files = StaticFiles::Disabled;
+    assert!(static_files.content(None, "".to_string()).is_none());
+    assert!(static_files.fontstacks().is_empty());
+    assert!(static_files.font("Roboto Regular", "0-255").is_none());
+}
+
+#[test]
+fn test_client_limit() {
+    use crate::client_limit::ClientLimit;
+    use std::net::IpAddr;
+
+    let limit = ClientLimit::new(2, &["10.0.0.1".to_string()]).unwrap();
+    let client: IpAddr = "192.168.1.10".parse().unwrap();
+    let slot1 = limit.acquire(Some(client));
+    let slot2 = limit.acquire(Some(client));
+    assert!(slot1.is_some() && slot2.is_some());
+    assert!(limit.acquire(Some(client)).is_none());
+    assert!(limit
+        .acquire(Some("192.168.1.11".parse().unwrap()))
+        .is_some());
+    drop(slot1);
+    assert_eq!(limit.active(&client), 1);
+    assert!(limit.acquire(Some(client)).is_some());
+
+    // Trusted proxies are not limited
+    let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+    let slots: Vec<_> = (0..5).map(|_| limit.acquire(Some(proxy))).collect();
+    assert!(slots.iter().all(|slot| slot.is_some()));
+    assert_eq!(limit.active(&proxy), 0);
+
+    assert!(ClientLimit::new(2, &["proxy".to_string()]).is_err());
+}
+
+#[test]
+fn test_referer_patterns() {
+    use crate::referer_check::RefererCheck;
+
+    let patterns = vec![
+        "https://*.example.com/*".to_string(),
+        "http://localhost:*".to_string(),
+        "https://maps.example.org/app/*".to_string(),
+    ];
+    let check = RefererCheck::new(&patterns, false).unwrap();
+    let allowed = |referer| check.allows(Some(referer));
+    assert!(allowed("https://www.example.com/map.html"));
+    assert!(allowed("https://WWW.Example.com/"));
+    assert!(allowed("https://www.example.com"));
+    assert!(allowed("https://www.example.com?embed=1"));
+    assert!(!allowed("https://example.com/"));
+    assert!(!allowed("http://www.example.com/"));
+    // Ports must match
+    assert!(!allowed("https://www.example.com:8443/"));
+    assert!(allowed("http://localhost:8080/index.html"));
+    assert!(!allowed("http://localhost/"));
+    // Trailing slashes are optional
+    assert!(allowed("https://maps.example.org/app"));
+    assert!(allowed("https://maps.example.org/app/"));
+    assert!(allowed("https://maps.example.org/app/view/1"));
+    assert!(!allowed("https://maps.example.org/apps"));
+    // Host can't be spoofed with path, query or user info
+    assert!(!allowed("https://evil.com/.example.com/"));
+    assert!(!allowed("https://evil.com?.example.com/"));
+    assert!(!allowed("https://www.example.com@evil.com/"));
+    assert!(!allowed("https://www.example.com.evil.com/"));
+    assert!(!allowed("example.com"));
+
+    assert!(!check.allows(None));
+    assert!(RefererCheck::new(&patterns, true).unwrap().allows(None));
+    assert!(RefererCheck::new(&["*.example.com".to_string()], true).is_err());
+    assert!(RefererCheck::new(&["https://".to_string()], true).is_err());
+}
+
+#[actix_rt::test]
+async fn test_referer_check() {
+    use crate::referer_check::RefererCheck;
+
+    let config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    let check = RefererCheck::new(&["https://*.example.com/*".to_string()], false).unwrap();
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(test_service())
+            .data(MetadataCache::new(10))
+            .app_data(check)
+            .configure(tileset_routes),
+    )
+    .await;
+    let get = |uri: &str, referer: Option<&str>| {
+        let req = test::TestRequest::get().uri(uri);
+        match referer {
+            Some(referer) => req.header("referer", referer).to_request(),
+            None => req.to_request(),
+        }
+    };
+    let resp = test::call_service(
+        &mut app,
+        get("/a/0/0/0.pbf", Some("https://www.example.com/")),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let resp = test::call_service(&mut app, get("/a/0/0/0.pbf", Some("https://other.org/"))).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = test::call_service(&mut app, get("/a/0/0/0.pbf", None)).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    // Metadata is not restricted
+    let resp = test::call_service(&mut app, get("/a.json", Some("https://other.org/"))).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+fn user_agent_check(toml: &str) -> crate::user_agent_check::UserAgentCheck {
+    use crate::core::config::UserAgentCfg;
+    use crate::user_agent_check::UserAgentCheck;
+
+    let config: UserAgentCfg = parse_config(toml.to_string(), "").unwrap();
+    UserAgentCheck::new(&config).unwrap()
+}
+
+#[test]
+fn test_user_agent_patterns() {
+    let check = user_agent_check(r#"deny = ["(?i)bot", "^python-requests/"]"#);
+    assert!(check.allows(Some("Mozilla/5.0 (X11; Linux x86_64)")));
+    assert!(!check.allows(Some("Googlebot/2.1")));
+    assert!(!check.allows(Some("python-requests/2.22.0")));
+    assert!(check.allows(None));
+    assert!(check.allows(Some("")));
+
+    let check = user_agent_check(
+        r#"
+        allow = ["^Mozilla/", "^QGIS/"]
+        deny = ["HeadlessChrome"]
+        require = true
+        "#,
+    );
+    assert!(check.allows(Some("QGIS/31000")));
+    assert!(!check.allows(Some("curl/7.68.0")));
+    // Denied patterns win
+    assert!(!check.allows(Some("Mozilla/5.0 HeadlessChrome/80.0")));
+    assert!(!check.allows(None));
+    assert!(!check.allows(Some(" ")));
+
+    let config = parse_config(r#"deny = ["(bot"]"#.to_string(), "").unwrap();
+    assert!(crate::user_agent_check::UserAgentCheck::new(&config)
+        .unwrap_err()
+        .starts_with("Invalid user_agent.deny pattern '(bot'"));
+}
+
+#[actix_rt::test]
+async fn test_user_agent_check() {
+    let config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    let check = user_agent_check("deny = [\"(?i)bot\"]\nrequire = true");
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(test_service())
+            .data(MetadataCache::new(10))
+            .app_data(check)
+            .configure(tileset_routes),
+    )
+    .await;
+    let get = |uri: &str, user_agent: Option<&str>| {
+        let req = test::TestRequest::get().uri(uri);
+        match user_agent {
+            Some(user_agent) => req.header("user-agent", user_agent).to_request(),
+            None => req.to_request(),
+        }
+    };
+    let resp = test::call_service(&mut app, get("/a/0/0/0.pbf", Some("Mozilla/5.0"))).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let resp = test::call_service(&mut app, get("/a/0/0/0.pbf", Some("SomeBot/1.0"))).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = test::call_service(&mut app, get("/a/0/0/0.pbf", None)).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    // Metadata is not restricted
+    let resp = test::call_service(&mut app, get("/a.json", None)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn test_content_length() {
+    use crate::cache::{Cache, Filecache};
+    use crate::server::font_routes;
+    use crate::static_files::StaticFiles;
+    use actix_web::dev::{BodySize, MessageBody};
+    use actix_web::http::header;
+    use actix_web::middleware::Compress;
+    use std::env;
+    use std::fs;
+    use t_rex_core::mvt::tile::Tile;
+
+    let mut dir = env::temp_dir();
+    dir.push("t_rex_test_content_length");
+    let _ = fs::remove_dir_all(&dir);
+    let cache = Filecache {
+        basepath: dir.join("tiles").to_str().unwrap().to_string(),
+        baseurl: None,
+        extension: None,
+    };
+    let tiledata = b"tile data".repeat(100);
+    cache
+        .write("a/0/0/0.pbf", &Tile::compress(&tiledata))
+        .unwrap();
+    let fontdir = dir.join("assets/fonts/Roboto Regular");
+    fs::create_dir_all(&fontdir).unwrap();
+    fs::write(fontdir.join("0-255.pbf"), Tile::compress(b"glyphs")).unwrap();
+
+    let mut service = test_service();
+    service.cache = Tilecache::Filecache(cache);
+    let config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    let mut app = test::init_service(
+        App::new()
+            .wrap(Compress::default())
+            .data(config)
+            .data(service)
+            .data(StaticFiles::Directory(dir.join("assets")))
+            .configure(font_routes)
+            .configure(tileset_routes),
+    )
+    .await;
+
+    for (uri, accept_encoding, encoding) in &[
+        ("/a/0/0/0.pbf", "gzip", Some("gzip")),
+        ("/a/0/0/0.pbf", "deflate", None),
+        ("/a/0/0/0.pbf", "identity", None),
+        ("/fonts/Roboto%20Regular/0-255.pbf", "gzip", Some("gzip")),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .header(header::ACCEPT_ENCODING, *accept_encoding)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers()
+                .get(header::CONTENT_ENCODING)
+                .map(|enc| enc.to_str().unwrap()),
+            *encoding
+        );
+        // Content-Length is written from the body size
+        let size = resp.response().body().size();
+        let body = test::read_body(resp).await;
+        assert_eq!(
+            size,
+            BodySize::Sized(body.len()),
+            "{} {}",
+            uri,
+            accept_encoding
+        );
+        if encoding.is_none() {
+            assert_eq!(&body[..], &tiledata[..]);
+        }
+    }
+}
+
+#[actix_rt::test]
+async fn test_always_gzip() {
+    use crate::cache::{Cache, Filecache};
+    use actix_web::http::header;
+    use actix_web::middleware::Compress;
+    use std::env;
+    use std::fs;
+    use t_rex_core::mvt::tile::Tile;
+
+    let mut dir = env::temp_dir();
+    dir.push("t_rex_test_always_gzip");
+    let _ = fs::remove_dir_all(&dir);
+    let cache = Filecache {
+        basepath: dir.to_str().unwrap().to_string(),
+        baseurl: None,
+        extension: None,
+    };
+    let tiledata = b"tile data".repeat(100);
+    for path in &["a/0/0/0.pbf", "gz/0/0/0.pbf"] {
+        cache.write(path, &Tile::compress(&tiledata)).unwrap();
+    }
+
+    let mut service = test_service();
+    service.cache = Tilecache::Filecache(cache);
+    let mut tileset = empty_tileset("gz");
+    tileset.always_gzip = true;
+    service.tilesets.push(tileset);
+    let config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    let mut app = test::init_service(
+        App::new()
+            .wrap(Compress::default())
+            .data(config)
+            .data(service)
+            .configure(tileset_routes),
+    )
+    .await;
+
+    let vary = Some("Accept-Encoding");
+    for (uri, accept_encoding, encoding, vary) in &[
+        ("/a/0/0/0.pbf", Some("gzip"), Some("gzip"), vary),
+        ("/a/0/0/0.pbf", None, None, vary),
+        ("/gz/0/0/0.pbf", Some("gzip"), Some("gzip"), None),
+        ("/gz/0/0/0.pbf", None, Some("gzip"), None),
+        ("/gz/0/0/0.pbf", Some("identity"), Some("gzip"), None),
+    ] {
+        let mut req = test::TestRequest::get().uri(uri);
+        if let Some(accept_encoding) = accept_encoding {
+            req = req.header(header::ACCEPT_ENCODING, *accept_encoding);
+        }
+        let resp = test::call_service(&mut app, req.to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let header = |name| resp.headers().get(name).map(|val| val.to_str().unwrap());
+        assert_eq!(
+            header(header::CONTENT_ENCODING),
+            *encoding,
+            "{} {:?}",
+            uri,
+            accept_encoding
+        );
+        assert_eq!(header(header::VARY), *vary);
+        let body = test::read_body(resp).await;
+        if encoding.is_some() {
+            assert_eq!(&body[..], &Tile::compress(&tiledata)[..]);
+        } else {
+            assert_eq!(&body[..], &tiledata[..]);
+        }
+    }
+}
+
+#[actix_rt::test]
+async fn test_uncompressed_cached_tiles() {
+    use crate::cache::{Cache, Filecache};
+    use actix_web::http::header;
+    use std::env;
+    use std::fs;
+    use t_rex_core::mvt::tile::Tile;
+
+    let mut dir = env::temp_dir();
+    dir.push("t_rex_test_uncompressed_cached_tiles");
+    let _ = fs::remove_dir_all(&dir);
+    let cache = Filecache {
+        basepath: dir.to_str().unwrap().to_string(),
+        baseurl: None,
+        extension: None,
+    };
+    // Cache produced by another tool with gzipped and uncompressed tiles
+    let tiledata = b"tile data".repeat(100);
+    cache
+        .write("a/0/0/0.pbf", &Tile::compress(&tiledata))
+        .unwrap();
+    cache.write("a/1/0/0.pbf", &tiledata).unwrap();
+
+    let mut service = test_service();
+    service.cache = Tilecache::Filecache(cache);
+    let config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(service)
+            .configure(tileset_routes),
+    )
+    .await;
+
+    for (uri, accept_encoding, encoding) in &[
+        ("/a/0/0/0.pbf", Some("gzip"), Some("gzip")),
+        ("/a/0/0/0.pbf", None, None),
+        ("/a/1/0/0.pbf", Some("gzip"), Some("gzip")),
+        ("/a/1/0/0.pbf", None, None),
+    ] {
+        let mut req = test::TestRequest::get().uri(uri);
+        if let Some(accept_encoding) = accept_encoding {
+            req = req.header(header::ACCEPT_ENCODING, *accept_encoding);
+        }
+        let resp = test::call_service(&mut app, req.to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers()
+                .get(header::CONTENT_ENCODING)
+                .map(|enc| enc.to_str().unwrap()),
+            *encoding,
+            "{} {:?}",
+            uri,
+            accept_encoding
+        );
+        let body = test::read_body(resp).await;
+        assert_eq!(Tile::is_gzip(&body), encoding.is_some());
+        assert_eq!(Tile::tile_content(body.to_vec(), false), tiledata);
+    }
+}
+
+#[actix_rt::test]
+async fn test_tileset_static_dir() {
+    use crate::server::static_file_handler;
+    use crate::static_files::{dir_file, StaticFiles};
+    use std::env;
+    use std::fs;
+    use std::str;
+
+    let mut dir = env::temp_dir();
+    dir.push("t_rex_test_tileset_static_dir");
+    let _ = fs::remove_dir_all(&dir);
+    let static_dir = dir.join("a");
+    fs::create_dir_all(static_dir.join("data")).unwrap();
+    fs::write(static_dir.join("legend.svg"), "<svg/>").unwrap();
+    fs::write(static_dir.join("data/info.json"), r#"{"a":1}"#).unwrap();
+    fs::write(static_dir.join("viewer.html"), "<html>a</html>").unwrap();
+    fs::write(dir.join("secret.txt"), "secret").unwrap();
+    let static_dir_b = dir.join("b");
+    fs::create_dir_all(&static_dir_b).unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(dir.join("secret.txt"), static_dir_b.join("link.txt")).unwrap();
+
+    let base = static_dir.to_str().unwrap();
+    assert!(dir_file(base, "legend.svg").is_some());
+    assert!(dir_file(base, "data/info.json").is_some());
+    assert!(dir_file(base, "data").is_none());
+    assert!(dir_file(base, "").is_none());
+    assert!(dir_file(base, "missing.svg").is_none());
+    assert!(dir_file(base, "../secret.txt").is_none());
+    assert!(dir_file(base, "data/../../secret.txt").is_none());
+    assert!(dir_file(base, "/etc/passwd").is_none());
+    assert!(dir_file(base, "..\\secret.txt").is_none());
+    assert!(dir_file(static_dir_b.to_str().unwrap(), "link.txt").is_none());
+
+    let mut service = test_service();
+    service.tilesets[0].static_dir = Some(base.to_string());
+    let mut tileset = empty_tileset("b");
+    tileset.static_dir = Some(static_dir_b.to_str().unwrap().to_string());
+    service.tilesets.push(tileset);
+    let config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(service)
+            .data(StaticFiles::from_dir("src/static").unwrap())
+            .configure(tileset_routes)
+            .default_service(web::to(static_file_handler)),
+    )
+    .await;
+
+    let (ok, not_found) = (StatusCode::OK, StatusCode::NOT_FOUND);
+    for (uri, status, content) in &[
+        ("/a/static/legend.svg", ok, Some("<svg/>")),
+        ("/a/static/data/info.json", ok, Some(r#"{"a":1}"#)),
+        ("/a/static/viewer.html", ok, Some("<html>a</html>")),
+        ("/a/", ok, Some("<html>a</html>")),
+        ("/a/static/", not_found, None),
+        ("/a/static/missing.svg", not_found, None),
+        ("/a/static/../secret.txt", not_found, None),
+        ("/a/static/..%2Fsecret.txt", not_found, None),
+        ("/a/static/data/%2E%2E/%2E%2E/secret.txt", not_found, None),
+        ("/a/static/1/2/3.pbf", not_found, None),
+        ("/b/static/link.txt", not_found, None),
+        // Tileset without static_dir
+        ("/a.b.c/static/legend.svg", not_found, None),
+        // Unknown tilesets are not served by the viewer static files
+        ("/unknown/static/index.html", not_found, None),
+        ("/unknown/", not_found, None),
+        ("/index.html", ok, None),
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), *status, "{}", uri);
+        if let Some(content) = content {
+            let body = test::read_body(resp).await;
+            assert_eq!(str::from_utf8(&body).unwrap(), *content, "{}", uri);
+        }
+    }
+
+    // Built-in viewer without viewer.html
+    let req = test::TestRequest::get().uri("/b/").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = test::read_body(resp).await;
+    assert!(str::from_utf8(&body).unwrap().contains("viewer.js"));
+}
+
+#[actix_rt::test]
+async fn test_tileset_aliases() {
+    use crate::cache::{Cache, Filecache};
+    use std::env;
+    use std::fs;
+    use t_rex_core::mvt::tile::Tile;
+
+    let mut dir = env::temp_dir();
+    dir.push("t_rex_test_tileset_aliases");
+    let _ = fs::remove_dir_all(&dir);
+    let cache = Filecache {
+        basepath: dir.to_str().unwrap().to_string(),
+        baseurl: None,
+        extension: None,
+    };
+    cache
+        .write("a/0/0/0.pbf", &Tile::compress(b"tile data"))
+        .unwrap();
+
+    let mut service = test_service();
+    service.cache = Tilecache::Filecache(cache);
+    service.tilesets[0].aliases = vec!["old".to_string(), "Ä".to_string()];
+    let config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(service)
+            .data(MetadataCache::new(10))
+            .configure(tileset_routes),
+    )
+    .await;
+
+    // Tiles of the tileset (and its cache path)
+    for uri in &["/old/0/0/0.pbf", "/%C3%84/0/0/0.pbf"] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{}", uri);
+        let body = test::read_body(resp).await;
+        assert_eq!(&body[..], b"tile data");
+    }
+
+    // Generated URLs with the tileset name
+    let req = test::TestRequest::get().uri("/old.json").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    assert_eq!(json["id"], "a");
+    assert!(json["tiles"][0]
+        .as_str()
+        .unwrap()
+        .ends_with("/a/{z}/{x}/{y}.pbf"));
+
+    let req = test::TestRequest::get().uri("/old.style.json").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    let json: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    assert!(json["sources"]["a"].is_object());
+
+    // Aliases are not listed as available tilesets
+    let req = test::TestRequest::get().uri("/x.json").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    let body = test::read_body(resp).await;
+    assert_eq!(
+        std::str::from_utf8(&body).unwrap(),
+        "Tileset 'x' not found. Available tilesets: a, a.b.c"
+    );
+}
+
+#[actix_rt::test]
+async fn test_tile_head() {
+    use crate::cache::{Cache, Filecache};
+    use actix_web::http::header;
+    use std::env;
+    use std::fs;
+    use t_rex_core::mvt::tile::Tile;
+    use t_rex_core::mvt::vector_tile;
+
+    let mut dir = env::temp_dir();
+    dir.push("t_rex_test_tile_head");
+    let _ = fs::remove_dir_all(&dir);
+    let cache = Filecache {
+        basepath: dir.to_str().unwrap().to_string(),
+        baseurl: None,
+        extension: None,
+    };
+    let mut mvt_layer = vector_tile::Tile_Layer::new();
+    mvt_layer.set_version(2);
+    mvt_layer.set_name("points".to_string());
+    for _ in 0..2 {
+        mvt_layer
+            .mut_features()
+            .push(vector_tile::Tile_Feature::new());
+    }
+    let mut mvt_tile = vector_tile::Tile::new();
+    mvt_tile.mut_layers().push(mvt_layer);
+    cache
+        .write("a/0/0/0.pbf", &Tile::tile_bytevec_gz(&mvt_tile))
+        .unwrap();
+    // Written by another tool, without feature count
+    cache
+        .write(
+            "a/1/0/0.pbf",
+            &Tile::compress(&Tile::tile_bytevec(&mvt_tile)),
+        )
+        .unwrap();
+
+    let mut service = test_service();
+    service.cache = Tilecache::Filecache(cache);
+    let config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(service)
+            .configure(tileset_routes)
+            .configure(ogcapi_routes),
+    )
+    .await;
+
+    for (uri, accept_encoding, status, count) in &[
+        ("/a/0/0/0.pbf", "gzip", StatusCode::OK, Some("2")),
+        ("/a/0/0/0.pbf", "identity", StatusCode::OK, Some("2")),
+        ("/a/1/0/0.pbf", "gzip", StatusCode::OK, None),
+        (
+            "/a.b.c/0/0/0.pbf",
+            "gzip",
+            StatusCode::NO_CONTENT,
+            Some("0"),
+        ),
+        (
+            "/collections/a/tiles/WebMercatorQuad/0/0/0",
+            "gzip",
+            StatusCode::OK,
+            Some("2"),
+        ),
+        ("/x/0/0/0.pbf", "gzip", StatusCode::NOT_FOUND, None),
+    ] {
+        let req = test::TestRequest::with_uri(uri)
+            .method(actix_web::http::Method::HEAD)
+            .header(header::ACCEPT_ENCODING, *accept_encoding)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), *status, "{}", uri);
+        let feature_count = resp
+            .headers()
+            .get("X-Feature-Count")
+            .map(|val| val.to_str().unwrap());
+        assert_eq!(feature_count, *count, "{}", uri);
+    }
+
+    // No feature count in GET responses
+    let req = test::TestRequest::get().uri("/a/0/0/0.pbf").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().get("X-Feature-Count").is_none());
+}
+
+#[actix_rt::test]
+async fn test_tile_last_modified() {
+    use crate::cache::{Cache, SqliteCache};
+    use actix_web::http::header;
+    use actix_web::http::header::HttpDate;
+    use std::env;
+    use std::fs;
+    use std::time::{Duration, SystemTime};
+    use t_rex_core::mvt::tile::Tile;
+
+    let mut dir = env::temp_dir();
+    dir.push("t_rex_test_tile_last_modified");
+    let _ = fs::remove_dir_all(&dir);
+    let cache = SqliteCache::new(dir.to_str().unwrap(), None, None, None);
+    let tiledata = Tile::compress(&b"tile data".repeat(100));
+    cache.write("a/0/0/0.pbf", &tiledata).unwrap();
+    cache.write("a/2/1/1.pbf", &tiledata).unwrap();
+    cache.write("a/2/1/2.pbf", &tiledata).unwrap();
+    let modified = cache.modified("a/0/0/0.pbf").unwrap();
+
+    let mut service = test_service();
+    service.cache = Tilecache::SqliteCache(cache);
+    let config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    let mut app = test::init_service(
+        App::new()
+            .data(config)
+            .data(service)
+            .configure(tileset_routes),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/a/0/0/0.pbf").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let last_modified = resp.headers().get(header::LAST_MODIFIED).unwrap();
+    assert_eq!(
+        last_modified.to_str().unwrap(),
+        HttpDate::from(modified).to_string()
+    );
+
+    let if_modified_since = |time: SystemTime| {
+        test::TestRequest::get()
+            .uri("/a/0/0/0.pbf")
+            .header(header::IF_MODIFIED_SINCE, HttpDate::from(time).to_string())
+            .to_request()
+    };
+    let resp = test::call_service(&mut app, if_modified_since(modified)).await;
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+    assert!(resp.headers().get(header::LAST_MODIFIED).is_some());
+    assert_eq!(test::read_body(resp).await.len(), 0);
+    let since = modified - Duration::from_secs(60);
+    let resp = test::call_service(&mut app, if_modified_since(since)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Aggregated tile times
+    let req = test::TestRequest::get()
+        .uri("/a/freshness.json")
+        .to_request();
+    let freshness: serde_json::Value = test::read_response_json(&mut app, req).await;
+    assert_eq!(freshness["tileset"], "a");
+    let zoom_levels = freshness["zoom_levels"].as_array().unwrap();
+    assert_eq!(zoom_levels.len(), 2);
+    assert_eq!(zoom_levels[0]["zoom"], 0);
+    assert_eq!(zoom_levels[1]["zoom"], 2);
+    assert_eq!(zoom_levels[1]["tiles"], 2);
+    assert!(freshness["oldest"].as_u64().unwrap() <= freshness["newest"].as_u64().unwrap());
+
+    // Tilesets without cached tiles and caches without creation times
+    let req = test::TestRequest::get()
+        .uri("/a.b.c/freshness.json")
+        .to_request();
+    let freshness: serde_json::Value = test::read_response_json(&mut app, req).await;
+    assert_eq!(freshness["zoom_levels"], serde_json::json!([]));
+    assert_eq!(freshness["oldest"], serde_json::Value::Null);
+    let json = get_json("/a/freshness.json").await;
+    assert_eq!(json["zoom_levels"], serde_json::Value::Null);
+    let req = test::TestRequest::get()
+        .uri("/x/freshness.json")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn test_version_json() {
+    use crate::build_info::BuildInfo;
+    use crate::datasource::CsvDatasource;
+    use crate::datasources::Datasource;
+    use crate::server::version_json;
+
+    let mut dependencies = std::collections::BTreeMap::new();
+    dependencies.insert("GDAL".to_string(), "3.1.0".to_string());
+    let build_info = BuildInfo {
+        version: "0.10.2".to_string(),
+        git_commit: Some("1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b".to_string()),
+        build_date: Some("2020-06-01T12:00:00Z".to_string()),
+        features: vec!["webviewer".to_string(), "with-gdal".to_string()],
+        dependencies,
+    };
+    assert_eq!(
+        build_info.summary(),
+        "t-rex 0.10.2 (commit 1a2b3c4d5e6f, built 2020-06-01T12:00:00Z, features: webviewer, with-gdal, GDAL 3.1.0)"
+    );
+    assert!(build_info
+        .details()
+        .contains("\nfeatures: webviewer, with-gdal\nGDAL: 3.1.0"));
+
+    let mut service = test_service();
+    service.datasources.add(
+        &"places".to_string(),
+        Datasource::Csv(CsvDatasource::new("../data/places.csv")),
+    );
+    let mut app = test::init_service(
+        App::new()
+            .data(build_info)
+            .data(service)
+            .service(web::resource("/version.json").route(web::get().to(version_json))),
+    )
+    .await;
+    let req = test::TestRequest::get().uri("/version.json").to_request();
+    let version: serde_json::Value = test::read_response_json(&mut app, req).await;
+    assert_eq!(version["version"], "0.10.2");
+    assert_eq!(
+        version["git_commit"],
+        "1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b"
+    );
+    assert_eq!(version["build_date"], "2020-06-01T12:00:00Z");
+    assert_eq!(version["features"][1], "with-gdal");
+    assert_eq!(version["dependencies"]["GDAL"], "3.1.0");
+    assert_eq!(version["datasources"]["places"]["type"], "csv");
+    assert!(version["datasources"]["places"]["version"].is_null());
+}
+
+#[actix_rt::test]
+async fn test_disable_compression() {
+    use actix_web::http::header;
+    use actix_web::middleware::Compress;
+    use t_rex_core::mvt::tile::Tile;
+
+    let config: ApplicationCfg = parse_config(DEFAULT_CONFIG.to_string(), "").unwrap();
+    let app = || {
+        App::new()
+            .data(test_service())
+            .data(MetadataCache::new(10))
+            .data(config.clone())
+            .configure(tileset_routes)
+    };
+    let request = || {
+        test::TestRequest::get()
+            .uri("/a.json")
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .to_request()
+    };
+
+    // `webserver.compress = true` adds the `Compress` middleware
+    let mut app_compress = test::init_service(app().wrap(Compress::default())).await;
+    let resp = test::call_service(&mut app_compress, request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get(header::CONTENT_ENCODING).unwrap(),
+        "gzip"
+    );
+    let body = test::read_body(resp).await;
+    assert!(Tile::is_gzip(&body));
+
+    let mut app = test::init_service(app()).await;
+    let resp = test::call_service(&mut app, request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().get(header::CONTENT_ENCODING).is_none());
+    let body = test::read_body(resp).await;
+    assert!(serde_json::from_slice::<serde_json::Value>(&body).is_ok());
+}
+
+#[test]
+fn test_listeners_reused() {
+    use std::net::TcpStream;
+
+    let listeners = Listeners::default();
+    let sockets = listeners.sockets("127.0.0.1:0", 16).unwrap();
+    let addr = sockets[0].local_addr().unwrap();
+    // Sockets of a stopped server are closed, the bound address stays open
+    drop(sockets);
+    assert!(TcpStream::connect(addr).is_ok());
+    let sockets = listeners.sockets("127.0.0.1:0", 16).unwrap();
+    assert_eq!(sockets[0].local_addr().unwrap(), addr);
+    // Connection accepted by the restarted server
+    assert!(sockets[0].accept().is_ok());
+    drop(sockets);
+
+    // Changed address is bound again
+    let sockets = listeners.sockets("0.0.0.0:0", 16).unwrap();
+    assert_ne!(sockets[0].local_addr().unwrap().port(), addr.port());
+    assert!(TcpStream::connect(addr).is_err());
+}
@@ -43,7 +43,7 @@ var e;!function(){"use strict";var r={}.hasOwnProperty;function n(){for(var e=[]
 },{"react":"1n8/","./TileListEntry.css":"E+qH","classnames":"9qb7"}],"SXJ9":[function(require,module,exports) {
 "use strict";Object.defineProperty(exports,"__esModule",{value:!0}),exports.default=void 0;var e=s(require("react"));require("./TileList.css");var t=r(require("./TileListEntry"));function r(e){return e&&e.__esModule?e:{default:e}}function s(e){if(e&&e.__esModule)return e;var t={};if(null!=e)for(var r in e)if(Object.prototype.hasOwnProperty.call(e,r)){var s=Object.defineProperty&&Object.getOwnPropertyDescriptor?Object.getOwnPropertyDescriptor(e,r):{};s.get||s.set?Object.defineProperty(t,r,s):t[r]=e[r]}return t.default=e,t}class i extends e.Component{constructor(e){super(e)}render(){var t=this.props.activeTileset?"Tile sets:":"Select tile set:";return e.default.createElement("div",{className:"TileList"},e.default.createElement("div",{className:"TileListTitle"},t),this.renderTileListEntries())}renderTileListEntries(){return this.props.tilesets.map(r=>e.default.createElement(t.default,{name:r.name,layers:r.layers,activeTileset:this.props.activeTileset,setTileset:this.props.setTileset}))}}var l=i;exports.default=l;
 },{"react":"1n8/","./TileList.css":"E+qH","./TileListEntry":"xwi7"}],"rvTa":[function(require,module,exports) {
-"use strict";Object.defineProperty(exports,"__esModule",{value:!0}),exports.default=void 0;var e=t(require("react"));function t(e){if(e&&e.__esModule)return e;var t={};if(null!=e)for(var l in e)if(Object.prototype.hasOwnProperty.call(e,l)){var n=Object.defineProperty&&Object.getOwnPropertyDescriptor?Object.getOwnPropertyDescriptor(e,l):{};n.get||n.set?Object.defineProperty(t,l,n):t[l]=e[l]}return t.default=e,t}require("./InfoWidget.css");class l extends e.Component{constructor(e){super(e)}render(){return e.default.createElement("pre",{className:"CodeWidget"},this.props.code)}}class n extends e.Component{constructor(e){super(e),this.state={viewer:"mbgl"}}render(){var t=this.props.activeTileset,n=window.location.protocol+"//"+window.location.host+"/",r=n+t+"/{z}/{x}/{y}.pbf",a=n+t+".style.json",s=n+t+".json",i="/maputnik.html?style="+a,o=this.props.tilesets.filter(function(e){return e.name===t}),p=o.length>0?o[0].layers:[],c="mbgl"===this.state.viewer?this.snippet_mbgl(a):this.snippet_openlayers(r,a);return e.default.createElement("div",{className:"InfoWidget"},e.default.createElement("h2",null,"Tileset: ",t),e.default.createElement("p",null,"Layers:",e.default.createElement("ul",null,p.map(t=>e.default.createElement("li",null,t.name," (",t.geometry_type,")")))),e.default.createElement("p",null,"Endpoints:",e.default.createElement("ul",null,e.default.createElement("li",null,"Tiles: ",e.default.createElement("code",null,r)),e.default.createElement("li",null,"Style JSON: ",e.default.createElement("a",{href:a},a)),e.default.createElement("li",null,"TileJSON: ",e.default.createElement("a",{href:s},s)),e.default.createElement("li",null,"Style map with ",e.default.createElement("a",{href:i,target:"maputnik"},"Maputnik")))),e.default.createElement("p",null,"Snippets:",e.default.createElement("ul",null,e.default.createElement("li",null,e.default.createElement("a",{href:"#",onClick:this.setViewer.bind(this,"mbgl")},"MapBox GL JS")),e.default.createElement("li",null,e.default.createElement("a",{href:"#",onClick:this.setViewer.bind(this,"openlayers")},"OpenLayers"))),e.default.createElement(l,{code:c})))}setViewer(e){this.setState({viewer:e})}snippet_mbgl(e){var t=this.props.center;return`<!DOCTYPE html>\n<html>\n  <head>\n    <meta charset='utf-8' />\n    <title></title>\n    <meta name='viewport' content='initial-scale=1,maximum-scale=1,user-scalable=no' />\n    <script src='https://api.tiles.mapbox.com/mapbox-gl-js/v0.51.0/mapbox-gl.js'><\/script>\n    <link href='https://api.tiles.mapbox.com/mapbox-gl-js/v0.51.0/mapbox-gl.css' rel='stylesheet' />\n    <style>\n      body { margin:0; padding:0; }\n      #map { position:absolute; top:0; bottom:0; width:100%; }\n    </style>\n  </head>\n  <body>\n    <div id='map'></div>\n    <script>\n      var map = new mapboxgl.Map({\n          container: 'map',\n          style: '${e}',\n          center: [${t[0]},${t[1]}],\n          zoom: ${this.props.zoom}\n      });\n    <\/script>\n  </body>\n</html>\n`}snippet_openlayers(e,t){var l=this.props.center;return`<!DOCTYPE html>\n<html>\n  <head>\n    <meta charset='utf-8' />\n    <title></title>\n    <link rel="stylesheet" href="https://cdn.rawgit.com/openlayers/openlayers.github.io/master/en/v5.3.0/css/ol.css" type="text/css">\n    <script src="https://cdn.polyfill.io/v2/polyfill.min.js?features=requestAnimationFrame,Element.prototype.classList,URL"><\/script>\n    <script src="https://cdn.rawgit.com/openlayers/openlayers.github.io/master/en/v5.3.0/build/ol.js"><\/script>\n  </head>\n  <body>\n    <div id="map" class="map"></div>\n    <script>\n      var map = new ol.Map({\n        layers: [\n          new ol.layer.VectorTile({\n            source: new ol.source.VectorTile({\n              format: new ol.format.MVT(),\n              tileGrid: ol.tilegrid.createXYZ({maxZoom: 22}),\n              tilePixelRatio: 16,\n              url: '${e}'\n            })\n          })\n        ],\n        target: 'map',\n        view: new ol.View({\n          center: [${l[0]},${l[1]}],\n          zoom: ${this.props.zoom}\n        })\n      });\n    <\/script>\n  </body>\n</html>\n`}}var r=n;exports.default=r;
+"use strict";Object.defineProperty(exports,"__esModule",{value:!0}),exports.default=void 0;var e=t(require("react"));function t(e){if(e&&e.__esModule)return e;var t={};if(null!=e)for(var l in e)if(Object.prototype.hasOwnProperty.call(e,l)){var n=Object.defineProperty&&Object.getOwnPropertyDescriptor?Object.getOwnPropertyDescriptor(e,l):{};n.get||n.set?Object.defineProperty(t,l,n):t[l]=e[l]}return t.default=e,t}require("./InfoWidget.css");class l extends e.Component{constructor(e){super(e)}render(){return e.default.createElement("pre",{className:"CodeWidget"},this.props.code)}}class n extends e.Component{constructor(e){super(e),this.state={viewer:"mbgl"}}render(){var t=this.props.activeTileset,n=window.location.protocol+"//"+window.location.host+"/",r=n+t+"/{z}/{x}/{y}.pbf",a=n+t+".style.json",s=n+t+".json",i="/maputnik.html?style="+a,o=this.props.tilesets.filter(function(e){return e.name===t}),p=o.length>0?o[0].layers:[],c="mbgl"===this.state.viewer?this.snippet_mbgl(a):this.snippet_openlayers(r,a);return e.default.createElement("div",{className:"InfoWidget"},e.default.createElement("h2",null,"Tileset: ",t),e.default.createElement("p",null,"Layers:",e.default.createElement("ul",null,p.map(t=>e.default.createElement("li",null,t.name," (",t.geometry_type,")")))),e.default.createElement("p",null,"Endpoints:",e.default.createElement("ul",null,e.default.createElement("li",null,"Tiles: ",e.default.createElement("code",null,r)),e.default.createElement("li",null,"Style JSON: ",e.default.createElement("a",{href:a},a)),e.default.createElement("li",null,"TileJSON: ",e.default.createElement("a",{href:s},s)),e.default.createElement("li",null,"Style map with ",e.default.createElement("a",{href:i,target:"maputnik"},"Maputnik")),e.default.createElement("li",null,"Tile sizes: ",e.default.createElement("a",{href:"/drilldown.html?tileset="+t},"Drilldown")))),e.default.createElement("p",null,"Snippets:",e.default.createElement("ul",null,e.default.createElement("li",null,e.default.createElement("a",{href:"#",onClick:this.setViewer.bind(this,"mbgl")},"MapBox GL JS")),e.default.createElement("li",null,e.default.createElement("a",{href:"#",onClick:this.setViewer.bind(this,"openlayers")},"OpenLayers"))),e.default.createElement(l,{code:c})))}setViewer(e){this.setState({viewer:e})}snippet_mbgl(e){var t=this.props.center;return`<!DOCTYPE html>\n<html>\n  <head>\n    <meta charset='utf-8' />\n    <title></title>\n    <meta name='viewport' content='initial-scale=1,maximum-scale=1,user-scalable=no' />\n    <script src='https://api.tiles.mapbox.com/mapbox-gl-js/v0.51.0/mapbox-gl.js'><\/script>\n    <link href='https://api.tiles.mapbox.com/mapbox-gl-js/v0.51.0/mapbox-gl.css' rel='stylesheet' />\n    <style>\n      body { margin:0; padding:0; }\n      #map { position:absolute; top:0; bottom:0; width:100%; }\n    </style>\n  </head>\n  <body>\n    <div id='map'></div>\n    <script>\n      var map = new mapboxgl.Map({\n          container: 'map',\n          style: '${e}',\n          center: [${t[0]},${t[1]}],\n          zoom: ${this.props.zoom}\n      });\n    <\/script>\n  </body>\n</html>\n`}snippet_openlayers(e,t){var l=this.props.center;return`<!DOCTYPE html>\n<html>\n  <head>\n    <meta charset='utf-8' />\n    <title></title>\n    <link rel="stylesheet" href="https://cdn.rawgit.com/openlayers/openlayers.github.io/master/en/v5.3.0/css/ol.css" type="text/css">\n    <script src="https://cdn.polyfill.io/v2/polyfill.min.js?features=requestAnimationFrame,Element.prototype.classList,URL"><\/script>\n    <script src="https://cdn.rawgit.com/openlayers/openlayers.github.io/master/en/v5.3.0/build/ol.js"><\/script>\n  </head>\n  <body>\n    <div id="map" class="map"></div>\n    <script>\n      var map = new ol.Map({\n        layers: [\n          new ol.layer.VectorTile({\n            source: new ol.source.VectorTile({\n              format: new ol.format.MVT(),\n              tileGrid: ol.tilegrid.createXYZ({maxZoom: 22}),\n              tilePixelRatio: 16,\n              url: '${e}'\n            })\n          })\n        ],\n        target: 'map',\n        view: new ol.View({\n          center: [${l[0]},${l[1]}],\n          zoom: ${this.props.zoom}\n        })\n      });\n    <\/script>\n  </body>\n</html>\n`}}var r=n;exports.default=r;
 },{"react":"1n8/","./InfoWidget.css":"E+qH"}],"Ov/a":[function(require,module,exports) {
 var define;
 var global = arguments[3];
@@ -582,7 +582,7 @@ exports.read=function(a,o,t,r,h){var M,p,w=8*h-r-1,f=(1<<w)-1,e=f>>1,i=-7,N=t?h-
 },{"react":"1n8/","ol":"tUV8","ol/layer/VectorTile":"6/g0","ol/layer/Tile":"PqrZ","ol/source/TileDebug":"zFtQ","ol/source/VectorTile":"EYMU","ol/format/MVT":"CAny","ol/tilegrid":"gNrJ","ol/proj":"VAQc","./OpenLayersMapWidget":"S0p3","./InspectorMapWidget.css":"E+qH"}],"dUiY":[function(require,module,exports) {
 "use strict";Object.defineProperty(exports,"__esModule",{value:!0}),exports.default=void 0;var e=s(require("react"));require("./TitleBar.css");var r=t(require("classnames"));function t(e){return e&&e.__esModule?e:{default:e}}function s(e){if(e&&e.__esModule)return e;var r={};if(null!=e)for(var t in e)if(Object.prototype.hasOwnProperty.call(e,t)){var s=Object.defineProperty&&Object.getOwnPropertyDescriptor?Object.getOwnPropertyDescriptor(e,t):{};s.get||s.set?Object.defineProperty(r,t,s):r[t]=e[t]}return r.default=e,r}class a extends e.Component{render(){var t=(0,r.default)({ViewerSelector:!0,ActiveViewerSelector:this.props.activeViewer===this.props.name});return e.default.createElement("span",{className:t,onClick:this.setViewer.bind(this)},this.props.name)}setViewer(){this.props.setViewer(this.props.name)}}class i extends e.Component{constructor(e){super(e),this.state={viewers:[{name:"Inspector",key:"inspector"},{name:"X-Ray",key:"xray"},{name:"OpenLayers",key:"openlayers"},{name:"Mapbox GL",key:"mbgl"},{name:"Info",key:"info"}]}}render(){return e.default.createElement("div",{className:"TitleBar"},"t-rex",this.renderViewerSelectors())}renderViewerSelectors(){return this.state.viewers.map(r=>e.default.createElement(a,{name:r.name,key:r.key,activeViewer:this.props.activeViewer,setViewer:this.props.setViewer}))}}var n=i;exports.default=n;
 },{"react":"1n8/","./TitleBar.css":"E+qH","classnames":"9qb7"}],"lY9v":[function(require,module,exports) {
-"use strict";Object.defineProperty(exports,"__esModule",{value:!0}),exports.default=void 0;var e=u(require("react"));require("./App.css");var t=l(require("./TileList")),s=l(require("./InfoWidget")),i=l(require("./MapWidgets/MapboxGLMapWidget")),r=l(require("./MapWidgets/OpenLayersMapWidget")),a=l(require("./MapWidgets/XRayMapWidget")),n=l(require("./MapWidgets/InspectorMapWidget")),o=l(require("./TitleBar"));function l(e){return e&&e.__esModule?e:{default:e}}function u(e){if(e&&e.__esModule)return e;var t={};if(null!=e)for(var s in e)if(Object.prototype.hasOwnProperty.call(e,s)){var i=Object.defineProperty&&Object.getOwnPropertyDescriptor?Object.getOwnPropertyDescriptor(e,s):{};i.get||i.set?Object.defineProperty(t,s,i):t[s]=e[s]}return t.default=e,t}class d extends e.Component{constructor(e){super(e),this.state={tilesets:[],tileset:null,viewer:"Info",bounds:null,center:[0,0],zoom:2}}componentDidMount(){fetch("/index.json").then(function(e){return e.json()}).then(function(e){this.populateTileList(e)}.bind(this))}render(){return e.default.createElement("div",{className:"App"},e.default.createElement(t.default,{tilesets:this.state.tilesets,activeTileset:this.state.tileset,setTileset:this.setTileset.bind(this)}),e.default.createElement(o.default,{activeTileset:this.state.tileset,activeViewer:this.state.viewer,setViewer:this.setViewer.bind(this)}),this.renderMapWidget())}populateTileList(e){this.setState({tilesets:e.tilesets}),e.tilesets.length>0&&this.setTileset(e.tilesets[0].name)}setTileset(e){this.setState({tileset:e});var t=this.state.tilesets.find(t=>t.name===e).bounds;t&&this.setState({bounds:t})}setViewer(e){this.setState({viewer:e})}storeExtent(e,t,s){null!==e&&this.setState({bounds:e}),this.setState({center:t,zoom:s})}renderMapWidget(){return"Info"===this.state.viewer?e.default.createElement(s.default,{tilesets:this.state.tilesets,activeTileset:this.state.tileset,center:this.state.center,zoom:this.state.zoom}):"Mapbox GL"===this.state.viewer?e.default.createElement(i.default,{activeTileset:this.state.tileset,storeExtent:this.storeExtent.bind(this),bounds:this.state.bounds,center:this.state.center,zoom:this.state.zoom}):"OpenLayers"===this.state.viewer?e.default.createElement(r.default,{activeTileset:this.state.tileset,storeExtent:this.storeExtent.bind(this),bounds:this.state.bounds,center:this.state.center,zoom:this.state.zoom}):"X-Ray"===this.state.viewer?e.default.createElement(a.default,{activeTileset:this.state.tileset,storeExtent:this.storeExtent.bind(this),bounds:this.state.bounds,center:this.state.center,zoom:this.state.zoom}):"Inspector"===this.state.viewer?e.default.createElement(n.default,{activeTileset:this.state.tileset,storeExtent:this.storeExtent.bind(this),bounds:this.state.bounds,center:this.state.center,zoom:this.state.zoom}):null}}var h=d;exports.default=h;
+"use strict";Object.defineProperty(exports,"__esModule",{value:!0}),exports.default=void 0;var e=u(require("react"));require("./App.css");var t=l(require("./TileList")),s=l(require("./InfoWidget")),i=l(require("./MapWidgets/MapboxGLMapWidget")),r=l(require("./MapWidgets/OpenLayersMapWidget")),a=l(require("./MapWidgets/XRayMapWidget")),n=l(require("./MapWidgets/InspectorMapWidget")),o=l(require("./TitleBar"));function l(e){return e&&e.__esModule?e:{default:e}}function u(e){if(e&&e.__esModule)return e;var t={};if(null!=e)for(var s in e)if(Object.prototype.hasOwnProperty.call(e,s)){var i=Object.defineProperty&&Object.getOwnPropertyDescriptor?Object.getOwnPropertyDescriptor(e,s):{};i.get||i.set?Object.defineProperty(t,s,i):t[s]=e[s]}return t.default=e,t}class d extends e.Component{constructor(e){super(e),this.state={tilesets:[],tileset:null,viewer:"Info",bounds:null,center:[0,0],zoom:2}}componentDidMount(){fetch("/index.json").then(function(e){return e.json()}).then(function(e){this.populateTileList(e)}.bind(this))}render(){return e.default.createElement("div",{className:"App"},e.default.createElement(t.default,{tilesets:this.state.tilesets,activeTileset:this.state.tileset,setTileset:this.setTileset.bind(this)}),e.default.createElement(o.default,{activeTileset:this.state.tileset,activeViewer:this.state.viewer,setViewer:this.setViewer.bind(this)}),this.renderMapWidget())}populateTileList(e){this.setState({tilesets:e.tilesets}),e.tilesets.length>0&&this.setTileset(e.tilesets[0].name)}setTileset(e){this.setState({tileset:e});var t=this.state.tilesets.find(t=>t.name===e);t.center?this.setState({bounds:null,center:[t.center[0],t.center[1]],zoom:t.center[2]}):t.bounds&&this.setState({bounds:t.bounds})}setViewer(e){this.setState({viewer:e})}storeExtent(e,t,s){null!==e&&this.setState({bounds:e}),this.setState({center:t,zoom:s})}renderMapWidget(){return"Info"===this.state.viewer?e.default.createElement(s.default,{tilesets:this.state.tilesets,activeTileset:this.state.tileset,center:this.state.center,zoom:this.state.zoom}):"Mapbox GL"===this.state.viewer?e.default.createElement(i.default,{activeTileset:this.state.tileset,storeExtent:this.storeExtent.bind(this),bounds:this.state.bounds,center:this.state.center,zoom:this.state.zoom}):"OpenLayers"===this.state.viewer?e.default.createElement(r.default,{activeTileset:this.state.tileset,storeExtent:this.storeExtent.bind(this),bounds:this.state.bounds,center:this.state.center,zoom:this.state.zoom}):"X-Ray"===this.state.viewer?e.default.createElement(a.default,{activeTileset:this.state.tileset,storeExtent:this.storeExtent.bind(this),bounds:this.state.bounds,center:this.state.center,zoom:this.state.zoom}):"Inspector"===this.state.viewer?e.default.createElement(n.default,{activeTileset:this.state.tileset,storeExtent:this.storeExtent.bind(this),bounds:this.state.bounds,center:this.state.center,zoom:this.state.zoom}):null}}var h=d;exports.default=h;
 },{"react":"1n8/","./App.css":"E+qH","./TileList":"SXJ9","./InfoWidget":"rvTa","./MapWidgets/MapboxGLMapWidget":"VsLq","./MapWidgets/OpenLayersMapWidget":"S0p3","./MapWidgets/XRayMapWidget":"yTi3","./MapWidgets/InspectorMapWidget":"X/Em","./TitleBar":"dUiY"}],"/deH":[function(require,module,exports) {
 "use strict";var e=l(require("react")),t=require("react-dom"),r=require("react-hot-loader"),u=l(require("./App"));function l(e){return e&&e.__esModule?e:{default:e}}require("./index.css");const n=document.getElementById("root"),o=()=>(0,t.render)(e.default.createElement(r.AppContainer,null,e.default.createElement(u.default,null)),n);module.hot&&module.hot.accept("./App",o),o();
 },{"react":"1n8/","react-dom":"NKHc","react-hot-loader":"/aVi","./App":"lY9v","./index.css":"E+qH"}]},{},["/deH"], null)
@@ -3,74 +3,113 @@
 // Licensed under the MIT License. See LICENSE file in the project root for full license information.
 //
 
-use std::collections::HashMap;
-use std::str;
+use std::borrow::Cow;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
 
-pub struct StaticFiles {
-    files: HashMap<&'static str, (&'static [u8], &'static str)>,
+// Include STATIC_FILE_TYPES, BUILD_ASSETS_DIR, EMBEDDED_ASSETS, EMBEDDED_FONTS and embedded_file()
+include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+
+/// Environment variable with an assets directory used instead of embedded files
+pub const ASSETS_DIR_ENV: &str = "TREX_ASSETS_DIR";
+
+/// File `path` (relative, with `/` separators) within `dir`.
+/// `None` for missing files and paths outside of `dir`, e.g. with `..` or symlinks leaving `dir`.
+pub fn dir_file(dir: &str, path: &str) -> Option<PathBuf> {
+    let relpath = Path::new(path);
+    let normal = relpath.components().all(|c| match c {
+        Component::Normal(_) => true,
+        _ => false,
+    });
+    if path.is_empty() || path.contains('\\') || !normal {
+        return None;
+    }
+    let dir = fs::canonicalize(dir).ok()?;
+    let file = fs::canonicalize(dir.join(relpath)).ok()?;
+    if file.starts_with(&dir) && file.is_file() {
+        Some(file)
+    } else {
+        None
+    }
+}
+
+/// Static files and font glyphs of the built-in viewer
+#[derive(Clone)]
+pub enum StaticFiles {
+    /// Files embedded at build time (feature `webviewer`), looked up on demand
+    Embedded,
+    /// Files read at runtime from an assets directory with the layout of `src/static`
+    Directory(PathBuf),
+    /// No viewer assets (built without feature `webviewer` or `--no-viewer-assets`)
+    Disabled,
 }
 
 impl StaticFiles {
-    pub fn init() -> StaticFiles {
-        let mut static_files = StaticFiles {
-            files: HashMap::new(),
-        };
-        static_files.add(
-            "favicon.ico",
-            include_bytes!("static/favicon.ico"),
-            "image/x-icon",
-        );
-        static_files.add(
-            "index.html",
-            include_bytes!("static/index.html"),
-            "text/html",
-        );
-        static_files.add(
-            "viewer.js",
-            include_bytes!("static/viewer.js"),
-            "application/javascript",
-        );
-        static_files.add(
-            "viewer.css",
-            include_bytes!("static/viewer.css"),
-            "text/css",
-        );
-        static_files.add(
-            "maputnik.html",
-            include_bytes!("static/maputnik.html"),
-            "text/html",
-        );
-        static_files.add(
-            "maputnik.js",
-            include_bytes!("static/maputnik.js"),
-            "application/javascript",
-        );
-        static_files.add(
-            "maputnik-vendor.js",
-            include_bytes!("static/maputnik-vendor.js"),
-            "application/javascript",
-        );
-        static_files.add(
-            "img/logo-color.svg",
-            include_bytes!("static/img/logo-color.svg"),
-            "image/svg+xml",
-        );
-        static_files.add(
-            "fonts/Roboto-Regular.ttf",
-            include_bytes!("static/fonts/Roboto-Regular.ttf"),
-            "font/ttf",
-        );
-        static_files.add(
-            "fonts/Roboto-Medium.ttf",
-            include_bytes!("static/fonts/Roboto-Medium.ttf"),
-            "font/ttf",
-        );
-        static_files
+    /// Select asset source. `TREX_ASSETS_DIR` has precedence over the configured directory.
+    pub fn init(assets_dir: Option<&str>) -> Result<StaticFiles, String> {
+        let dir = env::var(ASSETS_DIR_ENV)
+            .ok()
+            .or(assets_dir.map(|dir| dir.to_string()))
+            .or(BUILD_ASSETS_DIR.map(|dir| dir.to_string()));
+        match dir {
+            Some(dir) => StaticFiles::from_dir(&dir),
+            None if EMBEDDED_ASSETS => Ok(StaticFiles::embedded()),
+            None => Ok(StaticFiles::Disabled),
+        }
+    }
+    pub fn embedded() -> StaticFiles {
+        StaticFiles::Embedded
+    }
+    /// Assets directory source. Fails if static files or fonts are missing.
+    pub fn from_dir(dir: &str) -> Result<StaticFiles, String> {
+        let path = PathBuf::from(dir);
+        let mut missing = STATIC_FILE_TYPES
+            .iter()
+            .filter(|(name, _)| !path.join(name).is_file())
+            .map(|(name, _)| name.to_string())
+            .collect::<Vec<_>>();
+        let static_files = StaticFiles::Directory(path);
+        if static_files.fontstacks().is_empty() {
+            missing.push("fonts/<Font Name>/<range>.pbf".to_string());
+        }
+        if missing.is_empty() {
+            Ok(static_files)
+        } else {
+            Err(format!(
+                "Assets directory '{}' is incomplete, missing: {}",
+                dir,
+                missing.join(", ")
+            ))
+        }
+    }
+    pub fn info(&self) -> String {
+        match self {
+            StaticFiles::Embedded => "Serving embedded static files".to_string(),
+            StaticFiles::Directory(path) => {
+                format!("Serving static files from '{}'", path.display())
+            }
+            StaticFiles::Disabled => "Viewer assets disabled".to_string(),
+        }
     }
-    fn add(&mut self, name: &'static str, data: &'static [u8], media_type: &'static str) {
-        self.files.insert(name, (data, media_type));
+    pub fn is_disabled(&self) -> bool {
+        match self {
+            StaticFiles::Disabled => true,
+            _ => false,
+        }
     }
-    pub fn content(&self, base: Option<&str>, name: String) -> Option<&(&[u8], &str)> {
+    fn read(&self, key: &str) -> Option<Cow<'static, [u8]>> {
+        match self {
+            StaticFiles::Embedded => embedded_file(key).map(Cow::Borrowed),
+            StaticFiles::Directory(path) => fs::read(path.join(key)).ok().map(Cow::Owned),
+            StaticFiles::Disabled => None,
+        }
+    }
+    pub fn content(
+        &self,
+        base: Option<&str>,
+        name: String,
+    ) -> Option<(Cow<'static, [u8]>, &'static str)> {
         let mut key = if name == "" {
             "index.html".to_string()
         } else {
@@ -79,6 +118,41 @@ impl StaticFiles {
         if let Some(path) = base {
             key = format!("{}/{}", path, key);
         }
-        self.files.get(&key as &str)
+        // Only known files are served, also from an assets directory
+        let (name, media_type) = STATIC_FILE_TYPES.iter().find(|(name, _)| *name == key)?;
+        self.read(name).map(|data| (data, *media_type))
+    }
+    /// Gzip compressed glyph range of font, e.g. ("Roboto Regular", "0-255")
+    pub fn font(&self, font: &str, range: &str) -> Option<Cow<'static, [u8]>> {
+        if [font, range]
+            .iter()
+            .any(|s| s.contains('/') || s.contains('\\') || s.contains(".."))
+        {
+            return None;
+        }
+        self.read(&format!("fonts/{}/{}.pbf", font, range))
+    }
+    /// Names of available fonts
+    pub fn fontstacks(&self) -> Vec<String> {
+        let mut names: Vec<String> = match self {
+            StaticFiles::Embedded => EMBEDDED_FONTS
+                .iter()
+                .filter_map(|key| key.split('/').nth(1))
+                .map(|name| name.to_string())
+                .collect(),
+            StaticFiles::Directory(path) => fs::read_dir(path.join("fonts"))
+                .map(|entries| {
+                    entries
+                        .filter_map(|entry| entry.ok())
+                        .filter(|entry| entry.path().is_dir())
+                        .map(|entry| entry.file_name().to_string_lossy().to_string())
+                        .collect()
+                })
+                .unwrap_or(Vec::new()),
+            StaticFiles::Disabled => Vec::new(),
+        };
+        names.sort();
+        names.dedup();
+        names
     }
 }
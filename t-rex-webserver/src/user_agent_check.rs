@@ -0,0 +1,49 @@
+//
+// Copyright (c) Pirmin Kalberer. All rights reserved.
+// Licensed under the MIT License. See LICENSE file in the project root for full license information.
+//
+
+use crate::core::config::UserAgentCfg;
+use regex::Regex;
+
+/// Check of `User-Agent` headers against allow and deny patterns (abuse mitigation)
+#[derive(Clone, Debug)]
+pub struct UserAgentCheck {
+    allow: Vec<Regex>,
+    deny: Vec<Regex>,
+    /// Reject requests without `User-Agent` header
+    require: bool,
+}
+
+fn compile(patterns: &[String], option: &str) -> Result<Vec<Regex>, String> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Regex::new(pattern)
+                .map_err(|e| format!("Invalid user_agent.{} pattern '{}': {}", option, pattern, e))
+        })
+        .collect()
+}
+
+impl UserAgentCheck {
+    pub fn new(config: &UserAgentCfg) -> Result<UserAgentCheck, String> {
+        Ok(UserAgentCheck {
+            allow: compile(&config.allow, "allow")?,
+            deny: compile(&config.deny, "deny")?,
+            require: config.require,
+        })
+    }
+    /// Check whether a request with `user_agent` header is allowed.
+    /// Denied patterns win over allowed patterns. Without allow patterns, all
+    /// user agents not denied are allowed.
+    pub fn allows(&self, user_agent: Option<&str>) -> bool {
+        let user_agent = match user_agent {
+            Some(user_agent) if !user_agent.trim().is_empty() => user_agent,
+            _ => return !self.require,
+        };
+        if self.deny.iter().any(|re| re.is_match(user_agent)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|re| re.is_match(user_agent))
+    }
+}
@@ -28,6 +28,29 @@ pub struct ExtentInt {
 // Max grid cell numbers
 type CellIndex = (u32, u32);
 
+// Tile adressing schemes:
+// - Grid scheme: rows counted from the grid origin, used by `Grid::tile_extent`,
+//   `Grid::tile_limits` and `GridIterator`. Same as TMS for `Origin::BottomLeft`
+//   and XYZ for `Origin::TopLeft`. Plain `u32` tile indices are in this scheme.
+// - XYZ scheme (`XyzTile`): rows counted from the top, e.g. tile URLs of web maps.
+// - TMS scheme (`TmsTile`): rows counted from the bottom, e.g. MBTiles `tile_row`.
+
+/// Tile with row counted from the top of the grid (XYZ adressing scheme)
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct XyzTile {
+    pub x: u32,
+    pub y: u32,
+    pub z: u8,
+}
+
+/// Tile with row counted from the bottom of the grid (TMS adressing scheme)
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct TmsTile {
+    pub x: u32,
+    pub y: u32,
+    pub z: u8,
+}
+
 /// Grid origin
 #[derive(PartialEq, Clone, Debug)]
 pub enum Origin {
@@ -71,12 +94,15 @@ pub struct Grid {
     level_max: Vec<CellIndex>,
     /// Grid origin
     pub origin: Origin,
+    /// Identifier of the tile matrix set used in WMTS and OGC API Tiles,
+    /// e.g. `WebMercatorQuad` (OGC Two Dimensional Tile Matrix Set)
+    pub tile_matrix_set: Option<String>,
 }
 
 impl Grid {
     /// WGS84 grid
     pub fn wgs84() -> Grid {
-        Grid::new(
+        let mut grid = Grid::new(
             256,
             256,
             Extent {
@@ -108,12 +134,14 @@ impl Grid {
                 5.36441802978516e-6,
             ],
             Origin::BottomLeft,
-        )
+        );
+        grid.tile_matrix_set = Some("WorldCRS84Quad".to_string());
+        grid
     }
 
     /// Web Mercator grid (Google maps compatible)
     pub fn web_mercator() -> Grid {
-        Grid::new(
+        let mut grid = Grid::new(
             256,
             256,
             Extent {
@@ -151,7 +179,9 @@ impl Grid {
                 0.037322767717371225,
             ],
             Origin::BottomLeft,
-        )
+        );
+        grid.tile_matrix_set = Some("WebMercatorQuad".to_string());
+        grid
     }
 
     pub fn new(
@@ -172,6 +202,7 @@ impl Grid {
             resolutions,
             origin,
             level_max: Vec::new(),
+            tile_matrix_set: None,
         };
         grid.level_max = grid.level_max();
         grid
@@ -182,6 +213,14 @@ impl Grid {
     pub fn maxzoom(&self) -> u8 {
         self.nlevels() - 1
     }
+    /// Tile width and height in pixels
+    pub fn tile_size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+    /// Resolution of zoom level in grid units per pixel
+    pub fn resolution(&self, zoom: u8) -> f64 {
+        self.resolutions[zoom as usize]
+    }
     pub fn pixel_width(&self, zoom: u8) -> f64 {
         const METERS_PER_DEGREE: f64 = 6378137.0 * 2.0 * consts::PI / 360.0;
         match self.units {
@@ -197,7 +236,7 @@ impl Grid {
         // of 0.28 millimeters as defined by the OGC (Open Geospatial Consortium) SLD (Styled Layer Descriptor) Specification.
         self.pixel_width(zoom) / PIXEL_SCREEN_WIDTH
     }
-    /// Extent of a given tile in the grid given its x, y, and z in TMS adressing scheme
+    /// Extent of a given tile in the grid given its x, y, and z in grid adressing scheme
     pub fn tile_extent(&self, xtile: u32, ytile: u32, zoom: u8) -> Extent {
         // based on mapcache_grid_get_tile_extent
         let res = self.resolutions[zoom as usize];
@@ -218,20 +257,68 @@ impl Grid {
             },
         }
     }
-    /// reverse y tile for XYZ adressing scheme
-    pub fn ytile_from_xyz(&self, ytile: u32, zoom: u8) -> u32 {
+    /// Row counted from the other side of the grid
+    fn flip_ytile(&self, ytile: u32, zoom: u8) -> u32 {
         // y = maxy-ytile-1
         let maxy = self.level_max[zoom as usize].1;
-        let y = maxy.saturating_sub(ytile).saturating_sub(1);
-        y
+        maxy.saturating_sub(ytile).saturating_sub(1)
+    }
+    /// Row in grid adressing scheme of a row in XYZ adressing scheme (and vice versa)
+    pub fn ytile_from_xyz(&self, ytile: u32, zoom: u8) -> u32 {
+        match self.origin {
+            Origin::BottomLeft => self.flip_ytile(ytile, zoom),
+            Origin::TopLeft => ytile,
+        }
+    }
+    /// Row in grid adressing scheme of a row in TMS adressing scheme (and vice versa)
+    pub fn ytile_from_tms(&self, ytile: u32, zoom: u8) -> u32 {
+        match self.origin {
+            Origin::BottomLeft => ytile,
+            Origin::TopLeft => self.flip_ytile(ytile, zoom),
+        }
+    }
+    /// XYZ tile of a tile in grid adressing scheme
+    pub fn xyz_tile(&self, xtile: u32, ytile: u32, zoom: u8) -> XyzTile {
+        XyzTile {
+            x: xtile,
+            y: self.ytile_from_xyz(ytile, zoom),
+            z: zoom,
+        }
+    }
+    /// TMS tile of a tile in grid adressing scheme
+    pub fn tms_tile(&self, xtile: u32, ytile: u32, zoom: u8) -> TmsTile {
+        TmsTile {
+            x: xtile,
+            y: self.ytile_from_tms(ytile, zoom),
+            z: zoom,
+        }
+    }
+    pub fn xyz_to_tms(&self, tile: &XyzTile) -> TmsTile {
+        TmsTile {
+            x: tile.x,
+            y: self.flip_ytile(tile.y, tile.z),
+            z: tile.z,
+        }
+    }
+    pub fn tms_to_xyz(&self, tile: &TmsTile) -> XyzTile {
+        XyzTile {
+            x: tile.x,
+            y: self.flip_ytile(tile.y, tile.z),
+            z: tile.z,
+        }
     }
     /// Extent of a given tile in XYZ adressing scheme
     pub fn tile_extent_xyz(&self, xtile: u32, ytile: u32, zoom: u8) -> Extent {
         let y = self.ytile_from_xyz(ytile, zoom);
         self.tile_extent(xtile, y, zoom)
     }
+    /// Extent of a given tile in TMS adressing scheme
+    pub fn tile_extent_tms(&self, tile: &TmsTile) -> Extent {
+        let y = self.ytile_from_tms(tile.y, tile.z);
+        self.tile_extent(tile.x, y, tile.z)
+    }
     /// (maxx, maxy) of grid level
-    pub(crate) fn level_limit(&self, zoom: u8) -> CellIndex {
+    pub fn level_limit(&self, zoom: u8) -> CellIndex {
         let res = self.resolutions[zoom as usize];
         let unitheight = self.height as f64 * res;
         let unitwidth = self.width as f64 * res;
@@ -242,6 +329,15 @@ impl Grid {
             ((self.extent.maxx - self.extent.minx - 0.01 * unitwidth) / unitwidth).ceil() as u32;
         (maxx, maxy)
     }
+    /// Check whether a tile is within the tile matrix of its zoom level.
+    /// The number of columns and rows depends on the grid extent and resolutions and is
+    /// not necessarily a power of two. Rows in all adressing schemes have the same range.
+    pub fn tile_in_grid(&self, xtile: u32, ytile: u32, zoom: u8) -> bool {
+        match self.level_max.get(zoom as usize) {
+            Some(&(maxx, maxy)) => xtile < maxx && ytile < maxy,
+            None => false,
+        }
+    }
     /// (maxx, maxy) of all grid levels
     fn level_max(&self) -> Vec<CellIndex> {
         (0..self.nlevels())
@@ -308,7 +404,7 @@ impl Grid {
 }
 
 /// Returns the Spherical Mercator (x, y) in meters
-fn lonlat_to_merc(lon: f64, lat: f64) -> (f64, f64) {
+pub fn lonlat_to_merc(lon: f64, lat: f64) -> (f64, f64) {
     // from mod web_mercator in grid_test
     //lng, lat = truncate_lnglat(lng, lat)
     let x = 6378137.0 * lon.to_radians();
@@ -316,6 +412,13 @@ fn lonlat_to_merc(lon: f64, lat: f64) -> (f64, f64) {
     (x, y)
 }
 
+/// Returns the WGS84 (lon, lat) of Spherical Mercator (x, y) in meters
+pub fn merc_to_lonlat(x: f64, y: f64) -> (f64, f64) {
+    let lon = (x / 6378137.0).to_degrees();
+    let lat = (2.0 * (y / 6378137.0).exp().atan() - consts::PI * 0.5).to_degrees();
+    (lon, lat)
+}
+
 /// Projected extent
 pub fn extent_to_merc(extent: &Extent) -> Extent {
     let (minx, miny) = lonlat_to_merc(extent.minx, extent.miny);
@@ -3,7 +3,10 @@
 // Licensed under the MIT License. See LICENSE file in the project root for full license information.
 //
 
-use crate::grid::{extent_to_merc, Extent, ExtentInt, Grid};
+use crate::grid::{
+    extent_to_merc, lonlat_to_merc, merc_to_lonlat, Extent, ExtentInt, Grid, Origin, TmsTile, Unit,
+    XyzTile,
+};
 
 #[test]
 fn test_bbox() {
@@ -115,6 +118,8 @@ fn test_grid_calculations() {
 
     assert_eq!(grid.level_limit(0), (1, 1));
     assert_eq!(grid.level_limit(10), (1024, 1024));
+    assert_eq!(grid.resolution(10), 152.87405657035254);
+    assert_eq!(grid.tile_size(), (256, 256));
 
     let limits = grid.tile_limits(grid.tile_extent(0, 0, 0), 0);
     assert_eq!(
@@ -191,8 +196,33 @@ fn test_grid_calculations() {
 fn test_wgs84_grid() {
     let grid = Grid::wgs84();
 
+    assert_eq!(grid.tile_matrix_set, Some("WorldCRS84Quad".to_string()));
     assert_eq!(grid.pixel_width(10), 76.43702828517625);
     assert_eq!(grid.scale_denominator(10), 272989.38673277234);
+    // WorldCRS84Quad: 2x1 tiles at zoom level 0
+    assert_eq!(grid.level_limit(0), (2, 1));
+    assert_eq!(grid.level_limit(1), (4, 2));
+    assert_eq!(grid.maxzoom(), 17);
+    assert!((grid.scale_denominator(0) - 279541132.0143589).abs() < 1e-4);
+    assert_eq!(
+        grid.tile_extent_xyz(1, 0, 0),
+        Extent {
+            minx: 0.0,
+            miny: -90.0,
+            maxx: 180.0,
+            maxy: 90.0,
+        }
+    );
+    assert_eq!(
+        grid.tile_extent_xyz(0, 0, 1),
+        Extent {
+            minx: -180.0,
+            miny: 0.0,
+            maxx: -90.0,
+            maxy: 90.0,
+        }
+    );
+    assert!(!grid.tile_in_grid(0, 1, 0));
 }
 
 #[test]
@@ -220,6 +250,15 @@ fn test_projected_extent() {
     assert_eq!(extent_to_merc(&extent_wgs84), extent_3857);
 }
 
+#[test]
+fn test_merc_to_lonlat() {
+    let (x, y) = lonlat_to_merc(7.44, 46.95);
+    let (lon, lat) = merc_to_lonlat(x, y);
+    assert!((lon - 7.44).abs() < 1e-9);
+    assert!((lat - 46.95).abs() < 1e-9);
+    assert_eq!(merc_to_lonlat(0.0, 0.0), (0.0, 0.0));
+}
+
 mod web_mercator {
 
     // --- Web Mercator calculations ---
@@ -353,3 +392,186 @@ mod web_mercator {
         );
     }
 }
+
+/// Pseudo random numbers (xorshift) for property tests.
+/// Fixed seeds keep failures reproducible without adding a dependency (like proptest)
+/// to this dependency-free crate.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+    /// Random number in [lo, hi)
+    fn range(&mut self, lo: u32, hi: u32) -> u32 {
+        lo + (self.next() % (hi - lo) as u64) as u32
+    }
+    /// Random number in [lo, hi)
+    fn float(&mut self, lo: f64, hi: f64) -> f64 {
+        lo + (self.next() >> 11) as f64 / (1u64 << 53) as f64 * (hi - lo)
+    }
+}
+
+/// Random grid with partial tiles at the extent border
+fn random_grid(rng: &mut Rng, srid: i32) -> Grid {
+    let size = [256, 512, 300][rng.range(0, 3) as usize];
+    let (width, height) = (rng.float(1000.0, 1e7), rng.float(1000.0, 1e7));
+    let minx = rng.float(-1e7, 1e7);
+    let miny = rng.float(-1e7, 1e7);
+    let mut res = width.max(height) / size as f64 * rng.float(0.5, 2.0);
+    let resolutions = (0..rng.range(1, 20))
+        .map(|_| {
+            let r = res;
+            res /= rng.float(1.2, 2.5);
+            r
+        })
+        .collect();
+    let origin = if rng.range(0, 2) == 0 {
+        Origin::TopLeft
+    } else {
+        Origin::BottomLeft
+    };
+    Grid::new(
+        size,
+        size,
+        Extent {
+            minx,
+            miny,
+            maxx: minx + width,
+            maxy: miny + height,
+        },
+        srid,
+        Unit::Meters,
+        resolutions,
+        origin,
+    )
+}
+
+/// Random tile in grid adressing scheme
+fn random_tile(rng: &mut Rng, grid: &Grid) -> (u32, u32, u8) {
+    let zoom = rng.range(0, grid.nlevels() as u32) as u8;
+    let (maxx, maxy) = grid.level_limit(zoom);
+    (rng.range(0, maxx), rng.range(0, maxy), zoom)
+}
+
+#[test]
+fn test_adressing_schemes() {
+    let mut rng = Rng::new(0x5eed);
+    for _ in 0..1000 {
+        let grid = random_grid(&mut rng, 2056);
+        let (x, y, z) = random_tile(&mut rng, &grid);
+        let rows = grid.level_limit(z).1;
+        let xyz = grid.xyz_tile(x, y, z);
+        let tms = grid.tms_tile(x, y, z);
+        assert_eq!(xyz.y + tms.y, rows - 1);
+        assert_eq!(grid.xyz_to_tms(&xyz), tms);
+        assert_eq!(grid.tms_to_xyz(&tms), xyz);
+        assert_eq!(grid.ytile_from_xyz(xyz.y, z), y);
+        assert_eq!(grid.ytile_from_tms(tms.y, z), y);
+        match grid.origin {
+            Origin::BottomLeft => assert_eq!(tms.y, y),
+            Origin::TopLeft => assert_eq!(xyz.y, y),
+        }
+
+        // tile -> bbox
+        let extent = grid.tile_extent(x, y, z);
+        assert_eq!(grid.tile_extent_xyz(x, xyz.y, z), extent);
+        assert_eq!(grid.tile_extent_tms(&tms), extent);
+        // XYZ rows are counted from the top, TMS rows from the bottom
+        if xyz.y > 0 {
+            assert!(grid.tile_extent_xyz(x, xyz.y - 1, z).miny > extent.miny);
+        }
+        if tms.y > 0 {
+            let below = TmsTile {
+                y: tms.y - 1,
+                ..tms
+            };
+            assert!(grid.tile_extent_tms(&below).miny < extent.miny);
+        }
+
+        // bbox -> tile
+        let margin = (extent.maxx - extent.minx) / 10.0;
+        let inner = Extent {
+            minx: extent.minx + margin,
+            miny: extent.miny + margin,
+            maxx: extent.maxx - margin,
+            maxy: extent.maxy - margin,
+        };
+        assert_eq!(
+            grid.tile_limits(inner, 0)[z as usize],
+            ExtentInt {
+                minx: x,
+                miny: y,
+                maxx: x + 1,
+                maxy: y + 1,
+            },
+            "{:?} {}/{}/{}",
+            grid,
+            z,
+            x,
+            y
+        );
+    }
+}
+
+#[test]
+fn test_xyz_top_left() {
+    let merc = Grid::web_mercator();
+    let grid = Grid::new(
+        256,
+        256,
+        merc.extent.clone(),
+        3857,
+        Unit::Meters,
+        (0..5).map(|z| merc.resolution(z)).collect(),
+        Origin::TopLeft,
+    );
+    // Same tiles as in the Web Mercator grid with bottom left origin
+    let extent = grid.tile_extent_xyz(3, 5, 4);
+    let expected = merc.tile_extent_xyz(3, 5, 4);
+    assert!((extent.miny - expected.miny).abs() < 0.001);
+    assert!((extent.maxy - expected.maxy).abs() < 0.001);
+    assert_eq!(grid.xyz_tile(3, 5, 4), XyzTile { x: 3, y: 5, z: 4 });
+    assert_eq!(grid.tms_tile(3, 5, 4), TmsTile { x: 3, y: 10, z: 4 });
+    assert_eq!(merc.xyz_tile(3, 10, 4), XyzTile { x: 3, y: 5, z: 4 });
+}
+
+#[test]
+fn test_tile_in_grid() {
+    let merc = Grid::web_mercator();
+    assert!(merc.tile_in_grid(0, 0, 0));
+    assert!(!merc.tile_in_grid(1, 0, 0));
+    assert!(merc.tile_in_grid(15, 15, 4));
+    assert!(!merc.tile_in_grid(15, 16, 4));
+    assert!(!merc.tile_in_grid(0, 0, merc.nlevels()));
+
+    // Swiss grid with tile matrix dimensions differing from 2^z
+    let grid = Grid::new(
+        256,
+        256,
+        Extent {
+            minx: 2420000.0,
+            miny: 1030000.0,
+            maxx: 2900000.0,
+            maxy: 1350000.0,
+        },
+        2056,
+        Unit::Meters,
+        vec![4000.0, 250.0, 100.0],
+        Origin::TopLeft,
+    );
+    for (zoom, &(cols, rows)) in [(1, 1), (8, 5), (19, 13)].iter().enumerate() {
+        let zoom = zoom as u8;
+        assert_eq!(grid.level_limit(zoom), (cols, rows));
+        assert!(grid.tile_in_grid(cols - 1, rows - 1, zoom));
+        assert!(!grid.tile_in_grid(cols, 0, zoom));
+        assert!(!grid.tile_in_grid(0, rows, zoom));
+    }
+    assert!(!grid.tile_in_grid(0, 0, 3));
+}
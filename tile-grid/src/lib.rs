@@ -77,5 +77,8 @@ mod grid_iterator;
 #[cfg(test)]
 mod grid_test;
 
-pub use grid::{extent_to_merc, Extent, ExtentInt, Grid, Origin, Unit};
+pub use grid::{
+    extent_to_merc, lonlat_to_merc, merc_to_lonlat, Extent, ExtentInt, Grid, Origin, TmsTile, Unit,
+    XyzTile,
+};
 pub use grid_iterator::GridIterator;